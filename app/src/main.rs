@@ -1,9 +1,19 @@
 use riptide_lib::{Libs, run_riptide };
+use riptide_lib::cli::parse_args;
+use riptide_lib::config::Config;
+
+use std::io::IsTerminal;
 // use crate::shared::frames::FrameStorage;
 // use crate::shared::buffers::BufferStorage;
 
 fn main() {
-    let libs = Libs::default();
+    riptide_lib::logging::init();
+    let mut libs = Libs::new(Config::load());
+    libs.client.open_requests(parse_args(std::env::args().skip(1)));
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        libs.client.open_stream(stdin);
+    }
     // {
     //     let mut writable_shared = shared::SHARED.write().unwrap();
     //     writable_shared.frames = FrameStorage::default();
@@ -11,5 +21,5 @@ fn main() {
     // }
     // let client = libs.client;
     // client.subscribe = new_func;
-    run_riptide(libs);
+    run_riptide(libs).expect("failed to start riptide");
 }
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use crate::shared::buffers::char_index_at_byte;
+
+// Where in a file to open it. All 0-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAt {
+    Line(usize),
+    // For tools that report byte offsets; one inside a character means that
+    // character.
+    Byte(usize),
+    Char(usize),
+}
+
+impl OpenAt {
+    // The char index in `content`, clamped to its end. `None` for lines,
+    // which are jumped to as lines.
+    pub fn char_index(self, content: &str) -> Option<usize> {
+        match self {
+            Self::Line(_) => None,
+            Self::Byte(byte_idx) => Some(char_index_at_byte(content, byte_idx)),
+            Self::Char(char_idx) => Some(char_idx.min(content.chars().count())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenRequest {
+    pub path: PathBuf,
+    pub at: Option<OpenAt>,
+}
+
+// `+N` is 1-based like in other editors; `+bN` and `+cN` are byte and char
+// offsets from the start of the file.
+fn parse_position(arg: &str) -> Option<OpenAt> {
+    let arg = arg.strip_prefix('+')?;
+    if let Some(offset) = arg.strip_prefix('b') {
+        offset.parse().ok().map(OpenAt::Byte)
+    } else if let Some(offset) = arg.strip_prefix('c') {
+        offset.parse().ok().map(OpenAt::Char)
+    } else {
+        arg.parse::<usize>().ok().map(|line| OpenAt::Line(line.saturating_sub(1)))
+    }
+}
+
+// `riptide [+N | +bN | +cN] FILE...`: a position applies to the file that
+// follows it. Anything unrecognised is reported and skipped so a typo
+// never prevents startup.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Vec<OpenRequest> {
+    let mut requests = Vec::new();
+    let mut at = None;
+
+    for arg in args {
+        if arg.starts_with('+') {
+            match parse_position(&arg) {
+                Some(position) => at = Some(position),
+                None => log::warn!("ignoring invalid position argument: {arg}"),
+            }
+        } else if arg.starts_with('-') && arg != "-" {
+            log::warn!("ignoring unknown option: {arg}");
+        } else {
+            requests.push(OpenRequest {
+                path: PathBuf::from(arg),
+                at: at.take(),
+            });
+        }
+    }
+    requests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn positions_apply_to_the_next_file_and_bad_args_are_skipped() {
+        let requests = parse_args(args(&["+12", "main.rs", "--verbose", "+x", "lib.rs", "+b7", "-", "+c3"]));
+        assert_eq!(
+            requests,
+            vec![
+                OpenRequest { path: PathBuf::from("main.rs"), at: Some(OpenAt::Line(11)) },
+                OpenRequest { path: PathBuf::from("lib.rs"), at: None },
+                OpenRequest { path: PathBuf::from("-"), at: Some(OpenAt::Byte(7)) },
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_resolve_to_chars_within_the_content() {
+        assert_eq!(OpenAt::Byte(3).char_index("héllo"), Some(2));
+        assert_eq!(OpenAt::Char(99).char_index("héllo"), Some(5));
+        assert_eq!(OpenAt::Line(1).char_index("héllo"), None);
+    }
+}
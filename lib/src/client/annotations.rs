@@ -0,0 +1,43 @@
+use eframe::egui;
+
+use crate::shared::buffers::annotations::Annotations;
+
+// Space between the end of a line and its annotation.
+const GAP: f32 = 24.0;
+
+// Where each line's annotations go: right of the line's last row, joined
+// into one string. Folded lines are skipped.
+pub fn positions(annotations: &Annotations, galley: &egui::Galley, galley_pos: egui::Pos2) -> Vec<(egui::Pos2, String)> {
+    if annotations.is_empty() {
+        return Vec::new();
+    }
+    let mut positions = Vec::new();
+    let mut line = 0;
+    let last_row = galley.rows.len().saturating_sub(1);
+    for (row_idx, row) in galley.rows.iter().enumerate() {
+        if !row.ends_with_newline && row_idx != last_row {
+            continue;
+        }
+        if let Some(annotations) = annotations.get(&line)
+            && row.rect().height() >= 1.0
+        {
+            let text = annotations
+                .iter()
+                .map(|annotation| annotation.text.as_str())
+                .collect::<Vec<_>>()
+                .join("  ");
+            let pos = galley_pos + egui::vec2(row.rect().right() + GAP, row.rect().center().y);
+            positions.push((pos, text));
+        }
+        line += 1;
+    }
+    positions
+}
+
+pub fn paint(ui: &egui::Ui, annotations: &Annotations, galley: &egui::Galley, galley_pos: egui::Pos2) {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let color = ui.visuals().weak_text_color().gamma_multiply(0.7);
+    for (pos, text) in positions(annotations, galley, galley_pos) {
+        ui.painter().text(pos, egui::Align2::LEFT_CENTER, text, font_id.clone(), color);
+    }
+}
@@ -0,0 +1,147 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+use std::ops::Range;
+
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+fn opening_for(c: char) -> Option<char> {
+    PAIRS.iter().find(|(_, close)| *close == c).map(|(open, _)| *open)
+}
+
+fn closing_for(c: char) -> Option<char> {
+    PAIRS.iter().find(|(open, _)| *open == c).map(|(_, close)| *close)
+}
+
+// Index of the bracket pairing with the one at `at`, counting nesting of
+// the same kind only.
+fn pair_of(chars: &[char], at: usize) -> Option<usize> {
+    let c = *chars.get(at)?;
+    if let Some(close) = closing_for(c) {
+        let mut depth = 0usize;
+        for (idx, other) in chars.iter().enumerate().skip(at) {
+            if *other == c {
+                depth += 1;
+            } else if *other == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+        }
+        return None;
+    }
+    let open = opening_for(c)?;
+    let mut depth = 0usize;
+    for idx in (0..=at).rev() {
+        if chars[idx] == c {
+            depth += 1;
+        } else if chars[idx] == open {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+// The bracket under the cursor, or the one right before it, paired with its
+// match. Returns where the cursor goes, on the matching bracket.
+pub fn matching(content: &str, char_idx: usize) -> Option<usize> {
+    let chars: Vec<char> = content.chars().collect();
+    pair_of(&chars, char_idx).or_else(|| pair_of(&chars, char_idx.checked_sub(1)?))
+}
+
+// The innermost pair around `char_idx` as (opening, closing) indices.
+pub fn enclosing(content: &str, char_idx: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = content.chars().collect();
+    // Closing brackets passed on the way left, waiting for their opening.
+    let mut closed = Vec::new();
+    for idx in (0..char_idx.min(chars.len())).rev() {
+        let c = chars[idx];
+        if opening_for(c).is_some() {
+            closed.push(c);
+        } else if closing_for(c).is_some() {
+            if closed.last().copied() == closing_for(c) {
+                closed.pop();
+            } else if let Some(close) = pair_of(&chars, idx) {
+                return Some((idx, close));
+            }
+        }
+    }
+    None
+}
+
+// The text between the innermost pair around `char_idx`, with or without
+// the brackets themselves.
+pub fn inside(content: &str, char_idx: usize, include_brackets: bool) -> Option<Range<usize>> {
+    let (open, close) = enclosing(content, char_idx)?;
+    Some(if include_brackets { open..close + 1 } else { open + 1..close })
+}
+
+// Selects inside the enclosing brackets on Cmd+Alt+B, including them with
+// Shift. Outside any pair the key does nothing.
+pub fn handle_keys(ctx: &egui::Context, id: egui::Id, content: &str) {
+    let mut include_brackets = None;
+    ctx.input_mut(|input| {
+        input.events.retain(|event| {
+            let egui::Event::Key { key: egui::Key::B, pressed, modifiers, .. } = event else {
+                return true;
+            };
+            let selecting = egui::Modifiers { shift: false, ..*modifiers };
+            if selecting != egui::Modifiers::COMMAND.plus(egui::Modifiers::ALT) {
+                return true;
+            }
+            if *pressed {
+                include_brackets = Some(modifiers.shift);
+            }
+            false
+        });
+    });
+    let Some(include_brackets) = include_brackets else {
+        return;
+    };
+    let Some(mut state) = egui::TextEdit::load_state(ctx, id) else {
+        return;
+    };
+    let Some(range) = state.cursor.char_range() else {
+        return;
+    };
+    let Some(selection) = inside(content, range.primary.index.min(range.secondary.index), include_brackets) else {
+        return;
+    };
+    state.cursor.set_char_range(Some(CCursorRange {
+        primary: CCursor::new(selection.end),
+        secondary: CCursor::new(selection.start),
+        h_pos: None,
+    }));
+    state.store(ctx, id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brackets_jump_to_their_match_across_nesting() {
+        let content = "f(a[0], (b))";
+        assert_eq!(matching(content, 1), Some(11));
+        assert_eq!(matching(content, 11), Some(1));
+        // Right after a bracket counts as on it.
+        assert_eq!(matching(content, 12), Some(1));
+        assert_eq!(matching(content, 8), Some(10));
+        assert_eq!(matching(content, 0), None);
+        assert_eq!(matching("(()", 0), None);
+    }
+
+    #[test]
+    fn inside_selects_the_innermost_pair() {
+        let content = "{ x(a, [b]) }";
+        assert_eq!(enclosing(content, 5), Some((3, 10)));
+        assert_eq!(inside(content, 5, false), Some(4..10));
+        assert_eq!(inside(content, 8, true), Some(7..10));
+        assert_eq!(inside(content, 12, false), Some(1..12));
+        assert_eq!(inside("no brackets", 3, false), None);
+    }
+}
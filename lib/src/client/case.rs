@@ -0,0 +1,153 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::ops::Range;
+
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::{byte_index, Buffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    Title,
+}
+
+impl Case {
+    pub fn for_key(key: egui::Key, modifiers: egui::Modifiers) -> Option<Self> {
+        if modifiers != egui::Modifiers::COMMAND.plus(egui::Modifiers::ALT) {
+            return None;
+        }
+        match key {
+            egui::Key::U => Some(Self::Upper),
+            egui::Key::L => Some(Self::Lower),
+            egui::Key::T => Some(Self::Title),
+            _ => None,
+        }
+    }
+
+    // Full Unicode mappings, so the length can change (`ß` upper-cases to
+    // `SS`).
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::Upper => text.to_uppercase(),
+            Self::Lower => text.to_lowercase(),
+            Self::Title => text.split_word_bounds().map(title_case_word).collect(),
+        }
+    }
+}
+
+fn title_case_word(word: &str) -> String {
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let upper: String = first.to_uppercase().collect();
+    let mut upper_chars = upper.chars();
+    let mut title: String = upper_chars.next().into_iter().collect();
+    title.push_str(&upper_chars.as_str().to_lowercase());
+    title.push_str(&chars.as_str().to_lowercase());
+    title
+}
+
+// Replaces each selection with its transformed text. Returns the actions,
+// meant to be applied as one edit, and where each selection ends up.
+pub fn transform(content: &str, selections: &[Range<usize>], case: Case) -> (Vec<BufferActions>, Vec<Range<usize>>) {
+    let mut order: Vec<usize> = (0..selections.len()).collect();
+    order.sort_by_key(|&idx| selections[idx].start);
+
+    let mut actions = Vec::new();
+    let mut moved = selections.to_vec();
+    let mut shift = 0isize;
+    for idx in order {
+        let Range { start, end } = selections[idx];
+        let text = &content[byte_index(content, start)..byte_index(content, end)];
+        let replaced = case.apply(text);
+        let new_start = start.saturating_add_signed(shift);
+        let new_len = replaced.chars().count();
+        moved[idx] = new_start..new_start + new_len;
+        if replaced != text {
+            // Actions run in order, so earlier replacements have already
+            // shifted this selection by `shift`.
+            actions.push(BufferActions::DeleteRange { start: new_start, end: new_start + (end - start) });
+            actions.push(BufferActions::InsertText { char_idx: new_start, text: replaced });
+        }
+        shift += new_len as isize - (end - start) as isize;
+    }
+    (actions, moved)
+}
+
+// Applies a case shortcut to the editor's selection as a single undo step
+// and keeps the transformed text selected. Returns the actions and the new
+// cursor for macro recording.
+pub fn handle_keys(ctx: &egui::Context, id: egui::Id, buffer: &mut Buffer) -> Option<(Vec<BufferActions>, usize)> {
+    let mut case = None;
+    ctx.input_mut(|input| {
+        input.events.retain(|event| {
+            let egui::Event::Key { key, pressed, modifiers, .. } = event else {
+                return true;
+            };
+            let Some(matched) = Case::for_key(*key, *modifiers) else {
+                return true;
+            };
+            if *pressed {
+                case = Some(matched);
+            }
+            false
+        });
+    });
+    let case = case?;
+
+    let mut state = egui::TextEdit::load_state(ctx, id)?;
+    let range = state.cursor.char_range()?;
+    let (primary, secondary) = (range.primary.index, range.secondary.index);
+    if primary == secondary {
+        return None;
+    }
+    let selection = primary.min(secondary)..primary.max(secondary);
+    let (actions, moved) = transform(buffer.content(), &[selection], case);
+    if actions.is_empty() {
+        return None;
+    }
+    buffer.edit(&actions);
+
+    let moved = &moved[0];
+    let (primary, secondary) = if primary > secondary { (moved.end, moved.start) } else { (moved.start, moved.end) };
+    state.cursor.set_char_range(Some(CCursorRange {
+        primary: CCursor::new(primary),
+        secondary: CCursor::new(secondary),
+        h_pos: None,
+    }));
+    state.store(ctx, id);
+    Some((actions, primary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn case_mappings_cover_length_changes_and_title_case() {
+        assert_eq!(Case::Upper.apply("straße"), "STRASSE");
+        assert_eq!(Case::Lower.apply("ÉTÉ"), "été");
+        assert_eq!(Case::Title.apply("hello wORLD, élan"), "Hello World, Élan");
+        assert_eq!(Case::for_key(egui::Key::U, egui::Modifiers::COMMAND.plus(egui::Modifiers::ALT)), Some(Case::Upper));
+        assert_eq!(Case::for_key(egui::Key::U, egui::Modifiers::COMMAND), None);
+    }
+
+    #[test]
+    fn selections_are_transformed_as_one_edit_and_follow_the_new_text() {
+        let mut buffer = Buffer::with_content("ß and ß");
+        let (actions, moved) = transform(buffer.content(), &[6..7, 0..1, 2..5], Case::Upper);
+        assert_eq!(moved, vec![7..9, 0..2, 3..6]);
+        buffer.edit(&actions);
+        assert_eq!(buffer.content(), "SS AND SS");
+        assert!(buffer.undo());
+        assert_eq!(buffer.content(), "ß and ß");
+
+        let (actions, moved) = transform("ABC", &[0..1, 2..3], Case::Upper);
+        assert!(actions.is_empty());
+        assert_eq!(moved, vec![0..1, 2..3]);
+    }
+}
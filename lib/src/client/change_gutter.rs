@@ -0,0 +1,148 @@
+use crate::server::diff::{line_diff, DiffOp};
+use crate::server::persistence::MmapStorage;
+use crate::shared::buffers::Buffer;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+pub const GUTTER_WIDTH: f32 = 4.0;
+// Typing holds off the diff until it pauses this long; the marks from
+// before stay up meanwhile.
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+const ADDED_COLOR: egui::Color32 = egui::Color32::from_rgb(90, 170, 90);
+const MODIFIED_COLOR: egui::Color32 = egui::Color32::from_rgb(90, 140, 210);
+const REMOVED_COLOR: egui::Color32 = egui::Color32::from_rgb(210, 90, 90);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    // Lines were removed right before this one.
+    Removed,
+}
+
+type Changes = Arc<Vec<(usize, LineChange)>>;
+
+// Per editor: the saved text, read once per save, and the marks for the
+// content they were last computed on.
+#[derive(Clone, Default)]
+struct GutterState {
+    saved_hash: Option<u64>,
+    saved: Option<Arc<str>>,
+    content_hash: u64,
+    edited_at: Option<Instant>,
+    computed_for: Option<u64>,
+    changes: Changes,
+}
+
+// How each line of `content` differs from `saved`, by line of `content`.
+// In a run of changed lines, as many as were removed count as modified
+// and the rest as added; a run with only removals marks the line after.
+pub fn line_changes(saved: &str, content: &str) -> Vec<(usize, LineChange)> {
+    let line_count = content.lines().count();
+    let mut changes = Vec::new();
+    let mut removed = 0;
+    let mut inserted = Vec::new();
+    let mut flush = |removed: &mut usize, inserted: &mut Vec<usize>, next_line: usize| {
+        if inserted.is_empty() && *removed > 0 {
+            changes.push((next_line.min(line_count.saturating_sub(1)), LineChange::Removed));
+        }
+        for (idx, line) in inserted.drain(..).enumerate() {
+            let change = if idx < *removed { LineChange::Modified } else { LineChange::Added };
+            changes.push((line, change));
+        }
+        *removed = 0;
+    };
+    for op in line_diff(saved, content) {
+        match op {
+            DiffOp::Equal(_, line) => flush(&mut removed, &mut inserted, line),
+            DiffOp::Delete(_) => removed += 1,
+            DiffOp::Insert(line) => inserted.push(line),
+        }
+    }
+    flush(&mut removed, &mut inserted, line_count);
+    changes.sort_by_key(|(line, _)| *line);
+    changes
+}
+
+// The marks for the editor `id`, against the file as last saved. Nothing
+// for buffers without a file or without unsaved changes.
+pub fn cached_changes(ctx: &egui::Context, id: egui::Id, buffer: &Buffer) -> Changes {
+    let id = id.with("change_gutter");
+    let content_hash = buffer.content_hash();
+    if buffer.is_scratch || buffer.file_path.as_os_str().is_empty() || content_hash == buffer.saved_hash {
+        ctx.data_mut(|data| data.remove::<GutterState>(id));
+        return Changes::default();
+    }
+    let mut state = ctx.data(|data| data.get_temp::<GutterState>(id)).unwrap_or_default();
+    let now = Instant::now();
+    if state.saved_hash != Some(buffer.saved_hash) {
+        state.saved_hash = Some(buffer.saved_hash);
+        state.saved = buffer.disk_content(&MmapStorage).ok().map(Arc::from);
+        state.computed_for = None;
+    }
+    if state.content_hash != content_hash || state.edited_at.is_none() {
+        state.content_hash = content_hash;
+        state.edited_at = Some(now);
+    }
+    let waited = state.edited_at.map_or(SETTLE_DELAY, |edited_at| now.duration_since(edited_at));
+    if state.computed_for != Some(content_hash) {
+        if state.computed_for.is_none() || waited >= SETTLE_DELAY {
+            state.changes = Arc::new(line_changes(state.saved.as_deref().unwrap_or(""), buffer.content()));
+            state.computed_for = Some(content_hash);
+        } else {
+            ctx.request_repaint_after(SETTLE_DELAY - waited);
+        }
+    }
+    let changes = state.changes.clone();
+    ctx.data_mut(|data| data.insert_temp(id, state));
+    changes
+}
+
+// Draws a bar for each changed line `offset` left of `galley_pos`, over
+// every row a wrapped line takes up. Folded lines are skipped.
+pub fn paint(ui: &egui::Ui, changes: &[(usize, LineChange)], galley: &egui::Galley, galley_pos: egui::Pos2, offset: f32) {
+    if changes.is_empty() {
+        return;
+    }
+    let left = galley_pos.x - offset;
+    let mut line = 0;
+    for row in &galley.rows {
+        let rect = row.rect().translate(galley_pos.to_vec2());
+        if rect.height() >= 1.0
+            && let Ok(found) = changes.binary_search_by_key(&line, |(line, _)| *line)
+        {
+            let (min_y, max_y, color) = match changes[found].1 {
+                LineChange::Added => (rect.top(), rect.bottom(), ADDED_COLOR),
+                LineChange::Modified => (rect.top(), rect.bottom(), MODIFIED_COLOR),
+                LineChange::Removed => (rect.top() - 1.0, rect.top() + 2.0, REMOVED_COLOR),
+            };
+            let bar = egui::Rect::from_x_y_ranges(left..=left + GUTTER_WIDTH - 1.0, min_y..=max_y);
+            ui.painter().rect_filled(bar, 0.0, color);
+        }
+        if row.ends_with_newline {
+            line += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_lines_are_marked_added_modified_or_removed() {
+        let saved = "a\nb\nc\nd\ne\n";
+        assert!(line_changes(saved, saved).is_empty());
+        assert_eq!(
+            line_changes(saved, "a\nB\nnew\nc\ne\n"),
+            [(1, LineChange::Modified), (2, LineChange::Added), (4, LineChange::Removed)]
+        );
+        // Removing the last lines marks the one left at the end.
+        assert_eq!(line_changes(saved, "a\nb\n"), [(1, LineChange::Removed)]);
+        assert_eq!(line_changes("", "x\n"), [(0, LineChange::Added)]);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use super::sort::SortOptions;
+
+use eframe::egui;
+
+// Everything that can be asked of the editor outside of typing. Menus and
+// key bindings all go through `Client::dispatch`; commands act on the
+// current window, frame and buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    OpenFile,
+    Save,
+    SaveAs,
+    SaveAll,
+    // The current buffer, or its selection, as highlighted HTML.
+    ExportHtml,
+    Close,
+    Split,
+    CloseFrame,
+    Undo,
+    Redo,
+    Find,
+    // 1-based, as shown to the user.
+    GoToLine(u32),
+    NewWindow,
+    Reload,
+    HardReload,
+    DuplicateLine,
+    // The lines the selection touches, as one edit.
+    SortLines(SortOptions),
+    // Drops repeated lines in the selection, anywhere or only next to
+    // each other.
+    UniqueLines { adjacent: bool },
+    ReverseLines,
+    // Runs the last repeatable command again at the cursor.
+    RepeatLast,
+    // Goes back through the places edited last; see `RecentEdits`.
+    PreviousEdit,
+    // Replays the macro at the cursor and asks whether to keep the result.
+    PreviewMacro,
+}
+
+impl Command {
+    // Names as written in the `keymap` config, e.g. `save` or `go_to_line 42`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split_whitespace();
+        let command = match parts.next()? {
+            "open_file" => Self::OpenFile,
+            "save" => Self::Save,
+            "save_as" => Self::SaveAs,
+            "save_all" => Self::SaveAll,
+            "export_html" => Self::ExportHtml,
+            "close" => Self::Close,
+            "split" => Self::Split,
+            "close_frame" => Self::CloseFrame,
+            "undo" => Self::Undo,
+            "redo" => Self::Redo,
+            "find" => Self::Find,
+            "go_to_line" => Self::GoToLine(parts.next()?.parse().ok()?),
+            "new_window" => Self::NewWindow,
+            "reload" => Self::Reload,
+            "hard_reload" => Self::HardReload,
+            "duplicate_line" => Self::DuplicateLine,
+            // `sort_lines [desc] [ignore_case] [numeric]`
+            "sort_lines" => {
+                let mut options = SortOptions::default();
+                for flag in parts.by_ref() {
+                    match flag {
+                        "desc" => options.descending = true,
+                        "ignore_case" => options.ignore_case = true,
+                        "numeric" => options.numeric = true,
+                        _ => return None,
+                    }
+                }
+                Self::SortLines(options)
+            }
+            // `unique_lines [adjacent]`
+            "unique_lines" => match parts.next() {
+                None => Self::UniqueLines { adjacent: false },
+                Some("adjacent") => Self::UniqueLines { adjacent: true },
+                Some(_) => return None,
+            },
+            "reverse_lines" => Self::ReverseLines,
+            "repeat_last" => Self::RepeatLast,
+            "previous_edit" => Self::PreviousEdit,
+            "preview_macro" => Self::PreviewMacro,
+            _ => return None,
+        };
+        parts.next().is_none().then_some(command)
+    }
+
+    // Commands that change text where the cursor is, which is what repeating
+    // is for; moving around or saving again is left to their own keys.
+    pub fn is_repeatable(self) -> bool {
+        matches!(self, Self::DuplicateLine)
+    }
+}
+
+// Shortcuts like `Ctrl+Shift+S`. `Ctrl` and `Cmd` both mean the platform's
+// command key.
+fn parse_shortcut(text: &str) -> Option<egui::KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = egui::Key::from_name(parts.pop()?)?;
+    let mut modifiers = egui::Modifiers::NONE;
+    for part in parts {
+        modifiers = modifiers.plus(match part.to_lowercase().as_str() {
+            "ctrl" | "cmd" => egui::Modifiers::COMMAND,
+            "shift" => egui::Modifiers::SHIFT,
+            "alt" => egui::Modifiers::ALT,
+            _ => return None,
+        });
+    }
+    Some(egui::KeyboardShortcut::new(modifiers, key))
+}
+
+// The `keymap` config as shortcuts. Entries that don't parse are reported
+// and left out.
+pub fn bindings(keymap: &HashMap<String, String>) -> Vec<(egui::KeyboardShortcut, Command)> {
+    keymap
+        .iter()
+        .filter_map(|(shortcut, command)| match (parse_shortcut(shortcut), Command::parse(command)) {
+            (Some(shortcut), Some(command)) => Some((shortcut, command)),
+            _ => {
+                log::warn!("cant bind `{shortcut}` to `{command}`");
+                None
+            }
+        })
+        .collect()
+}
+
+// Commands whose shortcuts were pressed this frame. The presses are taken
+// out of the input so editors don't see them too.
+pub fn pressed(ctx: &egui::Context, bindings: &[(egui::KeyboardShortcut, Command)]) -> Vec<Command> {
+    ctx.input_mut(|input| {
+        bindings
+            .iter()
+            .filter(|(shortcut, _)| input.consume_shortcut(shortcut))
+            .map(|(_, command)| *command)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse_with_their_arguments_and_nothing_more() {
+        assert_eq!(Command::parse("save"), Some(Command::Save));
+        assert_eq!(Command::parse("  go_to_line 42 "), Some(Command::GoToLine(42)));
+        assert_eq!(Command::parse("go_to_line"), None);
+        assert_eq!(Command::parse("go_to_line -1"), None);
+        assert_eq!(Command::parse("save now"), None);
+        assert_eq!(Command::parse("unique_lines adjacent"), Some(Command::UniqueLines { adjacent: true }));
+        let options = SortOptions { descending: true, numeric: true, ..SortOptions::default() };
+        assert_eq!(Command::parse("sort_lines numeric desc"), Some(Command::SortLines(options)));
+        assert_eq!(Command::parse("sort_lines sideways"), None);
+        assert_eq!(Command::parse(""), None);
+    }
+
+    #[test]
+    fn keymap_entries_bind_and_presses_are_taken_from_the_input() {
+        let keymap = HashMap::from([
+            (String::from("Ctrl+Shift+S"), String::from("save_all")),
+            (String::from("Alt+G"), String::from("go_to_line 1")),
+            (String::from("Hyper+X"), String::from("save")),
+            (String::from("Ctrl+Q"), String::from("quit")),
+        ]);
+        let mut bound = bindings(&keymap);
+        bound.sort_by_key(|(_, command)| format!("{command:?}"));
+        let save_all = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT), egui::Key::S);
+        let go_to_line = egui::KeyboardShortcut::new(egui::Modifiers::ALT, egui::Key::G);
+        assert_eq!(bound, [(go_to_line, Command::GoToLine(1)), (save_all, Command::SaveAll)]);
+
+        let modifiers = egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT);
+        let input = egui::RawInput {
+            modifiers,
+            events: vec![egui::Event::Key { key: egui::Key::S, physical_key: None, pressed: true, repeat: false, modifiers }],
+            ..egui::RawInput::default()
+        };
+        let mut commands = Vec::new();
+        let mut left = 0;
+        let _ = egui::Context::default().run(input, |ctx| {
+            commands = pressed(ctx, &bound);
+            left = ctx.input(|input| input.events.len());
+        });
+        assert_eq!((commands, left), (vec![Command::SaveAll], 0));
+    }
+}
@@ -0,0 +1,147 @@
+use eframe::egui;
+
+use super::windows::Window;
+use crate::shared::frames::FrameKind;
+use crate::shared::{LockStats, Shared};
+
+pub const TOGGLE_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::SHIFT, egui::Key::F12);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameReport {
+    pub cluster_idx: usize,
+    pub frame_idx: usize,
+    pub kind: FrameKind,
+    pub buffer_index: usize,
+    pub position_type: String,
+    pub position: (i32, i32),
+}
+
+// What the overlay shows, read once per frame it's open.
+#[derive(Debug, Clone, Default)]
+pub struct DebugReport {
+    // (id, cluster index)
+    pub windows: Vec<(u32, usize)>,
+    pub clusters: usize,
+    pub frames: Vec<FrameReport>,
+    pub buffers: usize,
+    pub frames_lock: LockStats,
+    pub buffers_lock: LockStats,
+}
+
+impl DebugReport {
+    pub fn capture(shared: &Shared, windows: &[Window]) -> Self {
+        let (clusters, frames) = shared
+            .with_frames(|storage| {
+                let frames = storage
+                    .frame_clusters
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(cluster_idx, cluster)| {
+                        cluster.frames.iter().enumerate().map(move |(frame_idx, frame)| FrameReport {
+                            cluster_idx,
+                            frame_idx,
+                            kind: frame.kind,
+                            buffer_index: frame.buffer_index,
+                            position_type: format!("{:?}", frame.position_type),
+                            position: (frame.position.x, frame.position.y),
+                        })
+                    })
+                    .collect();
+                (storage.frame_clusters.len(), frames)
+            })
+            .unwrap_or_default();
+        let (frames_lock, buffers_lock) = shared.lock_stats();
+        Self {
+            windows: windows.iter().map(|window| (window.id, window.frame_cluster_index)).collect(),
+            clusters,
+            frames,
+            buffers: shared.buffer_meta().len(),
+            frames_lock,
+            buffers_lock,
+        }
+    }
+}
+
+// Frame and window structure for tracking down index mismatches. Indices
+// pointing at nothing are shown in the error color.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub open: bool,
+}
+
+impl DebugOverlay {
+    pub fn show(&mut self, ctx: &egui::Context, shared: &Shared, windows: &[Window]) {
+        if !self.open {
+            return;
+        }
+        let report = DebugReport::capture(shared, windows);
+        let error = ctx.style().visuals.error_fg_color;
+        egui::Window::new("Debug").open(&mut self.open).show(ctx, |ui| {
+            ui.label(format!(
+                "{} windows, {} clusters, {} frames, {} buffers",
+                report.windows.len(),
+                report.clusters,
+                report.frames.len(),
+                report.buffers
+            ));
+            for (name, stats) in [("frames", report.frames_lock), ("buffers", report.buffers_lock)] {
+                ui.label(format!("{name} lock: {} taken, {} contended", stats.acquired, stats.contended));
+            }
+            ui.separator();
+            for (id, cluster_idx) in &report.windows {
+                let text = format!("window #{id} -> cluster {cluster_idx}");
+                if *cluster_idx < report.clusters {
+                    ui.label(text);
+                } else {
+                    ui.colored_label(error, text);
+                }
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for frame in &report.frames {
+                    let text = format!(
+                        "[{}.{}] {:?} buffer {} {} at ({}, {})",
+                        frame.cluster_idx,
+                        frame.frame_idx,
+                        frame.kind,
+                        frame.buffer_index,
+                        frame.position_type,
+                        frame.position.0,
+                        frame.position.1
+                    );
+                    if frame.buffer_index < report.buffers {
+                        ui.monospace(text);
+                    } else {
+                        ui.colored_label(error, text);
+                    }
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::buffers::BufferStorage;
+    use crate::shared::frames::{Frame, FrameCluster, FrameStorage};
+
+    #[test]
+    fn the_report_lists_every_frame_and_window_as_they_are() {
+        let frames = FrameStorage {
+            frame_clusters: vec![
+                FrameCluster { frames: vec![Frame::default(), Frame { buffer_index: 5, ..Frame::default() }], ..FrameCluster::default() },
+                FrameCluster { frames: Vec::new(), ..FrameCluster::default() },
+            ],
+        };
+        let shared = Shared::new(BufferStorage::default(), frames);
+        let windows = [Window::default("Riptide"), Window { id: 1, frame_cluster_index: 9, ..Window::default("Riptide") }];
+        let report = DebugReport::capture(&shared, &windows);
+        assert_eq!((report.clusters, report.buffers), (2, 1));
+        assert_eq!(report.windows, [(0, 0), (1, 9)]);
+        let frames: Vec<_> = report.frames.iter().map(|frame| (frame.cluster_idx, frame.frame_idx, frame.buffer_index)).collect();
+        assert_eq!(frames, [(0, 0, 0), (0, 1, 5)]);
+        assert!(report.frames_lock.acquired >= 1);
+    }
+}
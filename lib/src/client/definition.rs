@@ -0,0 +1,41 @@
+use crate::server::lsp::Location;
+
+use eframe::egui;
+
+// Lets the user choose when a definition request returns several targets.
+#[derive(Default)]
+pub struct DefinitionPicker {
+    pub locations: Vec<Location>,
+}
+
+impl DefinitionPicker {
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<Location> {
+        if self.locations.is_empty() {
+            return None;
+        }
+
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("Go to definition")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                for location in &self.locations {
+                    let label = format!(
+                        "{}:{}:{}",
+                        location.path.display(),
+                        location.position.line + 1,
+                        location.position.character + 1
+                    );
+                    if ui.selectable_label(false, label).clicked() {
+                        chosen = Some(location.clone());
+                    }
+                }
+            });
+
+        if !open || chosen.is_some() {
+            self.locations.clear();
+        }
+        chosen
+    }
+}
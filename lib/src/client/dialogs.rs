@@ -0,0 +1,160 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use eframe::egui;
+
+use crate::server::wake::Waker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogRequest {
+    Open,
+    // Starts out at the buffer's current path, if it has one.
+    SaveAs { buffer_index: usize, suggested: PathBuf },
+    ExportHtml { buffer_index: usize, suggested: PathBuf },
+}
+
+// Asks the user for a path. Runs off the UI thread, so it may block for as
+// long as the dialog is up. `None` means the dialog was cancelled or
+// couldn't be shown.
+pub trait FileDialog: Send + Sync {
+    fn pick(&self, request: &DialogRequest) -> Option<PathBuf>;
+
+    // Why `pick` can't show anything here, if it can't. The path is then
+    // typed into a `PathPrompt` instead.
+    fn unavailable(&self) -> Option<String> {
+        None
+    }
+}
+
+// The desktop's file chooser through `zenity`, which is a separate program:
+// it is only there on desktops that ship it or where it was installed, and
+// only works where it can open a window of its own. Without it the editor
+// asks for the path itself, with no browsing.
+#[derive(Default)]
+pub struct ZenityDialog;
+
+// Looked up once; installing zenity while the editor runs needs a restart.
+fn zenity_installed() -> bool {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+    *INSTALLED.get_or_init(|| {
+        env::var_os("PATH").is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join("zenity").is_file()))
+    })
+}
+
+impl FileDialog for ZenityDialog {
+    fn unavailable(&self) -> Option<String> {
+        (!zenity_installed()).then(|| String::from("zenity isn't installed, so there's no file chooser"))
+    }
+
+    fn pick(&self, request: &DialogRequest) -> Option<PathBuf> {
+        let mut command = Command::new("zenity");
+        command.arg("--file-selection");
+        if let DialogRequest::SaveAs { suggested, .. } | DialogRequest::ExportHtml { suggested, .. } = request {
+            command.args(["--save", "--confirm-overwrite"]);
+            if !suggested.as_os_str().is_empty() {
+                command.arg("--filename").arg(suggested);
+            }
+        }
+        // Cancelling exits with 1 and prints nothing.
+        match command.output() {
+            Ok(output) if output.status.success() => {
+                let path = String::from_utf8_lossy(&output.stdout).trim_end_matches('\n').to_string();
+                (!path.is_empty()).then(|| PathBuf::from(path))
+            }
+            Ok(_) => None,
+            Err(err) => {
+                log::error!("cant show file dialog: {err}");
+                None
+            }
+        }
+    }
+}
+
+// A dialog that's up on its own thread. The UI is woken when it closes.
+pub struct PendingDialog {
+    pub request: DialogRequest,
+    result: mpsc::Receiver<Option<PathBuf>>,
+}
+
+impl PendingDialog {
+    pub fn spawn(dialog: Arc<dyn FileDialog>, request: DialogRequest, waker: Waker) -> Self {
+        let (sender, result) = mpsc::channel();
+        let asked = request.clone();
+        thread::spawn(move || {
+            let _ = sender.send(dialog.pick(&asked));
+            waker.wake();
+        });
+        Self { request, result }
+    }
+
+    // `Some` once the dialog is closed, with the path if one was chosen.
+    pub fn poll(&self) -> Option<Option<PathBuf>> {
+        match self.result.try_recv() {
+            Ok(picked) => Some(picked),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(None),
+        }
+    }
+}
+
+// Asks for a path in the editor, for when no file dialog can be shown.
+#[derive(Default)]
+pub struct PathPrompt {
+    pub request: Option<DialogRequest>,
+    pub path: String,
+}
+
+impl PathPrompt {
+    pub fn open(&mut self, request: DialogRequest) {
+        self.path = match &request {
+            DialogRequest::Open => String::new(),
+            DialogRequest::SaveAs { suggested, .. } | DialogRequest::ExportHtml { suggested, .. } => {
+                suggested.display().to_string()
+            }
+        };
+        self.request = Some(request);
+    }
+
+    // The request and the typed path once confirmed; closing the window
+    // cancels like a cancelled dialog.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<(DialogRequest, PathBuf)> {
+        let title = match self.request.as_ref()? {
+            DialogRequest::Open => "Open file",
+            DialogRequest::SaveAs { .. } => "Save as",
+            DialogRequest::ExportHtml { .. } => "Export as HTML",
+        };
+        let mut open = true;
+        let mut submitted = false;
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.path);
+                response.request_focus();
+                submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                submitted |= ui.button("OK").clicked();
+            });
+
+        if !open {
+            self.request = None;
+            return None;
+        }
+        if !submitted {
+            return None;
+        }
+        self.submit()
+    }
+
+    // An empty path keeps the prompt up.
+    pub fn submit(&mut self) -> Option<(DialogRequest, PathBuf)> {
+        let path = self.path.trim();
+        if path.is_empty() {
+            return None;
+        }
+        Some((self.request.take()?, PathBuf::from(path)))
+    }
+}
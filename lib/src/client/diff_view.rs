@@ -0,0 +1,101 @@
+use crate::server::diff::{self, DiffOp};
+use crate::shared::Shared;
+
+use eframe::egui;
+
+const ADDED_COLOR: egui::Color32 = egui::Color32::from_rgb(40, 90, 40);
+const REMOVED_COLOR: egui::Color32 = egui::Color32::from_rgb(110, 40, 40);
+
+// The compared texts are snapshotted when the diff is computed so the rows
+// stay aligned while the buffers keep changing underneath.
+#[derive(Default)]
+pub struct DiffView {
+    pub open: bool,
+    pub left: usize,
+    pub right: usize,
+    left_lines: Vec<String>,
+    right_lines: Vec<String>,
+    ops: Vec<DiffOp>,
+}
+
+impl DiffView {
+    pub fn compare(&mut self, shared: &Shared, left: usize, right: usize) {
+        let texts = shared.with_buffers(|buffers| {
+            let old = buffers.buffers.get(left)?.content().to_string();
+            let new = buffers.buffers.get(right)?.content().to_string();
+            Some((old, new))
+        });
+        let Ok(Some((old, new))) = texts else {
+            return;
+        };
+        self.left = left;
+        self.right = right;
+        self.compare_texts(&old, &new);
+    }
+
+    // Compares texts that aren't both buffers, e.g. a buffer against its
+    // file on disk.
+    pub fn compare_texts(&mut self, old: &str, new: &str) {
+        self.left_lines = old.lines().map(String::from).collect();
+        self.right_lines = new.lines().map(String::from).collect();
+        self.ops = diff::line_diff(old, new);
+        self.open = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, shared: &Shared) {
+        let mut open = self.open;
+        let (mut left, mut right) = (self.left, self.right);
+
+        egui::Window::new("Diff")
+            .open(&mut open)
+            .default_size([640.0, 400.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let count = shared.with_buffers(|buffers| buffers.buffers.len()).unwrap_or(0);
+                    let max = count.saturating_sub(1);
+                    ui.add(egui::DragValue::new(&mut left).range(0..=max).prefix("left: "));
+                    ui.add(egui::DragValue::new(&mut right).range(0..=max).prefix("right: "));
+                    if ui.button("Refresh").clicked() {
+                        self.compare(shared, left, right);
+                    }
+                });
+                ui.separator();
+                self.show_rows(ui);
+            });
+
+        self.open = open;
+        if (left, right) != (self.left, self.right) {
+            self.compare(shared, left, right);
+        }
+    }
+
+    fn show_rows(&self, ui: &mut egui::Ui) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both().show_rows(ui, row_height, self.ops.len(), |ui, rows| {
+            egui::Grid::new("diff_rows").num_columns(4).show(ui, |ui| {
+                for op in &self.ops[rows] {
+                    let (old, new, color) = match *op {
+                        DiffOp::Equal(old, new) => (Some(old), Some(new), None),
+                        DiffOp::Delete(old) => (Some(old), None, Some(REMOVED_COLOR)),
+                        DiffOp::Insert(new) => (None, Some(new), Some(ADDED_COLOR)),
+                    };
+                    Self::show_side(ui, old, &self.left_lines, color);
+                    Self::show_side(ui, new, &self.right_lines, color);
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    fn show_side(ui: &mut egui::Ui, line: Option<usize>, lines: &[String], color: Option<egui::Color32>) {
+        let gutter = line.map(|line| (line + 1).to_string()).unwrap_or_default();
+        ui.weak(egui::RichText::new(gutter).monospace());
+
+        let text = line.and_then(|line| lines.get(line)).map_or("", String::as_str);
+        let mut text = egui::RichText::new(text).monospace();
+        if let (Some(color), Some(_)) = (color, line) {
+            text = text.background_color(color);
+        }
+        ui.label(text);
+    }
+}
@@ -0,0 +1,28 @@
+use crate::interfaces::enums::BufferActions;
+
+// Copies the cursor's line in below itself. Returns the action and where
+// the cursor goes: the same column on the copy.
+pub fn duplicate_line(content: &str, cursor: usize) -> (BufferActions, usize) {
+    let chars: Vec<char> = content.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let start = chars[..cursor].iter().rposition(|c| *c == '\n').map_or(0, |idx| idx + 1);
+    let end = chars[cursor..].iter().position(|c| *c == '\n').map_or(chars.len(), |idx| cursor + idx);
+    let line: String = chars[start..end].iter().collect();
+    let action = BufferActions::InsertText { char_idx: end, text: format!("\n{line}") };
+    (action, cursor + line.chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_copy_goes_below_with_the_cursor_on_the_same_column() {
+        assert_eq!(
+            duplicate_line("one\ntwo\nthree", 5),
+            (BufferActions::InsertText { char_idx: 7, text: String::from("\ntwo") }, 9)
+        );
+        assert_eq!(duplicate_line("last", 99), (BufferActions::InsertText { char_idx: 4, text: String::from("\nlast") }, 9));
+        assert_eq!(duplicate_line("a\n", 2), (BufferActions::InsertText { char_idx: 2, text: String::from("\n") }, 3));
+    }
+}
@@ -0,0 +1,471 @@
+use std::any::TypeId;
+use std::ops::Range;
+use std::time::Instant;
+
+use super::brackets;
+use super::case;
+use super::change_gutter;
+use super::folding;
+use super::guides::Guides;
+use super::highlight;
+use super::hover::{self, HoverInfo};
+use super::indent;
+use super::join;
+use super::modal::{ModalCommand, ModalState};
+use super::movement;
+use super::occurrences;
+use super::pairs;
+use super::search::SearchState;
+use super::spell::{self, SpellState};
+use super::tabs;
+use crate::config::ThemeId;
+use crate::interfaces::enums::{BufferActions, ClientEvents};
+use crate::server::syntax;
+use crate::shared::buffers::Buffer;
+
+use eframe::egui;
+
+const UNDO_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT), egui::Key::Z);
+const REDO_ALT_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+const SAVE_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+// Shift+F12 is left to the debug overlay.
+const DEFINITION_KEY: egui::Key = egui::Key::F12;
+const RECORD_MACRO_KEY: egui::Key = egui::Key::F3;
+const REPLAY_MACRO_KEY: egui::Key = egui::Key::F4;
+
+// Lets `egui::TextEdit` edit a `Buffer` while every change goes through
+// `Buffer::apply`, so typing produces the same `BufferActions` as commands
+// do. The inverses collected during one frame form one undo step, or join
+// the previous one; see `History::record_typed`.
+pub struct EditorText<'a> {
+    pub buffer: &'a mut Buffer,
+    pub actions: Vec<BufferActions>,
+    inverse: Vec<BufferActions>,
+}
+
+impl<'a> EditorText<'a> {
+    pub fn new(buffer: &'a mut Buffer) -> Self {
+        Self {
+            buffer,
+            actions: Vec::new(),
+            inverse: Vec::new(),
+        }
+    }
+
+    // Records this frame's edits in the buffer history, joining the
+    // previous frame's when typed in quick succession at the same spot, and
+    // returns them. `cursor` is where the edits left it.
+    pub fn finish(mut self, cursor: usize) -> Vec<BufferActions> {
+        self.inverse.reverse();
+        self.buffer.history.record_typed(&self.actions, self.inverse, Instant::now(), cursor);
+        self.actions
+    }
+
+    fn push(&mut self, action: BufferActions) {
+        let inverse = self.buffer.apply(&action);
+        self.actions.push(action);
+        self.inverse.push(inverse);
+    }
+}
+
+impl egui::TextBuffer for EditorText<'_> {
+    fn is_mutable(&self) -> bool {
+        true
+    }
+
+    fn as_str(&self) -> &str {
+        self.buffer.content()
+    }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        if text.is_empty() {
+            return 0;
+        }
+        self.push(BufferActions::InsertText {
+            char_idx: char_index,
+            text: text.to_string(),
+        });
+        text.chars().count()
+    }
+
+    fn delete_char_range(&mut self, char_range: Range<usize>) {
+        if char_range.is_empty() {
+            return;
+        }
+        self.push(BufferActions::DeleteRange {
+            start: char_range.start,
+            end: char_range.end,
+        });
+    }
+
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<EditorText<'static>>()
+    }
+}
+
+// Per-frame inputs the editors share, beyond the buffers and frames.
+pub struct EditorView<'a> {
+    pub hover: Option<&'a HoverInfo>,
+    pub guides: Guides,
+    // Syntax palette for buffers without their own.
+    pub syntax_theme: ThemeId,
+    // Close brackets and quotes as they're typed.
+    pub auto_pair: bool,
+    // Columns per indent level: what Tab and Shift+Tab add to or take off a
+    // selection's lines, and where tab characters stop.
+    pub indent_width: usize,
+    pub modal: Option<&'a mut ModalState>,
+    // The buffer whose editor last had focus.
+    pub active: Option<usize>,
+    // (cluster, frame) of that editor.
+    pub focused_frame: Option<(usize, usize)>,
+    // (buffer, char index) of the cursor in the focused editor.
+    pub cursor: Option<(usize, usize)>,
+    // (buffer, char range) selected there.
+    pub selection: Option<(usize, Range<usize>)>,
+    // Matches of the search panel's query, highlighted in every editor.
+    pub search: &'a mut SearchState,
+    pub spell: &'a mut SpellState,
+}
+
+// One editor on a buffer: the text with everything Riptide draws over it,
+// and the keys it handles on top of `TextEdit`'s. What the keys and edits
+// ask of the rest of the editor is pushed to `events`.
+pub struct EditorWidget<'a> {
+    id: egui::Id,
+    buffer: &'a mut Buffer,
+    buffer_index: usize,
+    is_terminal: bool,
+}
+
+impl<'a> EditorWidget<'a> {
+    pub fn new(id: egui::Id, buffer: &'a mut Buffer, buffer_index: usize) -> Self {
+        Self {
+            id,
+            buffer,
+            buffer_index,
+            is_terminal: false,
+        }
+    }
+
+    // Terminal output: read-only, drawn plain and without editing keys.
+    pub fn terminal(mut self, is_terminal: bool) -> Self {
+        self.is_terminal = is_terminal;
+        self
+    }
+
+    pub fn show(
+        self,
+        ui: &mut egui::Ui,
+        view: &mut EditorView,
+        events: &mut Vec<ClientEvents>,
+    ) -> egui::text_edit::TextEditOutput {
+        let Self { id, buffer, buffer_index, is_terminal } = self;
+        // Undo/redo go through the buffer history rather than TextEdit's own
+        // undoer, so they also cover edits made by commands.
+        if !is_terminal && ui.memory(|memory| memory.has_focus(id)) {
+            view.active = Some(buffer_index);
+            if let Some(modal) = view.modal.as_deref_mut()
+                && let Some((actions, cursor)) = modal.handle_keys(ui.ctx(), id, buffer)
+            {
+                match modal.take_mark_request() {
+                    Some(ModalCommand::SetMark(name)) => {
+                        events.push(ClientEvents::SetGlobalMark { buffer_index, name, cursor });
+                    }
+                    Some(ModalCommand::JumpToMark(name)) => {
+                        events.push(ClientEvents::JumpToMark { buffer_index, name, cursor });
+                    }
+                    _ => {}
+                }
+                if !actions.is_empty() {
+                    events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+                }
+            }
+            if view.auto_pair
+                && let Some((actions, cursor)) = pairs::handle_keys(ui.ctx(), id, buffer)
+            {
+                events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+            }
+            if ui.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT) || i.consume_shortcut(&REDO_ALT_SHORTCUT)) {
+                buffer.redo();
+            } else if ui.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+                buffer.undo();
+            }
+            if let Some((actions, cursor)) = case::handle_keys(ui.ctx(), id, buffer) {
+                events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+            }
+            if let Some((actions, cursor)) = join::handle_keys(ui.ctx(), id, buffer) {
+                events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+            }
+            if let Some((actions, cursor)) = indent::handle_keys(ui.ctx(), id, buffer, view.indent_width) {
+                events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+            }
+            brackets::handle_keys(ui.ctx(), id, buffer.content());
+            for (movement, cursor) in movement::handle_keys(ui.ctx(), id, buffer.content()) {
+                events.push(ClientEvents::Moved { buffer_index, movement, cursor });
+            }
+            let cursor = egui::TextEdit::load_state(ui.ctx(), id)
+                .and_then(|state| state.cursor.char_range())
+                .map_or(0, |cursor_range| cursor_range.primary.index);
+            if ui.input(|i| i.key_pressed(RECORD_MACRO_KEY)) {
+                events.push(ClientEvents::ToggleMacroRecording(buffer_index, cursor));
+            }
+            if ui.input(|i| i.key_pressed(REPLAY_MACRO_KEY)) {
+                events.push(ClientEvents::ReplayMacro(buffer_index, cursor));
+            }
+            if ui.input_mut(|i| i.consume_shortcut(&SAVE_SHORTCUT)) {
+                events.push(ClientEvents::Save(buffer_index));
+            }
+            if ui.input(|i| i.key_pressed(egui::Key::F2)) {
+                events.push(ClientEvents::RenameSymbol(buffer_index, cursor));
+            }
+        }
+
+        let plain = is_terminal || buffer.big_file;
+        let ranges = (!plain).then(|| folding::cached_ranges(ui.ctx(), &buffer.file_path, buffer.content()));
+        let hidden = folding::hidden_lines(&buffer.folds, buffer.content().split('\n').count());
+        // Spans come from the text being laid out, which can already differ
+        // from the buffer by this frame's typing.
+        let highlighted = (!plain && syntax::language(&buffer.file_path).is_some()).then(|| buffer.file_path.clone());
+        let theme = buffer.theme_override.unwrap_or(view.syntax_theme);
+        let word = (!plain && ui.memory(|memory| memory.has_focus(id)))
+            .then(|| egui::TextEdit::load_state(ui.ctx(), id)?.cursor.char_range())
+            .flatten()
+            .filter(|range| range.is_empty())
+            .and_then(|range| occurrences::word_at(buffer.content(), range.primary.index));
+        let word = occurrences::settled_word(ui.ctx(), id, word);
+        let occurrence_fill = ui.visuals().selection.bg_fill.gamma_multiply(0.35);
+        let searched = !plain && view.search.is_active() && !view.search.matches(buffer_index, buffer.content()).is_empty();
+        let search_fill = ui.visuals().warn_fg_color.gamma_multiply(0.35);
+        let error_stroke = egui::Stroke::new(1.0, ui.visuals().error_fg_color);
+        let spell_checked = !plain && buffer.spell_check && view.spell.dictionary.is_some();
+        let spell_stroke = egui::Stroke::new(1.0, ui.visuals().warn_fg_color);
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let text_color = ui
+            .visuals()
+            .override_text_color
+            .unwrap_or_else(|| ui.visuals().widgets.inactive.text_color());
+        let (tab_width, row_height) = (view.indent_width, ui.text_style_height(&egui::TextStyle::Monospace));
+        let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
+            let mut job = folding::layout_job(text.as_str(), &hidden, font_id.clone(), text_color);
+            let highlights = highlighted.as_ref().map(|path| highlight::cached_highlights(ui.ctx(), path, text.as_str()));
+            if let Some(highlights) = &highlights {
+                highlight::colorize(&mut job, &highlights.spans, theme);
+                occurrences::underline(&mut job, &highlights.errors, error_stroke);
+            }
+            if spell_checked {
+                let spans = highlights.as_ref().map(|highlights| &highlights.spans);
+                occurrences::underline(&mut job, &view.spell.misspelled(buffer_index, text.as_str(), spans), spell_stroke);
+            }
+            if let Some(word) = &word {
+                let ranges = occurrences::cached_occurrences(ui.ctx(), text.as_str(), word);
+                if ranges.len() > 1 {
+                    occurrences::mark(&mut job, &ranges, occurrence_fill);
+                }
+            }
+            if searched {
+                occurrences::mark(&mut job, &view.search.matches(buffer_index, text.as_str()), search_fill);
+            }
+            tabs::align(&mut job, tab_width, row_height);
+            job.wrap.max_width = wrap_width;
+            ui.fonts_mut(|fonts| fonts.layout_job(job))
+        };
+
+        let changes = (!plain).then(|| change_gutter::cached_changes(ui.ctx(), id, buffer));
+        let custom_layout =
+            !buffer.folds.is_empty()
+                || highlighted.is_some()
+                || word.is_some()
+                || searched
+                || spell_checked
+                || buffer.content().contains('\t');
+        let mut text = EditorText::new(buffer);
+        let mut text_edit = egui::TextEdit::multiline(&mut text)
+            .id(id)
+            .code_editor()
+            .interactive(!is_terminal)
+            .desired_width(f32::INFINITY);
+        if custom_layout {
+            text_edit = text_edit.layouter(&mut layouter);
+        }
+        let output = ui
+            .horizontal_top(|ui| {
+                if !plain {
+                    ui.add_space(change_gutter::GUTTER_WIDTH);
+                }
+                if ranges.is_some() {
+                    ui.add_space(folding::GUTTER_WIDTH);
+                }
+                text_edit.show(ui)
+            })
+            .inner;
+        let cursor = output
+            .cursor_range
+            .map_or(0, |cursor_range| cursor_range.primary.index);
+        let actions = text.finish(cursor);
+
+        if let Some(changes) = &changes {
+            let offset = change_gutter::GUTTER_WIDTH + if ranges.is_some() { folding::GUTTER_WIDTH } else { 0.0 };
+            change_gutter::paint(ui, changes, &output.galley, output.galley_pos, offset);
+        }
+
+        if let Some(ranges) = ranges
+            && let Some(range) =
+                folding::show_gutter(ui, id, &output.galley, output.galley_pos, &ranges, &buffer.folds, &hidden)
+        {
+            folding::toggle(&mut buffer.folds, range);
+        }
+
+        if !actions.is_empty() {
+            events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+        }
+
+        if spell_checked
+            && let Some(dictionary) = view.spell.dictionary.clone()
+            && let Some((actions, cursor)) = spell::context_menu(ui, &output, buffer, &dictionary, |content| {
+                let spans = highlighted.as_ref().map(|path| highlight::cached_highlights(ui.ctx(), path, content).spans);
+                view.spell.misspelled(buffer_index, content, spans.as_ref())
+            })
+        {
+            events.push(ClientEvents::Edited { buffer_index, actions, cursor });
+        }
+
+        if output.response.has_focus()
+            && ui.input(definition_requested)
+            && let Some(cursor_range) = output.cursor_range
+        {
+            events.push(ClientEvents::GoToDefinition(buffer_index, cursor_range.primary.index));
+        }
+
+        show_hover(ui, &output, buffer.content(), buffer_index, view.hover, events);
+        output
+    }
+}
+
+fn definition_requested(input: &egui::InputState) -> bool {
+    input.modifiers.is_none() && input.key_pressed(DEFINITION_KEY)
+}
+
+fn show_hover(
+    ui: &egui::Ui,
+    output: &egui::text_edit::TextEditOutput,
+    content: &str,
+    buffer_index: usize,
+    hover: Option<&HoverInfo>,
+    events: &mut Vec<ClientEvents>,
+) {
+    let Some(pointer) = output.response.hover_pos() else {
+        return;
+    };
+    let char_idx = output.galley.cursor_from_pos(pointer - output.galley_pos).index;
+    if output.response.clicked() && ui.input(|i| i.modifiers.command) {
+        events.push(ClientEvents::GoToDefinition(buffer_index, char_idx));
+    }
+
+    let Some(word_start) = hover::word_start(content, char_idx) else {
+        return;
+    };
+    events.push(ClientEvents::Hover(buffer_index, word_start));
+    if let Some(info) = hover
+        .filter(|info| info.buffer_index == buffer_index && info.char_idx == word_start)
+    {
+        output
+            .response
+            .clone()
+            .on_hover_ui_at_pointer(|ui| hover::show_markdown(ui, &info.contents));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(modifiers: egui::Modifiers, key: egui::Key) -> bool {
+        let ctx = egui::Context::default();
+        let input = egui::RawInput {
+            modifiers,
+            events: vec![egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers }],
+            ..egui::RawInput::default()
+        };
+        let mut requested = false;
+        let _ = ctx.run(input, |ctx| requested = ctx.input(definition_requested));
+        requested
+    }
+
+    #[test]
+    fn only_plain_f12_goes_to_definition() {
+        assert!(pressed(egui::Modifiers::NONE, egui::Key::F12));
+        assert!(!pressed(egui::Modifiers::SHIFT, egui::Key::F12));
+        assert!(!pressed(egui::Modifiers::NONE, egui::Key::F11));
+    }
+
+    // Shows the widget focused for one frame, then again with `input`.
+    fn show_with(buffer: &mut Buffer, is_terminal: bool, input: Vec<egui::Event>) -> Vec<ClientEvents> {
+        let (mut search, mut spell) = (SearchState::default(), SpellState::default());
+        let mut view = EditorView {
+            hover: None,
+            guides: Guides::default(),
+            syntax_theme: ThemeId::default(),
+            auto_pair: false,
+            indent_width: 4,
+            modal: None,
+            active: None,
+            focused_frame: None,
+            cursor: None,
+            selection: None,
+            search: &mut search,
+            spell: &mut spell,
+        };
+        let ctx = egui::Context::default();
+        let id = egui::Id::new("editor");
+        let mut events = Vec::new();
+        for (frame, input) in [Vec::new(), input].into_iter().enumerate() {
+            let modifiers = input
+                .iter()
+                .find_map(|event| match event {
+                    egui::Event::Key { modifiers, .. } => Some(*modifiers),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let raw = egui::RawInput { events: input, modifiers, ..egui::RawInput::default() };
+            let _ = ctx.run(raw, |ctx| {
+                if frame == 0 {
+                    ctx.memory_mut(|memory| memory.request_focus(id));
+                }
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    events.clear();
+                    EditorWidget::new(id, buffer, 7).terminal(is_terminal).show(ui, &mut view, &mut events);
+                });
+            });
+        }
+        events
+    }
+
+    fn key(key: egui::Key, modifiers: egui::Modifiers) -> egui::Event {
+        egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers }
+    }
+
+    #[test]
+    fn focused_editors_edit_through_the_buffer_and_report_events() {
+        let mut buffer = Buffer::with_content("abc");
+        // Taking focus puts the cursor at the end.
+        let events = show_with(&mut buffer, false, vec![egui::Event::Text(String::from("x"))]);
+        assert_eq!(buffer.content(), "abcx");
+        assert!(matches!(events[..], [ClientEvents::Edited { buffer_index: 7, .. }]));
+
+        show_with(&mut buffer, false, vec![key(egui::Key::Z, egui::Modifiers::COMMAND)]);
+        assert_eq!(buffer.content(), "abc");
+        let events = show_with(&mut buffer, false, vec![key(egui::Key::S, egui::Modifiers::COMMAND)]);
+        assert!(matches!(events[..], [ClientEvents::Save(7)]));
+    }
+
+    #[test]
+    fn terminal_output_is_read_only() {
+        let mut buffer = Buffer::with_content("$ ls");
+        let typed = vec![egui::Event::Text(String::from("x")), key(egui::Key::S, egui::Modifiers::COMMAND)];
+        assert!(show_with(&mut buffer, true, typed).is_empty());
+        assert_eq!(buffer.content(), "$ ls");
+    }
+}
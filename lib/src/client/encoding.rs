@@ -0,0 +1,62 @@
+use crate::server::encoding;
+use crate::shared::Shared;
+
+use eframe::egui;
+use encoding_rs::{Encoding, UTF_8};
+
+pub enum EncodingAction {
+    Convert(usize, &'static Encoding),
+    Reinterpret(usize, &'static Encoding),
+}
+
+pub struct EncodingMenu {
+    pub buffer_index: usize,
+    pub encoding: &'static Encoding,
+    pub warning: Option<String>,
+}
+
+impl Default for EncodingMenu {
+    fn default() -> Self {
+        Self {
+            buffer_index: 0,
+            encoding: UTF_8,
+            warning: None,
+        }
+    }
+}
+
+impl EncodingMenu {
+    pub fn show(&mut self, ui: &mut egui::Ui, shared: &Shared) -> Option<EncodingAction> {
+        let current = shared.with_buffers(|buffers| {
+            let max = buffers.buffers.len().saturating_sub(1);
+            self.buffer_index = self.buffer_index.min(max);
+            (max, buffers.buffers.get(self.buffer_index).map(|buffer| buffer.encoding))
+        });
+        let Ok((max, current)) = current else {
+            return None;
+        };
+
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.buffer_index).range(0..=max).prefix("buffer: "));
+            if let Some(current) = current {
+                ui.weak(encoding::display_name(current));
+            }
+        });
+        for choice in encoding::CHOICES {
+            ui.radio_value(&mut self.encoding, choice, encoding::display_name(choice));
+        }
+        ui.separator();
+
+        let mut action = None;
+        if ui.button("Save with encoding").clicked() {
+            action = Some(EncodingAction::Convert(self.buffer_index, self.encoding));
+        }
+        if ui.button("Reopen with encoding").clicked() {
+            action = Some(EncodingAction::Reinterpret(self.buffer_index, self.encoding));
+        }
+        if let Some(warning) = &self.warning {
+            ui.colored_label(ui.visuals().warn_fg_color, warning);
+        }
+        action
+    }
+}
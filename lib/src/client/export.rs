@@ -0,0 +1,73 @@
+use crate::config::ThemeId;
+use crate::server::syntax::TokenKind;
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use eframe::egui;
+
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn css(color: egui::Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+// `content[range]` as a page of its own, colored by `spans` (byte ranges
+// into all of `content`) in `theme`, on the editor's background. Styles are
+// inline so the markup survives being pasted elsewhere.
+pub fn to_html(
+    content: &str,
+    range: Range<usize>,
+    spans: &[(Range<usize>, TokenKind)],
+    theme: ThemeId,
+    visuals: &egui::Visuals,
+    title: &str,
+) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+    escape(title, &mut html);
+    let _ = write!(
+        html,
+        "</title>\n</head>\n<body>\n<pre style=\"background: {}; color: {}; padding: 1em; font-family: monospace;\">",
+        css(visuals.panel_fill),
+        css(visuals.override_text_color.unwrap_or_else(|| visuals.widgets.inactive.text_color())),
+    );
+    let mut at = range.start;
+    for (span, token) in spans.iter().filter(|(span, _)| span.end > range.start && span.start < range.end) {
+        let span = span.start.max(range.start)..span.end.min(range.end);
+        escape(&content[at..span.start], &mut html);
+        let _ = write!(html, "<span style=\"color: {}\">", css(theme.color(*token)));
+        escape(&content[span.clone()], &mut html);
+        html.push_str("</span>");
+        at = span.end;
+    }
+    escape(&content[at..range.end], &mut html);
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_the_range_escaped_with_spans_clipped_to_it() {
+        let content = "let s = \"<a & b>\";";
+        let spans = [(0..3, TokenKind::Keyword), (8..17, TokenKind::String)];
+        let visuals = egui::Visuals::dark();
+        let html = to_html(content, 4..12, &spans, ThemeId::Default, &visuals, "a <b>.rs");
+        let string = css(ThemeId::Default.color(TokenKind::String));
+        assert!(html.contains("<title>a &lt;b&gt;.rs</title>"));
+        assert!(html.contains(&format!(">s = <span style=\"color: {string}\">&quot;&lt;a </span></pre>")));
+        assert!(!html.contains(&css(ThemeId::Default.color(TokenKind::Keyword))));
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+
+const INDENT: f32 = 12.0;
+
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+// Immediate children of `dir`, directories first, each group by name.
+pub fn list_dir(dir: &Path) -> io::Result<Vec<TreeEntry>> {
+    let mut entries: Vec<TreeEntry> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| TreeEntry {
+            is_dir: entry.file_type().is_ok_and(|file_type| file_type.is_dir()),
+            path: entry.path(),
+        })
+        .collect();
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.file_name().cmp(&b.path.file_name())));
+    Ok(entries)
+}
+
+// Directories are only read when first expanded, so large workspaces open
+// without walking everything up front.
+pub struct FileTree {
+    pub open: bool,
+    pub root: PathBuf,
+    listed: HashMap<PathBuf, Vec<TreeEntry>>,
+    expanded: HashSet<PathBuf>,
+}
+
+impl FileTree {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            open: false,
+            root,
+            listed: HashMap::new(),
+            expanded: HashSet::new(),
+        }
+    }
+
+    // Re-roots the tree at `root`, dropping what was listed under the old one.
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = root;
+        self.listed.clear();
+        self.expanded.clear();
+    }
+
+    pub fn expand(&mut self, dir: &Path) {
+        if !self.listed.contains_key(dir) {
+            let entries = list_dir(dir).unwrap_or_else(|err| {
+                log::error!("cant list {}: {err}", dir.display());
+                Vec::new()
+            });
+            self.listed.insert(dir.to_path_buf(), entries);
+        }
+        self.expanded.insert(dir.to_path_buf());
+    }
+
+    pub fn collapse(&mut self, dir: &Path) {
+        self.expanded.remove(dir);
+    }
+
+    pub fn is_expanded(&self, dir: &Path) -> bool {
+        self.expanded.contains(dir)
+    }
+
+    // What `dir` held when it was expanded; `None` before that.
+    pub fn children(&self, dir: &Path) -> Option<&[TreeEntry]> {
+        self.listed.get(dir).map(Vec::as_slice)
+    }
+
+    // Opens the panel and expands every directory between the root and
+    // `path`. Files outside the root are left alone.
+    pub fn reveal(&mut self, path: &Path) {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return;
+        };
+        self.open = true;
+        let mut dir = self.root.clone();
+        self.expand(&dir.clone());
+        if let Some(parent) = relative.parent() {
+            for component in parent {
+                dir.push(component);
+                self.expand(&dir.clone());
+            }
+        }
+    }
+
+    // Returns the file the user clicked.
+    pub fn show(&mut self, ctx: &egui::Context, active: Option<&Path>) -> Option<PathBuf> {
+        if !self.open {
+            return None;
+        }
+        let root = self.root.clone();
+        if !self.is_expanded(&root) {
+            self.expand(&root);
+        }
+        let mut clicked = None;
+        let mut toggled = None;
+        egui::SidePanel::left("file_tree").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                self.show_dir(ui, &root, 0, active, &mut clicked, &mut toggled);
+            });
+        });
+        if let Some(dir) = toggled {
+            if self.is_expanded(&dir) {
+                self.collapse(&dir);
+            } else {
+                self.expand(&dir);
+            }
+        }
+        clicked
+    }
+
+    fn show_dir(
+        &self,
+        ui: &mut egui::Ui,
+        dir: &Path,
+        depth: usize,
+        active: Option<&Path>,
+        clicked: &mut Option<PathBuf>,
+        toggled: &mut Option<PathBuf>,
+    ) {
+        for entry in self.children(dir).unwrap_or_default() {
+            let name = entry.path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+            let expanded = entry.is_dir && self.is_expanded(&entry.path);
+            let label = match (entry.is_dir, expanded) {
+                (true, true) => format!("▾ {name}"),
+                (true, false) => format!("▸ {name}"),
+                (false, _) => name,
+            };
+            let response = ui
+                .horizontal(|ui| {
+                    ui.add_space(depth as f32 * INDENT);
+                    ui.selectable_label(active == Some(entry.path.as_path()), label)
+                })
+                .inner;
+            if response.clicked() {
+                if entry.is_dir {
+                    *toggled = Some(entry.path.clone());
+                } else {
+                    *clicked = Some(entry.path.clone());
+                }
+            }
+            if expanded {
+                self.show_dir(ui, &entry.path, depth + 1, active, clicked, toggled);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn names(entries: &[TreeEntry]) -> Vec<String> {
+        entries.iter().map(|entry| entry.path.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn directories_are_listed_on_demand_and_revealed() {
+        let temp = TempDir::new("tree");
+        let root = temp.path().to_path_buf();
+        let nested = root.join("src").join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        for file in [root.join("b.txt"), root.join("a.txt"), nested.join("deep.rs")] {
+            fs::write(file, "").unwrap();
+        }
+
+        let mut tree = FileTree::new(root.clone());
+        assert!(tree.children(&root).is_none());
+        tree.expand(&root);
+        assert_eq!(names(tree.children(&root).unwrap()), ["assets", "src", "a.txt", "b.txt"]);
+        tree.collapse(&root);
+        assert!(!tree.is_expanded(&root));
+        assert!(tree.children(&root).is_some());
+
+        tree.reveal(&nested.join("deep.rs"));
+        assert!(tree.open);
+        assert!([&root, &root.join("src"), &nested].iter().all(|dir| tree.is_expanded(dir)));
+        assert_eq!(names(tree.children(&nested).unwrap()), ["deep.rs"]);
+
+        tree.set_root(nested.clone());
+        assert!(!tree.is_expanded(&root));
+        tree.reveal(&root.join("a.txt"));
+        assert!(!tree.is_expanded(&nested));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::server::syntax;
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use eframe::egui;
+use tree_sitter::Tree;
+
+pub const GUTTER_WIDTH: f32 = 14.0;
+// Hidden lines stay in the galley so char indices line up with the buffer;
+// they're just laid out too small to take up any room.
+const HIDDEN_FONT_SIZE: f32 = 0.01;
+
+// Ranges of lines that can be collapsed. The first line of each range is the
+// header that stays visible, nested ranges are allowed.
+pub fn foldable_ranges(path: &Path, content: &str) -> Vec<Range<usize>> {
+    match syntax::parse(path, content) {
+        Some(tree) => syntax_ranges(&tree),
+        None => indent_ranges(content),
+    }
+}
+
+fn syntax_ranges(tree: &Tree) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut cursor = tree.walk();
+    // The root spans the whole file, only its descendants are foldable.
+    let mut descend = cursor.goto_first_child();
+    while descend {
+        let node = cursor.node();
+        let (start, end) = (node.start_position().row, node.end_position().row);
+        if node.is_named() && end > start {
+            match ranges.iter_mut().find(|range| range.start == start) {
+                Some(range) => range.end = range.end.max(end + 1),
+                None => ranges.push(start..end + 1),
+            }
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() || cursor.depth() == 0 {
+                descend = false;
+                break;
+            }
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+fn indent_ranges(content: &str) -> Vec<Range<usize>> {
+    let indents: Vec<Option<usize>> = content
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty()).then(|| line.len() - trimmed.len())
+        })
+        .collect();
+
+    let mut ranges = Vec::new();
+    for (start, indent) in indents.iter().enumerate() {
+        let Some(indent) = indent else {
+            continue;
+        };
+        let mut last = start;
+        for (line, inner) in indents.iter().enumerate().skip(start + 1) {
+            match inner {
+                None => continue,
+                Some(inner) if inner > indent => last = line,
+                Some(_) => break,
+            }
+        }
+        if last > start {
+            ranges.push(start..last + 1);
+        }
+    }
+    ranges
+}
+
+pub fn toggle(folds: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match folds.iter().position(|fold| *fold == range) {
+        Some(index) => {
+            folds.remove(index);
+        }
+        None => folds.push(range),
+    }
+}
+
+pub fn hidden_lines(folds: &[Range<usize>], line_count: usize) -> Vec<bool> {
+    let mut hidden = vec![false; line_count];
+    for fold in folds {
+        let end = fold.end.min(line_count);
+        if fold.start + 1 < end {
+            hidden[fold.start + 1..end].fill(true);
+        }
+    }
+    hidden
+}
+
+pub fn layout_job(text: &str, hidden: &[bool], font_id: egui::FontId, color: egui::Color32) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let hidden_format = egui::TextFormat::simple(egui::FontId::monospace(HIDDEN_FONT_SIZE), egui::Color32::TRANSPARENT);
+    let visible_format = egui::TextFormat::simple(font_id, color);
+    for (line, text) in text.split_inclusive('\n').enumerate() {
+        let format = if hidden.get(line).copied().unwrap_or(false) {
+            hidden_format.clone()
+        } else {
+            visible_format.clone()
+        };
+        job.append(text, 0.0, format);
+    }
+    job
+}
+
+#[derive(Default)]
+struct RangesComputer;
+
+impl egui::cache::ComputerMut<(&Path, &str), Arc<Vec<Range<usize>>>> for RangesComputer {
+    fn compute(&mut self, (path, content): (&Path, &str)) -> Arc<Vec<Range<usize>>> {
+        Arc::new(foldable_ranges(path, content))
+    }
+}
+
+type RangesCache = egui::cache::FrameCache<Arc<Vec<Range<usize>>>, RangesComputer>;
+
+// Reparsing on every frame is too slow for big files, so the ranges are
+// cached until the content changes.
+pub fn cached_ranges(ctx: &egui::Context, path: &Path, content: &str) -> Arc<Vec<Range<usize>>> {
+    ctx.memory_mut(|memory| memory.caches.cache::<RangesCache>().get((path, content)))
+}
+
+// Draws fold toggles in the gutter left of `galley_pos` and a summary after
+// each collapsed header. Returns the range the user toggled, if any.
+pub fn show_gutter(
+    ui: &egui::Ui,
+    id: egui::Id,
+    galley: &egui::Galley,
+    galley_pos: egui::Pos2,
+    ranges: &[Range<usize>],
+    folds: &[Range<usize>],
+    hidden: &[bool],
+) -> Option<Range<usize>> {
+    let mut toggled = None;
+    let mut line = 0;
+    let mut line_start = true;
+    for row in &galley.rows {
+        let header = line_start && !hidden.get(line).copied().unwrap_or(false);
+        if header && let Some(range) = ranges.iter().find(|range| range.start == line) {
+            let rect = row.rect().translate(galley_pos.to_vec2());
+            let toggle_rect = egui::Rect::from_min_size(
+                egui::pos2(galley_pos.x - GUTTER_WIDTH, rect.top()),
+                egui::vec2(GUTTER_WIDTH, rect.height()),
+            );
+            let response = ui.interact(toggle_rect, id.with(("fold", line)), egui::Sense::click());
+            let folded = folds.contains(range);
+            let text_color = if response.hovered() {
+                ui.visuals().strong_text_color()
+            } else {
+                ui.visuals().weak_text_color()
+            };
+            let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+            ui.painter().text(
+                toggle_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                if folded { "▸" } else { "▾" },
+                font_id.clone(),
+                text_color,
+            );
+            if folded {
+                ui.painter().text(
+                    egui::pos2(rect.right() + 8.0, rect.center().y),
+                    egui::Align2::LEFT_CENTER,
+                    format!("⋯ {} lines", range.len() - 1),
+                    font_id,
+                    ui.visuals().weak_text_color(),
+                );
+            }
+            if response.clicked() {
+                toggled = Some(range.clone());
+            }
+        }
+        line_start = row.ends_with_newline;
+        if row.ends_with_newline {
+            line += 1;
+        }
+    }
+    toggled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::enums::BufferActions;
+    use crate::shared::buffers::Buffer;
+
+    #[test]
+    fn indentation_folds_nest_and_skip_blank_lines() {
+        let content = "a:\n  b:\n    c\n\n  d\ne\n";
+        assert_eq!(foldable_ranges(Path::new("notes.txt"), content), vec![0..5, 1..3]);
+        assert_eq!(foldable_ranges(Path::new("lib.rs"), "fn a() {\n    1\n}\nfn b() {}\n"), vec![0..3]);
+    }
+
+    #[test]
+    fn folded_lines_hide_and_follow_edits_above_them() {
+        let mut folds = Vec::new();
+        toggle(&mut folds, 1..3);
+        assert_eq!(hidden_lines(&folds, 4), vec![false, false, true, false]);
+        toggle(&mut folds, 1..3);
+        assert!(folds.is_empty());
+
+        let mut buffer = Buffer::with_content("x\nfn a() {\n  1\n}\n");
+        buffer.folds.push(1..4);
+        buffer.edit(&[BufferActions::InsertText { char_idx: 0, text: String::from("//\n") }]);
+        assert_eq!(buffer.folds, vec![2..5]);
+        buffer.edit(&[BufferActions::InsertText { char_idx: 14, text: String::from("\n  2") }]);
+        assert_eq!(buffer.folds, vec![2..6]);
+    }
+}
@@ -0,0 +1,125 @@
+use eframe::egui;
+
+use crate::shared::frames::WindowGeometry;
+
+// Restored windows never come back smaller than this.
+const MIN_SIZE: egui::Vec2 = egui::vec2(200.0, 100.0);
+// Size of a new side window, unless the monitor is smaller.
+const NEW_WINDOW_SIZE: egui::Vec2 = egui::vec2(800.0, 600.0);
+// New side windows step down and right by this much from the last one and
+// start over after `CASCADE_STEPS`.
+const CASCADE_STEP: egui::Vec2 = egui::vec2(32.0, 32.0);
+const CASCADE_STEPS: usize = 8;
+
+pub fn capture(info: &egui::ViewportInfo) -> Option<WindowGeometry> {
+    let inner = info.inner_rect?;
+    Some(WindowGeometry {
+        size: inner.size().into(),
+        position: info.outer_rect.map(|outer| outer.min.into()),
+    })
+}
+
+// egui only knows the size of the monitor a viewport is on, not where it
+// sits on the desktop. Monitors are taken to be laid out on a grid of that
+// size, which holds for the usual row of same-sized screens.
+fn monitor_origin(position: egui::Pos2, monitor: egui::Vec2) -> egui::Pos2 {
+    egui::pos2(
+        (position.x / monitor.x).floor() * monitor.x,
+        (position.y / monitor.y).floor() * monitor.y,
+    )
+}
+
+// Keeps the window on screen: at least `MIN_SIZE`, no bigger than the
+// monitor, and with its top left corner where the whole window fits on
+// the monitor it's on. An unknown monitor only rules out negative
+// positions.
+pub fn clamp(geometry: WindowGeometry, monitor: Option<egui::Vec2>) -> (egui::Vec2, Option<egui::Pos2>) {
+    let mut size = egui::Vec2::from(geometry.size).max(MIN_SIZE);
+    if let Some(monitor) = monitor {
+        size = size.min(monitor);
+    }
+    let position = geometry.position.map(|position| {
+        let position = egui::Pos2::from(position);
+        match monitor.filter(|monitor| monitor.x > 0.0 && monitor.y > 0.0) {
+            Some(monitor) => {
+                let origin = monitor_origin(position, monitor);
+                position.clamp(origin, origin + (monitor - size))
+            }
+            None => position.max(egui::Pos2::ZERO),
+        }
+    });
+    (size, position)
+}
+
+// Where the `index`th side window opens: cascaded from the top left of
+// `anchor` (the main window) when given, so it lands on the same monitor,
+// or from the desktop's origin otherwise.
+pub fn cascade(anchor: Option<WindowGeometry>, index: usize, monitor: Option<egui::Vec2>) -> WindowGeometry {
+    let origin = anchor.and_then(|anchor| anchor.position).map_or(egui::Pos2::ZERO, egui::Pos2::from);
+    let step = (index % CASCADE_STEPS + 1) as f32;
+    let geometry = WindowGeometry {
+        size: NEW_WINDOW_SIZE.into(),
+        position: Some((origin + CASCADE_STEP * step).into()),
+    };
+    let (size, position) = clamp(geometry, monitor);
+    WindowGeometry {
+        size: size.into(),
+        position: position.map(Into::into),
+    }
+}
+
+pub fn apply(
+    builder: egui::ViewportBuilder,
+    geometry: Option<WindowGeometry>,
+    monitor: Option<egui::Vec2>,
+) -> egui::ViewportBuilder {
+    let Some(geometry) = geometry else {
+        return builder;
+    };
+    let (size, position) = clamp(geometry, monitor);
+    let builder = builder.with_inner_size(size);
+    match position {
+        Some(position) => builder.with_position(position),
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(size: [f32; 2], position: Option<[f32; 2]>) -> WindowGeometry {
+        WindowGeometry { size, position }
+    }
+
+    #[test]
+    fn restored_windows_are_clamped_onto_their_monitor() {
+        let monitor = Some(egui::vec2(1920.0, 1080.0));
+        assert_eq!(
+            clamp(geometry([50.0, 50.0], Some([-40.0, 10.0])), None),
+            (MIN_SIZE, Some(egui::pos2(0.0, 10.0)))
+        );
+        assert_eq!(
+            clamp(geometry([4000.0, 600.0], Some([100.0, 100.0])), monitor),
+            (egui::vec2(1920.0, 600.0), Some(egui::pos2(0.0, 100.0)))
+        );
+        // On the second monitor of a row, hanging off its right edge.
+        assert_eq!(
+            clamp(geometry([800.0, 600.0], Some([3500.0, 900.0])), monitor),
+            (egui::vec2(800.0, 600.0), Some(egui::pos2(3040.0, 480.0)))
+        );
+        assert_eq!(clamp(geometry([800.0, 600.0], None), monitor).1, None);
+    }
+
+    #[test]
+    fn new_windows_cascade_from_the_main_window_and_wrap() {
+        let main = Some(geometry([1280.0, 720.0], Some([1920.0, 0.0])));
+        let monitor = Some(egui::vec2(1920.0, 1080.0));
+        assert_eq!(cascade(main, 0, monitor), geometry([800.0, 600.0], Some([1952.0, 32.0])));
+        assert_eq!(cascade(main, 2, monitor).position, Some([2016.0, 96.0]));
+        assert_eq!(cascade(main, CASCADE_STEPS, monitor), cascade(main, 0, monitor));
+        // Kept whole on a small monitor.
+        let small = Some(egui::vec2(1024.0, 640.0));
+        assert_eq!(cascade(None, 7, small), geometry([800.0, 600.0], Some([224.0, 40.0])));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::Config;
+
+use eframe::egui;
+
+const TAB_GLYPH: &str = "→";
+const SPACE_GLYPH: &str = "·";
+const EOF_MARKER: &str = "EOF";
+const NO_NEWLINE_MARKER: &str = "EOF, no newline";
+
+// Editor overlays that don't change the text: a ruler at a fixed column
+// and markers for tabs and trailing whitespace.
+#[derive(Clone, Copy, Default)]
+pub struct Guides {
+    pub ruler_column: Option<usize>,
+    pub show_whitespace: bool,
+}
+
+impl Guides {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            ruler_column: config.ruler_column,
+            show_whitespace: config.show_whitespace,
+        }
+    }
+}
+
+// Distance from the start of a line to the left edge of `column`. Taken
+// from a laid out line rather than multiplied from one glyph's width, so it
+// lands where the editor's own layout puts that column.
+pub fn ruler_offset(ctx: &egui::Context, font_id: &egui::FontId, column: usize) -> f32 {
+    let line = format!("{}|", " ".repeat(column));
+    let galley = ctx.fonts_mut(|fonts| fonts.layout_no_wrap(line, font_id.clone(), egui::Color32::PLACEHOLDER));
+    galley.rows[0].glyphs[column].pos.x
+}
+
+pub fn paint(ui: &egui::Ui, guides: Guides, galley: &egui::Galley, galley_pos: egui::Pos2, clip: egui::Rect) {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let color = ui.visuals().weak_text_color().gamma_multiply(0.5);
+
+    if let Some(column) = guides.ruler_column {
+        let x = galley_pos.x + ruler_offset(ui.ctx(), &font_id, column);
+        ui.painter()
+            .vline(x, clip.y_range(), egui::Stroke::new(1.0, color));
+    }
+
+    paint_eof(ui, galley, galley_pos, &font_id, color);
+    if !guides.show_whitespace {
+        return;
+    }
+    let last_row = galley.rows.len().saturating_sub(1);
+    for (row_idx, row) in galley.rows.iter().enumerate() {
+        let line_ends = row.ends_with_newline || row_idx == last_row;
+        let trailing_from = if line_ends {
+            row.glyphs
+                .iter()
+                .rposition(|glyph| !glyph.chr.is_whitespace())
+                .map_or(0, |idx| idx + 1)
+        } else {
+            row.glyphs.len()
+        };
+        for (glyph_idx, glyph) in row.glyphs.iter().enumerate() {
+            // Folded lines are laid out at a near-zero size; skip them.
+            if glyph.font_height < 1.0 {
+                break;
+            }
+            let marker = match glyph.chr {
+                '\t' => TAB_GLYPH,
+                ' ' if glyph_idx >= trailing_from => SPACE_GLYPH,
+                _ => continue,
+            };
+            let pos = galley_pos + row.pos.to_vec2() + egui::vec2(glyph.pos.x, row.rect().height() / 2.0);
+            ui.painter()
+                .text(pos, egui::Align2::LEFT_CENTER, marker, font_id.clone(), color);
+        }
+    }
+}
+
+// Marks the end of the text, noting when the last line has no newline.
+fn paint_eof(ui: &egui::Ui, galley: &egui::Galley, galley_pos: egui::Pos2, font_id: &egui::FontId, color: egui::Color32) {
+    let text = galley.text();
+    if text.is_empty() {
+        return;
+    }
+    let marker = if text.ends_with('\n') { EOF_MARKER } else { NO_NEWLINE_MARKER };
+    let end = galley.pos_from_cursor(galley.end()).translate(galley_pos.to_vec2());
+    let pos = egui::pos2(end.left() + 4.0, end.center().y);
+    ui.painter()
+        .text(pos, egui::Align2::LEFT_CENTER, marker, font_id.clone(), color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_ruler_sits_at_its_column_in_the_editor_font() {
+        let ctx = egui::Context::default();
+        let font_id = egui::FontId::monospace(14.0);
+        let (mut offsets, mut text_x) = (Vec::new(), 0.0);
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            offsets = [0, 1, 80].map(|column| ruler_offset(ctx, &font_id, column)).to_vec();
+            let line = format!("{}b", "a".repeat(80));
+            let galley = ctx.fonts_mut(|fonts| fonts.layout_no_wrap(line, font_id.clone(), egui::Color32::PLACEHOLDER));
+            text_x = galley.rows[0].glyphs[80].pos.x;
+        });
+        assert_eq!(offsets[0], 0.0);
+        assert!(offsets[1] > 0.0);
+        // Glyph positions are rounded, so this isn't simply 80 glyph widths.
+        assert_eq!(offsets[2], text_x);
+
+        let config = Config { ruler_column: Some(100), show_whitespace: true, ..Config::default() };
+        let guides = Guides::from_config(&config);
+        assert_eq!((guides.ruler_column, guides.show_whitespace), (Some(100), true));
+    }
+}
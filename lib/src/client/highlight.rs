@@ -0,0 +1,174 @@
+use crate::config::ThemeId;
+use crate::server::bus::Throttled;
+use crate::server::hash;
+use crate::server::parse::{BackgroundParser, Errors, ParseThrottle, Parsed, Spans};
+use crate::server::syntax::{self, TokenKind};
+use crate::server::wake::Waker;
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use eframe::egui;
+use egui::text::{LayoutJob, LayoutSection};
+
+// What the editor draws from a parse: colored spans and syntax errors.
+#[derive(Clone, Default)]
+pub struct Highlights {
+    pub spans: Spans,
+    pub errors: Errors,
+}
+
+// Per file: its parser thread, when to feed it, and what it made last.
+struct HighlightState {
+    parser: BackgroundParser,
+    throttle: ParseThrottle,
+    parsed: Option<Parsed>,
+    // That moved onto the text asked about last, by its hash.
+    shifted: Option<(u64, Highlights)>,
+}
+
+// A range in `old` moved onto `new`, given what `changed_span` found. Ranges
+// before the changed part stay and those after it move along; the ones
+// inside it are left out until the next parse.
+fn shift_range(range: &Range<usize>, prefix: usize, old_end: usize, new_end: usize) -> Option<Range<usize>> {
+    if range.end <= prefix {
+        Some(range.clone())
+    } else if range.start >= old_end {
+        Some(range.start - old_end + new_end..range.end - old_end + new_end)
+    } else {
+        None
+    }
+}
+
+fn shift(parsed: &Parsed, new: &str) -> Highlights {
+    let (prefix, suffix) = syntax::changed_span(&parsed.content, new);
+    let (old_end, new_end) = (parsed.content.len() - suffix, new.len() - suffix);
+    let spans = parsed
+        .spans
+        .iter()
+        .filter_map(|(range, kind)| Some((shift_range(range, prefix, old_end, new_end)?, *kind)))
+        .collect();
+    let errors = parsed.errors.iter().filter_map(|range| shift_range(range, prefix, old_end, new_end)).collect();
+    Highlights {
+        spans: Arc::new(spans),
+        errors: Arc::new(errors),
+    }
+}
+
+// Highlights for `content`, from the last parse that finished. Parsing
+// happens off the UI thread and is held off while typing; see
+// `ParseThrottle`.
+pub fn cached_highlights(ctx: &egui::Context, path: &Path, content: &str) -> Highlights {
+    let Some(language) = syntax::language(path) else {
+        return Highlights::default();
+    };
+    let state = ctx.data_mut(|data| {
+        data.get_temp_mut_or_insert_with(egui::Id::new(("highlight", path)), || {
+            let waker = Waker::default();
+            let repaint = ctx.clone();
+            waker.set(move || repaint.request_repaint());
+            Arc::new(Mutex::new(HighlightState {
+                parser: BackgroundParser::spawn(language, waker),
+                throttle: ParseThrottle::default(),
+                parsed: None,
+                shifted: None,
+            }))
+        })
+        .clone()
+    });
+    let Ok(mut state) = state.lock() else {
+        return Highlights::default();
+    };
+    match state.throttle.offer(content, Instant::now()) {
+        Throttled::Send => state.parser.submit(Arc::from(content)),
+        Throttled::Hold(wait) => ctx.request_repaint_after(wait),
+        Throttled::Unchanged => {}
+    }
+    if let Some(parsed) = state.parser.take() {
+        state.parsed = Some(parsed);
+        state.shifted = None;
+    }
+    let Some(parsed) = state.parsed.clone() else {
+        return Highlights::default();
+    };
+    if *parsed.content == *content {
+        return Highlights {
+            spans: parsed.spans,
+            errors: parsed.errors,
+        };
+    }
+    let hash = hash::content_hash(content);
+    if let Some((shifted_for, highlights)) = &state.shifted
+        && *shifted_for == hash
+    {
+        return highlights.clone();
+    }
+    let highlights = shift(&parsed, content);
+    state.shifted = Some((hash, highlights.clone()));
+    highlights
+}
+
+// Splits the job's sections at span edges and colors the spans from
+// `theme`. Sections already made transparent (folded lines) are left be.
+pub fn colorize(job: &mut LayoutJob, spans: &[(Range<usize>, TokenKind)], theme: ThemeId) {
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut spans = spans.iter().peekable();
+    for section in job.sections.drain(..) {
+        if section.format.color == egui::Color32::TRANSPARENT {
+            sections.push(section);
+            continue;
+        }
+        let section_end = section.byte_range.end;
+        let mut at = section.byte_range.start;
+        let mut leading_space = section.leading_space;
+        while at < section_end {
+            while spans.next_if(|(range, _)| range.end <= at).is_some() {}
+            let (end, color) = match spans.peek() {
+                Some((range, token)) if range.start <= at => (range.end.min(section_end), theme.color(*token)),
+                Some((range, _)) => (range.start.min(section_end), section.format.color),
+                None => (section_end, section.format.color),
+            };
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: at..end,
+                format: egui::TextFormat {
+                    color,
+                    ..section.format.clone()
+                },
+            });
+            leading_space = 0.0;
+            at = end;
+        }
+    }
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_split_sections_and_skip_folded_ones() {
+        let mut job = LayoutJob::default();
+        let plain = egui::TextFormat { color: egui::Color32::GRAY, ..Default::default() };
+        job.append("let x = 1;\n", 0.0, plain.clone());
+        job.append("let y", 0.0, egui::TextFormat { color: egui::Color32::TRANSPARENT, ..Default::default() });
+        let spans = [(0..3, TokenKind::Keyword), (8..9, TokenKind::Number), (11..14, TokenKind::Keyword)];
+        colorize(&mut job, &spans, ThemeId::Soft);
+
+        let sections: Vec<(Range<usize>, egui::Color32)> =
+            job.sections.iter().map(|section| (section.byte_range.clone(), section.format.color)).collect();
+        assert_eq!(
+            sections,
+            [
+                (0..3, ThemeId::Soft.color(TokenKind::Keyword)),
+                (3..8, egui::Color32::GRAY),
+                (8..9, ThemeId::Soft.color(TokenKind::Number)),
+                (9..11, egui::Color32::GRAY),
+                (11..16, egui::Color32::TRANSPARENT),
+            ]
+        );
+    }
+}
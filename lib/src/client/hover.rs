@@ -0,0 +1,135 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+// How long the pointer has to rest on a word before hover info is requested.
+const HOVER_DELAY: Duration = Duration::from_millis(300);
+
+pub struct HoverInfo {
+    pub buffer_index: usize,
+    pub char_idx: usize,
+    pub contents: String,
+}
+
+#[derive(Default)]
+pub struct HoverState {
+    candidate: Option<(usize, usize)>,
+    since: Option<Instant>,
+    requested: bool,
+    pub info: Option<HoverInfo>,
+}
+
+impl HoverState {
+    // Returns the (buffer, char) position to request once the pointer has
+    // stayed on the same word for the hover delay; each word is asked once.
+    pub fn update(&mut self, hovered: Option<(usize, usize)>, now: Instant) -> Option<(usize, usize)> {
+        if hovered != self.candidate {
+            self.candidate = hovered;
+            self.since = Some(now);
+            self.requested = false;
+            self.info = None;
+            return None;
+        }
+        let hovered = hovered?;
+        if self.requested || now.duration_since(self.since?) < HOVER_DELAY {
+            return None;
+        }
+        self.requested = true;
+        Some(hovered)
+    }
+
+    pub fn receive(&mut self, buffer_index: usize, char_idx: usize, contents: String) {
+        if self.candidate == Some((buffer_index, char_idx)) && !contents.trim().is_empty() {
+            self.info = Some(HoverInfo {
+                buffer_index,
+                char_idx,
+                contents,
+            });
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.candidate.is_some() && !self.requested
+    }
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Snaps a hovered char to the start of the identifier under it, so moving
+// within one word doesn't restart the hover delay.
+pub fn word_start(content: &str, char_idx: usize) -> Option<usize> {
+    let chars: Vec<char> = content.chars().collect();
+    if !chars.get(char_idx).copied().is_some_and(is_word_char) {
+        return None;
+    }
+    let start = chars[..char_idx]
+        .iter()
+        .rposition(|c| !is_word_char(*c))
+        .map_or(0, |idx| idx + 1);
+    Some(start)
+}
+
+// Renders the subset of markdown language servers commonly send: fenced
+// code blocks, `inline code` and **bold**.
+pub fn show_markdown(ui: &mut egui::Ui, text: &str) {
+    let mut in_code_block = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            ui.label(egui::RichText::new(line).monospace());
+            continue;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            for (code_idx, segment) in line.split('`').enumerate() {
+                if code_idx % 2 == 1 {
+                    ui.label(egui::RichText::new(segment).code());
+                    continue;
+                }
+                for (bold_idx, part) in segment.split("**").enumerate() {
+                    if part.is_empty() {
+                        continue;
+                    }
+                    if bold_idx % 2 == 1 {
+                        ui.label(egui::RichText::new(part).strong());
+                    } else {
+                        ui.label(part);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hover_is_requested_once_after_resting_on_a_word() {
+        let mut hover = HoverState::default();
+        let start = Instant::now();
+        assert_eq!(hover.update(Some((0, 4)), start), None);
+        assert_eq!(hover.update(Some((0, 4)), start + HOVER_DELAY / 2), None);
+        assert_eq!(hover.update(Some((0, 4)), start + HOVER_DELAY), Some((0, 4)));
+        assert_eq!(hover.update(Some((0, 4)), start + HOVER_DELAY * 2), None);
+
+        hover.receive(0, 9, String::from("stale"));
+        hover.receive(0, 4, String::from("fn main()"));
+        assert_eq!(hover.info.as_ref().map(|info| info.contents.as_str()), Some("fn main()"));
+        assert_eq!(hover.update(None, start + HOVER_DELAY * 3), None);
+        assert!(hover.info.is_none());
+    }
+
+    #[test]
+    fn hovering_inside_a_word_snaps_to_its_start() {
+        assert_eq!(word_start("let ünï_x = 1;", 7), Some(4));
+        assert_eq!(word_start("let ünï_x = 1;", 3), None);
+        assert_eq!(word_start("abc", 0), Some(0));
+    }
+}
@@ -0,0 +1,127 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+use std::ops::Range;
+
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::Buffer;
+
+// The start of every line the selection touches. A selection ending right
+// after a newline doesn't reach into the line after it.
+fn line_starts(chars: &[char], selection: Range<usize>) -> Vec<usize> {
+    let end = selection.end.min(chars.len());
+    let start = selection.start.min(end);
+    let last = if end > start && chars[end - 1] == '\n' { end - 1 } else { end };
+    let first = chars[..start].iter().rposition(|c| *c == '\n').map_or(0, |idx| idx + 1);
+    std::iter::once(first)
+        .chain((first..last).filter(|&idx| chars[idx] == '\n').map(|idx| idx + 1))
+        .collect()
+}
+
+// Puts `width` spaces in front of every line the selection touches, blank
+// ones included. The actions go bottom up so each leaves the positions
+// above it alone.
+pub fn indent_lines(content: &str, selection: Range<usize>, width: usize) -> Vec<BufferActions> {
+    let chars: Vec<char> = content.chars().collect();
+    line_starts(&chars, selection)
+        .into_iter()
+        .rev()
+        .map(|char_idx| BufferActions::InsertText { char_idx, text: " ".repeat(width) })
+        .collect()
+}
+
+// Takes up to `width` leading spaces, or one tab, off every line the
+// selection touches.
+pub fn dedent_lines(content: &str, selection: Range<usize>, width: usize) -> Vec<BufferActions> {
+    let chars: Vec<char> = content.chars().collect();
+    line_starts(&chars, selection)
+        .into_iter()
+        .rev()
+        .filter_map(|start| {
+            let end = if chars.get(start) == Some(&'\t') {
+                start + 1
+            } else {
+                start + chars[start..].iter().take(width).take_while(|c| **c == ' ').count()
+            };
+            (end > start).then_some(BufferActions::DeleteRange { start, end })
+        })
+        .collect()
+}
+
+// Where `char_idx` ends up once `actions` are applied in order. Text put in
+// right at it goes after it, so a selection starting at a line start takes
+// in the new indentation.
+pub fn shift(char_idx: usize, actions: &[BufferActions]) -> usize {
+    actions.iter().fold(char_idx, |char_idx, action| match action {
+        BufferActions::InsertText { char_idx: at, text } if *at < char_idx => char_idx + text.chars().count(),
+        BufferActions::DeleteRange { start, end } if *start < char_idx => char_idx - (char_idx.min(*end) - start),
+        _ => char_idx,
+    })
+}
+
+// Tab indents the lines of a selection spanning more than one line, and
+// Shift+Tab dedents the selection's or the cursor's lines, each as one undo
+// step. The selection is kept over the same lines. Returns the actions and
+// the new cursor for macro recording.
+pub fn handle_keys(
+    ctx: &egui::Context,
+    id: egui::Id,
+    buffer: &mut Buffer,
+    width: usize,
+) -> Option<(Vec<BufferActions>, usize)> {
+    if !ctx.input(|i| i.key_pressed(egui::Key::Tab)) {
+        return None;
+    }
+    let mut state = egui::TextEdit::load_state(ctx, id)?;
+    let range = state.cursor.char_range()?;
+    let (primary, secondary) = (range.primary.index, range.secondary.index);
+    let selection = primary.min(secondary)..primary.max(secondary);
+    let actions = if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab)) {
+        dedent_lines(buffer.content(), selection, width)
+    } else if buffer.content().chars().skip(selection.start).take(selection.len()).any(|c| c == '\n')
+        && ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab))
+    {
+        indent_lines(buffer.content(), selection, width)
+    } else {
+        return None;
+    };
+    if actions.is_empty() {
+        return None;
+    }
+    buffer.edit(&actions);
+
+    let (primary, secondary) = (shift(primary, &actions), shift(secondary, &actions));
+    state.cursor.set_char_range(Some(CCursorRange::two(CCursor::new(secondary), CCursor::new(primary))));
+    state.store(ctx, id);
+    Some((actions, primary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(content: &str, actions: &[BufferActions]) -> String {
+        let mut buffer = Buffer::with_content(content);
+        buffer.edit(actions);
+        buffer.content().to_string()
+    }
+
+    #[test]
+    fn every_touched_line_is_indented_and_the_selection_follows() {
+        let content = "one\n\ntwo\nthree";
+        let actions = indent_lines(content, 4..9, 2);
+        assert_eq!(applied(content, &actions), "one\n  \n  two\nthree");
+        // Starting at a line start takes in the new indentation.
+        assert_eq!((shift(4, &actions), shift(9, &actions)), (4, 13));
+        assert_eq!(applied(content, &indent_lines(content, 1..1, 4)), "    one\n\ntwo\nthree");
+    }
+
+    #[test]
+    fn dedent_takes_spaces_up_to_the_width_or_one_tab() {
+        let content = "      six\n\t\ttabs\n  two\nnone";
+        let actions = dedent_lines(content, 0..content.chars().count(), 4);
+        assert_eq!(applied(content, &actions), "  six\n\ttabs\ntwo\nnone");
+        assert_eq!(shift(13, &actions), 8);
+        assert!(dedent_lines("none", 0..4, 4).is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+use std::ops::Range;
+
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::Buffer;
+
+const JOIN_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::J);
+
+// Joins every line the selection touches, or the cursor's line with the next
+// one, replacing each newline and the indentation after it with one space.
+// Nothing is inserted in front of an empty line. Returns the actions, meant
+// to be applied as one edit, and where the cursor goes: the first join.
+pub fn join_lines(content: &str, selection: Range<usize>) -> Option<(Vec<BufferActions>, usize)> {
+    let chars: Vec<char> = content.chars().collect();
+    let end = selection.end.min(chars.len());
+    let start = selection.start.min(end);
+    let mut newlines: Vec<usize> = (start..end).filter(|&idx| chars[idx] == '\n').collect();
+    // A selection ending right after a newline doesn't reach into that line.
+    if end > start && chars[end - 1] == '\n' {
+        newlines.pop();
+    }
+    if newlines.is_empty() {
+        newlines.extend((end..chars.len()).find(|&idx| chars[idx] == '\n'));
+    }
+    let first = *newlines.first()?;
+
+    // Bottom up, so each join leaves the positions above it alone.
+    let mut actions = Vec::new();
+    for &newline in newlines.iter().rev() {
+        let next = (newline + 1..chars.len())
+            .find(|&idx| chars[idx] != ' ' && chars[idx] != '\t')
+            .unwrap_or(chars.len());
+        actions.push(BufferActions::DeleteRange { start: newline, end: next });
+        if next < chars.len() && chars[next] != '\n' {
+            actions.push(BufferActions::InsertText { char_idx: newline, text: " ".to_string() });
+        }
+    }
+    Some((actions, first))
+}
+
+// Applies the join shortcut to the editor's cursor or selection as a single
+// undo step. Returns the actions and the new cursor for macro recording.
+pub fn handle_keys(ctx: &egui::Context, id: egui::Id, buffer: &mut Buffer) -> Option<(Vec<BufferActions>, usize)> {
+    if !ctx.input_mut(|i| i.consume_shortcut(&JOIN_SHORTCUT)) {
+        return None;
+    }
+    let mut state = egui::TextEdit::load_state(ctx, id)?;
+    let range = state.cursor.char_range()?;
+    let (primary, secondary) = (range.primary.index, range.secondary.index);
+    let (actions, cursor) = join_lines(buffer.content(), primary.min(secondary)..primary.max(secondary))?;
+    buffer.edit(&actions);
+
+    state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+    state.store(ctx, id);
+    Some((actions, cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joined(content: &str, selection: Range<usize>) -> Option<(String, usize)> {
+        let (actions, cursor) = join_lines(content, selection)?;
+        let mut buffer = Buffer::with_content(content);
+        buffer.edit(&actions);
+        Some((buffer.content().to_string(), cursor))
+    }
+
+    #[test]
+    fn the_cursor_line_joins_the_next_without_its_indentation() {
+        assert_eq!(joined("fn a() {\n    body\n}", 2..2), Some(("fn a() { body\n}".to_string(), 8)));
+        assert_eq!(joined("a\n\nb", 0..0), Some(("a\nb".to_string(), 1)));
+        assert_eq!(joined("last line", 3..3), None);
+    }
+
+    #[test]
+    fn a_selection_joins_every_line_it_touches_as_one_edit() {
+        let content = "one\n  two\n\tthree\nfour";
+        assert_eq!(joined(content, 1..12), Some(("one two three\nfour".to_string(), 3)));
+        // Ending just after a newline leaves the next line alone.
+        assert_eq!(joined(content, 0..10), Some(("one two\n\tthree\nfour".to_string(), 3)));
+
+        let (actions, _) = join_lines(content, 1..12).unwrap();
+        let mut buffer = Buffer::with_content(content);
+        buffer.edit(&actions);
+        assert!(buffer.undo());
+        assert_eq!(buffer.content(), content);
+    }
+}
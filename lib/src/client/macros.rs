@@ -0,0 +1,205 @@
+use super::movement::Movement;
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::Buffer;
+
+use std::collections::HashMap;
+
+pub const DEFAULT_REGISTER: char = 'q';
+
+// Offsets are relative to the cursor at the time the step ran, so a macro
+// replays wherever the cursor is rather than at the recorded positions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroStep {
+    Insert { offset: isize, text: String },
+    Delete { start: isize, end: isize },
+    Move(Movement),
+    MoveBy(isize),
+}
+
+struct Recording {
+    register: char,
+    buffer_index: usize,
+    cursor: usize,
+    steps: Vec<MacroStep>,
+}
+
+pub struct MacroRecorder {
+    pub register: char,
+    pub count: usize,
+    recording: Option<Recording>,
+    registers: HashMap<char, Vec<MacroStep>>,
+}
+
+impl Default for MacroRecorder {
+    fn default() -> Self {
+        Self {
+            register: DEFAULT_REGISTER,
+            count: 1,
+            recording: None,
+            registers: HashMap::new(),
+        }
+    }
+}
+
+fn offset(from: usize, to: usize) -> isize {
+    to as isize - from as isize
+}
+
+impl MacroRecorder {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start(&mut self, buffer_index: usize, cursor: usize) {
+        self.recording = Some(Recording {
+            register: self.register,
+            buffer_index,
+            cursor,
+            steps: Vec::new(),
+        });
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            self.registers.insert(recording.register, recording.steps);
+        }
+    }
+
+    pub fn steps(&self, register: char) -> &[MacroStep] {
+        self.registers.get(&register).map_or(&[], Vec::as_slice)
+    }
+
+    fn recording_for(&mut self, buffer_index: usize) -> Option<&mut Recording> {
+        self.recording
+            .as_mut()
+            .filter(|recording| recording.buffer_index == buffer_index)
+    }
+
+    // `cursor` is where the editor's cursor ended up after the edit; a
+    // difference from where the edits left it is recorded as a move.
+    pub fn record_edit(&mut self, buffer_index: usize, actions: &[BufferActions], cursor: usize) {
+        let Some(recording) = self.recording_for(buffer_index) else {
+            return;
+        };
+        for action in actions {
+            match action {
+                BufferActions::InsertText { char_idx, text } => {
+                    recording.steps.push(MacroStep::Insert {
+                        offset: offset(recording.cursor, *char_idx),
+                        text: text.clone(),
+                    });
+                    recording.cursor = char_idx + text.chars().count();
+                }
+                BufferActions::DeleteRange { start, end } => {
+                    recording.steps.push(MacroStep::Delete {
+                        start: offset(recording.cursor, *start),
+                        end: offset(recording.cursor, *end),
+                    });
+                    recording.cursor = *start;
+                }
+                BufferActions::ReplaceRange { start, end, text } => {
+                    recording.steps.push(MacroStep::Delete {
+                        start: offset(recording.cursor, *start),
+                        end: offset(recording.cursor, *end),
+                    });
+                    recording.steps.push(MacroStep::Insert { offset: 0, text: text.clone() });
+                    recording.cursor = start + text.chars().count();
+                }
+            }
+        }
+        if recording.cursor != cursor {
+            recording.steps.push(MacroStep::MoveBy(offset(recording.cursor, cursor)));
+            recording.cursor = cursor;
+        }
+    }
+
+    pub fn record_move(&mut self, buffer_index: usize, movement: Movement, cursor: usize) {
+        let Some(recording) = self.recording_for(buffer_index) else {
+            return;
+        };
+        recording.steps.push(MacroStep::Move(movement));
+        recording.cursor = cursor;
+    }
+
+    // Replays `register` `count` times from `cursor` as a single undo step
+    // and returns the cursor afterwards.
+    pub fn replay(&self, register: char, count: usize, buffer: &mut Buffer, cursor: usize) -> usize {
+        let (actions, cursor) = replay_steps(self.steps(register), count, buffer.content(), cursor);
+        if !actions.is_empty() {
+            buffer.edit(&actions);
+        }
+        cursor
+    }
+}
+
+// Steps that would reach past either end of the content clamp to it.
+pub fn replay_steps(steps: &[MacroStep], count: usize, content: &str, cursor: usize) -> (Vec<BufferActions>, usize) {
+    let mut scratch = Buffer::with_content(content);
+    let mut cursor = cursor;
+    let mut actions = Vec::new();
+    for _ in 0..count {
+        for step in steps {
+            let len = scratch.content().chars().count();
+            let at = |offset: isize| cursor.saturating_add_signed(offset).min(len);
+            let action = match step {
+                MacroStep::Insert { offset, text } => {
+                    let char_idx = at(*offset);
+                    cursor = char_idx + text.chars().count();
+                    Some(BufferActions::InsertText { char_idx, text: text.clone() })
+                }
+                MacroStep::Delete { start, end } => {
+                    let (start, end) = (at(*start), at(*end));
+                    cursor = start;
+                    (end > start).then_some(BufferActions::DeleteRange { start, end })
+                }
+                MacroStep::Move(movement) => {
+                    cursor = movement.apply(scratch.content(), cursor.min(len));
+                    None
+                }
+                MacroStep::MoveBy(offset) => {
+                    cursor = at(*offset);
+                    None
+                }
+            };
+            if let Some(action) = action {
+                scratch.apply(&action);
+                actions.push(action);
+            }
+        }
+    }
+    (actions, cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_edits_replay_relative_to_the_cursor() {
+        let mut macros = MacroRecorder::default();
+        macros.start(0, 0);
+        macros.record_move(0, Movement::LineEnd, 1);
+        macros.record_edit(1, &[BufferActions::InsertText { char_idx: 0, text: String::from("ignored") }], 7);
+        macros.record_edit(0, &[BufferActions::InsertText { char_idx: 1, text: String::from(";") }], 3);
+        macros.stop();
+        assert_eq!(
+            macros.steps(DEFAULT_REGISTER),
+            [MacroStep::Move(Movement::LineEnd), MacroStep::Insert { offset: 0, text: String::from(";") }, MacroStep::MoveBy(1)]
+        );
+
+        let mut buffer = Buffer::with_content("a\nb\nc");
+        assert_eq!(macros.replay(DEFAULT_REGISTER, 3, &mut buffer, 0), 8);
+        assert_eq!(buffer.content(), "a;\nb;\nc;");
+        assert!(buffer.undo());
+        assert_eq!(buffer.content(), "a\nb\nc");
+        assert_eq!(macros.replay('z', 3, &mut buffer, 0), 0);
+    }
+
+    #[test]
+    fn deletes_clamp_to_the_content() {
+        let steps = [MacroStep::Delete { start: -2, end: 5 }];
+        let (actions, cursor) = replay_steps(&steps, 3, "abcdef", 3);
+        assert_eq!(actions, [BufferActions::DeleteRange { start: 1, end: 6 }, BufferActions::DeleteRange { start: 0, end: 1 }]);
+        assert_eq!(cursor, 0);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use crate::shared::buffers::BufferStorage;
+
+const JUMP_LIST_LIMIT: usize = 100;
+
+// Where each mark jump started, as (buffer, char) positions, so `''` can
+// go back even when the jump crossed buffers.
+#[derive(Default)]
+pub struct JumpList {
+    entries: VecDeque<(usize, usize)>,
+}
+
+impl JumpList {
+    pub fn push(&mut self, buffer_index: usize, char_idx: usize) {
+        if self.entries.len() == JUMP_LIST_LIMIT {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((buffer_index, char_idx));
+    }
+
+    pub fn pop(&mut self) -> Option<(usize, usize)> {
+        self.entries.pop_back()
+    }
+}
+
+// Steps back through where buffers were edited last, newest first across
+// all of them, and around again. Starts over from the newest after
+// another edit.
+#[derive(Default)]
+pub struct RecentEdits {
+    newest: Option<Instant>,
+    visited: usize,
+}
+
+impl RecentEdits {
+    pub fn previous(&mut self, buffers: &BufferStorage) -> Option<(usize, usize)> {
+        let mut edits: Vec<(Instant, usize, usize)> = buffers
+            .buffers
+            .iter()
+            .enumerate()
+            .flat_map(|(buffer_index, buffer)| {
+                buffer.recent_edits.iter().map(move |(char_idx, at)| (*at, buffer_index, *char_idx))
+            })
+            .collect();
+        edits.sort_by_key(|(at, _, _)| std::cmp::Reverse(*at));
+        let newest = edits.first().map(|(at, _, _)| *at);
+        if newest != self.newest {
+            self.newest = newest;
+            self.visited = 0;
+        }
+        let (_, buffer_index, char_idx) = *edits.get(self.visited % edits.len().max(1))?;
+        self.visited += 1;
+        Some((buffer_index, char_idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::enums::BufferActions;
+    use crate::shared::buffers::Buffer;
+
+    // Spaced out so no two edits share an `Instant`.
+    fn typed(buffer: &mut Buffer, char_idx: usize, text: &str) {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        buffer.edit(&[BufferActions::InsertText { char_idx, text: String::from(text) }]);
+    }
+
+    #[test]
+    fn previous_edits_cycle_newest_first_across_buffers() {
+        let mut buffers = BufferStorage {
+            buffers: vec![
+                Buffer::with_content("a\nb\n"),
+                Buffer::with_content("c\n"),
+            ],
+        };
+        let mut recent = RecentEdits::default();
+        assert_eq!(recent.previous(&buffers), None);
+        typed(&mut buffers.buffers[0], 0, "x");
+        typed(&mut buffers.buffers[1], 0, "y");
+        typed(&mut buffers.buffers[0], 3, "z");
+        assert_eq!(recent.previous(&buffers), Some((0, 4)));
+        assert_eq!(recent.previous(&buffers), Some((1, 1)));
+        assert_eq!(recent.previous(&buffers), Some((0, 1)));
+        assert_eq!(recent.previous(&buffers), Some((0, 4)));
+        // Another edit starts over from it.
+        typed(&mut buffers.buffers[1], 2, "w");
+        assert_eq!(recent.previous(&buffers), Some((1, 3)));
+    }
+}
@@ -1,44 +1,2931 @@
+pub mod annotations;
+pub mod brackets;
+pub mod case;
+pub mod change_gutter;
+pub mod command;
+pub mod debug;
+pub mod definition;
+pub mod dialogs;
+pub mod duplicate;
+pub mod diff_view;
+pub mod editor;
+pub mod encoding;
+pub mod export;
+pub mod file_tree;
+pub mod folding;
+pub mod geometry;
+pub mod guides;
+pub mod highlight;
+pub mod hover;
+pub mod indent;
+pub mod join;
+pub mod macros;
+pub mod marks;
+pub mod modal;
+pub mod movement;
+pub mod occurrences;
+pub mod outline;
+pub mod pairs;
+pub mod reload;
+pub mod rename;
+pub mod search;
+pub mod sort;
+pub mod spell;
+pub mod stats;
+pub mod switcher;
+pub mod tabs;
+pub mod terminal;
+pub mod welcome;
 pub mod windows;
+pub mod workspaces;
+use command::Command;
+use debug::DebugOverlay;
+use definition::DefinitionPicker;
+use dialogs::{DialogRequest, FileDialog, PathPrompt, PendingDialog, ZenityDialog};
+use diff_view::DiffView;
+use encoding::{EncodingAction, EncodingMenu};
+use file_tree::FileTree;
+use guides::Guides;
+use macros::MacroRecorder;
+use marks::{JumpList, RecentEdits};
+use modal::ModalState;
+use outline::OutlinePanel;
+use reload::{ReloadChoice, ReloadPrompt};
+use rename::RenamePrompt;
+use search::SearchPanel;
+use sort::{LineEdit, SortOptions};
+use spell::SpellState;
+use stats::StatsPanel;
+use switcher::WindowSwitcher;
+use workspaces::WorkspaceAction;
+use hover::HoverState;
+use terminal::Terminal;
+use welcome::{WelcomeAction, WelcomeScreen};
+use editor::EditorView;
 use windows::Window;
+use crate::cli::{OpenAt, OpenRequest};
+use crate::config::{Config, ThemeId};
+use crate::interfaces::enums::ClientEvents;
+use crate::logging;
+use crate::server::lsp::{self, Location, LspReply, WorkspaceEdit};
+use crate::server::{SaveAll, Server};
+use crate::server::geometry::SavedGeometry;
+use crate::server::capture::Capture;
+use crate::server::loader::{self, FileLoad};
+use crate::server::maintenance::{Maintenance, Task};
+use crate::server::persistence::{MmapStorage, Storage};
+use crate::server::spell::Dictionary;
+use crate::server::stream::StreamReader;
+use crate::server::syntax;
 use crate::shared::Shared;
+use crate::shared::buffers::{byte_index, BufferSnapshot, BufferStorage, ExternalAction, ExternalChange};
+use crate::shared::frames::{Frame, FrameCluster, FrameKind, FrameStorage, WindowGeometry};
+use crate::shared::snapshot::BufferMeta;
 
-use eframe::egui::{self, pos2};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use eframe::egui;
+
+// How long a save waits for the language server's formatting reply before
+// writing the buffer unformatted.
+const FORMAT_ON_SAVE_TIMEOUT: Duration = Duration::from_secs(2);
+const CAPTURE_DIR_NAME: &str = "captures";
+// How often open files are checked for outside changes.
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
-#[derive(Default)]
 pub struct Client {
+    pub config:    Config,
     pub windows:   Vec<Window>,
-    pub shared :   Shared,
+    pub shared :   Arc<Shared>,
+    pub server :   Server,
+    pub search :   SearchPanel,
+    pub spell : SpellState,
+    pub welcome :  WelcomeScreen,
+    pub diff_view : DiffView,
+    pub terminals : Vec<Terminal>,
+    pub streams :  Vec<StreamReader>,
+    // Background loads of big files, with where to go once open.
+    pub loads :    Vec<(FileLoad, Option<OpenAt>)>,
+    pub hover :    HoverState,
+    // Open and save dialogs; one at a time.
+    pub file_dialog : Arc<dyn FileDialog>,
+    pub dialog :   Option<PendingDialog>,
+    // Where the path is typed instead when `file_dialog` can't be shown.
+    pub path_prompt : PathPrompt,
+    // Untitled buffers left by save-all, each asked for a path in turn.
+    pub save_as_queue : VecDeque<usize>,
+    pub definitions : DefinitionPicker,
+    pub rename :   RenamePrompt,
+    pub reload :   ReloadPrompt,
+    pub outline :  OutlinePanel,
+    pub stats :    StatsPanel,
+    pub macros :   MacroRecorder,
+    pub encoding : EncodingMenu,
+    // Only set when modal editing is turned on in the config.
+    pub modal :    Option<ModalState>,
+    pub jumps :    JumpList,
+    pub recent_edits : RecentEdits,
+    pub file_tree : FileTree,
+    pub active_buffer : Option<usize>,
+    // Side window whose viewport last had focus. Focusing the main window
+    // keeps it, so the menus there can act on it.
+    pub focused_window : Option<u32>,
+    // Ctrl+Tab order of the side windows.
+    pub window_switcher : WindowSwitcher,
+    pub focused_frame : Option<(usize, usize)>,
+    // Window kept by a "close all but" that's waiting on confirmation.
+    pub pending_close : Option<u32>,
+    // A session to switch to once unsaved buffers have been asked about.
+    pub pending_session : Option<String>,
+    // The buffer a macro replay is being previewed in, as it was before.
+    pub macro_preview : Option<(usize, BufferSnapshot)>,
+    // Quitting is waiting on the unsaved changes prompt.
+    pub exit_prompt : bool,
+    // The user agreed to quit with unsaved buffers.
+    exit_confirmed : bool,
+    pub session_name : String,
+    // Where the main window is now; saved on exit for the next run.
+    pub main_geometry : Option<WindowGeometry>,
+    main_clamped : bool,
+    // The main window shows only its bars; see `Config::main_window_toolbar`.
+    pub toolbar_only : bool,
+    // Its size before it was shrunk to them, to go back to.
+    full_size : Option<egui::Vec2>,
+    // Size of the main window's monitor, once known.
+    main_monitor : Option<egui::Vec2>,
+    pub pending_saves : Vec<(usize, Instant)>,
+    // Upkeep off the UI thread; it sets the flags below and wakes the UI.
+    maintenance : Maintenance,
+    autosave_due : Arc<AtomicBool>,
+    disk_changed : Arc<AtomicBool>,
+    // The buffer typed into last; typing in another one ends its undo step.
+    last_edited : Option<usize>,
+    // (buffer, char index) of the cursor in the focused editor, as of the
+    // last frame.
+    pub cursor : Option<(usize, usize)>,
+    // (buffer, char range) selected there; empty when nothing is.
+    pub selection : Option<(usize, Range<usize>)>,
+    // What the Sort lines menu sorts by.
+    pub sort_options : SortOptions,
+    // What `Command::RepeatLast` runs.
+    pub last_command : Option<Command>,
+    // Latest logged error, shown in the status bar until dismissed.
+    pub last_error : Option<String>,
+    pub debug : DebugOverlay,
+    // From the `keymap` config.
+    pub bindings : Vec<(egui::KeyboardShortcut, Command)>,
+}
+
+fn workspace_root() -> PathBuf {
+    let dir = std::env::current_dir().unwrap_or_default();
+    std::fs::canonicalize(&dir).unwrap_or(dir)
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(&Config::default())
+    }
 }
 
 impl Client {
-    pub fn default() -> Self {
+    pub fn new(config: &Config) -> Self {
+        Self::with_storage(config, Arc::new(MmapStorage))
+    }
+
+    // Like `new`, but everything the server reads and writes goes through
+    // `storage`.
+    pub fn with_storage(config: &Config, storage: Arc<dyn Storage>) -> Self {
+        let mut shared = Shared::default();
+        shared.set_poison_recovery(config.recover_poisoned_locks);
+        if let Err(err) = shared.with_buffers_mut(BufferStorage::ensure_scratch) {
+            log::error!("cant add scratch buffer: {err}");
+        }
+        if let Err(err) = shared.with_frames_mut(FrameStorage::ensure_cluster) {
+            log::error!("cant add frame cluster: {err}");
+        }
+        let server = Server::with_storage(config, storage);
+        let saved = server.load_geometry();
+        let restored = shared.with_frames_mut(|frames| {
+            for (cluster, geometry) in frames.frame_clusters.iter_mut().zip(&saved.clusters) {
+                cluster.geometry = *geometry;
+            }
+        });
+        if let Err(err) = restored {
+            log::error!("cant restore window sizes: {err}");
+        }
+        let shared = Arc::new(shared);
+        let (autosave_due, disk_changed) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+        let maintenance = Maintenance::spawn(
+            Self::maintenance_tasks(config, &shared, &server.storage, &autosave_due, &disk_changed),
+            server.waker.clone(),
+        );
         Self {
-            windows: vec![
-                Window::default("Window"),
-            ],
-            shared: Shared::default(),
+            config: config.clone(),
+            windows: vec![Window {
+                placement: saved.clusters.first().copied().flatten(),
+                ..Window::default("Window")
+            }],
+            shared,
+            server,
+            search: SearchPanel::default(),
+            spell: SpellState::default(),
+            welcome: WelcomeScreen::default(),
+            diff_view: DiffView::default(),
+            terminals: Vec::new(),
+            streams: Vec::new(),
+            loads: Vec::new(),
+            hover: HoverState::default(),
+            file_dialog: Arc::new(ZenityDialog),
+            dialog: None,
+            path_prompt: PathPrompt::default(),
+            save_as_queue: VecDeque::new(),
+            definitions: DefinitionPicker::default(),
+            rename: RenamePrompt::default(),
+            reload: ReloadPrompt::default(),
+            outline: OutlinePanel::default(),
+            stats: StatsPanel::default(),
+            macros: MacroRecorder::default(),
+            encoding: EncodingMenu::default(),
+            modal: config.modal_editing.then(ModalState::default),
+            jumps: JumpList::default(),
+            recent_edits: RecentEdits::default(),
+            file_tree: FileTree::new(workspace_root()),
+            active_buffer: None,
+            focused_window: None,
+            window_switcher: WindowSwitcher::default(),
+            focused_frame: None,
+            pending_close: None,
+            pending_session: None,
+            macro_preview: None,
+            exit_prompt: false,
+            exit_confirmed: false,
+            session_name: String::new(),
+            main_geometry: saved.main,
+            main_clamped: false,
+            toolbar_only: config.main_window_toolbar,
+            full_size: None,
+            main_monitor: None,
+            pending_saves: Vec::new(),
+            maintenance,
+            autosave_due,
+            disk_changed,
+            last_edited: None,
+            cursor: None,
+            selection: None,
+            sort_options: SortOptions::default(),
+            last_command: None,
+            last_error: None,
+            debug: DebugOverlay::default(),
+            bindings: command::bindings(&config.keymap),
         }
     }
-}
 
-impl eframe::App for Client {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.label("Hello from the root viewport");
+    // Each task only wakes the UI the first time it finds work, until the UI
+    // has taken it.
+    fn maintenance_tasks(
+        config: &Config,
+        shared: &Arc<Shared>,
+        storage: &Arc<dyn Storage>,
+        autosave_due: &Arc<AtomicBool>,
+        disk_changed: &Arc<AtomicBool>,
+    ) -> Vec<Task> {
+        let mut tasks = Vec::new();
+        let (watched, storage, flag) = (shared.clone(), storage.clone(), disk_changed.clone());
+        tasks.push(Task::new(DISK_CHECK_INTERVAL, move || {
+            let baselines: Vec<(PathBuf, SystemTime)> = watched
+                .with_buffers(|buffers| {
+                    buffers
+                        .buffers
+                        .iter()
+                        .filter_map(|buffer| buffer.disk_baseline().map(|(path, modified)| (path.to_path_buf(), modified)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let changed = baselines.iter().any(|(path, modified)| {
+                storage.metadata(path).is_ok_and(|metadata| metadata.modified != *modified)
+            });
+            changed && !flag.swap(true, Ordering::Relaxed)
+        }));
+        let interval = Duration::from_secs(config.autosave_interval_secs);
+        if !interval.is_zero() {
+            let (watched, flag) = (shared.clone(), autosave_due.clone());
+            tasks.push(Task::new(interval, move || {
+                let pristine = watched.with_buffers(BufferStorage::is_pristine).unwrap_or(true);
+                !pristine && !flag.swap(true, Ordering::Relaxed)
+            }));
+        }
+        tasks
+    }
+
+    // Big files, and anything that will open in big-file mode, are handed
+    // to a background load and return `None` here.
+    pub fn open_file(&mut self, path: &Path) -> Option<usize> {
+        self.open_file_at(path, None)
+    }
+
+    pub fn open_file_at(&mut self, path: &Path, at: Option<OpenAt>) -> Option<usize> {
+        let size = self.server.storage.metadata(path).map_or(0, |metadata| metadata.len);
+        if size >= loader::BACKGROUND_LOAD_BYTES || size > self.server.big_file_threshold {
+            self.loads.push((FileLoad::spawn(path.to_path_buf()), at));
+            self.welcome.dismissed = true;
+            return None;
+        }
+        let buffer_index = match self.server.open_file(&self.shared, path) {
+            Ok(buffer_index) => buffer_index,
+            Err(err) => {
+                log::error!("cant open {}: {err}", path.display());
+                return None;
+            }
+        };
+        self.show_opened(buffer_index, at);
+        Some(buffer_index)
+    }
+
+    // Without a position to go to, the cursor goes back to where it was
+    // when the file was last open.
+    fn show_opened(&mut self, buffer_index: usize, at: Option<OpenAt>) {
+        if let Some(meta) = self.shared.buffer_meta().get(buffer_index)
+            && meta.big_file
+        {
+            let name = meta.file_path.file_name().unwrap_or_default().to_string_lossy();
+            self.last_error = Some(format!(
+                "{name} is over {} bytes; highlighting and the language server are off",
+                self.server.big_file_threshold
+            ));
+        }
+        if !(self.config.open_in_current_frame && self.retarget_current_frame(buffer_index)) {
+            self.add_frame(Frame {
+                buffer_index,
+                ..Frame::default()
+            });
+        }
+        match at {
+            Some(OpenAt::Line(line)) => self.jump_to_line(buffer_index, line),
+            Some(at) => {
+                let char_idx = self
+                    .shared
+                    .with_buffers(|buffers| at.char_index(buffers.buffers.get(buffer_index)?.content()))
+                    .ok()
+                    .flatten();
+                if let Some(char_idx) = char_idx {
+                    self.place_cursor(buffer_index, char_idx);
+                }
+            }
+            None => self.restore_position(buffer_index),
+        }
+    }
+
+    // Shows the buffer in the current frame, if there is one, and makes it
+    // the one commands act on.
+    fn retarget_current_frame(&mut self, buffer_index: usize) -> bool {
+        let Some((cluster_idx, frame_idx)) = self.current_frame() else {
+            return false;
+        };
+        let buffer_count = self.shared.buffer_meta().len();
+        match self
+            .shared
+            .with_frames_mut(|frames| frames.set_frame_buffer(cluster_idx, frame_idx, buffer_index, buffer_count))
+        {
+            Ok(Some(_)) => {
+                self.active_buffer = Some(buffer_index);
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                log::error!("cant open buffer {buffer_index} in the current frame: {err}");
+                false
+            }
+        }
+    }
+
+    pub fn remember_position(&mut self, buffer_index: usize, char_idx: usize) {
+        if let Some(meta) = self.shared.buffer_meta().get(buffer_index) {
+            self.server.positions.remember(&meta.file_path, char_idx);
+        }
+    }
+
+    fn restore_position(&self, buffer_index: usize) {
+        let restored = self.shared.with_buffers(|buffers| {
+            let buffer = buffers.buffers.get(buffer_index)?;
+            self.server.positions.get(&buffer.file_path)
         });
-        // for window in windows {
-            ctx.show_viewport_immediate(
-                egui::ViewportId::from_hash_of("riptide"),
-                egui::ViewportBuilder::default()
-                    // .with_position(pos2(x, y))
-                    .with_title("Viewport")
-                .with_inner_size([200.0, 100.0]),
-                |ctx, _| {
-                    egui::CentralPanel::default().show(ctx, |ui| {
-                        ui.label("Hello from deferred viewport");
-                    });
+        if let Ok(Some(char_idx)) = restored {
+            self.place_cursor(buffer_index, char_idx);
+        }
+    }
+
+    // Puts the cursor of the frames showing the buffer at `char_idx` and
+    // scrolls its line into view.
+    fn place_cursor(&self, buffer_index: usize, char_idx: usize) {
+        let line = self.shared.with_buffers(|buffers| {
+            let content = &buffers.buffers.get(buffer_index)?.content();
+            Some(content.chars().take(char_idx).filter(|c| *c == '\n').count())
+        });
+        let Ok(Some(line)) = line else {
+            return;
+        };
+        let moved = self.shared.with_frames_mut(|frames| {
+            frames.set_cursor(buffer_index, char_idx);
+            frames.jump_to_line(buffer_index, line);
+        });
+        if let Err(err) = moved {
+            log::error!("cant move cursor in buffer {buffer_index}: {err}");
+        }
+    }
+
+    fn jump_to_line(&self, buffer_index: usize, line: usize) {
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.jump_to_line(buffer_index, line)) {
+            log::error!("cant jump to line {}: {err}", line + 1);
+        }
+    }
+
+    // Whether a dialog or the path prompt is already asking for a path.
+    pub fn asking_for_path(&self) -> bool {
+        self.dialog.is_some() || self.path_prompt.request.is_some()
+    }
+
+    fn show_dialog(&mut self, request: DialogRequest) {
+        if self.asking_for_path() {
+            return;
+        }
+        if let Some(reason) = self.file_dialog.unavailable() {
+            self.last_error = Some(format!("{reason}; type the path instead"));
+            self.path_prompt.open(request);
+            return;
+        }
+        let waker = self.server.waker.clone();
+        self.dialog = Some(PendingDialog::spawn(self.file_dialog.clone(), request, waker));
+    }
+
+    fn ask_save_as(&mut self, buffer_index: usize) {
+        let suggested = self
+            .shared
+            .buffer_meta()
+            .get(buffer_index)
+            .map(|meta| meta.file_path.clone())
+            .unwrap_or_default();
+        self.show_dialog(DialogRequest::SaveAs { buffer_index, suggested });
+    }
+
+    // Acts on the dialog once it's closed; cancelling does nothing.
+    pub fn pump_dialog(&mut self) {
+        if !self.asking_for_path()
+            && let Some(buffer_index) = self.save_as_queue.pop_front()
+        {
+            self.ask_save_as(buffer_index);
+        }
+        let Some(picked) = self.dialog.as_ref().and_then(PendingDialog::poll) else {
+            return;
+        };
+        let Some(dialog) = self.dialog.take() else {
+            return;
+        };
+        if let Some(path) = picked {
+            self.act_on_path(dialog.request, &path);
+        }
+    }
+
+    // Does what the dialog or path prompt was asking for.
+    fn act_on_path(&mut self, request: DialogRequest, path: &Path) {
+        match request {
+            DialogRequest::Open => {
+                self.open_file(path);
+            }
+            DialogRequest::SaveAs { buffer_index, .. } => self.save_buffer_as(buffer_index, path),
+            DialogRequest::ExportHtml { buffer_index, .. } => self.export_html(buffer_index, path),
+        }
+    }
+
+    // Writes the buffer, or just its selection, as HTML colored the way the
+    // editor shows it.
+    pub fn export_html(&mut self, buffer_index: usize, path: &Path) {
+        let selection = self
+            .selection
+            .clone()
+            .filter(|(selected, range)| *selected == buffer_index && !range.is_empty())
+            .map(|(_, range)| range);
+        let html = self.shared.with_buffers(|buffers| {
+            let buffer = buffers.buffers.get(buffer_index)?;
+            let spans = if buffer.big_file { Vec::new() } else { syntax::highlights(&buffer.file_path, buffer.content()) };
+            let range = selection.map_or(0..buffer.content().len(), |range| {
+                byte_index(buffer.content(), range.start)..byte_index(buffer.content(), range.end)
+            });
+            let title = windows::buffer_title(&BufferMeta::from(buffer));
+            let theme = buffer.theme_override.unwrap_or(self.config.theme.syntax);
+            Some(export::to_html(buffer.content(), range, &spans, theme, &self.config.theme.visuals(), &title))
+        });
+        match html {
+            Ok(Some(html)) => {
+                if let Err(err) = self.server.storage.write_atomic(path, html.as_bytes()) {
+                    log::error!("cant export buffer {buffer_index} to {}: {err}", path.display());
+                }
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("cant export buffer {buffer_index}: {err}"),
+        }
+    }
+
+    pub fn save_buffer_as(&mut self, buffer_index: usize, path: &Path) {
+        match self.server.save_buffer_as(&self.shared, buffer_index, path) {
+            Ok(0) => {}
+            Ok(unmappable) => self.warn_unmappable(buffer_index, unmappable),
+            Err(err) => log::error!("cant save buffer {buffer_index} as {}: {err}", path.display()),
+        }
+    }
+
+    fn pump_loads(&mut self) {
+        let mut index = 0;
+        while index < self.loads.len() {
+            let Some(result) = self.loads[index].0.poll() else {
+                index += 1;
+                continue;
+            };
+            let (load, at) = self.loads.remove(index);
+            match result {
+                Ok(bytes) => match self.server.add_buffer(&self.shared, &load.path, &bytes) {
+                    Ok(buffer_index) => self.show_opened(buffer_index, at),
+                    Err(err) => log::error!("cant open {}: {err}", load.path.display()),
+                },
+                Err(err) if load.is_cancelled() => log::info!("cancelled loading {}: {err}", load.path.display()),
+                Err(err) => log::error!("cant open {}: {err}", load.path.display()),
+            }
+        }
+    }
+
+    fn show_status_bar(&mut self, ctx: &egui::Context) {
+        if let Some(error) = logging::take_errors().pop() {
+            self.last_error = Some(error);
+        }
+        if self.loads.is_empty() && self.last_error.is_none() {
+            return;
+        }
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            if let Some(error) = &self.last_error {
+                let mut dismissed = false;
+                ui.horizontal(|ui| {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                    dismissed = ui.small_button("Dismiss").clicked();
+                });
+                if dismissed {
+                    self.last_error = None;
+                }
+            }
+            for (load, _) in &self.loads {
+                ui.horizontal(|ui| {
+                    let name = load.path.file_name().unwrap_or_default().to_string_lossy();
+                    ui.label(format!("Loading {name}"));
+                    if ui.button("Cancel").clicked() {
+                        load.cancel();
+                    }
+                    ui.add(egui::ProgressBar::new(load.pct as f32 / 100.0).show_percentage());
+                });
+            }
+        });
+    }
+
+    // Reuses the buffer already holding `path` instead of opening it twice.
+    pub fn open_or_find(&mut self, path: &Path) -> Option<usize> {
+        let canonical = self.server.storage.canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.shared
+            .with_buffers(|buffers| buffers.find_by_path(&canonical))
+            .ok()
+            .flatten()
+            .or_else(|| self.open_file(path))
+    }
+
+    pub fn go_to_location(&mut self, location: &Location) {
+        let Some(buffer_index) = self.open_or_find(&location.path) else {
+            return;
+        };
+        let char_idx = self
+            .shared
+            .with_buffers(|buffers| Some(lsp::char_index_at(buffers.buffers.get(buffer_index)?.content(), location.position)))
+            .ok()
+            .flatten();
+        match char_idx {
+            Some(char_idx) => self.place_cursor(buffer_index, char_idx),
+            None => self.jump_to_line(buffer_index, location.position.line as usize),
+        }
+    }
+
+    // Upper case marks name one place across all buffers, so setting one
+    // clears it everywhere else.
+    fn set_global_mark(&self, buffer_index: usize, name: char, cursor: usize) {
+        let set = self.shared.with_buffers_mut(|buffers| {
+            for buffer in &mut buffers.buffers {
+                buffer.marks.remove(&name);
+            }
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                buffer.set_mark(name, cursor);
+            }
+        });
+        if let Err(err) = set {
+            log::error!("cant set mark {name}: {err}");
+        }
+    }
+
+    fn jump_to_mark(&mut self, buffer_index: usize, name: char, cursor: usize) {
+        let target = if name == '\'' {
+            self.jumps.pop()
+        } else {
+            let found = self.shared.with_buffers(|buffers| {
+                if name.is_lowercase() {
+                    let mark = buffers.buffers.get(buffer_index)?.mark(name)?;
+                    return Some((buffer_index, mark));
+                }
+                buffers
+                    .buffers
+                    .iter()
+                    .enumerate()
+                    .find_map(|(idx, buffer)| Some((idx, buffer.mark(name)?)))
+            });
+            match found {
+                Ok(found) => found,
+                Err(err) => {
+                    log::error!("cant jump to mark {name}: {err}");
+                    return;
+                }
+            }
+        };
+        let Some((target_buffer, char_idx)) = target else {
+            return;
+        };
+        // `''` pushes too, so repeating it goes back and forth.
+        self.jumps.push(buffer_index, cursor);
+        self.ensure_shown(target_buffer);
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.set_cursor(target_buffer, char_idx)) {
+            log::error!("cant jump to mark {name}: {err}");
+        }
+    }
+
+    // Adds a frame for the buffer unless one already shows it.
+    fn ensure_shown(&mut self, buffer_index: usize) {
+        if !self.shared.with_frames(|frames| frames.shows_buffer(buffer_index)).unwrap_or(true) {
+            self.show_opened(buffer_index, None);
+        }
+    }
+
+    fn active_path(&self) -> Option<PathBuf> {
+        let buffer_index = self.active_buffer?;
+        self.shared
+            .with_buffers(|buffers| Some(buffers.buffers.get(buffer_index)?.file_path.clone()))
+            .ok()
+            .flatten()
+            .filter(|path| !path.as_os_str().is_empty())
+    }
+
+    // Files the edit touches that aren't open yet are opened first; each
+    // buffer receives its share of the edit as a single undo step.
+    pub fn apply_workspace_edit(&mut self, workspace_edit: WorkspaceEdit) {
+        for (path, edits) in workspace_edit {
+            let Some(buffer_index) = self.open_or_find(&path) else {
+                continue;
+            };
+            self.edit_buffer(buffer_index, &edits);
+        }
+    }
+
+    fn edit_buffer(&self, buffer_index: usize, edits: &[lsp::TextEdit]) {
+        let edited = self.shared.with_buffers_mut(|buffers| {
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                let actions = lsp::edits_to_actions(buffer.content(), edits);
+                buffer.edit(&actions);
+            }
+        });
+        if let Err(err) = edited {
+            log::error!("cant edit buffer {buffer_index}: {err}");
+        }
+    }
+
+    pub fn save_buffer(&mut self, buffer_index: usize) {
+        let path = self
+            .shared
+            .with_buffers(|buffers| Some(buffers.buffers.get(buffer_index)?.file_path.clone()));
+        let Ok(Some(path)) = path else {
+            return;
+        };
+        let format = self.config.format_on_save_for(lsp::language_id(&path))
+            && self.server.lsp.formatting(&self.shared, buffer_index, self.config.tab_width);
+        if format {
+            self.pending_saves.push((buffer_index, Instant::now()));
+        } else {
+            self.write_buffer(buffer_index);
+        }
+    }
+
+    // Skips format on save. Failures are listed in the status bar together.
+    pub fn save_all(&mut self) {
+        let SaveAll { saved, untitled } = match self.server.save_all(&self.shared) {
+            Ok(saved) => saved,
+            Err(err) => {
+                log::error!("cant save all buffers: {err}");
+                return;
+            }
+        };
+        let meta = self.shared.buffer_meta();
+        let mut failed = Vec::new();
+        for (buffer_index, result) in saved {
+            match result {
+                Ok(0) => {}
+                Ok(unmappable) => self.warn_unmappable(buffer_index, unmappable),
+                Err(err) => {
+                    let name = meta.get(buffer_index).map_or_else(|| buffer_index.to_string(), windows::buffer_title);
+                    log::warn!("cant save {name}: {err}");
+                    failed.push(format!("{name} ({err})"));
+                }
+            }
+        }
+        if !failed.is_empty() {
+            self.last_error = Some(format!("couldn't save {}", failed.join(", ")));
+        }
+        for buffer_index in untitled {
+            if !self.save_as_queue.contains(&buffer_index) {
+                self.save_as_queue.push_back(buffer_index);
+            }
+        }
+    }
+
+    fn write_buffer(&mut self, buffer_index: usize) {
+        match self.server.save_buffer(&self.shared, buffer_index) {
+            Ok(None | Some(0)) => {}
+            Ok(Some(unmappable)) => self.warn_unmappable(buffer_index, unmappable),
+            Err(err) => log::error!("cant save buffer {buffer_index}: {err}"),
+        }
+    }
+
+    // A hard reload always re-reads the file. A soft one only does when
+    // there's nothing unsaved to lose, and asks otherwise.
+    pub fn reload_buffer(&mut self, buffer_index: usize, hard: bool) {
+        let modified = self
+            .shared
+            .with_buffers(|buffers| buffers.buffers.get(buffer_index).is_some_and(|buffer| buffer.is_modified()))
+            .unwrap_or(false);
+        if hard || !modified {
+            if let Err(err) = self.server.reload_buffer(&self.shared, buffer_index) {
+                log::error!("cant reload buffer {buffer_index}: {err}");
+            }
+            return;
+        }
+        match self.server.disk_state(&self.shared, buffer_index) {
+            Ok((_, disk_changed)) => self.reload.open(buffer_index, true, disk_changed),
+            Err(err) => log::error!("cant reload buffer {buffer_index}: {err}"),
+        }
+    }
+
+    fn handle_reload_choice(&mut self, buffer_index: usize, choice: ReloadChoice) {
+        match choice {
+            ReloadChoice::Discard => self.reload_buffer(buffer_index, true),
+            ReloadChoice::KeepMine => {}
+            ReloadChoice::Compare => {
+                let content = self
+                    .shared
+                    .with_buffers(|buffers| buffers.buffers.get(buffer_index).map(|buffer| buffer.content().to_string()));
+                match (content, self.server.disk_state(&self.shared, buffer_index)) {
+                    (Ok(Some(content)), Ok((disk, _))) => {
+                        self.diff_view.left = buffer_index;
+                        self.diff_view.right = buffer_index;
+                        self.diff_view.compare_texts(&content, &disk);
+                    }
+                    (_, Err(err)) => log::error!("cant read buffer {buffer_index} from disk: {err}"),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn warn_unmappable(&mut self, buffer_index: usize, unmappable: usize) {
+        let warning = format!("buffer {buffer_index}: {unmappable} characters saved as `?`, the encoding can't represent them");
+        log::warn!("{warning}");
+        self.encoding.warning = Some(warning);
+    }
+
+    fn handle_encoding_action(&mut self, action: EncodingAction) {
+        self.encoding.warning = None;
+        match action {
+            EncodingAction::Convert(buffer_index, target) => {
+                match self.server.convert_encoding(&self.shared, buffer_index, target) {
+                    Ok(0) => {}
+                    Ok(unmappable) => self.warn_unmappable(buffer_index, unmappable),
+                    Err(err) => log::error!("cant save buffer {buffer_index} as {}: {err}", target.name()),
+                }
+            }
+            EncodingAction::Reinterpret(buffer_index, source) => {
+                if let Err(err) = self.server.reinterpret_buffer(&self.shared, buffer_index, source) {
+                    log::error!("cant reopen buffer {buffer_index} as {}: {err}", source.name());
+                }
+            }
+        }
+    }
+
+    fn finish_formatted_save(&mut self, buffer_index: usize, edits: &[lsp::TextEdit]) {
+        let Some(position) = self.pending_saves.iter().position(|(index, _)| *index == buffer_index) else {
+            return;
+        };
+        self.pending_saves.remove(position);
+        self.edit_buffer(buffer_index, edits);
+        self.write_buffer(buffer_index);
+    }
+
+    fn flush_stale_saves(&mut self) {
+        let now = Instant::now();
+        let (stale, waiting) = self
+            .pending_saves
+            .drain(..)
+            .partition(|(_, since)| now.duration_since(*since) >= FORMAT_ON_SAVE_TIMEOUT);
+        self.pending_saves = waiting;
+        for (buffer_index, _) in stale {
+            self.write_buffer(buffer_index);
+        }
+    }
+
+    pub fn replay_macro(&mut self, buffer_index: usize, cursor: usize) {
+        if self.macros.is_recording() {
+            return;
+        }
+        let (register, count) = (self.macros.register, self.macros.count);
+        let replayed = self.shared.with_buffers_mut(|buffers| {
+            let buffer = buffers.buffers.get_mut(buffer_index)?;
+            Some(self.macros.replay(register, count, buffer, cursor))
+        });
+        let cursor = match replayed {
+            Ok(Some(cursor)) => cursor,
+            Ok(None) => return,
+            Err(err) => {
+                log::error!("cant replay macro: {err}");
+                return;
+            }
+        };
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.set_cursor(buffer_index, cursor)) {
+            log::error!("cant move cursor: {err}");
+        }
+    }
+
+    // Replays the macro at the cursor from a checkpoint, kept until the
+    // replay is confirmed or reverted.
+    pub fn preview_macro(&mut self, buffer_index: usize) {
+        if self.macros.is_recording() {
+            return;
+        }
+        let cursor = self.cursor.filter(|(buffer, _)| *buffer == buffer_index).map_or(0, |(_, cursor)| cursor);
+        let snapshot = self
+            .shared
+            .with_buffers(|buffers| buffers.buffers.get(buffer_index).map(|buffer| buffer.snapshot(cursor)));
+        match snapshot {
+            Ok(Some(snapshot)) => self.macro_preview = Some((buffer_index, snapshot)),
+            Ok(None) => return,
+            Err(err) => {
+                log::error!("cant checkpoint buffer {buffer_index}: {err}");
+                return;
+            }
+        }
+        self.replay_macro(buffer_index, cursor);
+    }
+
+    // Reverting also drops edits made since the preview started.
+    pub fn finish_macro_preview(&mut self, keep: bool) {
+        let Some((buffer_index, snapshot)) = self.macro_preview.take() else {
+            return;
+        };
+        if keep {
+            return;
+        }
+        let restored = self
+            .shared
+            .with_buffers_mut(|buffers| buffers.buffers.get_mut(buffer_index).map(|buffer| buffer.restore(&snapshot)));
+        match restored {
+            Ok(Some(cursor)) => {
+                self.cursor = Some((buffer_index, cursor));
+                if let Err(err) = self.shared.with_frames_mut(|frames| frames.set_cursor(buffer_index, cursor)) {
+                    log::error!("cant move cursor: {err}");
+                }
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("cant revert macro preview: {err}"),
+        }
+    }
+
+    fn show_macro_preview(&mut self, ctx: &egui::Context) {
+        if self.macro_preview.is_none() {
+            return;
+        }
+        let mut decided = None;
+        egui::Window::new("Macro preview")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Replayed register {} {} times.", self.macros.register, self.macros.count));
+                ui.horizontal(|ui| {
+                    if ui.button("Keep").clicked() {
+                        decided = Some(true);
+                    }
+                    if ui.button("Revert").clicked() {
+                        decided = Some(false);
+                    }
+                });
+            });
+        if let Some(keep) = decided {
+            self.finish_macro_preview(keep);
+        }
+    }
+
+    pub fn save_session(&mut self, name: &str) {
+        match self.server.save_session(&self.shared, name) {
+            Ok(()) => self.session_name = name.to_string(),
+            Err(err) => log::error!("cant save session {name}: {err}"),
+        }
+    }
+
+    // Switching unloads the current session: it's saved first if it has a
+    // name, and unsaved buffers are asked about before they're dropped.
+    pub fn load_session(&mut self, name: &str) {
+        if !self.session_name.is_empty() && self.session_name != name {
+            let current = self.session_name.clone();
+            if let Err(err) = self.server.save_session(&self.shared, &current) {
+                log::error!("cant save session {current} before switching: {err}");
+                return;
+            }
+        }
+        if self.unsaved_buffers().is_empty() {
+            self.switch_session(name);
+        } else {
+            self.pending_session = Some(name.to_string());
+        }
+    }
+
+    pub fn confirm_session_switch(&mut self, switch: bool) {
+        if let Some(name) = self.pending_session.take()
+            && switch
+        {
+            self.switch_session(&name);
+        }
+    }
+
+    fn switch_session(&mut self, name: &str) {
+        if let Err(err) = self.server.load_session(&self.shared, name) {
+            log::error!("cant load session {name}: {err}");
+            return;
+        }
+        self.session_name = name.to_string();
+        self.reset_after_load();
+    }
+
+    pub fn restore_snapshot(&mut self, index: usize) {
+        if let Err(err) = self.server.restore_snapshot(&self.shared, index) {
+            log::error!("cant restore snapshot {index}: {err}");
+            return;
+        }
+        self.reset_after_load();
+    }
+
+    // Snapshots the session every `autosave_interval_secs`, once something
+    // has been opened or typed.
+    fn autosave_snapshot(&mut self) {
+        if !self.autosave_due.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Err(err) = self.server.save_session_rotating(&self.shared) {
+            log::error!("cant snapshot session: {err}");
+        }
+    }
+
+    // Follows each changed buffer's policy. One prompt shows at a time;
+    // changes that would need another are only logged.
+    fn check_disk_changes(&mut self) {
+        if !self.disk_changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let changes = match self.server.external_changes(&self.shared, self.config.external_change) {
+            Ok(changes) => changes,
+            Err(err) => {
+                log::error!("cant check files for outside changes: {err}");
+                return;
+            }
+        };
+        for (buffer_index, action) in changes {
+            match action {
+                ExternalAction::Reload => {
+                    if let Err(err) = self.server.reload_buffer(&self.shared, buffer_index) {
+                        log::error!("cant reload buffer {buffer_index}: {err}");
+                    }
+                }
+                ExternalAction::Prompt if self.reload.buffer_index.is_none() => {
+                    let modified = self
+                        .shared
+                        .with_buffers(|buffers| buffers.buffers.get(buffer_index).is_some_and(|buffer| buffer.is_modified()))
+                        .unwrap_or(false);
+                    self.reload.open(buffer_index, modified, true);
+                }
+                ExternalAction::Prompt => log::warn!("buffer {buffer_index} changed on disk"),
+            }
+        }
+    }
+
+    // Keeps the next edit in the buffer out of the undo step being typed.
+    fn seal_history(&self, buffer_index: usize) {
+        let sealed = self.shared.with_buffers_mut(|buffers| {
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                buffer.history.seal();
+            }
+        });
+        if let Err(err) = sealed {
+            log::error!("cant end undo step in buffer {buffer_index}: {err}");
+        }
+    }
+
+    // Everything tied to the old session's buffer indices goes away with it.
+    fn reset_after_load(&mut self) {
+        if let Err(err) = self.shared.with_buffers_mut(BufferStorage::ensure_scratch) {
+            log::error!("cant add scratch buffer: {err}");
+        }
+        if let Err(err) = self.shared.with_frames_mut(FrameStorage::ensure_cluster) {
+            log::error!("cant add frame cluster: {err}");
+        }
+        let layout = self.shared.frame_layout();
+        for window in &mut self.windows {
+            window.placement = layout
+                .clusters
+                .get(window.frame_cluster_index)
+                .and_then(|cluster| cluster.geometry);
+        }
+        windows::reconcile(&mut self.windows, &layout);
+        self.terminals.clear();
+        self.streams.clear();
+        self.loads.clear();
+        self.pending_saves.clear();
+        self.save_as_queue.clear();
+        self.server.lsp.close_unloaded(&self.shared);
+        self.hover = HoverState::default();
+        self.definitions.locations.clear();
+        self.rename = RenamePrompt::default();
+        self.path_prompt = PathPrompt::default();
+        self.reload = ReloadPrompt::default();
+        self.diff_view.open = false;
+        self.outline.clear();
+        self.jumps = JumpList::default();
+        self.recent_edits = RecentEdits::default();
+        self.active_buffer = None;
+        self.last_edited = None;
+        self.focused_frame = None;
+        self.macros.stop();
+        self.macro_preview = None;
+        self.welcome.dismissed = true;
+    }
+
+    pub fn open_requests(&mut self, requests: Vec<OpenRequest>) {
+        for request in requests {
+            self.open_file_at(&request.path, request.at);
+        }
+    }
+
+    // Opens piped input as an unnamed buffer that fills in as data arrives.
+    pub fn open_stream(&mut self, reader: impl Read + Send + 'static) {
+        let mut stream = match StreamReader::spawn(&self.shared, reader) {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::error!("cant open stream: {err}");
+                return;
+            }
+        };
+        if self.config.capture_streams {
+            stream = stream.capture_to(self.stream_capture("stream"));
+        }
+        self.add_frame(Frame {
+            buffer_index: stream.buffer_index,
+            follow: true,
+            ..Frame::default()
+        });
+        self.streams.push(stream);
+        self.welcome.dismissed = true;
+    }
+
+    fn stream_capture(&self, prefix: &str) -> Capture {
+        let dir = self.config.data_dir.join(CAPTURE_DIR_NAME);
+        if let Err(err) = self.server.storage.create_dir(&dir) {
+            log::error!("cant create {}: {err}", dir.display());
+        }
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let path = dir.join(format!("{prefix}-{}.log", started.as_millis()));
+        Capture::new(path, self.config.capture_window_bytes, self.server.storage.clone())
+    }
+
+    fn frame_cluster_index(&self) -> usize {
+        self.windows.first().map_or(0, |window| window.frame_cluster_index)
+    }
+
+    // Commands that open frames need the main window's cluster to exist.
+    fn has_frame_cluster(&self) -> bool {
+        let cluster_idx = self.frame_cluster_index();
+        self.shared
+            .with_frames(|frames| cluster_idx < frames.frame_clusters.len())
+            .unwrap_or(false)
+    }
+
+    // A new window on an empty frame cluster of its own, cascaded from the
+    // others.
+    pub fn open_window(&mut self) -> u32 {
+        let id = self.windows.iter().map(|window| window.id + 1).max().unwrap_or(0);
+        let anchor = self.main_geometry.filter(|_| self.config.windows_follow_main);
+        let placement = geometry::cascade(anchor, self.windows.len(), self.main_monitor);
+        let cluster_idx = self.shared.with_frames_mut(|frames| {
+            frames.frame_clusters.push(FrameCluster {
+                name: format!("Workspace {}", frames.frame_clusters.len() + 1),
+                is_visible: true,
+                frames: Vec::new(),
+                geometry: None,
+                active_frame: 0,
+            });
+            frames.frame_clusters.len() - 1
+        });
+        match cluster_idx {
+            Ok(cluster_idx) => self.windows.push(Window {
+                id,
+                frame_cluster_index: cluster_idx,
+                placement: Some(placement),
+                ..Window::default("Window")
+            }),
+            Err(err) => log::error!("cant open window: {err}"),
+        }
+        id
+    }
+
+    // Windows are closed by id, so closing several never skips one whose
+    // position shifted.
+    pub fn close_window(&mut self, id: u32) {
+        self.close_windows(|window| window.id == id);
+    }
+
+    // Frames and buffers stay where they are, but the clusters are hidden so
+    // a reload doesn't bring the windows back.
+    fn close_windows(&mut self, close: impl Fn(&Window) -> bool) {
+        let (closed, kept): (Vec<Window>, Vec<Window>) = std::mem::take(&mut self.windows).into_iter().partition(close);
+        self.windows = kept;
+        let hidden = self.shared.with_frames_mut(|frames| {
+            for window in &closed {
+                if let Some(cluster) = frames.frame_clusters.get_mut(window.frame_cluster_index) {
+                    cluster.is_visible = false;
+                }
+            }
+        });
+        if let Err(err) = hidden {
+            log::error!("cant hide closed windows: {err}");
+        }
+    }
+
+    pub fn focus_window(&mut self, id: u32) {
+        self.focused_window = Some(id);
+        self.window_switcher.focused(id);
+    }
+
+    // Brings the next side window in most recently focused order to the
+    // front; see `WindowSwitcher`.
+    fn switch_window(&mut self, ctx: &egui::Context, backwards: bool) {
+        let open: Vec<u32> = self.windows.iter().map(|window| window.id).collect();
+        if let Some(id) = self.window_switcher.step(&open, backwards) {
+            ctx.send_viewport_cmd_to(egui::ViewportId::from_hash_of(("riptide", id)), egui::ViewportCommand::Focus);
+        }
+    }
+
+    // Shows a frame cluster's window again if it was closed and brings it to
+    // the front.
+    fn switch_workspace(&mut self, ctx: &egui::Context, cluster_idx: usize) {
+        let shown = self.shared.with_frames_mut(|frames| {
+            if let Some(cluster) = frames.frame_clusters.get_mut(cluster_idx) {
+                cluster.is_visible = true;
+            }
+        });
+        if let Err(err) = shown {
+            log::error!("cant show workspace: {err}");
+            return;
+        }
+        windows::reconcile(&mut self.windows, &self.shared.frame_layout());
+        if let Some(id) = self.windows.iter().find(|window| window.frame_cluster_index == cluster_idx).map(|window| window.id) {
+            self.focus_window(id);
+            ctx.send_viewport_cmd_to(egui::ViewportId::from_hash_of(("riptide", id)), egui::ViewportCommand::Focus);
+        }
+    }
+
+    fn show_workspace_bar(&mut self, ctx: &egui::Context) {
+        let layout = self.shared.frame_layout();
+        let current = self
+            .current_window()
+            .and_then(|id| self.windows.iter().find(|window| window.id == id))
+            .map(|window| window.frame_cluster_index);
+        match workspaces::show(ctx, &layout, current) {
+            Some(WorkspaceAction::Switch(cluster_idx)) => self.switch_workspace(ctx, cluster_idx),
+            Some(WorkspaceAction::New) => {
+                self.open_window();
+            }
+            Some(WorkspaceAction::Close(cluster_idx)) => self.close_windows(|window| window.frame_cluster_index == cluster_idx),
+            None => {}
+        }
+    }
+
+    // The window "current" commands act on: the last focused side window, or
+    // the first one if none has had focus yet.
+    pub fn current_window(&self) -> Option<u32> {
+        self.focused_window
+            .filter(|id| self.windows.iter().any(|window| window.id == *id))
+            .or_else(|| self.windows.first().map(|window| window.id))
+    }
+
+    // The focused frame, or the active one in the current window.
+    pub fn current_frame(&self) -> Option<(usize, usize)> {
+        self.focused_frame.or_else(|| {
+            let id = self.current_window()?;
+            let cluster_idx = self.windows.iter().find(|window| window.id == id)?.frame_cluster_index;
+            let layout = self.shared.frame_layout();
+            let cluster = layout.clusters.get(cluster_idx)?;
+            (!cluster.frames.is_empty()).then_some((cluster_idx, cluster.active_frame))
+        })
+    }
+
+    // The buffer last edited, or the one in the current frame.
+    pub fn current_buffer(&self) -> Option<usize> {
+        self.active_buffer.or_else(|| {
+            let (cluster_idx, frame_idx) = self.current_frame()?;
+            Some(self.shared.frame_layout().clusters.get(cluster_idx)?.frames.get(frame_idx)?.buffer_index)
+        })
+    }
+
+    pub fn dispatch(&mut self, command: Command) {
+        let buffer_index = self.current_buffer();
+        if command.is_repeatable() {
+            self.last_command = Some(command);
+        }
+        match command {
+            Command::OpenFile => self.show_dialog(DialogRequest::Open),
+            Command::SaveAs => {
+                if let Some(buffer_index) = buffer_index {
+                    self.ask_save_as(buffer_index);
                 }
-            )
-        // }
+            }
+            Command::Save => {
+                if let Some(buffer_index) = buffer_index {
+                    self.save_buffer(buffer_index);
+                }
+            }
+            Command::ExportHtml => {
+                if let Some(buffer_index) = buffer_index {
+                    let suggested = self
+                        .shared
+                        .buffer_meta()
+                        .get(buffer_index)
+                        .map(|meta| meta.file_path.as_os_str().to_owned())
+                        .filter(|path| !path.is_empty())
+                        .map(|mut path| {
+                            path.push(".html");
+                            PathBuf::from(path)
+                        })
+                        .unwrap_or_default();
+                    self.show_dialog(DialogRequest::ExportHtml { buffer_index, suggested });
+                }
+            }
+            Command::SaveAll => self.save_all(),
+            Command::Close => {
+                if let Some(id) = self.current_window() {
+                    self.close_window(id);
+                }
+            }
+            Command::Split => self.split_current_frame(),
+            Command::CloseFrame => self.close_current_frame(),
+            Command::Undo | Command::Redo => {
+                let Some(buffer_index) = buffer_index else {
+                    return;
+                };
+                let undone = self.shared.with_buffers_mut(|buffers| {
+                    if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                        if command == Command::Undo {
+                            buffer.undo();
+                        } else {
+                            buffer.redo();
+                        }
+                    }
+                });
+                if let Err(err) = undone {
+                    log::error!("cant undo in buffer {buffer_index}: {err}");
+                }
+            }
+            // The search panel is in the main window's body.
+            Command::Find => {
+                self.toolbar_only = false;
+                self.search.focus_query = true;
+            }
+            Command::GoToLine(line) => {
+                if let Some(buffer_index) = buffer_index {
+                    self.jump_to_line(buffer_index, (line as usize).saturating_sub(1));
+                }
+            }
+            Command::NewWindow => {
+                self.open_window();
+            }
+            Command::Reload | Command::HardReload => {
+                if let Some(buffer_index) = buffer_index {
+                    self.reload_buffer(buffer_index, command == Command::HardReload);
+                }
+            }
+            Command::DuplicateLine => {
+                if let Some(buffer_index) = buffer_index {
+                    self.duplicate_line(buffer_index);
+                }
+            }
+            Command::SortLines(options) => {
+                if let Some(buffer_index) = buffer_index {
+                    self.edit_lines(buffer_index, LineEdit::Sort(options));
+                }
+            }
+            Command::UniqueLines { adjacent } => {
+                if let Some(buffer_index) = buffer_index {
+                    self.edit_lines(buffer_index, LineEdit::Unique { adjacent });
+                }
+            }
+            Command::ReverseLines => {
+                if let Some(buffer_index) = buffer_index {
+                    self.edit_lines(buffer_index, LineEdit::Reverse);
+                }
+            }
+            Command::RepeatLast => {
+                if let Some(last) = self.last_command {
+                    self.dispatch(last);
+                }
+            }
+            Command::PreviousEdit => self.go_to_previous_edit(),
+            Command::PreviewMacro => {
+                if let Some(buffer_index) = buffer_index {
+                    self.preview_macro(buffer_index);
+                }
+            }
+        }
+    }
+
+    pub fn go_to_previous_edit(&mut self) {
+        let target = match self.shared.with_buffers(|buffers| self.recent_edits.previous(buffers)) {
+            Ok(target) => target,
+            Err(err) => {
+                log::error!("cant go to previous edit: {err}");
+                return;
+            }
+        };
+        let Some((buffer_index, char_idx)) = target else {
+            return;
+        };
+        self.ensure_shown(buffer_index);
+        self.active_buffer = Some(buffer_index);
+        self.cursor = Some((buffer_index, char_idx));
+        self.place_cursor(buffer_index, char_idx);
+    }
+
+    pub fn duplicate_line(&mut self, buffer_index: usize) {
+        let cursor = self.cursor.filter(|(buffer, _)| *buffer == buffer_index).map_or(0, |(_, cursor)| cursor);
+        let duplicated = self.shared.with_buffers_mut(|buffers| {
+            let buffer = buffers.buffers.get_mut(buffer_index)?;
+            let (action, cursor) = duplicate::duplicate_line(buffer.content(), cursor);
+            buffer.edit(&[action]);
+            Some(cursor)
+        });
+        let cursor = match duplicated {
+            Ok(Some(cursor)) => cursor,
+            Ok(None) => return,
+            Err(err) => {
+                log::error!("cant duplicate line in buffer {buffer_index}: {err}");
+                return;
+            }
+        };
+        self.cursor = Some((buffer_index, cursor));
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.set_cursor(buffer_index, cursor)) {
+            log::error!("cant move cursor: {err}");
+        }
+    }
+
+    // Sorts, dedups or reverses the selected lines as one undo step and
+    // keeps them selected.
+    pub fn edit_lines(&mut self, buffer_index: usize, edit: LineEdit) {
+        let Some((_, selection)) = self.selection.clone().filter(|(buffer, _)| *buffer == buffer_index) else {
+            return;
+        };
+        let edited = self.shared.with_buffers_mut(|buffers| {
+            let buffer = buffers.buffers.get_mut(buffer_index)?;
+            let (action, range) = sort::edit_lines(buffer.content(), selection, edit)?;
+            buffer.edit(&[action]);
+            Some(range)
+        });
+        let range = match edited {
+            Ok(Some(range)) => range,
+            Ok(None) => return,
+            Err(err) => {
+                log::error!("cant edit lines in buffer {buffer_index}: {err}");
+                return;
+            }
+        };
+        self.cursor = Some((buffer_index, range.end));
+        self.selection = Some((buffer_index, range.clone()));
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.set_selection(buffer_index, range)) {
+            log::error!("cant select edited lines: {err}");
+        }
+    }
+
+    // Focus moves to the frame that becomes active in its place.
+    pub fn close_current_frame(&mut self) {
+        let Some((cluster_idx, frame_idx)) = self.current_frame() else {
+            return;
+        };
+        let closed = self.shared.with_frames_mut(|frames| {
+            frames.remove_frame(cluster_idx, frame_idx)?;
+            let cluster = &frames.frame_clusters[cluster_idx];
+            Some((!cluster.frames.is_empty()).then_some(cluster.active_frame))
+        });
+        match closed {
+            Ok(Some(active)) => self.focused_frame = active.map(|frame_idx| (cluster_idx, frame_idx)),
+            Ok(None) => {}
+            Err(err) => log::error!("cant close frame: {err}"),
+        }
+    }
+
+    // Opens the diff view with the current buffer on the left.
+    pub fn compare_current_with(&mut self, other: usize) {
+        if let Some(buffer_index) = self.current_buffer() {
+            self.diff_view.compare(&self.shared, buffer_index, other);
+        }
+    }
+
+    pub fn split_current_frame(&mut self) {
+        let Some((cluster_idx, frame_idx)) = self.current_frame() else {
+            return;
+        };
+        if let Err(err) = self.shared.with_frames_mut(|frames| frames.split_frame(cluster_idx, frame_idx)) {
+            log::error!("cant split frame: {err}");
+        }
+    }
+
+    // Moves the focused frame into another window's cluster, keeping focus
+    // on it.
+    pub fn move_current_frame(&mut self, to_window: u32) {
+        let Some((cluster_idx, frame_idx)) = self.focused_frame else {
+            return;
+        };
+        let Some(to_cluster) = self
+            .windows
+            .iter()
+            .find(|window| window.id == to_window)
+            .map(|window| window.frame_cluster_index)
+        else {
+            return;
+        };
+        match self.shared.with_frames_mut(|frames| frames.move_frame(cluster_idx, frame_idx, to_cluster)) {
+            Ok(Some(moved)) => {
+                self.focused_frame = Some((to_cluster, moved));
+                self.focus_window(to_window);
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("cant move frame: {err}"),
+        }
+    }
+
+    // Asks first when a window about to close has unsaved changes.
+    pub fn close_all_but(&mut self, keep: u32) {
+        let modified = self
+            .windows
+            .iter()
+            .any(|window| window.id != keep && window.is_modified(&self.shared));
+        if modified {
+            self.pending_close = Some(keep);
+        } else {
+            self.close_windows(|window| window.id != keep);
+        }
+    }
+
+    pub fn close_saved_windows(&mut self) {
+        let saved: Vec<u32> = self
+            .windows
+            .iter()
+            .filter(|window| !window.is_modified(&self.shared))
+            .map(|window| window.id)
+            .collect();
+        self.close_windows(|window| saved.contains(&window.id));
+    }
+
+    // Buffers whose changes would be lost by quitting now.
+    pub fn unsaved_buffers(&self) -> Vec<usize> {
+        self.shared
+            .with_buffers(BufferStorage::unsaved)
+            .unwrap_or_default()
+    }
+
+    pub fn open_scratch(&mut self) {
+        match self.shared.with_buffers_mut(BufferStorage::ensure_scratch) {
+            Ok(buffer_index) => self.ensure_shown(buffer_index),
+            Err(err) => log::error!("cant open scratch buffer: {err}"),
+        }
+    }
+
+    // Holds the main window open while buffers have unsaved changes, until
+    // the user confirms.
+    fn guard_exit(&mut self, ctx: &egui::Context) {
+        if ctx.input(|input| input.viewport().close_requested())
+            && !self.exit_confirmed
+            && !self.unsaved_buffers().is_empty()
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.exit_prompt = true;
+        }
+        if !self.exit_prompt {
+            return;
+        }
+        let unsaved = self.unsaved_buffers().len();
+        egui::Window::new("Quit")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{unsaved} buffers have unsaved changes."));
+                ui.horizontal(|ui| {
+                    if ui.button("Quit anyway").clicked() {
+                        self.exit_confirmed = true;
+                        self.exit_prompt = false;
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.exit_prompt = false;
+                    }
+                });
+            });
+    }
+
+    fn show_close_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(keep) = self.pending_close else {
+            return;
+        };
+        let modified = self
+            .windows
+            .iter()
+            .filter(|window| window.id != keep && window.is_modified(&self.shared))
+            .count();
+        let mut decided = None;
+        egui::Window::new("Close windows")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{modified} of the windows to close have unsaved changes."));
+                ui.horizontal(|ui| {
+                    if ui.button("Close anyway").clicked() {
+                        decided = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        decided = Some(false);
+                    }
+                });
+            });
+        if let Some(close) = decided {
+            self.pending_close = None;
+            if close {
+                self.close_windows(|window| window.id != keep);
+            }
+        }
+    }
+
+    fn show_session_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(name) = &self.pending_session else {
+            return;
+        };
+        let unsaved = self.unsaved_buffers().len();
+        let mut decided = None;
+        egui::Window::new("Switch session")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("{unsaved} buffers have unsaved changes."));
+                if !self.session_name.is_empty() {
+                    ui.label(format!("They are kept in session {}.", self.session_name));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button(format!("Switch to {name}")).clicked() {
+                        decided = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        decided = Some(false);
+                    }
+                });
+            });
+        if let Some(switch) = decided {
+            self.confirm_session_switch(switch);
+        }
+    }
+
+    fn add_frame(&mut self, frame: Frame) {
+        if self.windows.is_empty() {
+            self.windows.push(Window::default("Window"));
+        }
+        let cluster_idx = self.frame_cluster_index();
+        match self.shared.with_frames_mut(|frames| frames.add_frame(cluster_idx, frame)) {
+            Ok(Some(_)) => {}
+            Ok(None) => log::error!("cant add frame: no frame cluster {cluster_idx}"),
+            Err(err) => log::error!("cant add frame: {err}"),
+        }
+    }
+
+    pub fn open_terminal(&mut self) {
+        if !self.has_frame_cluster() {
+            log::error!("cant open terminal: no frame cluster to show it in");
+            return;
+        }
+        let mut terminal = match Terminal::spawn_shell(&self.shared) {
+            Ok(terminal) => terminal,
+            Err(err) => {
+                log::error!("cant spawn terminal: {err}");
+                return;
+            }
+        };
+        if self.config.capture_streams {
+            terminal = terminal.capture_to(self.stream_capture("terminal"));
+        }
+        self.add_frame(Frame {
+            kind: FrameKind::Terminal { closed: false },
+            buffer_index: terminal.buffer_index,
+            follow: true,
+            ..Frame::default()
+        });
+        self.terminals.push(terminal);
+    }
+
+    fn send_terminal_input(&mut self, buffer_index: usize, line: &str) {
+        let Some(terminal) = self
+            .terminals
+            .iter_mut()
+            .find(|terminal| terminal.buffer_index == buffer_index)
+        else {
+            return;
+        };
+        if let Err(err) = terminal.send_line(line) {
+            log::error!("cant write to terminal: {err}");
+        }
+    }
+
+    fn handle_client_events(&mut self, events: Vec<ClientEvents>) {
+        let mut hovered = None;
+        for event in events {
+            match event {
+                ClientEvents::TerminalInput(buffer_index, line) => {
+                    self.send_terminal_input(buffer_index, &line);
+                }
+                ClientEvents::Hover(buffer_index, char_idx) => hovered = Some((buffer_index, char_idx)),
+                ClientEvents::GoToDefinition(buffer_index, char_idx) => {
+                    self.server.lsp.definition(&self.shared, buffer_index, char_idx);
+                }
+                ClientEvents::RenameSymbol(buffer_index, char_idx) => {
+                    self.rename.open(buffer_index, char_idx);
+                }
+                ClientEvents::Save(buffer_index) => self.save_buffer(buffer_index),
+                ClientEvents::Edited { buffer_index, actions, cursor } => {
+                    if let Some(previous) = self.last_edited.replace(buffer_index)
+                        && previous != buffer_index
+                    {
+                        self.seal_history(previous);
+                    }
+                    self.macros.record_edit(buffer_index, &actions, cursor);
+                }
+                ClientEvents::Moved { buffer_index, movement, cursor } => {
+                    self.seal_history(buffer_index);
+                    self.macros.record_move(buffer_index, movement, cursor);
+                }
+                ClientEvents::ToggleMacroRecording(buffer_index, cursor) => {
+                    if self.macros.is_recording() {
+                        self.macros.stop();
+                    } else {
+                        self.macros.start(buffer_index, cursor);
+                    }
+                }
+                ClientEvents::ReplayMacro(buffer_index, cursor) => self.replay_macro(buffer_index, cursor),
+                ClientEvents::SplitFrame { cluster_idx, frame_idx, cursor } => {
+                    let split = self.shared.with_frames_mut(|frames| {
+                        if let Some(split_idx) = frames.split_frame(cluster_idx, frame_idx) {
+                            frames.frame_clusters[cluster_idx].frames[split_idx].pending_cursor = Some(cursor);
+                        }
+                    });
+                    if let Err(err) = split {
+                        log::error!("cant split frame: {err}");
+                    }
+                }
+                ClientEvents::WindowCloseEvent(id) => self.close_window(id),
+                ClientEvents::SetGlobalMark { buffer_index, name, cursor } => {
+                    self.set_global_mark(buffer_index, name, cursor);
+                }
+                ClientEvents::JumpToMark { buffer_index, name, cursor } => {
+                    self.jump_to_mark(buffer_index, name, cursor);
+                }
+                _ => {}
+            }
+        }
+        if let Some((buffer_index, char_idx)) = self.hover.update(hovered, Instant::now()) {
+            self.server.lsp.hover(&self.shared, buffer_index, char_idx);
+        }
+    }
+
+    fn handle_lsp_replies(&mut self) {
+        for reply in self.server.lsp.poll() {
+            match reply {
+                LspReply::Hover { buffer_index, char_idx, contents } => {
+                    self.hover.receive(buffer_index, char_idx, contents);
+                }
+                LspReply::Definition(locations) => match locations.as_slice() {
+                    [] => {}
+                    [location] => self.go_to_location(location),
+                    _ => self.definitions.locations = locations,
+                },
+                LspReply::Rename(workspace_edit) => self.apply_workspace_edit(workspace_edit),
+                LspReply::Formatting { buffer_index, edits } => {
+                    self.finish_formatted_save(buffer_index, &edits);
+                }
+                LspReply::DocumentSymbol { buffer_index, symbols } => {
+                    self.outline.receive(&self.shared, buffer_index, symbols);
+                }
+            }
+        }
+    }
+
+    fn create_menu_bar(&mut self, ui: &mut egui::Ui) {
+        egui::MenuBar::new().ui(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                ui.menu_button("Open Recent", |ui| {
+                    let recent_files = self.server.recent_files();
+                    if recent_files.is_empty() {
+                        ui.label("No recent files");
+                    }
+                    for path in recent_files {
+                        if ui.button(path.display().to_string()).clicked() {
+                            self.open_file(&path);
+                        }
+                    }
+                });
+                let active = self.active_buffer.is_some();
+                if ui.add_enabled(!self.asking_for_path(), egui::Button::new("Open…")).clicked() {
+                    self.dispatch(Command::OpenFile);
+                }
+                if ui.add_enabled(active, egui::Button::new("Save")).clicked() {
+                    self.dispatch(Command::Save);
+                }
+                if ui.add_enabled(active && !self.asking_for_path(), egui::Button::new("Save as…")).clicked() {
+                    self.dispatch(Command::SaveAs);
+                }
+                if ui.button("Save all").clicked() {
+                    self.dispatch(Command::SaveAll);
+                }
+                if ui.add_enabled(active && !self.asking_for_path(), egui::Button::new("Export as HTML…")).clicked() {
+                    self.dispatch(Command::ExportHtml);
+                }
+                if ui.add_enabled(active, egui::Button::new("Reload")).clicked() {
+                    self.dispatch(Command::Reload);
+                }
+                if ui.add_enabled(active, egui::Button::new("Reload, discarding changes")).clicked() {
+                    self.dispatch(Command::HardReload);
+                }
+                ui.menu_button("Sessions", |ui| {
+                    for name in self.server.list_sessions() {
+                        if ui.selectable_label(name == self.session_name, &name).clicked() {
+                            self.load_session(&name);
+                        }
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let mut name = self.session_name.clone();
+                        ui.text_edit_singleline(&mut name);
+                        if ui.button("Save").clicked() {
+                            self.save_session(&name);
+                        } else {
+                            self.session_name = name;
+                        }
+                    });
+                    ui.menu_button("Snapshots", |ui| {
+                        let snapshots = self.server.list_snapshots();
+                        if snapshots.is_empty() {
+                            ui.label("No snapshots yet");
+                        }
+                        for index in snapshots {
+                            let label = if index == 0 { String::from("Latest") } else { format!("{index} back") };
+                            if ui.button(label).clicked() {
+                                self.restore_snapshot(index);
+                            }
+                        }
+                    });
+                });
+                ui.menu_button("Encoding", |ui| {
+                    if let Some(action) = self.encoding.show(ui, &self.shared) {
+                        self.handle_encoding_action(action);
+                    }
+                });
+            });
+            ui.menu_button("Edit", |ui| {
+                let active = self.active_buffer.is_some();
+                if ui.add_enabled(active, egui::Button::new("Duplicate line")).clicked() {
+                    self.dispatch(Command::DuplicateLine);
+                }
+                ui.menu_button("Sort lines", |ui| {
+                    ui.checkbox(&mut self.sort_options.ignore_case, "Ignore case");
+                    ui.checkbox(&mut self.sort_options.numeric, "Numbers by value");
+                    if ui.add_enabled(active, egui::Button::new("Ascending")).clicked() {
+                        self.dispatch(Command::SortLines(SortOptions { descending: false, ..self.sort_options }));
+                    }
+                    if ui.add_enabled(active, egui::Button::new("Descending")).clicked() {
+                        self.dispatch(Command::SortLines(SortOptions { descending: true, ..self.sort_options }));
+                    }
+                });
+                ui.menu_button("Unique lines", |ui| {
+                    if ui.add_enabled(active, egui::Button::new("Anywhere")).clicked() {
+                        self.dispatch(Command::UniqueLines { adjacent: false });
+                    }
+                    if ui.add_enabled(active, egui::Button::new("Next to each other")).clicked() {
+                        self.dispatch(Command::UniqueLines { adjacent: true });
+                    }
+                });
+                if ui.add_enabled(active, egui::Button::new("Reverse lines")).clicked() {
+                    self.dispatch(Command::ReverseLines);
+                }
+                if ui.add_enabled(self.last_command.is_some(), egui::Button::new("Repeat last command")).clicked() {
+                    self.dispatch(Command::RepeatLast);
+                }
+                if ui.button("Go to previous edit").clicked() {
+                    self.dispatch(Command::PreviousEdit);
+                }
+                ui.menu_button("Macros", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Register");
+                        let mut register = self.macros.register.to_string();
+                        ui.add(egui::TextEdit::singleline(&mut register).desired_width(16.0));
+                        if let Some(register) = register.chars().last() {
+                            self.macros.register = register;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Replay count");
+                        ui.add(egui::DragValue::new(&mut self.macros.count).range(1..=1000));
+                    });
+                    if ui.add_enabled(active && !self.macros.is_recording(), egui::Button::new("Preview replay")).clicked() {
+                        self.dispatch(Command::PreviewMacro);
+                    }
+                    let status = if self.macros.is_recording() { "Recording (F3 to stop)" } else { "F3 to record, F4 to replay" };
+                    ui.weak(status);
+                });
+            });
+            ui.menu_button("Window", |ui| {
+                if ui.button("New window").clicked() {
+                    self.dispatch(Command::NewWindow);
+                }
+                let current = self.current_window();
+                if ui.add_enabled(self.current_frame().is_some(), egui::Button::new("Split current frame")).clicked() {
+                    self.dispatch(Command::Split);
+                }
+                if ui.add_enabled(self.current_frame().is_some(), egui::Button::new("Close current frame")).clicked() {
+                    self.dispatch(Command::CloseFrame);
+                }
+                ui.add_enabled_ui(self.focused_frame.is_some(), |ui| {
+                    ui.menu_button("Move current frame to", |ui| {
+                        let windows: Vec<(u32, String)> = self
+                            .windows
+                            .iter()
+                            .map(|window| (window.id, window.title(&self.shared)))
+                            .collect();
+                        for (id, title) in windows {
+                            if ui.button(format!("{title} #{id}")).clicked() {
+                                self.move_current_frame(id);
+                            }
+                        }
+                    });
+                });
+                if ui.add_enabled(current.is_some(), egui::Button::new("Close current window")).clicked() {
+                    self.dispatch(Command::Close);
+                }
+                if ui.add_enabled(current.is_some(), egui::Button::new("Close others")).clicked()
+                    && let Some(id) = current
+                {
+                    self.close_all_but(id);
+                }
+                ui.menu_button("Close all but", |ui| {
+                    let windows: Vec<(u32, String)> = self
+                        .windows
+                        .iter()
+                        .map(|window| (window.id, window.title(&self.shared)))
+                        .collect();
+                    for (id, title) in windows {
+                        if ui.button(format!("{title} #{id}")).clicked() {
+                            self.close_all_but(id);
+                        }
+                    }
+                });
+                if ui.button("Close all saved").clicked() {
+                    self.close_saved_windows();
+                }
+            });
+            ui.menu_button("View", |ui| {
+                let current = self.current_buffer();
+                ui.add_enabled_ui(current.is_some(), |ui| {
+                    ui.menu_button("Compare current buffer with", |ui| {
+                        let others: Vec<(usize, String)> = self
+                            .shared
+                            .buffer_meta()
+                            .iter()
+                            .enumerate()
+                            .filter(|(buffer_index, _)| Some(*buffer_index) != current)
+                            .map(|(buffer_index, meta)| (buffer_index, windows::buffer_title(meta)))
+                            .collect();
+                        for (buffer_index, title) in others {
+                            if ui.button(format!("{title} ({buffer_index})")).clicked() {
+                                self.compare_current_with(buffer_index);
+                                ui.close();
+                            }
+                        }
+                    });
+                });
+                if ui.add_enabled(self.has_frame_cluster(), egui::Button::new("New terminal")).clicked() {
+                    self.open_terminal();
+                }
+                ui.checkbox(&mut self.outline.open, "Outline");
+                ui.checkbox(&mut self.stats.open, "Statistics");
+                ui.checkbox(&mut self.file_tree.open, "File tree");
+                ui.checkbox(&mut self.toolbar_only, "Main window as toolbar");
+                if self.config.debug {
+                    ui.checkbox(&mut self.debug.open, "Debug overlay");
+                }
+                if ui.button("Scratch pad").clicked() {
+                    self.open_scratch();
+                }
+                self.syntax_theme_menu(ui);
+                self.external_change_menu(ui);
+                self.spell_check_toggle(ui);
+                let active_path = self.active_path();
+                if ui.add_enabled(active_path.is_some(), egui::Button::new("Reveal in file tree")).clicked()
+                    && let Some(path) = active_path
+                {
+                    self.file_tree.reveal(&path);
+                }
+            });
+            if let Some(modal) = &self.modal {
+                ui.weak(format!("-- {} --", modal.mode.label()));
+            }
+        });
+    }
+
+    // Dropped files open in new frames; a dropped directory becomes the file
+    // tree's root.
+    pub fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|input| input.raw.dropped_files.clone());
+        for file in dropped {
+            let Some(path) = file.path else {
+                continue;
+            };
+            if path.is_dir() {
+                self.file_tree.set_root(std::fs::canonicalize(&path).unwrap_or(path));
+                self.file_tree.open = true;
+            } else {
+                self.open_file(&path);
+            }
+        }
+    }
+
+    fn show_drop_overlay(ctx: &egui::Context) {
+        if ctx.input(|input| input.raw.hovered_files.is_empty()) {
+            return;
+        }
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_overlay")));
+        let rect = ctx.content_rect();
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Drop to open",
+            egui::TextStyle::Heading.resolve(&ctx.style()),
+            egui::Color32::WHITE,
+        );
+    }
+
+    // Picks a syntax palette for the active buffer, or hands it back to the
+    // global one.
+    fn syntax_theme_menu(&mut self, ui: &mut egui::Ui) {
+        let Some(buffer_index) = self.active_buffer else {
+            ui.add_enabled(false, egui::Button::new("Syntax theme"));
+            return;
+        };
+        let current = self
+            .shared
+            .with_buffers(|buffers| buffers.buffers.get(buffer_index).and_then(|buffer| buffer.theme_override))
+            .unwrap_or_default();
+        ui.menu_button("Syntax theme", |ui| {
+            let mut choice = current;
+            ui.radio_value(&mut choice, None, format!("Global ({})", self.config.theme.syntax.name()));
+            for theme in ThemeId::ALL {
+                ui.radio_value(&mut choice, Some(theme), theme.name());
+            }
+            if choice == current {
+                return;
+            }
+            let set = self.shared.with_buffers_mut(|buffers| {
+                if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                    buffer.theme_override = choice;
+                }
+            });
+            if let Err(err) = set {
+                log::error!("cant set syntax theme: {err}");
+            }
+        });
+    }
+
+    fn spell_check_toggle(&mut self, ui: &mut egui::Ui) {
+        let Some(buffer_index) = self.active_buffer else {
+            ui.add_enabled(false, egui::Checkbox::new(&mut false, "Spell check"));
+            return;
+        };
+        let mut enabled = self
+            .shared
+            .with_buffers(|buffers| buffers.buffers.get(buffer_index).is_some_and(|buffer| buffer.spell_check))
+            .unwrap_or_default();
+        if ui.checkbox(&mut enabled, "Spell check").changed() {
+            self.set_spell_check(buffer_index, enabled);
+        }
+    }
+
+    // The dictionary is read the first time any buffer turns checking on.
+    pub fn set_spell_check(&mut self, buffer_index: usize, enabled: bool) {
+        if enabled && self.spell.dictionary.is_none() {
+            let path = &self.config.dictionary_path;
+            match self.server.storage.read(path) {
+                Ok(bytes) => self.spell.dictionary = Some(Arc::new(Dictionary::parse(&String::from_utf8_lossy(&bytes)))),
+                Err(err) => {
+                    log::error!("cant read dictionary {}: {err}", path.display());
+                    return;
+                }
+            }
+        }
+        let set = self.shared.with_buffers_mut(|buffers| {
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                buffer.spell_check = enabled;
+            }
+        });
+        if let Err(err) = set {
+            log::error!("cant set spell check: {err}");
+        }
+    }
+
+    // Picks what the active buffer does when its file changes on disk, or
+    // hands it back to the global policy.
+    fn external_change_menu(&mut self, ui: &mut egui::Ui) {
+        let Some(buffer_index) = self.active_buffer else {
+            ui.add_enabled(false, egui::Button::new("On outside changes"));
+            return;
+        };
+        let current = self
+            .shared
+            .with_buffers(|buffers| buffers.buffers.get(buffer_index).and_then(|buffer| buffer.external_change))
+            .unwrap_or_default();
+        ui.menu_button("On outside changes", |ui| {
+            let mut choice = current;
+            ui.radio_value(&mut choice, None, format!("Global ({})", self.config.external_change.name()));
+            for policy in ExternalChange::ALL {
+                ui.radio_value(&mut choice, Some(policy), policy.name());
+            }
+            if choice == current {
+                return;
+            }
+            let set = self.shared.with_buffers_mut(|buffers| {
+                if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                    buffer.external_change = choice;
+                }
+            });
+            if let Err(err) = set {
+                log::error!("cant set outside change policy: {err}");
+            }
+        });
+    }
+
+    // Shows every side window in its own viewport and notes which one has
+    // focus. Returns what their editors asked for.
+    pub fn show_windows(&mut self, ctx: &egui::Context) -> Vec<ClientEvents> {
+        let mut events = Vec::new();
+        self.search.highlights.set_query(&self.search.query, self.search.mode);
+        let mut view = EditorView {
+            hover: self.hover.info.as_ref(),
+            guides: Guides::from_config(&self.config),
+            syntax_theme: self.config.theme.syntax,
+            auto_pair: self.config.auto_pair,
+            indent_width: self.config.tab_width as usize,
+            modal: self.modal.as_mut(),
+            active: self.active_buffer,
+            focused_frame: self.focused_frame,
+            cursor: None,
+            selection: None,
+            search: &mut self.search.highlights,
+            spell: &mut self.spell,
+        };
+        let mut focused_window = None;
+        let mut command_held = false;
+        let mut switches = Vec::new();
+        let mut moved = Vec::new();
+        let mut commands = Vec::new();
+        let monitor = ctx.input(|input| input.viewport().monitor_size);
+        for window in &self.windows {
+            let builder = egui::ViewportBuilder::default()
+                .with_title(window.title(&self.shared))
+                .with_inner_size([200.0, 100.0]);
+            let builder = self.config.window_decorations.apply(builder);
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of(("riptide", window.id)),
+                geometry::apply(builder, window.placement, monitor),
+                |ctx, _| {
+                    commands.extend(command::pressed(ctx, &self.bindings));
+                    if ctx.input_mut(|i| i.consume_shortcut(&switcher::PREVIOUS_WINDOW_SHORTCUT)) {
+                        switches.push(true);
+                    } else if ctx.input_mut(|i| i.consume_shortcut(&switcher::NEXT_WINDOW_SHORTCUT)) {
+                        switches.push(false);
+                    }
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        events.extend(window.show(ui, &self.shared, &mut view));
+                    });
+                    if ctx.input(|input| input.viewport().focused == Some(true)) {
+                        focused_window = Some(window.id);
+                        command_held = ctx.input(|input| input.modifiers.command);
+                    }
+                    if ctx.input(|input| input.viewport().close_requested()) {
+                        events.push(ClientEvents::WindowCloseEvent(window.id));
+                    }
+                    if let Some(geometry) = ctx.input(|input| geometry::capture(input.viewport())) {
+                        moved.push((window.frame_cluster_index, geometry));
+                    }
+                }
+            );
+        }
+        self.active_buffer = view.active;
+        self.focused_frame = view.focused_frame;
+        if view.cursor.is_some() {
+            self.cursor = view.cursor;
+            self.selection = view.selection.take();
+        }
+        if let Some((buffer_index, char_idx)) = view.cursor {
+            self.remember_position(buffer_index, char_idx);
+            if let Some(wait) = self.server.cursor_moved(&self.shared, buffer_index, char_idx) {
+                ctx.request_repaint_after(wait);
+            }
+        }
+        self.remember_geometry(&moved);
+        if let Some(id) = focused_window {
+            self.focus_window(id);
+            if !command_held {
+                self.window_switcher.finish();
+            }
+        }
+        for backwards in switches {
+            self.switch_window(ctx, backwards);
+        }
+        for command in commands {
+            self.dispatch(command);
+        }
+        events
+    }
+
+    // Only touches the frames when a window actually moved or resized.
+    fn remember_geometry(&self, moved: &[(usize, WindowGeometry)]) {
+        let layout = self.shared.frame_layout();
+        let changed = moved.iter().any(|(cluster_idx, geometry)| {
+            layout.clusters.get(*cluster_idx).is_some_and(|cluster| cluster.geometry != Some(*geometry))
+        });
+        if !changed {
+            return;
+        }
+        let _ = self.shared.try_with_frames_mut(|frames| {
+            for (cluster_idx, geometry) in moved {
+                if let Some(cluster) = frames.frame_clusters.get_mut(*cluster_idx) {
+                    cluster.geometry = Some(*geometry);
+                }
+            }
+        });
+    }
+
+    // Follows the main window, pulling it back on screen the first time the
+    // monitor is known, since it was opened before that.
+    fn track_main_geometry(&mut self, ctx: &egui::Context) {
+        let (current, monitor) = ctx.input(|input| (geometry::capture(input.viewport()), input.viewport().monitor_size));
+        if !self.main_clamped
+            && let Some(monitor) = monitor
+            && let Some(current) = current
+        {
+            self.main_clamped = true;
+            let (size, position) = geometry::clamp(current, Some(monitor));
+            if size != egui::Vec2::from(current.size) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+            }
+            if let Some(position) = position
+                && current.position != Some(position.into())
+            {
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+            }
+        }
+        if current.is_some() {
+            self.main_geometry = current;
+        }
+        if monitor.is_some() {
+            self.main_monitor = monitor;
+        }
+    }
+
+    pub fn saved_geometry(&self) -> SavedGeometry {
+        SavedGeometry {
+            main: self.main_geometry,
+            clusters: self.shared.frame_layout().clusters.iter().map(|cluster| cluster.geometry).collect(),
+        }
+    }
+
+    fn show_welcome(&self) -> bool {
+        !self.welcome.dismissed && self.shared.with_buffers(BufferStorage::is_pristine).unwrap_or(false)
+    }
+
+    // While the main window is a toolbar it's as tall as its bars. The quit
+    // prompt needs the room back, so it's shown full size then.
+    fn fit_main_window(&mut self, ctx: &egui::Context, compact: bool) {
+        if compact && self.full_size.is_none() {
+            let Some(size) = ctx.input(|input| input.viewport().inner_rect).map(|rect| rect.size()) else {
+                return;
+            };
+            self.full_size = Some(size);
+            let height = ctx.used_rect().height();
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(size.x, height)));
+        } else if !compact && let Some(size) = self.full_size.take() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+        }
+    }
+
+    // The main window holds the menus, workspace tabs, status and side
+    // panels; the editors are in the side windows, one per frame cluster.
+    // Closing it quits, after asking about unsaved buffers.
+    fn create_main_window(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            self.create_menu_bar(ui);
+        });
+        self.show_workspace_bar(ctx);
+        self.show_status_bar(ctx);
+        if let Some(buffer_index) = self.current_buffer() {
+            self.stats.show(ctx, &self.shared, buffer_index, Instant::now());
+        }
+        let active_path = self.active_path();
+        if let Some(path) = self.file_tree.show(ctx, active_path.as_deref())
+            && let Some(buffer_index) = self.open_or_find(&path)
+        {
+            self.ensure_shown(buffer_index);
+        }
+        if let Some((buffer_index, line)) = self.outline.show(ctx, &self.shared) {
+            self.jump_to_line(buffer_index, line);
+        }
+        let compact = self.toolbar_only && !self.exit_prompt;
+        self.fit_main_window(ctx, compact);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if compact {
+                return;
+            }
+            if !self.show_welcome() {
+                self.search.show(ui, &self.shared);
+                return;
+            }
+            let recent_files = self.server.recent_files();
+            match self.welcome.show(ui, &recent_files) {
+                Some(WelcomeAction::NewFile) => self.welcome.dismissed = true,
+                Some(WelcomeAction::OpenFile(path)) => {
+                    self.open_file(&path);
+                }
+                None => {}
+            }
+        });
+        self.diff_view.show(ctx, &self.shared);
+        self.show_close_confirmation(ctx);
+        self.show_session_confirmation(ctx);
+        self.show_macro_preview(ctx);
+        self.guard_exit(ctx);
+        if let Some((buffer_index, choice)) = self.reload.show(ctx) {
+            self.handle_reload_choice(buffer_index, choice);
+        }
+        if let Some(location) = self.definitions.show(ctx) {
+            self.go_to_location(&location);
+        }
+        if let Some((request, path)) = self.path_prompt.show(ctx) {
+            self.act_on_path(request, &path);
+        }
+        if let Some((target, new_name)) = self.rename.show(ctx) {
+            self.server
+                .lsp
+                .rename(&self.shared, target.buffer_index, target.char_idx, &new_name);
+        }
+    }
+}
+
+impl eframe::App for Client {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        for terminal in &mut self.terminals {
+            if let Err(err) = terminal.pump(&self.shared) {
+                log::error!("cant read terminal output: {err}");
+            }
+        }
+        self.terminals.retain(|terminal| !terminal.closed);
+        for stream in &mut self.streams {
+            if let Err(err) = stream.pump(&self.shared) {
+                log::error!("cant read stream: {err}");
+            }
+        }
+        self.streams.retain(|stream| !stream.finished);
+        self.pump_loads();
+        self.pump_dialog();
+
+        self.handle_lsp_replies();
+        self.flush_stale_saves();
+        // Coming back to the editor is when files were most likely changed
+        // elsewhere.
+        if ctx.input(|input| input.events.contains(&egui::Event::WindowFocused(true))) {
+            self.maintenance.run_now();
+        }
+        self.autosave_snapshot();
+        self.check_disk_changes();
+        self.outline.refresh(&self.shared, &mut self.server.lsp, Instant::now());
+
+        self.handle_dropped_files(ctx);
+        self.track_main_geometry(ctx);
+        for command in command::pressed(ctx, &self.bindings) {
+            self.dispatch(command);
+        }
+        self.create_main_window(ctx);
+        if self.config.debug && ctx.input_mut(|input| input.consume_shortcut(&debug::TOGGLE_SHORTCUT)) {
+            self.debug.open = !self.debug.open;
+        }
+        self.debug.show(ctx, &self.shared, &self.windows);
+        Self::show_drop_overlay(ctx);
+        let events = self.show_windows(ctx);
+        self.handle_client_events(events);
+        self.server.enforce_memory_budget(&self.shared);
+
+        let waiting = !self.terminals.is_empty()
+            || !self.streams.is_empty()
+            || !self.loads.is_empty()
+            || self.hover.is_waiting()
+            || self.server.lsp.has_pending()
+            || !self.pending_saves.is_empty();
+        if waiting {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        } else if self.outline.open {
+            // The outline catches up with edits on its own interval.
+            ctx.request_repaint_after(outline::REFRESH_INTERVAL);
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(err) = self.server.save_geometry(&self.saved_geometry()) {
+            log::error!("cant save window sizes: {err}");
+        }
+        if let Err(err) = self.server.save_positions() {
+            log::error!("cant save cursor positions: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+    use crate::interfaces::enums::BufferActions;
+    use crate::test_support::TempDir;
+    use crate::shared::buffers::Buffer;
+
+    // Everything it saves stays in memory; nothing here is on disk.
+    fn client() -> Client {
+        let config = Config {
+            session_path: PathBuf::from("/riptide/sessions"),
+            data_dir: PathBuf::from("/riptide"),
+            ..Config::default()
+        };
+        Client::with_storage(&config, Arc::new(InMemoryStorage::default()))
+    }
+
+    fn contents(client: &Client) -> Vec<String> {
+        client
+            .shared
+            .with_buffers(|buffers| buffers.buffers.iter().map(|buffer| buffer.content().to_string()).collect())
+            .unwrap()
+    }
+
+    fn type_into_new_buffer(client: &Client, text: &str) {
+        client
+            .shared
+            .with_buffers_mut(|buffers| buffers.buffers.push(Buffer::with_content(text)))
+            .unwrap();
+    }
+
+    #[test]
+    fn files_named_on_the_command_line_open_in_buffers() {
+        let mut client = client();
+        let dir = PathBuf::from("/project");
+        for (name, content) in [("a.rs", "fn a() {}\n"), ("b.rs", "fn b() {}\n")] {
+            client.server.storage.write(&dir.join(name), content.as_bytes(), false).unwrap();
+        }
+        let args = ["a.rs", "missing.rs", "+1", "b.rs"].map(|name| match name {
+            "+1" => name.to_string(),
+            _ => dir.join(name).display().to_string(),
+        });
+        let before = contents(&client).len();
+        client.open_requests(crate::cli::parse_args(args));
+        assert_eq!(contents(&client)[before..], ["fn a() {}\n", "fn b() {}\n"]);
+    }
+
+    #[test]
+    fn renames_open_the_files_they_touch_and_undo_in_one_step() {
+        let mut client = client();
+        let path = PathBuf::from("/project/a.rs");
+        client.server.storage.write(&path, b"fn old() {}\nold();\nold();\n", false).unwrap();
+        let edit = |line, start| lsp::TextEdit {
+            start: lsp::Position { line, character: start },
+            end: lsp::Position { line, character: start + 3 },
+            new_text: String::from("new"),
+        };
+        client.apply_workspace_edit(vec![(path.clone(), vec![edit(0, 3), edit(1, 0), edit(2, 0)])]);
+        let renamed = client
+            .shared
+            .with_buffers(|buffers| {
+                let buffer = &buffers.buffers[buffers.find_by_path(&path)?];
+                Some((buffer.content().to_string(), buffer.history.undo_len()))
+            })
+            .unwrap();
+        assert_eq!(renamed, Some((String::from("fn new() {}\nnew();\nnew();\n"), 1)));
+    }
+
+    #[test]
+    fn formatted_saves_write_the_formatted_text_or_give_up_waiting() {
+        let mut client = client();
+        let dir = PathBuf::from("/project");
+        let (a, b) = (dir.join("a.rs"), dir.join("b.rs"));
+        for path in [&a, &b] {
+            client.server.storage.write(path, b"fn  main() {}\n", false).unwrap();
+        }
+        let (a_index, b_index) = (client.open_or_find(&a).unwrap(), client.open_or_find(&b).unwrap());
+        client.shared.with_buffers_mut(|buffers| { buffers.buffers[b_index].apply(&BufferActions::InsertText { char_idx: 0, text: String::from(" ") }); }).unwrap();
+        let edits = [lsp::TextEdit {
+            start: lsp::Position { line: 0, character: 2 },
+            end: lsp::Position { line: 0, character: 4 },
+            new_text: String::from(" "),
+        }];
+
+        client.finish_formatted_save(a_index, &edits);
+        assert_eq!(client.server.storage.read(&a).unwrap(), b"fn  main() {}\n");
+        client.pending_saves.push((a_index, Instant::now()));
+        client.finish_formatted_save(a_index, &edits);
+        assert_eq!(client.server.storage.read(&a).unwrap(), b"fn main() {}\n");
+
+        client.pending_saves.push((b_index, Instant::now() - FORMAT_ON_SAVE_TIMEOUT));
+        client.flush_stale_saves();
+        assert!(client.pending_saves.is_empty());
+        assert_eq!(client.server.storage.read(&b).unwrap(), b" fn  main() {}\n");
+    }
+
+    #[test]
+    fn welcome_shows_until_something_is_opened_or_typed() {
+        let mut client = client();
+        assert!(client.show_welcome());
+        client.shared.with_buffers_mut(|buffers| buffers.buffers[0].append("x")).unwrap();
+        assert!(!client.show_welcome());
+        client.shared.with_buffers_mut(|buffers| buffers.buffers[0].set_content("")).unwrap();
+        assert!(client.show_welcome());
+        client.welcome.dismissed = true;
+        assert!(!client.show_welcome());
+    }
+
+    #[test]
+    fn switching_sessions_saves_the_current_one_and_asks_about_unsaved_buffers() {
+        let mut client = client();
+        client.save_session("other");
+        type_into_new_buffer(&client, "draft");
+        client.save_session("work");
+
+        client.load_session("other");
+        assert_eq!(client.pending_session.as_deref(), Some("other"));
+        assert!(contents(&client).contains(&String::from("draft")));
+        client.confirm_session_switch(false);
+        assert_eq!(client.session_name, "work");
+
+        type_into_new_buffer(&client, "more");
+        client.load_session("other");
+        client.confirm_session_switch(true);
+        assert_eq!(client.session_name, "other");
+        assert!(client.unsaved_buffers().is_empty());
+
+        client.load_session("work");
+        assert_eq!(client.pending_session, None);
+        let restored = contents(&client);
+        assert!(restored.contains(&String::from("draft")) && restored.contains(&String::from("more")));
+    }
+
+    #[test]
+    fn a_corrupt_session_reaches_the_status_bar_log() {
+        logging::init();
+        let mut client = client();
+        let path = client.server.session_dir.join("broken").with_extension(crate::server::session::SESSION_EXTENSION);
+        client.server.storage.write(&path, b"{ not json", false).unwrap();
+
+        client.load_session("broken");
+        assert_eq!(client.session_name, "");
+        // Other tests log through the same logger, so only look for ours.
+        let errors = logging::take_errors();
+        assert!(errors.iter().any(|error| error.starts_with("cant load session broken: ")), "{errors:?}");
+    }
+
+    #[test]
+    fn macro_previews_keep_or_revert_the_replay() {
+        let mut client = client();
+        type_into_new_buffer(&client, "ab");
+        let index = contents(&client).len() - 1;
+        client.macros.start(index, 0);
+        client.macros.record_edit(index, &[BufferActions::InsertText { char_idx: 0, text: String::from("-") }], 1);
+        client.macros.stop();
+        client.macros.count = 2;
+
+        client.cursor = Some((index, 0));
+        client.preview_macro(index);
+        assert_eq!(contents(&client)[index], "--ab");
+        client.finish_macro_preview(false);
+        assert_eq!(contents(&client)[index], "ab");
+        assert_eq!(client.cursor, Some((index, 0)));
+        assert!(client.macro_preview.is_none());
+
+        client.preview_macro(index);
+        client.finish_macro_preview(true);
+        assert_eq!(contents(&client)[index], "--ab");
+    }
+
+    #[test]
+    fn global_marks_are_unique_and_jumps_can_be_undone() {
+        let mut client = client();
+        type_into_new_buffer(&client, "first buffer");
+        type_into_new_buffer(&client, "second buffer");
+        let second = contents(&client).len() - 1;
+        let first = second - 1;
+        client.set_global_mark(first, 'A', 3);
+        client.set_global_mark(second, 'A', 6);
+        let marks = client.shared.with_buffers(|buffers| (buffers.buffers[first].mark('A'), buffers.buffers[second].mark('A')));
+        assert_eq!(marks.unwrap(), (None, Some(6)));
+
+        let cursor_in = |client: &Client, buffer_index: usize| {
+            client
+                .shared
+                .with_frames(|frames| {
+                    frames
+                        .frame_clusters
+                        .iter()
+                        .flat_map(|cluster| &cluster.frames)
+                        .find(|frame| frame.buffer_index == buffer_index)
+                        .and_then(|frame| frame.pending_cursor)
+                })
+                .unwrap()
+        };
+        client.jump_to_mark(first, 'A', 2);
+        assert_eq!(cursor_in(&client, second), Some(6));
+        client.jump_to_mark(second, '\'', 6);
+        assert_eq!(cursor_in(&client, first), Some(2));
+        // Nothing to go to leaves the jump list alone.
+        client.jump_to_mark(first, 'z', 2);
+        client.jump_to_mark(first, '\'', 2);
+        assert_eq!(cursor_in(&client, second), Some(6));
+    }
+
+    #[test]
+    fn dropped_files_open_and_dropped_directories_root_the_tree() {
+        let mut client = client();
+        // The tree reads real directories; the file only has to be in storage.
+        let temp = TempDir::new("drop");
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("dropped.rs");
+        client.server.storage.write(&file, b"fn dropped() {}\n", false).unwrap();
+        let input = egui::RawInput {
+            dropped_files: [&file, &dir].map(|path| egui::DroppedFile { path: Some(path.clone()), ..Default::default() }).to_vec(),
+            ..egui::RawInput::default()
+        };
+        let _ = egui::Context::default().run(input, |ctx| client.handle_dropped_files(ctx));
+        assert!(contents(&client).contains(&String::from("fn dropped() {}\n")));
+        assert!(client.file_tree.open);
+        assert_eq!(client.file_tree.root, std::fs::canonicalize(&dir).unwrap());
+    }
+
+    #[test]
+    fn closing_windows_keeps_unsaved_work_unless_confirmed() {
+        let mut client = client();
+        client.windows.clear();
+        let [keep, saved, unsaved] = [(); 3].map(|_| client.open_window());
+        type_into_new_buffer(&client, "not saved yet");
+        let buffer_index = contents(&client).len() - 1;
+        let cluster_idx = client.windows.iter().find(|window| window.id == unsaved).unwrap().frame_cluster_index;
+        client
+            .shared
+            .with_frames_mut(|frames| frames.add_frame(cluster_idx, Frame { buffer_index, ..Frame::default() }))
+            .unwrap();
+        let ids = |client: &Client| client.windows.iter().map(|window| window.id).collect::<Vec<_>>();
+
+        client.close_all_but(keep);
+        assert_eq!(client.pending_close, Some(keep));
+        assert_eq!(ids(&client), [keep, saved, unsaved]);
+
+        client.close_saved_windows();
+        assert_eq!(ids(&client), [unsaved]);
+        client.close_window(unsaved);
+        assert!(client.windows.is_empty());
+    }
+
+    #[test]
+    fn current_window_commands_follow_focus() {
+        let mut client = client();
+        client.windows.clear();
+        assert_eq!(client.current_window(), None);
+        let [first, second] = [(); 2].map(|_| client.open_window());
+        assert_eq!(client.current_window(), Some(first));
+        client.focus_window(second);
+        assert_eq!(client.current_window(), Some(second));
+        // A closed window falls back to the first one left.
+        client.close_window(second);
+        assert_eq!(client.current_window(), Some(first));
+
+        let cluster_idx = client.windows[0].frame_cluster_index;
+        let frame_count = |client: &Client| client.shared.with_frames(|frames| frames.frame_clusters[cluster_idx].frames.len()).unwrap();
+        client.split_current_frame();
+        assert_eq!(frame_count(&client), 0);
+        client.shared.with_frames_mut(|frames| frames.add_frame(cluster_idx, Frame::default())).unwrap();
+        client.focused_frame = Some((cluster_idx, 0));
+        client.split_current_frame();
+        assert_eq!(frame_count(&client), 2);
+    }
+
+    #[test]
+    fn files_can_open_in_the_current_frame_instead_of_a_new_one() {
+        let mut client = client();
+        let dir = PathBuf::from("/project");
+        let [first, second] = ["first.txt", "second.txt"].map(|name| dir.join(name));
+        for path in [&first, &second] {
+            client.server.storage.write(path, b"text\n", false).unwrap();
+        }
+        let frame_count = |client: &Client| client.shared.frame_layout().clusters.iter().map(|cluster| cluster.frames.len()).sum::<usize>();
+
+        let first_index = client.open_file(&first).unwrap();
+        let frames = frame_count(&client);
+        client.config.open_in_current_frame = true;
+        let cluster_idx = client.windows[0].frame_cluster_index;
+        let frame_idx = client.shared.frame_layout().clusters[cluster_idx].frames.len() - 1;
+        client.focused_frame = Some((cluster_idx, frame_idx));
+        let second_index = client.open_file(&second).unwrap();
+        assert_eq!(frame_count(&client), frames);
+        let shown = client.shared.frame_layout().clusters[cluster_idx].frames[frame_idx].buffer_index;
+        assert_eq!((shown, client.active_buffer), (second_index, Some(second_index)));
+        assert_ne!(first_index, second_index);
+    }
+
+    #[test]
+    fn dispatched_split_and_undo_act_on_the_focused_frame() {
+        let mut client = client();
+        type_into_new_buffer(&client, "one");
+        let buffer_index = contents(&client).len() - 1;
+        client.open_window();
+        let cluster_idx = client.windows[0].frame_cluster_index;
+        client.shared.with_frames_mut(|frames| frames.add_frame(cluster_idx, Frame { buffer_index, ..Frame::default() })).unwrap();
+        let frames = |client: &Client| client.shared.frame_layout().clusters[cluster_idx].frames.clone();
+        let before = frames(&client).len();
+        client.focused_frame = Some((cluster_idx, before - 1));
+
+        client.dispatch(Command::Split);
+        let after = frames(&client);
+        assert_eq!(after.len(), before + 1);
+        assert_eq!(after[before].buffer_index, buffer_index);
+
+        client
+            .shared
+            .with_buffers_mut(|buffers| buffers.buffers[buffer_index].edit(&[BufferActions::InsertText { char_idx: 3, text: String::from(" two") }]))
+            .unwrap();
+        assert_eq!(contents(&client)[buffer_index], "one two");
+        client.dispatch(Command::Undo);
+        assert_eq!(contents(&client)[buffer_index], "one");
+    }
+
+    #[test]
+    fn buffers_are_compared_against_the_chosen_one() {
+        let mut client = client();
+        for text in ["one\n", "two\n", "one\nthree\n"] {
+            type_into_new_buffer(&client, text);
+        }
+        let last = contents(&client).len() - 1;
+        client.active_buffer = Some(last - 2);
+        client.compare_current_with(last);
+        assert!(client.diff_view.open);
+        assert_eq!((client.diff_view.left, client.diff_view.right), (last - 2, last));
+    }
+
+    #[test]
+    fn repeat_last_runs_the_last_repeatable_command_again() {
+        let mut client = client();
+        type_into_new_buffer(&client, "ab\ncd");
+        let buffer_index = contents(&client).len() - 1;
+        client.active_buffer = Some(buffer_index);
+        client.cursor = Some((buffer_index, 4));
+        client.dispatch(Command::RepeatLast);
+        assert_eq!(contents(&client)[buffer_index], "ab\ncd");
+
+        client.dispatch(Command::DuplicateLine);
+        client.dispatch(Command::Find);
+        client.dispatch(Command::RepeatLast);
+        assert_eq!(contents(&client)[buffer_index], "ab\ncd\ncd\ncd");
+        assert_eq!(client.cursor, Some((buffer_index, 10)));
+    }
+
+    #[test]
+    fn soft_reloads_ask_before_dropping_unsaved_edits() {
+        let mut client = client();
+        let path = PathBuf::from("/project/notes.txt");
+        client.server.storage.write(&path, b"disk v1\n", false).unwrap();
+        let buffer_index = client.open_file(&path).unwrap();
+        client.server.storage.write(&path, b"disk v2\n", false).unwrap();
+        client.reload_buffer(buffer_index, false);
+        assert_eq!(contents(&client)[buffer_index], "disk v2\n");
+        assert_eq!(client.reload.buffer_index, None);
+
+        let edit = [BufferActions::InsertText { char_idx: 0, text: String::from("mine ") }];
+        client.shared.with_buffers_mut(|buffers| buffers.buffers[buffer_index].edit(&edit)).unwrap();
+        client.server.storage.write(&path, b"disk v3\n", false).unwrap();
+        client.reload_buffer(buffer_index, false);
+        assert_eq!((client.reload.buffer_index, client.reload.disk_changed), (Some(buffer_index), true));
+        assert_eq!(contents(&client)[buffer_index], "mine disk v2\n");
+
+        client.handle_reload_choice(buffer_index, ReloadChoice::KeepMine);
+        assert_eq!(contents(&client)[buffer_index], "mine disk v2\n");
+        client.handle_reload_choice(buffer_index, ReloadChoice::Discard);
+        assert_eq!(contents(&client)[buffer_index], "disk v3\n");
+    }
+
+    struct PickedPath(Option<PathBuf>);
+
+    impl FileDialog for PickedPath {
+        fn pick(&self, _request: &DialogRequest) -> Option<PathBuf> {
+            self.0.clone()
+        }
+    }
+
+    fn finish_dialog(client: &mut Client) {
+        while client.dialog.is_some() {
+            client.pump_dialog();
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn save_as_writes_where_the_dialog_points_and_cancelling_does_nothing() {
+        let mut client = client();
+        type_into_new_buffer(&client, "draft\n");
+        let buffer_index = contents(&client).len() - 1;
+        client.active_buffer = Some(buffer_index);
+
+        client.file_dialog = Arc::new(PickedPath(None));
+        client.dispatch(Command::SaveAs);
+        finish_dialog(&mut client);
+        assert!(client.dialog.is_none());
+        assert!(client.shared.buffer_meta()[buffer_index].file_path.as_os_str().is_empty());
+
+        let path = PathBuf::from("/project/draft.txt");
+        client.file_dialog = Arc::new(PickedPath(Some(path.clone())));
+        client.dispatch(Command::SaveAs);
+        finish_dialog(&mut client);
+        assert_eq!(client.shared.buffer_meta()[buffer_index].file_path, path);
+        assert_eq!(client.server.storage.read(&path).unwrap(), b"draft\n");
+    }
+
+    struct NoDialog;
+
+    impl FileDialog for NoDialog {
+        fn pick(&self, _request: &DialogRequest) -> Option<PathBuf> {
+            unreachable!("an unavailable dialog is never shown")
+        }
+
+        fn unavailable(&self) -> Option<String> {
+            Some(String::from("no chooser here"))
+        }
+    }
+
+    #[test]
+    fn without_a_file_dialog_the_path_is_typed_in() {
+        let mut client = client();
+        type_into_new_buffer(&client, "draft\n");
+        let buffer_index = contents(&client).len() - 1;
+        client.active_buffer = Some(buffer_index);
+        client.file_dialog = Arc::new(NoDialog);
+
+        client.dispatch(Command::SaveAs);
+        assert!(client.dialog.is_none());
+        assert!(client.asking_for_path());
+        assert_eq!(client.last_error.as_deref(), Some("no chooser here; type the path instead"));
+        assert_eq!(client.path_prompt.path, "");
+        assert!(client.path_prompt.submit().is_none());
+
+        let path = PathBuf::from("/project/draft.txt");
+        client.path_prompt.path = path.display().to_string();
+        let (request, typed) = client.path_prompt.submit().unwrap();
+        client.act_on_path(request, &typed);
+        assert!(!client.asking_for_path());
+        assert_eq!(client.server.storage.read(&path).unwrap(), b"draft\n");
+    }
+
+    #[test]
+    fn switching_to_a_closed_workspace_opens_its_window_again() {
+        let mut client = client();
+        let id = client.open_window();
+        let cluster_idx = client.windows.iter().find(|window| window.id == id).unwrap().frame_cluster_index;
+        client.close_windows(|window| window.id == id);
+        assert!(!client.shared.frame_layout().clusters[cluster_idx].is_visible);
+
+        client.switch_workspace(&egui::Context::default(), cluster_idx);
+        assert!(client.shared.frame_layout().clusters[cluster_idx].is_visible);
+        let window = client.windows.iter().find(|window| window.frame_cluster_index == cluster_idx).unwrap();
+        assert_eq!(client.focused_window, Some(window.id));
+    }
+
+    #[test]
+    fn loading_a_session_without_clusters_leaves_one_to_show() {
+        let mut client = client();
+        client.shared.with_frames_mut(|frames| frames.frame_clusters.clear()).unwrap();
+        client.server.save_session(&client.shared, "bare").unwrap();
+        client.switch_session("bare");
+        assert_eq!(client.session_name, "bare");
+        assert_eq!(client.shared.frame_layout().clusters.len(), 1);
+    }
+
+    #[test]
+    fn the_toolbar_shrinks_the_main_window_and_gives_its_size_back() {
+        let mut client = client();
+        let ctx = egui::Context::default();
+        let resize = |client: &mut Client, compact: bool| {
+            let mut input = egui::RawInput::default();
+            input.viewports.entry(egui::ViewportId::ROOT).or_default().inner_rect =
+                Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(800.0, 600.0)));
+            let output = ctx.run(input, |ctx| client.fit_main_window(ctx, compact));
+            output.viewport_output[&egui::ViewportId::ROOT]
+                .commands
+                .iter()
+                .find_map(|command| match command {
+                    egui::ViewportCommand::InnerSize(size) => Some(*size),
+                    _ => None,
+                })
+        };
+        let shrunk = resize(&mut client, true).unwrap();
+        assert!(shrunk.x == 800.0 && shrunk.y < 600.0);
+        assert_eq!(resize(&mut client, true), None);
+        assert_eq!(resize(&mut client, false), Some(egui::vec2(800.0, 600.0)));
+        assert_eq!(resize(&mut client, false), None);
+
+        client.toolbar_only = true;
+        client.dispatch(Command::Find);
+        assert!(!client.toolbar_only);
+    }
+
+    #[test]
+    fn files_open_with_the_cursor_at_a_byte_or_char_offset() {
+        let mut client = client();
+        let path = PathBuf::from("/project/notes.txt");
+        client.server.storage.write(&path, "héllo\nworld\n".as_bytes(), false).unwrap();
+        let pending = |client: &Client, buffer_index: usize| {
+            client
+                .shared
+                .with_frames(|frames| {
+                    frames
+                        .frame_clusters
+                        .iter()
+                        .flat_map(|cluster| &cluster.frames)
+                        .find(|frame| frame.buffer_index == buffer_index)
+                        .and_then(|frame| frame.pending_cursor)
+                })
+                .unwrap()
+        };
+        // Byte 2 is inside the `é`.
+        let buffer_index = client.open_file_at(&path, Some(OpenAt::Byte(2))).unwrap();
+        assert_eq!(pending(&client, buffer_index), Some(1));
+        let reopened = client.open_file_at(&path, Some(OpenAt::Char(99))).unwrap();
+        assert_eq!(pending(&client, reopened), Some(12));
+    }
+
+    #[test]
+    fn definitions_in_another_file_open_it_at_the_line_and_column() {
+        let mut client = client();
+        let dir = PathBuf::from("/project");
+        let (caller, callee) = (dir.join("main.rs"), dir.join("helpers.rs"));
+        client.server.storage.write(&caller, b"fn main() { helper(); }\n", false).unwrap();
+        client.server.storage.write(&callee, b"// helpers\nfn helper() {}\n", false).unwrap();
+        client.open_file(&caller).unwrap();
+        let before = contents(&client).len();
+
+        client.go_to_location(&Location { path: callee.clone(), position: lsp::Position { line: 1, character: 3 } });
+        assert_eq!(contents(&client).len(), before + 1);
+        let opened = client.shared.buffer_meta()[before].file_path.clone();
+        assert_eq!(opened, callee);
+        let frame = client
+            .shared
+            .with_frames(|frames| {
+                let frame = frames.frame_clusters.iter().flat_map(|cluster| &cluster.frames).find(|frame| frame.buffer_index == before)?;
+                Some((frame.pending_jump, frame.pending_cursor))
+            })
+            .unwrap();
+        // Line 1, column 3 is the `h` of `helper`, 11 + 3 chars in.
+        assert_eq!(frame, Some((Some(1), Some(14))));
     }
 }
@@ -0,0 +1,384 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+use std::collections::HashMap;
+
+use super::movement;
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::Buffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditMode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl EditMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalCommand {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBack,
+    LineStart,
+    LineEnd,
+    Insert,
+    Append,
+    Visual,
+    Normal,
+    DeleteChar,
+    DeleteLine,
+    DeleteSelection,
+    Undo,
+    Redo,
+    // `m` plus a letter; upper case marks are global and go to the client.
+    SetMark(char),
+    // `'` plus a letter, or `''` to go back to where the last jump started.
+    JumpToMark(char),
+}
+
+const SET_MARK: &str = "m";
+const JUMP_TO_MARK: &str = "'";
+
+fn mark_command(pending: &str, key: &str) -> Option<ModalCommand> {
+    let mut chars = key.chars();
+    let name = chars.next().filter(|_| chars.next().is_none())?;
+    match pending {
+        SET_MARK if name.is_alphabetic() => Some(ModalCommand::SetMark(name)),
+        JUMP_TO_MARK if name.is_alphabetic() || name == '\'' => Some(ModalCommand::JumpToMark(name)),
+        _ => None,
+    }
+}
+
+impl ModalCommand {
+    fn motion(self, content: &str, char_idx: usize) -> Option<usize> {
+        let len = content.chars().count();
+        Some(match self {
+            Self::Left => char_idx.saturating_sub(1),
+            Self::Right => (char_idx + 1).min(len),
+            Self::Up => line_up(content, char_idx),
+            Self::Down => line_down(content, char_idx),
+            Self::WordForward => movement::word_right(content, char_idx),
+            Self::WordBack => movement::word_left(content, char_idx),
+            Self::LineStart => line_bounds(content, char_idx).0,
+            Self::LineEnd => movement::line_end(content, char_idx),
+            _ => return None,
+        })
+    }
+}
+
+// Start and column of the line holding `char_idx`.
+fn line_bounds(content: &str, char_idx: usize) -> (usize, usize) {
+    let start = content
+        .chars()
+        .take(char_idx)
+        .enumerate()
+        .filter(|(_, c)| *c == '\n')
+        .last()
+        .map_or(0, |(newline, _)| newline + 1);
+    (start, char_idx - start)
+}
+
+// Keeps the column, clamped to the target line's length.
+pub fn line_down(content: &str, char_idx: usize) -> usize {
+    let (_, column) = line_bounds(content, char_idx);
+    let end = movement::line_end(content, char_idx);
+    if end >= content.chars().count() {
+        return char_idx;
+    }
+    let next_start = end + 1;
+    (next_start + column).min(movement::line_end(content, next_start))
+}
+
+pub fn line_up(content: &str, char_idx: usize) -> usize {
+    let (start, column) = line_bounds(content, char_idx);
+    if start == 0 {
+        return char_idx;
+    }
+    let (previous_start, _) = line_bounds(content, start - 1);
+    (previous_start + column).min(start - 1)
+}
+
+// What each mode does with typed keys, by the text they produce (`j`, `$`,
+// `dd`) or the key name for keys without text (`Escape`). Rebinding is a
+// matter of inserting into `bindings`.
+pub struct ModalKeymap {
+    pub bindings: HashMap<EditMode, HashMap<String, ModalCommand>>,
+}
+
+impl Default for ModalKeymap {
+    fn default() -> Self {
+        let motions = [
+            ("h", ModalCommand::Left),
+            ("l", ModalCommand::Right),
+            ("k", ModalCommand::Up),
+            ("j", ModalCommand::Down),
+            ("w", ModalCommand::WordForward),
+            ("b", ModalCommand::WordBack),
+            ("0", ModalCommand::LineStart),
+            ("$", ModalCommand::LineEnd),
+        ];
+        let normal = [
+            ("i", ModalCommand::Insert),
+            ("a", ModalCommand::Append),
+            ("v", ModalCommand::Visual),
+            ("x", ModalCommand::DeleteChar),
+            ("dd", ModalCommand::DeleteLine),
+            ("u", ModalCommand::Undo),
+            ("Escape", ModalCommand::Normal),
+        ];
+        let visual = [
+            ("d", ModalCommand::DeleteSelection),
+            ("x", ModalCommand::DeleteSelection),
+            ("v", ModalCommand::Normal),
+            ("Escape", ModalCommand::Normal),
+        ];
+        let bind = |pairs: &[(&str, ModalCommand)]| -> HashMap<String, ModalCommand> {
+            pairs.iter().map(|(key, command)| (key.to_string(), *command)).collect()
+        };
+        let mut bindings = HashMap::new();
+        bindings.insert(EditMode::Normal, bind(&[&motions[..], &normal[..]].concat()));
+        bindings.insert(EditMode::Visual, bind(&[&motions[..], &visual[..]].concat()));
+        bindings.insert(EditMode::Insert, bind(&[("Escape", ModalCommand::Normal)]));
+        Self { bindings }
+    }
+}
+
+// Vim-style modes layered over the editor. Only used when
+// `modal_editing` is on in the config.
+pub struct ModalState {
+    pub mode: EditMode,
+    pub keymap: ModalKeymap,
+    pending: String,
+    // Mark commands `run` can't finish within one buffer.
+    mark_request: Option<ModalCommand>,
+}
+
+impl Default for ModalState {
+    fn default() -> Self {
+        Self {
+            mode: EditMode::Normal,
+            keymap: ModalKeymap::default(),
+            pending: String::new(),
+            mark_request: None,
+        }
+    }
+}
+
+impl ModalState {
+    // Feeds one key; returns the command once a binding is complete.
+    // Prefixes of longer bindings (`d` of `dd`) wait for the next key.
+    pub fn feed(&mut self, key: &str) -> Option<ModalCommand> {
+        let bindings = self.keymap.bindings.get(&self.mode)?;
+        if self.mode != EditMode::Insert
+            && let Some(command) = mark_command(&self.pending, key)
+        {
+            self.pending.clear();
+            return Some(command);
+        }
+        self.pending.push_str(key);
+        if let Some(command) = bindings.get(&self.pending) {
+            self.pending.clear();
+            return Some(*command);
+        }
+        if self.mode != EditMode::Insert && [SET_MARK, JUMP_TO_MARK].contains(&self.pending.as_str()) {
+            return None;
+        }
+        if !bindings.keys().any(|binding| binding.starts_with(self.pending.as_str())) {
+            self.pending.clear();
+        }
+        None
+    }
+
+    // Runs `command` against the buffer and the cursor range (primary,
+    // secondary). Returns the new range and any edit it made.
+    pub fn run(
+        &mut self,
+        command: ModalCommand,
+        buffer: &mut Buffer,
+        (primary, secondary): (usize, usize),
+    ) -> ((usize, usize), Vec<BufferActions>) {
+        if let Some(target) = command.motion(buffer.content(), primary) {
+            let anchor = if self.mode == EditMode::Visual { secondary } else { target };
+            return ((target, anchor), Vec::new());
+        }
+
+        let len = buffer.content().chars().count();
+        let (cursor, actions) = match command {
+            ModalCommand::Insert => {
+                self.mode = EditMode::Insert;
+                (primary, Vec::new())
+            }
+            ModalCommand::Append => {
+                self.mode = EditMode::Insert;
+                ((primary + 1).min(movement::line_end(buffer.content(), primary)), Vec::new())
+            }
+            ModalCommand::Visual => {
+                self.mode = EditMode::Visual;
+                return ((primary, primary), Vec::new());
+            }
+            ModalCommand::Normal => {
+                self.mode = EditMode::Normal;
+                (primary, Vec::new())
+            }
+            ModalCommand::DeleteChar if primary < len => {
+                (primary, vec![BufferActions::DeleteRange { start: primary, end: primary + 1 }])
+            }
+            ModalCommand::DeleteLine => {
+                let (start, _) = line_bounds(buffer.content(), primary);
+                let end = (movement::line_end(buffer.content(), primary) + 1).min(len);
+                // The last line takes the newline before it instead.
+                let start = if end == len && start > 0 { start - 1 } else { start };
+                (start, vec![BufferActions::DeleteRange { start, end }])
+            }
+            ModalCommand::DeleteSelection => {
+                self.mode = EditMode::Normal;
+                let (start, end) = (primary.min(secondary), (primary.max(secondary) + 1).min(len));
+                (start, vec![BufferActions::DeleteRange { start, end }])
+            }
+            ModalCommand::Undo => {
+                buffer.undo();
+                (primary.min(buffer.content().chars().count()), Vec::new())
+            }
+            ModalCommand::Redo => {
+                buffer.redo();
+                (primary.min(buffer.content().chars().count()), Vec::new())
+            }
+            ModalCommand::SetMark(name) if name.is_lowercase() => {
+                buffer.set_mark(name, primary);
+                (primary, Vec::new())
+            }
+            ModalCommand::SetMark(_) | ModalCommand::JumpToMark(_) => {
+                self.mark_request = Some(command);
+                (primary, Vec::new())
+            }
+            _ => (primary, Vec::new()),
+        };
+        buffer.edit(&actions);
+        ((cursor, cursor), actions)
+    }
+
+    // Global marks and jumps, left for the client since they can reach
+    // other buffers.
+    pub fn take_mark_request(&mut self) -> Option<ModalCommand> {
+        self.mark_request.take()
+    }
+
+    // Outside Insert mode, typed text and editing keys are taken out of the
+    // input and run through the keymap instead of reaching the TextEdit.
+    // Shortcuts with Cmd/Ctrl still go through. Returns the edits made and
+    // the cursor they left.
+    pub fn handle_keys(&mut self, ctx: &egui::Context, id: egui::Id, buffer: &mut Buffer) -> Option<(Vec<BufferActions>, usize)> {
+        let mut keys = Vec::new();
+        let modal = self.mode != EditMode::Insert;
+        ctx.input_mut(|input| {
+            input.events.retain(|event| match event {
+                egui::Event::Text(text) if modal => {
+                    keys.push(text.clone());
+                    false
+                }
+                egui::Event::Key { key: egui::Key::Escape, pressed, .. } => {
+                    if *pressed {
+                        keys.push(String::from("Escape"));
+                    }
+                    false
+                }
+                egui::Event::Key { key, modifiers, .. } if modal && !modifiers.command => !matches!(
+                    key,
+                    egui::Key::Backspace | egui::Key::Delete | egui::Key::Enter | egui::Key::Tab
+                ),
+                egui::Event::Paste(_) | egui::Event::Cut => !modal,
+                _ => true,
+            });
+        });
+        if keys.is_empty() {
+            return None;
+        }
+
+        let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+        let range = state.cursor.char_range().unwrap_or_default();
+        let mut cursor = (range.primary.index, range.secondary.index);
+        let mut edits = Vec::new();
+        for key in keys {
+            if let Some(command) = self.feed(&key) {
+                let (moved, actions) = self.run(command, buffer, cursor);
+                cursor = moved;
+                edits.extend(actions);
+            }
+        }
+        state.cursor.set_char_range(Some(CCursorRange {
+            primary: CCursor::new(cursor.0),
+            secondary: CCursor::new(cursor.1),
+            h_pos: None,
+        }));
+        state.store(ctx, id);
+        Some((edits, cursor.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_resolve_per_mode_and_prefixes_wait() {
+        let mut modal = ModalState::default();
+        assert_eq!(modal.feed("j"), Some(ModalCommand::Down));
+        assert_eq!(modal.feed("d"), None);
+        assert_eq!(modal.feed("d"), Some(ModalCommand::DeleteLine));
+        // An unbound sequence is dropped rather than blocking what follows.
+        assert_eq!(modal.feed("d"), None);
+        assert_eq!(modal.feed("q"), None);
+        assert_eq!(modal.feed("x"), Some(ModalCommand::DeleteChar));
+
+        modal.mode = EditMode::Insert;
+        assert_eq!(modal.feed("j"), None);
+        assert_eq!(modal.feed("Escape"), Some(ModalCommand::Normal));
+    }
+
+    #[test]
+    fn vertical_motions_keep_the_column_where_they_can() {
+        let content = "hello\nhi\nworld";
+        assert_eq!(line_down(content, 4), 8);
+        assert_eq!(line_down(content, 8), 11);
+        assert_eq!(line_up(content, 13), 8);
+        assert_eq!(line_up(content, 2), 2);
+        assert_eq!(line_down(content, 13), 13);
+    }
+
+    #[test]
+    fn commands_edit_the_buffer_and_switch_modes() {
+        let mut modal = ModalState::default();
+        let mut buffer = Buffer::with_content("one\ntwo\nthree");
+
+        let (cursor, actions) = modal.run(ModalCommand::DeleteLine, &mut buffer, (5, 5));
+        assert_eq!((cursor, buffer.content()), ((4, 4), "one\nthree"));
+        assert_eq!(actions, vec![BufferActions::DeleteRange { start: 4, end: 8 }]);
+        // The last line takes the newline before it.
+        modal.run(ModalCommand::DeleteLine, &mut buffer, (6, 6));
+        assert_eq!(buffer.content(), "one");
+
+        modal.run(ModalCommand::Visual, &mut buffer, (0, 0));
+        let (range, _) = modal.run(ModalCommand::Right, &mut buffer, (0, 0));
+        assert_eq!(range, (1, 0));
+        modal.run(ModalCommand::DeleteSelection, &mut buffer, range);
+        assert_eq!((buffer.content(), modal.mode), ("e", EditMode::Normal));
+
+        modal.run(ModalCommand::Undo, &mut buffer, (0, 0));
+        assert_eq!(buffer.content(), "one");
+        modal.run(ModalCommand::Append, &mut buffer, (0, 0));
+        assert_eq!(modal.mode, EditMode::Insert);
+    }
+}
@@ -0,0 +1,256 @@
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::brackets;
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    WordLeft,
+    WordRight,
+    SubwordLeft,
+    SubwordRight,
+    LineStart,
+    LineEnd,
+    MatchingBracket,
+}
+
+impl Movement {
+    // Shift is left out: it picks the selecting variant of the same movement.
+    pub fn for_key(key: egui::Key, modifiers: egui::Modifiers) -> Option<Self> {
+        let modifiers = egui::Modifiers { shift: false, ..modifiers };
+        match key {
+            egui::Key::ArrowLeft if modifiers == egui::Modifiers::COMMAND => Some(Self::WordLeft),
+            egui::Key::ArrowRight if modifiers == egui::Modifiers::COMMAND => Some(Self::WordRight),
+            egui::Key::ArrowLeft if modifiers == egui::Modifiers::ALT => Some(Self::SubwordLeft),
+            egui::Key::ArrowRight if modifiers == egui::Modifiers::ALT => Some(Self::SubwordRight),
+            egui::Key::Home if modifiers.is_none() => Some(Self::LineStart),
+            egui::Key::End if modifiers.is_none() => Some(Self::LineEnd),
+            egui::Key::Backslash if modifiers == egui::Modifiers::ALT => Some(Self::MatchingBracket),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, content: &str, char_idx: usize) -> usize {
+        match self {
+            Self::WordLeft => word_left(content, char_idx),
+            Self::WordRight => word_right(content, char_idx),
+            Self::SubwordLeft => subword_left(content, char_idx),
+            Self::SubwordRight => subword_right(content, char_idx),
+            Self::LineStart => line_start(content, char_idx),
+            Self::LineEnd => line_end(content, char_idx),
+            Self::MatchingBracket => brackets::matching(content, char_idx).unwrap_or(char_idx),
+        }
+    }
+}
+
+// Char ranges of the Unicode words in `content`, skipping whitespace and
+// punctuation segments.
+fn words(content: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut char_idx = 0;
+    for segment in content.split_word_bounds() {
+        let len = segment.chars().count();
+        if segment.chars().any(char::is_alphanumeric) {
+            words.push(char_idx..char_idx + len);
+        }
+        char_idx += len;
+    }
+    words
+}
+
+pub fn word_left(content: &str, char_idx: usize) -> usize {
+    words(content)
+        .iter()
+        .rev()
+        .find(|word| word.start < char_idx)
+        .map_or(0, |word| word.start)
+}
+
+pub fn word_right(content: &str, char_idx: usize) -> usize {
+    words(content)
+        .iter()
+        .find(|word| word.end > char_idx)
+        .map_or_else(|| content.chars().count(), |word| word.end)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Lower,
+    Upper,
+    Digit,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_lowercase() {
+        CharClass::Lower
+    } else if c.is_uppercase() {
+        CharClass::Upper
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_alphabetic() {
+        CharClass::Lower
+    } else {
+        CharClass::Other
+    }
+}
+
+// Whether a subword starts at `i`: `parseHTTPResponse` splits into
+// `parse`, `HTTP` and `Response`.
+fn is_subword_start(classes: &[CharClass], i: usize) -> bool {
+    if i == 0 || i >= classes.len() {
+        return true;
+    }
+    match (classes[i - 1], classes[i]) {
+        (CharClass::Upper, CharClass::Lower) => false,
+        (CharClass::Upper, CharClass::Upper) => classes.get(i + 1) == Some(&CharClass::Lower),
+        (previous, current) => previous != current,
+    }
+}
+
+pub fn subword_left(content: &str, char_idx: usize) -> usize {
+    let classes: Vec<CharClass> = content.chars().map(char_class).collect();
+    let mut i = char_idx.min(classes.len());
+    while i > 0 && classes[i - 1] == CharClass::Other {
+        i -= 1;
+    }
+    while i > 0 {
+        i -= 1;
+        if is_subword_start(&classes, i) {
+            break;
+        }
+    }
+    i
+}
+
+pub fn subword_right(content: &str, char_idx: usize) -> usize {
+    let classes: Vec<CharClass> = content.chars().map(char_class).collect();
+    let mut i = char_idx.min(classes.len());
+    while i < classes.len() && classes[i] == CharClass::Other {
+        i += 1;
+    }
+    while i < classes.len() {
+        i += 1;
+        if is_subword_start(&classes, i) {
+            break;
+        }
+    }
+    i
+}
+
+// Goes to the first non-blank char of the line, or to column 0 when
+// already there.
+pub fn line_start(content: &str, char_idx: usize) -> usize {
+    let chars: Vec<char> = content.chars().collect();
+    let char_idx = char_idx.min(chars.len());
+    let start = chars[..char_idx]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |newline| newline + 1);
+    let first_non_blank = chars[start..]
+        .iter()
+        .position(|c| *c == '\n' || !c.is_whitespace())
+        .map_or(chars.len(), |offset| start + offset);
+    if char_idx == first_non_blank { start } else { first_non_blank }
+}
+
+pub fn line_end(content: &str, char_idx: usize) -> usize {
+    content
+        .chars()
+        .enumerate()
+        .skip(char_idx)
+        .find(|(_, c)| *c == '\n')
+        .map_or_else(|| content.chars().count(), |(newline, _)| newline)
+}
+
+// Runs the movement keys pressed this frame against the editor's stored
+// cursor, taking them out of the input so TextEdit doesn't apply its own.
+// Returns each movement with the cursor it led to.
+pub fn handle_keys(ctx: &egui::Context, id: egui::Id, content: &str) -> Vec<(Movement, usize)> {
+    let Some(mut state) = egui::TextEdit::load_state(ctx, id) else {
+        return Vec::new();
+    };
+    let Some(mut range) = state.cursor.char_range() else {
+        return Vec::new();
+    };
+
+    let mut moved = Vec::new();
+    ctx.input_mut(|input| {
+        input.events.retain(|event| {
+            let egui::Event::Key { key, pressed: true, modifiers, .. } = event else {
+                return true;
+            };
+            let Some(movement) = Movement::for_key(*key, *modifiers) else {
+                return true;
+            };
+            let primary = CCursor::new(movement.apply(content, range.primary.index));
+            range = if modifiers.shift {
+                CCursorRange { primary, secondary: range.secondary, h_pos: None }
+            } else {
+                CCursorRange::one(primary)
+            };
+            moved.push((movement, primary.index));
+            false
+        });
+    });
+
+    if !moved.is_empty() {
+        state.cursor.set_char_range(Some(range));
+        state.store(ctx, id);
+    }
+    moved
+}
+
+pub fn set_cursor(ctx: &egui::Context, id: egui::Id, char_idx: usize) {
+    let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(char_idx))));
+    state.store(ctx, id);
+}
+
+// Selects `range`, with the cursor at its end.
+pub fn set_selection(ctx: &egui::Context, id: egui::Id, range: Range<usize>) {
+    let mut state = egui::TextEdit::load_state(ctx, id).unwrap_or_default();
+    state.cursor.set_char_range(Some(CCursorRange::two(CCursor::new(range.start), CCursor::new(range.end))));
+    state.store(ctx, id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_step_over_multibyte_text_and_punctuation() {
+        let content = "naïve café, über";
+        assert_eq!(word_right(content, 0), 5);
+        assert_eq!(word_right(content, 5), 10);
+        assert_eq!(word_right(content, 12), 16);
+        assert_eq!(word_left(content, 16), 12);
+        assert_eq!(word_left(content, 11), 6);
+        assert_eq!(word_left(content, 3), 0);
+    }
+
+    #[test]
+    fn subwords_split_camel_case_acronyms_and_digits() {
+        let content = "parseHTTPResponse2go";
+        let mut stops = vec![0];
+        while *stops.last().unwrap() < content.len() {
+            stops.push(subword_right(content, *stops.last().unwrap()));
+        }
+        assert_eq!(stops, vec![0, 5, 9, 17, 18, 20]);
+        assert_eq!(subword_left(content, 17), 9);
+        assert_eq!(subword_left("ÉtéÀ", 4), 3);
+    }
+
+    #[test]
+    fn home_toggles_between_indent_and_column_zero() {
+        let content = "x\n    indented";
+        assert_eq!(line_start(content, 10), 6);
+        assert_eq!(line_start(content, 6), 2);
+        assert_eq!(line_end(content, 0), 1);
+        assert_eq!(Movement::for_key(egui::Key::Home, egui::Modifiers::SHIFT), Some(Movement::LineStart));
+        assert_eq!(Movement::for_key(egui::Key::Home, egui::Modifiers::CTRL), None);
+    }
+}
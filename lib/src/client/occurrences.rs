@@ -0,0 +1,144 @@
+use super::hover::is_word_char;
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use egui::text::{LayoutJob, LayoutSection};
+
+// How long the cursor rests on a word before its occurrences light up, so
+// moving through text doesn't flash highlights on every word passed.
+const SETTLE_DELAY: Duration = Duration::from_millis(150);
+
+type Ranges = Arc<Vec<Range<usize>>>;
+
+#[derive(Default)]
+struct OccurrencesComputer;
+
+impl egui::cache::ComputerMut<(&str, &str), Ranges> for OccurrencesComputer {
+    fn compute(&mut self, (content, word): (&str, &str)) -> Ranges {
+        Arc::new(occurrences(content, word))
+    }
+}
+
+type OccurrencesCache = egui::cache::FrameCache<Ranges, OccurrencesComputer>;
+
+pub fn cached_occurrences(ctx: &egui::Context, content: &str, word: &str) -> Ranges {
+    ctx.memory_mut(|memory| memory.caches.cache::<OccurrencesCache>().get((content, word)))
+}
+
+// The identifier under `char_idx`, or the one ending right before it.
+pub fn word_at(content: &str, char_idx: usize) -> Option<String> {
+    let chars: Vec<char> = content.chars().collect();
+    let is_word_at = |idx: usize| chars.get(idx).copied().is_some_and(is_word_char);
+    let at = if is_word_at(char_idx) {
+        char_idx
+    } else {
+        char_idx.checked_sub(1).filter(|idx| is_word_at(*idx))?
+    };
+    let start = chars[..at].iter().rposition(|c| !is_word_char(*c)).map_or(0, |idx| idx + 1);
+    let end = chars[at..].iter().position(|c| !is_word_char(*c)).map_or(chars.len(), |idx| at + idx);
+    Some(chars[start..end].iter().collect())
+}
+
+// Byte ranges of `word` where it stands as a whole word, so `foo` doesn't
+// match inside `foobar`.
+pub fn occurrences(content: &str, word: &str) -> Vec<Range<usize>> {
+    content
+        .match_indices(word)
+        .filter(|(start, _)| {
+            let before = content[..*start].chars().next_back();
+            let after = content[start + word.len()..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        })
+        .map(|(start, _)| start..start + word.len())
+        .collect()
+}
+
+// `word` once it has stayed the same for `SETTLE_DELAY` in the editor `id`.
+pub fn settled_word(ctx: &egui::Context, id: egui::Id, word: Option<String>) -> Option<String> {
+    let id = id.with("occurrences");
+    let now = Instant::now();
+    let since = ctx.data_mut(|data| {
+        let entry = data.get_temp_mut_or_insert_with(id, || (word.clone(), now));
+        if entry.0 != word {
+            *entry = (word.clone(), now);
+        }
+        entry.1
+    });
+    let waited = now.duration_since(since);
+    if waited < SETTLE_DELAY {
+        if word.is_some() {
+            ctx.request_repaint_after(SETTLE_DELAY - waited);
+        }
+        return None;
+    }
+    word
+}
+
+// Gives the byte ranges a background, splitting sections at their edges.
+// Sections already made transparent (folded lines) are left be.
+pub fn mark(job: &mut LayoutJob, ranges: &[Range<usize>], background: egui::Color32) {
+    restyle(job, ranges, |format| format.background = background);
+}
+
+// Underlines the byte ranges, the same way `mark` fills them.
+pub fn underline(job: &mut LayoutJob, ranges: &[Range<usize>], stroke: egui::Stroke) {
+    restyle(job, ranges, |format| format.underline = stroke);
+}
+
+fn restyle(job: &mut LayoutJob, ranges: &[Range<usize>], style: impl Fn(&mut egui::TextFormat)) {
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut ranges = ranges.iter().peekable();
+    for section in job.sections.drain(..) {
+        if section.format.color == egui::Color32::TRANSPARENT {
+            sections.push(section);
+            continue;
+        }
+        let section_end = section.byte_range.end;
+        let mut at = section.byte_range.start;
+        let mut leading_space = section.leading_space;
+        while at < section_end {
+            while ranges.next_if(|range| range.end <= at).is_some() {}
+            let (end, marked) = match ranges.peek() {
+                Some(range) if range.start <= at => (range.end.min(section_end), true),
+                Some(range) => (range.start.min(section_end), false),
+                None => (section_end, false),
+            };
+            let mut format = section.format.clone();
+            if marked {
+                style(&mut format);
+            }
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: at..end,
+                format,
+            });
+            leading_space = 0.0;
+            at = end;
+        }
+    }
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_found_under_or_just_before_the_cursor() {
+        let content = "let café_au = x;";
+        assert_eq!(word_at(content, 6).as_deref(), Some("café_au"));
+        assert_eq!(word_at(content, 11).as_deref(), Some("café_au"));
+        assert_eq!(word_at(content, 13), None);
+        assert_eq!(word_at("", 0), None);
+    }
+
+    #[test]
+    fn only_whole_word_occurrences_match() {
+        let content = "foo foobar é_foo (foo) foo";
+        assert_eq!(occurrences(content, "foo"), [0..3, 19..22, 24..27]);
+        assert!(occurrences(content, "oba").is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::server::hash;
+use crate::server::lsp::LspManager;
+use crate::server::symbols::{self, Symbol};
+use crate::shared::Shared;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+// Edits that keep the line count only refresh the outline this often.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+struct Outline {
+    symbols: Vec<Symbol>,
+    content_hash: u64,
+    line_count: usize,
+    refreshed_at: Instant,
+    waiting: bool,
+}
+
+#[derive(Default)]
+pub struct OutlinePanel {
+    pub open: bool,
+    pub buffer_index: usize,
+    outlines: HashMap<usize, Outline>,
+}
+
+impl OutlinePanel {
+    // Asks the language server for the selected buffer's symbols when they're
+    // stale, or derives them from the syntax tree if no server covers it.
+    pub fn refresh(&mut self, shared: &Shared, lsp: &mut LspManager, now: Instant) {
+        if !self.open {
+            return;
+        }
+        let buffer_index = self.buffer_index;
+        let state = shared.with_buffers(|buffers| {
+            let content = &buffers.buffers.get(buffer_index)?.content();
+            Some((hash::content_hash(content), content.lines().count()))
+        });
+        let Ok(Some((content_hash, line_count))) = state else {
+            return;
+        };
+        if let Some(outline) = self.outlines.get(&buffer_index) {
+            let significant = outline.line_count != line_count
+                || now.duration_since(outline.refreshed_at) >= REFRESH_INTERVAL;
+            if outline.waiting || outline.content_hash == content_hash || !significant {
+                return;
+            }
+        }
+
+        let waiting = lsp.document_symbols(shared, buffer_index);
+        let symbols = match self.outlines.remove(&buffer_index) {
+            Some(outline) if waiting => outline.symbols,
+            _ if waiting => Vec::new(),
+            _ => syntax_symbols(shared, buffer_index),
+        };
+        self.outlines.insert(buffer_index, Outline {
+            symbols,
+            content_hash,
+            line_count,
+            refreshed_at: now,
+            waiting,
+        });
+    }
+
+    pub fn receive(&mut self, shared: &Shared, buffer_index: usize, symbols: Vec<Symbol>) {
+        let Some(outline) = self.outlines.get_mut(&buffer_index) else {
+            return;
+        };
+        outline.waiting = false;
+        outline.symbols = if symbols.is_empty() {
+            syntax_symbols(shared, buffer_index)
+        } else {
+            symbols
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer_index = 0;
+        self.outlines.clear();
+    }
+
+    pub fn symbols(&self, buffer_index: usize) -> &[Symbol] {
+        self.outlines
+            .get(&buffer_index)
+            .map_or(&[], |outline| outline.symbols.as_slice())
+    }
+
+    // Returns the buffer and line of the symbol the user clicked.
+    pub fn show(&mut self, ctx: &egui::Context, shared: &Shared) -> Option<(usize, usize)> {
+        if !self.open {
+            return None;
+        }
+
+        let names: Vec<String> = shared
+            .with_buffers(|buffers| {
+                buffers
+                    .buffers
+                    .iter()
+                    .enumerate()
+                    .map(|(buffer_index, buffer)| {
+                        buffer.file_path.file_name().map_or_else(
+                            || format!("untitled {buffer_index}"),
+                            |name| name.to_string_lossy().into_owned(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        if self.buffer_index >= names.len() {
+            self.buffer_index = 0;
+        }
+
+        let mut jump = None;
+        egui::SidePanel::right("outline").show(ctx, |ui| {
+            egui::ComboBox::from_id_salt("outline_buffer")
+                .selected_text(names.get(self.buffer_index).map_or("", String::as_str))
+                .show_ui(ui, |ui| {
+                    for (buffer_index, name) in names.iter().enumerate() {
+                        ui.selectable_value(&mut self.buffer_index, buffer_index, name);
+                    }
+                });
+            ui.separator();
+
+            let symbols = self.symbols(self.buffer_index);
+            if symbols.is_empty() {
+                ui.weak("No symbols");
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                show_symbols(ui, symbols, &mut jump);
+            });
+        });
+        jump.map(|line| (self.buffer_index, line))
+    }
+}
+
+fn syntax_symbols(shared: &Shared, buffer_index: usize) -> Vec<Symbol> {
+    shared
+        .with_buffers(|buffers| {
+            let buffer = buffers.buffers.get(buffer_index)?;
+            symbols::from_syntax(&buffer.file_path, buffer.content())
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn show_symbols(ui: &mut egui::Ui, symbols: &[Symbol], jump: &mut Option<usize>) {
+    for symbol in symbols {
+        let label = format!("{} {}", symbol.kind, symbol.name);
+        if symbol.children.is_empty() {
+            if ui.selectable_label(false, label).clicked() {
+                *jump = Some(symbol.line);
+            }
+            continue;
+        }
+        let id = ui.make_persistent_id(("outline", symbol.line, &symbol.name));
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                if ui.selectable_label(false, label).clicked() {
+                    *jump = Some(symbol.line);
+                }
+            })
+            .body(|ui| show_symbols(ui, &symbol.children, jump));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Instant;
+
+use eframe::egui;
+use egui::text::{CCursor, CCursorRange};
+
+use crate::interfaces::enums::BufferActions;
+use crate::shared::buffers::Buffer;
+
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+fn closing_for(c: char) -> Option<char> {
+    PAIRS.iter().find(|(open, _)| *open == c).map(|(_, close)| *close)
+}
+
+// Quotes are left alone after a word character (`don't`), and `'` in Rust,
+// where it mostly starts a lifetime.
+fn pairs_here(typed: char, before: Option<char>, after: Option<char>, language: &str) -> bool {
+    let quote = typed == '"' || typed == '\'';
+    if quote && before.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+        return false;
+    }
+    if typed == '\'' && language == "rust" {
+        return false;
+    }
+    // Only where the closing char won't split a word.
+    after.is_none_or(|c| c.is_whitespace() || PAIRS.iter().any(|(_, close)| *close == c))
+}
+
+// What typing `typed` at `cursor` does instead of a plain insert: adds the
+// closing counterpart, or steps over it when it's already next. Returns the
+// actions, if any, and the new cursor.
+pub fn on_type(content: &str, cursor: usize, typed: char, language: &str) -> Option<(Vec<BufferActions>, usize)> {
+    let before = cursor.checked_sub(1).and_then(|idx| content.chars().nth(idx));
+    let after = content.chars().nth(cursor);
+    if after == Some(typed) && PAIRS.iter().any(|(_, close)| *close == typed) {
+        return Some((Vec::new(), cursor + 1));
+    }
+    let close = closing_for(typed)?;
+    if !pairs_here(typed, before, after, language) {
+        return None;
+    }
+    let text = format!("{typed}{close}");
+    Some((vec![BufferActions::InsertText { char_idx: cursor, text }], cursor + 1))
+}
+
+// Backspace between an empty pair removes both halves.
+pub fn on_backspace(content: &str, cursor: usize) -> Option<(Vec<BufferActions>, usize)> {
+    let start = cursor.checked_sub(1)?;
+    let mut chars = content.chars().skip(start);
+    let (open, close) = (chars.next()?, chars.next()?);
+    if closing_for(open) != Some(close) {
+        return None;
+    }
+    Some((vec![BufferActions::DeleteRange { start, end: cursor + 1 }], start))
+}
+
+// Takes the typed pair characters and backspaces it handles out of this
+// frame's input, so the editor only sees the rest. Stops at the first event
+// it leaves alone to keep the order of typing. Returns the actions, applied
+// as one undo step, and the new cursor.
+pub fn handle_keys(ctx: &egui::Context, id: egui::Id, buffer: &mut Buffer) -> Option<(Vec<BufferActions>, usize)> {
+    let mut state = egui::TextEdit::load_state(ctx, id)?;
+    let range = state.cursor.char_range()?;
+    if range.primary.index != range.secondary.index {
+        return None;
+    }
+    let mut cursor = range.primary.index;
+    let mut actions = Vec::new();
+    let mut inverse = Vec::new();
+    let mut handled = false;
+    ctx.input_mut(|input| {
+        let mut done = false;
+        input.events.retain(|event| {
+            if done {
+                return true;
+            }
+            let edit = match event {
+                egui::Event::Text(text) => {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(typed), None) => on_type(buffer.content(), cursor, typed, buffer.language),
+                        _ => None,
+                    }
+                }
+                egui::Event::Key { key: egui::Key::Backspace, pressed: true, modifiers, .. } if modifiers.is_none() => {
+                    on_backspace(buffer.content(), cursor)
+                }
+                egui::Event::Key { pressed: false, .. } => return true,
+                egui::Event::Key { .. } => None,
+                _ => return true,
+            };
+            let Some((edit, moved)) = edit else {
+                done = true;
+                return true;
+            };
+            inverse.extend(edit.iter().map(|action| buffer.apply(action)));
+            actions.extend(edit);
+            cursor = moved;
+            handled = true;
+            false
+        });
+    });
+    if !handled {
+        return None;
+    }
+    inverse.reverse();
+    buffer.history.record_typed(&actions, inverse, Instant::now(), cursor);
+    state.cursor.set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+    state.store(ctx, id);
+    (!actions.is_empty()).then_some((actions, cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(char_idx: usize, text: &str) -> Vec<BufferActions> {
+        vec![BufferActions::InsertText { char_idx, text: text.to_string() }]
+    }
+
+    #[test]
+    fn openers_pair_only_where_the_closer_fits() {
+        assert_eq!(on_type("f", 1, '(', "rust"), Some((insert(1, "()"), 2)));
+        assert_eq!(on_type("a  b", 2, '[', "rust"), Some((insert(2, "[]"), 3)));
+        assert_eq!(on_type("word", 0, '{', "rust"), None);
+        assert_eq!(on_type("say ", 4, '"', "python"), Some((insert(4, "\"\""), 5)));
+        assert_eq!(on_type("don", 3, '\'', "python"), None);
+        assert_eq!(on_type("<", 1, '\'', "rust"), None);
+        assert_eq!(on_type("x", 1, 'y', "rust"), None);
+    }
+
+    #[test]
+    fn closers_step_over_and_backspace_empties_the_pair() {
+        assert_eq!(on_type("f()", 2, ')', "rust"), Some((Vec::new(), 3)));
+        assert_eq!(on_type("\"\"", 1, '"', "rust"), Some((Vec::new(), 2)));
+        assert_eq!(on_backspace("f()", 2), Some((vec![BufferActions::DeleteRange { start: 1, end: 3 }], 1)));
+        assert_eq!(on_backspace("f(x)", 2), None);
+        assert_eq!(on_backspace("()", 0), None);
+    }
+}
@@ -0,0 +1,59 @@
+use eframe::egui;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadChoice {
+    Discard,
+    KeepMine,
+    Compare,
+}
+
+// Asks before a reload would throw away unsaved changes, or before picking
+// up an outside change when the buffer's policy says to ask.
+#[derive(Default)]
+pub struct ReloadPrompt {
+    pub buffer_index: Option<usize>,
+    pub modified: bool,
+    // The file changed on disk as well, so both sides have edits.
+    pub disk_changed: bool,
+}
+
+impl ReloadPrompt {
+    pub fn open(&mut self, buffer_index: usize, modified: bool, disk_changed: bool) {
+        self.buffer_index = Some(buffer_index);
+        self.modified = modified;
+        self.disk_changed = disk_changed;
+    }
+
+    // Returns the buffer and what to do with it once the user picks.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<(usize, ReloadChoice)> {
+        let buffer_index = self.buffer_index?;
+        let mut choice = None;
+        egui::Window::new("Reload from disk")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(match (self.modified, self.disk_changed) {
+                    (true, true) => "The buffer has unsaved changes and the file changed on disk too.",
+                    (true, false) => "Reloading discards the buffer's unsaved changes.",
+                    (false, _) => "The file changed on disk.",
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Reload").clicked() {
+                        choice = Some(ReloadChoice::Discard);
+                    }
+                    if ui.button("Keep mine").clicked() {
+                        choice = Some(ReloadChoice::KeepMine);
+                    }
+                    if self.disk_changed && ui.button("Compare").clicked() {
+                        choice = Some(ReloadChoice::Compare);
+                    }
+                });
+            });
+        let choice = choice?;
+        // Comparing leaves the prompt up to decide after looking.
+        if choice != ReloadChoice::Compare {
+            self.buffer_index = None;
+        }
+        Some((buffer_index, choice))
+    }
+}
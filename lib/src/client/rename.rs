@@ -0,0 +1,60 @@
+use crate::server::lsp;
+
+use eframe::egui;
+
+pub struct RenameTarget {
+    pub buffer_index: usize,
+    pub char_idx: usize,
+}
+
+#[derive(Default)]
+pub struct RenamePrompt {
+    pub target: Option<RenameTarget>,
+    pub new_name: String,
+    pub error: Option<String>,
+}
+
+impl RenamePrompt {
+    pub fn open(&mut self, buffer_index: usize, char_idx: usize) {
+        self.target = Some(RenameTarget {
+            buffer_index,
+            char_idx,
+        });
+        self.new_name.clear();
+        self.error = None;
+    }
+
+    // Returns the target and a validated new name once the user confirms.
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<(RenameTarget, String)> {
+        self.target.as_ref()?;
+
+        let mut open = true;
+        let mut submitted = false;
+        egui::Window::new("Rename symbol")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.new_name);
+                response.request_focus();
+                submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                submitted |= ui.button("Rename").clicked();
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+            });
+
+        if !open {
+            self.target = None;
+            return None;
+        }
+        if !submitted {
+            return None;
+        }
+        let new_name = self.new_name.trim().to_string();
+        if !lsp::is_valid_identifier(&new_name) {
+            self.error = Some(format!("`{new_name}` is not a valid identifier"));
+            return None;
+        }
+        self.target.take().map(|target| (target, new_name))
+    }
+}
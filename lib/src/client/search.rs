@@ -0,0 +1,267 @@
+use crate::interfaces::enums::BufferActions;
+use crate::server::hash;
+use crate::server::search::{self, Match, SearchMode};
+use crate::shared::Shared;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use eframe::egui;
+
+type Ranges = Arc<Vec<Range<usize>>>;
+
+// Where the query matches in each open buffer, for highlighting in the
+// editors. Entries are kept until the query or that buffer's content
+// changes, so scrolling only re-marks the ranges it already has.
+#[derive(Default)]
+pub struct SearchState {
+    query: String,
+    mode: SearchMode,
+    // Per buffer index: the content hash the ranges were found in.
+    matches: HashMap<usize, (u64, Ranges)>,
+}
+
+impl SearchState {
+    pub fn set_query(&mut self, query: &str, mode: SearchMode) {
+        if self.query != query || self.mode != mode {
+            self.query = query.to_string();
+            self.mode = mode;
+            self.matches.clear();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    // Byte ranges of the query in `content`, the text of `buffer_index`.
+    // A query that doesn't compile matches nothing.
+    pub fn matches(&mut self, buffer_index: usize, content: &str) -> Ranges {
+        if self.query.is_empty() {
+            return Ranges::default();
+        }
+        let content_hash = hash::content_hash(content);
+        match self.matches.get(&buffer_index) {
+            Some((hash, ranges)) if *hash == content_hash => ranges.clone(),
+            _ => {
+                let ranges = Arc::new(search::match_ranges(&self.query, self.mode, content).unwrap_or_default());
+                self.matches.insert(buffer_index, (content_hash, ranges.clone()));
+                ranges
+            }
+        }
+    }
+}
+
+// Byte-ranged replacements as buffer actions, last first so each one's
+// char indices still hold when it's applied.
+fn replace_actions(content: &str, replacements: Vec<(Range<usize>, String)>) -> Vec<BufferActions> {
+    let mut chars = 0;
+    let mut at = 0;
+    let mut char_index = |byte_idx: usize| {
+        chars += content[at..byte_idx].chars().count();
+        at = byte_idx;
+        chars
+    };
+    let mut actions: Vec<BufferActions> = replacements
+        .into_iter()
+        .map(|(range, text)| {
+            let start = char_index(range.start);
+            let end = char_index(range.end);
+            BufferActions::ReplaceRange { start, end, text }
+        })
+        .collect();
+    actions.reverse();
+    actions
+}
+
+#[derive(Default)]
+pub struct SearchPanel {
+    pub query: String,
+    pub replacement: String,
+    pub mode: SearchMode,
+    pub results: Vec<Match>,
+    pub error: Option<String>,
+    // Move keyboard focus to the query field on the next frame.
+    pub focus_query: bool,
+    pub highlights: SearchState,
+}
+
+impl SearchPanel {
+    pub fn run(&mut self, shared: &Shared) {
+        let results = shared
+            .snapshot()
+            .map_err(|err| err.to_string())
+            .and_then(|snapshot| search::grep(&self.query, self.mode, &snapshot.buffers).map_err(|err| err.to_string()));
+        match results {
+            Ok(results) => {
+                self.results = results;
+                self.error = None;
+            }
+            Err(err) => {
+                self.results.clear();
+                self.error = Some(err);
+            }
+        }
+    }
+
+    // Replaces what `run` would find, as one undo step per buffer. Scratch,
+    // terminal and stream buffers are left alone.
+    pub fn replace(&mut self, shared: &Shared) {
+        let replaced = shared.with_buffers_mut(|buffers| {
+            for buffer in &mut buffers.buffers {
+                if buffer.is_scratch || buffer.is_terminal || buffer.is_stream {
+                    continue;
+                }
+                let actions = replace_actions(
+                    buffer.content(),
+                    search::replacements(&self.query, self.mode, &self.replacement, buffer.content())?,
+                );
+                if !actions.is_empty() {
+                    buffer.edit(&actions);
+                }
+            }
+            Ok::<_, regex::Error>(())
+        });
+        match replaced {
+            Ok(Ok(())) => self.run(shared),
+            Ok(Err(err)) => self.error = Some(err.to_string()),
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, shared: &Shared) {
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.query);
+            if std::mem::take(&mut self.focus_query) {
+                response.request_focus();
+            }
+            ui.selectable_value(&mut self.mode, SearchMode::Plain, "Plain");
+            ui.selectable_value(&mut self.mode, SearchMode::Regex, "Regex");
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.run(shared);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.replacement);
+            if ui.button("Replace all").clicked() {
+                self.replace(shared);
+            }
+        });
+
+        if let Some(error) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        let mut jump = None;
+        egui::ScrollArea::vertical()
+            .id_salt("search_results")
+            .show(ui, |ui| {
+                for found in &self.results {
+                    let label = format!(
+                        "buffer {}:{}:{}",
+                        found.buffer_id,
+                        found.line + 1,
+                        found.range.start + 1
+                    );
+                    if ui.selectable_label(false, label).clicked() {
+                        jump = Some((found.buffer_id, found.line));
+                    }
+                }
+            });
+
+        if let Some((buffer_id, line)) = jump
+            && let Err(err) = shared.with_frames_mut(|frames| frames.jump_to_line(buffer_id, line))
+        {
+            self.error = Some(err.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::buffers::{Buffer, BufferStorage};
+    use crate::shared::frames::FrameStorage;
+
+    fn panel(query: &str, mode: SearchMode, replacement: &str) -> SearchPanel {
+        SearchPanel {
+            query: query.to_string(),
+            mode,
+            replacement: replacement.to_string(),
+            ..SearchPanel::default()
+        }
+    }
+
+    fn shared(buffers: Vec<Buffer>) -> Shared {
+        Shared::new(BufferStorage { buffers }, FrameStorage::default())
+    }
+
+    fn text(content: &str) -> Buffer {
+        Buffer::with_content(content)
+    }
+
+    fn special(flag: impl FnOnce(&mut Buffer)) -> Buffer {
+        let mut buffer = text("a.b");
+        flag(&mut buffer);
+        buffer
+    }
+
+    fn contents(shared: &Shared) -> Vec<String> {
+        shared
+            .with_buffers(|buffers| buffers.buffers.iter().map(|buffer| buffer.content().to_string()).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn replace_expands_capture_groups_and_undoes_in_one_step() {
+        let shared = shared(vec![text("let é = foo(1);\nfoo(22) + foo(3)\n")]);
+        let mut panel = panel(r"foo\((\d+)\)", SearchMode::Regex, "bar[$1]");
+        panel.replace(&shared);
+        assert_eq!(contents(&shared), ["let é = bar[1];\nbar[22] + bar[3]\n"]);
+        assert!(panel.results.is_empty());
+        shared.with_buffers_mut(|buffers| assert!(buffers.buffers[0].undo())).unwrap();
+        assert_eq!(contents(&shared), ["let é = foo(1);\nfoo(22) + foo(3)\n"]);
+    }
+
+    #[test]
+    fn replace_only_touches_what_search_highlights() {
+        let shared = shared(vec![text("a\nb\n")]);
+        panel(r"a\nb", SearchMode::Regex, "x").replace(&shared);
+        panel("$", SearchMode::Regex, ";").replace(&shared);
+        assert_eq!(contents(&shared), ["a\nb\n"]);
+        panel("^", SearchMode::Regex, "> ").replace(&shared);
+        assert_eq!(contents(&shared), ["a\nb\n"]);
+    }
+
+    #[test]
+    fn replace_is_literal_in_plain_mode_and_skips_special_buffers() {
+        let shared = shared(vec![
+            text("a.b a.b"),
+            special(|buffer| buffer.is_scratch = true),
+            special(|buffer| buffer.is_terminal = true),
+            special(|buffer| buffer.is_stream = true),
+        ]);
+        panel("a.b", SearchMode::Plain, "$1").replace(&shared);
+        assert_eq!(contents(&shared), ["$1 $1", "a.b", "a.b", "a.b"]);
+    }
+
+    #[test]
+    fn highlight_ranges_are_cached_until_the_query_or_content_changes() {
+        let mut state = SearchState::default();
+        assert!(!state.is_active());
+        assert!(state.matches(0, "a a").is_empty());
+        state.set_query("a", SearchMode::Plain);
+        let first = state.matches(0, "a a");
+        assert_eq!(*first, vec![0..1, 2..3]);
+        assert!(Arc::ptr_eq(&first, &state.matches(0, "a a")));
+        assert_eq!(*state.matches(0, "ba"), vec![1..2]);
+        assert_eq!(*state.matches(1, "a"), vec![0..1]);
+        state.set_query("a", SearchMode::Plain);
+        assert!(Arc::ptr_eq(&state.matches(1, "a"), &state.matches(1, "a")));
+        state.set_query("(", SearchMode::Regex);
+        assert!(state.matches(0, "a (").is_empty());
+        state.set_query("(", SearchMode::Plain);
+        assert_eq!(*state.matches(0, "a ("), vec![2..3]);
+    }
+}
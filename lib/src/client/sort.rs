@@ -0,0 +1,144 @@
+use crate::interfaces::enums::BufferActions;
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SortOptions {
+    pub descending: bool,
+    pub ignore_case: bool,
+    // Runs of digits compare by value, so `item2` comes before `item10`.
+    pub numeric: bool,
+}
+
+impl SortOptions {
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        let (a, b) = if self.ignore_case { (a.to_lowercase(), b.to_lowercase()) } else { (a.to_string(), b.to_string()) };
+        let ordering = if self.numeric { natural_cmp(&a, &b) } else { a.cmp(&b) };
+        if self.descending { ordering.reverse() } else { ordering }
+    }
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x: String = std::iter::from_fn(|| a.next_if(char::is_ascii_digit)).collect();
+                let y: String = std::iter::from_fn(|| b.next_if(char::is_ascii_digit)).collect();
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+                a.next();
+                b.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+// What's done to the lines of a selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEdit {
+    Sort(SortOptions),
+    // Drops lines seen before; with `adjacent`, only repeats of the line
+    // right above.
+    Unique { adjacent: bool },
+    Reverse,
+}
+
+impl LineEdit {
+    fn apply(self, lines: &mut Vec<&str>) {
+        match self {
+            Self::Sort(options) => lines.sort_by(|a, b| options.compare(a, b)),
+            Self::Unique { adjacent: true } => lines.dedup(),
+            Self::Unique { adjacent: false } => {
+                let mut seen = HashSet::new();
+                lines.retain(|line| seen.insert(*line));
+            }
+            Self::Reverse => lines.reverse(),
+        }
+    }
+}
+
+// Rewrites the whole lines `selection` (chars) touches. A selection ending
+// at the start of a line leaves that line out, and the newline after the
+// last line stays where it is, so a file's trailing newline survives.
+// Returns the replacement and the rewritten lines' span, or `None` when
+// nothing changes.
+pub fn edit_lines(content: &str, selection: Range<usize>, edit: LineEdit) -> Option<(BufferActions, Range<usize>)> {
+    let chars: Vec<char> = content.chars().collect();
+    let selection = selection.start.min(chars.len())..selection.end.min(chars.len());
+    let mut last = selection.end;
+    if last > selection.start && chars[last - 1] == '\n' {
+        last -= 1;
+    }
+    let start = chars[..selection.start].iter().rposition(|c| *c == '\n').map_or(0, |idx| idx + 1);
+    let end = chars[last..].iter().position(|c| *c == '\n').map_or(chars.len(), |idx| last + idx);
+    let text: String = chars[start..end].iter().collect();
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    if lines.len() < 2 {
+        return None;
+    }
+    edit.apply(&mut lines);
+    let edited = lines.join("\n");
+    if edited == text {
+        return None;
+    }
+    let len = edited.chars().count();
+    Some((BufferActions::ReplaceRange { start, end, text: edited }, start..start + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The selected lines after the edit, or `None` if nothing changed.
+    fn edited(content: &str, selection: Range<usize>, edit: LineEdit) -> Option<(String, Range<usize>)> {
+        match edit_lines(content, selection, edit)? {
+            (BufferActions::ReplaceRange { text, .. }, span) => Some((text, span)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn sorting_takes_whole_lines_and_keeps_the_trailing_newline() {
+        let sort = LineEdit::Sort(SortOptions::default());
+        let content = "head\npear\nApple\nfig\n";
+        // From inside `pear` to the start of the last line, which is left out.
+        assert_eq!(edited(content, 7..16, sort), Some((String::from("Apple\npear"), 5..15)));
+        assert_eq!(edited(content, 5..21, sort), Some((String::from("Apple\nfig\npear"), 5..19)));
+        assert_eq!(edited(content, 0..3, sort), None);
+        assert_eq!(edited("a\nb\n", 0..4, sort), None);
+    }
+
+    #[test]
+    fn sort_options_compare_case_and_numbers() {
+        let options = |descending, ignore_case, numeric| SortOptions { descending, ignore_case, numeric };
+        assert_eq!(options(false, false, false).compare("item10", "item2"), Ordering::Less);
+        assert_eq!(options(false, false, true).compare("item10", "item2"), Ordering::Greater);
+        assert_eq!(options(false, false, true).compare("v007", "v7"), Ordering::Equal);
+        assert_eq!(options(false, false, false).compare("a", "B"), Ordering::Greater);
+        assert_eq!(options(false, true, false).compare("a", "B"), Ordering::Less);
+        assert_eq!(options(true, true, false).compare("a", "B"), Ordering::Greater);
+    }
+
+    #[test]
+    fn unique_and_reverse_rewrite_the_selected_lines() {
+        let content = "b\na\nb\nb\n";
+        let all = 0..content.len();
+        assert_eq!(edited(content, all.clone(), LineEdit::Unique { adjacent: false }), Some((String::from("b\na"), 0..3)));
+        assert_eq!(edited(content, all.clone(), LineEdit::Unique { adjacent: true }), Some((String::from("b\na\nb"), 0..5)));
+        assert_eq!(edited(content, all, LineEdit::Reverse), Some((String::from("b\nb\na\nb"), 0..7)));
+        assert_eq!(edited("a\nb\na", 0..5, LineEdit::Unique { adjacent: true }), None);
+    }
+}
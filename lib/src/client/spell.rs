@@ -0,0 +1,118 @@
+use crate::interfaces::enums::BufferActions;
+use crate::server::hash;
+use crate::server::parse::Spans;
+use crate::server::spell::{self, Dictionary};
+use crate::shared::buffers::{byte_index, char_index_at_byte, Buffer};
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use eframe::egui;
+
+type Ranges = Arc<Vec<Range<usize>>>;
+
+const SUGGESTIONS: usize = 8;
+
+// Misspelled words in the buffers that have spell checking on, for the
+// editors to underline. Entries are kept until that buffer's text or its
+// syntax spans change.
+#[derive(Default)]
+pub struct SpellState {
+    pub dictionary: Option<Arc<Dictionary>>,
+    // Per buffer index: the content hash and spans they were found with.
+    checked: HashMap<usize, (u64, usize, Ranges)>,
+}
+
+impl SpellState {
+    // Byte ranges of unknown words in `content`. With `spans` (a file with a
+    // grammar) only comments and strings are checked; without, all of it
+    // but code in markdown.
+    pub fn misspelled(&mut self, buffer_index: usize, content: &str, spans: Option<&Spans>) -> Ranges {
+        let Some(dictionary) = &self.dictionary else {
+            return Ranges::default();
+        };
+        let content_hash = hash::content_hash(content);
+        let spans_id = spans.map_or(0, |spans| Arc::as_ptr(spans) as usize);
+        match self.checked.get(&buffer_index) {
+            Some((hash, id, ranges)) if *hash == content_hash && *id == spans_id => ranges.clone(),
+            _ => {
+                let regions = spans.map_or_else(|| spell::prose_regions(content), |spans| spell::code_regions(spans));
+                let ranges = Arc::new(spell::misspelled(dictionary, content, &regions));
+                self.checked.insert(buffer_index, (content_hash, spans_id, ranges.clone()));
+                ranges
+            }
+        }
+    }
+}
+
+// Right-clicking a misspelled word offers known words one edit away to
+// replace it with. `misspelled` is only asked on the click. Returns the
+// replacement, applied as one undo step, and the cursor after it.
+pub fn context_menu(
+    ui: &egui::Ui,
+    output: &egui::text_edit::TextEditOutput,
+    buffer: &mut Buffer,
+    dictionary: &Dictionary,
+    misspelled: impl FnOnce(&str) -> Ranges,
+) -> Option<(Vec<BufferActions>, usize)> {
+    let target_id = output.response.id.with("spell_target");
+    if output.response.secondary_clicked()
+        && let Some(pointer) = output.response.interact_pointer_pos()
+    {
+        let char_idx = output.galley.cursor_from_pos(pointer - output.galley_pos).index;
+        let byte_idx = byte_index(buffer.content(), char_idx);
+        let misspelled = misspelled(buffer.content());
+        let target = misspelled.iter().find(|range| range.start <= byte_idx && byte_idx < range.end);
+        ui.data_mut(|data| match target {
+            Some(range) => data.insert_temp(target_id, (range.clone(), buffer.content()[range.clone()].to_string())),
+            None => data.remove::<(Range<usize>, String)>(target_id),
+        });
+    }
+    let (range, word) = ui
+        .data(|data| data.get_temp::<(Range<usize>, String)>(target_id))
+        .filter(|(range, word)| buffer.content().get(range.clone()) == Some(word.as_str()))?;
+    let mut replaced = None;
+    output.response.context_menu(|ui| {
+        let suggestions = dictionary.suggestions(&word, SUGGESTIONS);
+        if suggestions.is_empty() {
+            ui.weak("No suggestions");
+        }
+        for suggestion in suggestions {
+            if ui.button(&suggestion).clicked() {
+                let start = char_index_at_byte(buffer.content(), range.start);
+                let end = char_index_at_byte(buffer.content(), range.end);
+                let cursor = start + suggestion.chars().count();
+                let action = BufferActions::ReplaceRange { start, end, text: suggestion };
+                buffer.edit(std::slice::from_ref(&action));
+                replaced = Some((vec![action], cursor));
+                ui.close();
+            }
+        }
+    });
+    if replaced.is_some() {
+        ui.data_mut(|data| data.remove::<(Range<usize>, String)>(target_id));
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::syntax::TokenKind;
+
+    #[test]
+    fn misspellings_are_cached_per_content_and_spans() {
+        let mut state = SpellState::default();
+        assert!(state.misspelled(0, "teh", None).is_empty());
+        state.dictionary = Some(Arc::new(Dictionary::parse("the\n")));
+        let prose = state.misspelled(0, "teh the", None);
+        assert_eq!(*prose, vec![0..3]);
+        assert!(Arc::ptr_eq(&prose, &state.misspelled(0, "teh the", None)));
+
+        let content = "let teh = \"teh\";";
+        let spans: Spans = Arc::new(vec![(0..3, TokenKind::Keyword), (10..15, TokenKind::String)]);
+        assert_eq!(*state.misspelled(0, content, Some(&spans)), vec![11..14]);
+        assert_eq!(*state.misspelled(0, content, None), vec![0..3, 4..7, 11..14]);
+    }
+}
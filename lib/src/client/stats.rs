@@ -0,0 +1,53 @@
+use crate::shared::buffers::BufferStats;
+use crate::shared::Shared;
+
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+// Counting walks the whole buffer, so while typing it's redone at most
+// this often. Nothing asks for a redraw to catch up; the cursor blinking
+// in the focused editor does.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+// Counts for the current buffer along the bottom of the main window.
+#[derive(Default)]
+pub struct StatsPanel {
+    pub open: bool,
+    // (buffer, when counted, counts)
+    counted: Option<(usize, Instant, BufferStats)>,
+}
+
+impl StatsPanel {
+    pub fn show(&mut self, ctx: &egui::Context, shared: &Shared, buffer_index: usize, now: Instant) {
+        if !self.open {
+            self.counted = None;
+            return;
+        }
+        let fresh = self.counted.is_some_and(|(counted_for, counted_at, _)| {
+            counted_for == buffer_index && now.duration_since(counted_at) < REFRESH_INTERVAL
+        });
+        if !fresh {
+            let stats = shared.with_buffers(|buffers| buffers.buffers.get(buffer_index).map(|buffer| buffer.stats()));
+            match stats {
+                Ok(Some(stats)) => self.counted = Some((buffer_index, now, stats)),
+                Ok(None) => self.counted = None,
+                Err(err) => log::error!("cant count buffer {buffer_index}: {err}"),
+            }
+        }
+        let Some((_, _, stats)) = self.counted else {
+            return;
+        };
+        egui::TopBottomPanel::bottom("stats_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} lines", stats.lines));
+                ui.separator();
+                ui.label(format!("{} words", stats.words));
+                ui.separator();
+                ui.label(format!("{} chars", stats.chars));
+                ui.separator();
+                ui.label(format!("{} bytes", stats.bytes));
+            });
+        });
+    }
+}
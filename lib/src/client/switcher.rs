@@ -0,0 +1,71 @@
+use eframe::egui;
+
+pub const NEXT_WINDOW_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Tab);
+pub const PREVIOUS_WINDOW_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT), egui::Key::Tab);
+
+// Side windows by when they last had focus, newest first. While Ctrl is
+// held the order is frozen, so pressing Tab again goes further back
+// instead of flipping between the same two windows.
+#[derive(Default)]
+pub struct WindowSwitcher {
+    recent: Vec<u32>,
+    // The frozen order and where in it the switcher is.
+    cycle: Option<(Vec<u32>, usize)>,
+}
+
+impl WindowSwitcher {
+    pub fn focused(&mut self, id: u32) {
+        self.recent.retain(|recent| *recent != id);
+        self.recent.insert(0, id);
+    }
+
+    // The window to switch to out of `open`. Windows that never had focus
+    // come after the ones that did, in the order given.
+    pub fn step(&mut self, open: &[u32], backwards: bool) -> Option<u32> {
+        let (order, at) = self.cycle.get_or_insert_with(|| {
+            let mut order: Vec<u32> = self.recent.iter().copied().filter(|id| open.contains(id)).collect();
+            order.extend(open.iter().filter(|id| !self.recent.contains(id)));
+            (order, 0)
+        });
+        order.retain(|id| open.contains(id));
+        if order.is_empty() {
+            self.cycle = None;
+            return None;
+        }
+        *at = if backwards { (*at + order.len() - 1) % order.len() } else { (*at + 1) % order.len() };
+        Some(order[*at])
+    }
+
+    // Ctrl was let go: the next press starts from the current order.
+    pub fn finish(&mut self) {
+        self.cycle = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_ctrl_walks_back_through_recent_windows() {
+        let mut switcher = WindowSwitcher::default();
+        for id in [1, 2, 3] {
+            switcher.focused(id);
+        }
+        let open = [1, 2, 3, 4];
+        assert_eq!(switcher.step(&open, false), Some(2));
+        assert_eq!(switcher.step(&open, false), Some(1));
+        assert_eq!(switcher.step(&open, false), Some(4));
+        assert_eq!(switcher.step(&open, true), Some(1));
+        switcher.finish();
+
+        switcher.focused(1);
+        assert_eq!(switcher.step(&open, false), Some(3));
+        switcher.finish();
+        assert_eq!(switcher.step(&[1], false), Some(1));
+        switcher.finish();
+        assert_eq!(switcher.step(&[], false), None);
+    }
+}
@@ -0,0 +1,107 @@
+use eframe::egui;
+use egui::epaint::text::TAB_SIZE;
+use egui::text::{LayoutJob, LayoutSection};
+
+// The byte offset of every tab in `text` and how many columns it takes to
+// reach the next multiple of `tab_width`. Every other char is one column.
+pub fn tab_spans(text: &str, tab_width: usize) -> Vec<(usize, usize)> {
+    let tab_width = tab_width.max(1);
+    let mut spans = Vec::new();
+    let mut column = 0;
+    for (byte_idx, c) in text.char_indices() {
+        match c {
+            '\n' => column = 0,
+            '\t' => {
+                let span = tab_width - column % tab_width;
+                spans.push((byte_idx, span));
+                column += span;
+            }
+            _ => column += 1,
+        }
+    }
+    spans
+}
+
+// epaint draws every tab `TAB_SIZE` spaces wide wherever it is. Each tab
+// gets a section of its own with the font scaled so it ends on the next
+// tab stop instead; `row_height` keeps the smaller or bigger font from
+// changing the row. Sections already made transparent (folded lines) are
+// left be.
+pub fn align(job: &mut LayoutJob, tab_width: usize, row_height: f32) {
+    let spans = tab_spans(&job.text, tab_width);
+    if spans.is_empty() {
+        return;
+    }
+    let mut spans = spans.into_iter().peekable();
+    let mut sections = Vec::with_capacity(job.sections.len() + spans.len() * 2);
+    for section in job.sections.drain(..) {
+        let section_end = section.byte_range.end;
+        let mut at = section.byte_range.start;
+        let mut leading_space = section.leading_space;
+        while let Some((tab, span)) = spans.next_if(|(tab, _)| *tab < section_end) {
+            if tab < at || section.format.color == egui::Color32::TRANSPARENT {
+                continue;
+            }
+            if tab > at {
+                sections.push(LayoutSection {
+                    leading_space,
+                    byte_range: at..tab,
+                    format: section.format.clone(),
+                });
+                leading_space = 0.0;
+            }
+            let mut format = section.format.clone();
+            format.font_id.size *= span as f32 / TAB_SIZE as f32;
+            format.line_height = Some(row_height);
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: tab..tab + 1,
+                format,
+            });
+            leading_space = 0.0;
+            at = tab + 1;
+        }
+        if at < section_end {
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range: at..section_end,
+                format: section.format,
+            });
+        }
+    }
+    job.sections = sections;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(text: &str, color: egui::Color32) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        job.append(text, 0.0, egui::TextFormat::simple(egui::FontId::monospace(10.0), color));
+        job
+    }
+
+    #[test]
+    fn tabs_run_to_the_next_stop() {
+        assert_eq!(tab_spans("\tab\tc\n  \t", 4), vec![(0, 4), (3, 2), (8, 2)]);
+        assert_eq!(tab_spans("abc\t", 0), vec![(3, 1)]);
+        assert!(tab_spans("no tabs", 4).is_empty());
+    }
+
+    #[test]
+    fn each_tab_gets_a_section_sized_to_its_span() {
+        let mut aligned = job("a\tb", egui::Color32::WHITE);
+        align(&mut aligned, 4, 14.0);
+        let ranges: Vec<_> = aligned.sections.iter().map(|section| section.byte_range.clone()).collect();
+        assert_eq!(ranges, vec![0..1, 1..2, 2..3]);
+        let tab = &aligned.sections[1].format;
+        assert_eq!(tab.font_id.size, 10.0 * 3.0 / TAB_SIZE as f32);
+        assert_eq!(tab.line_height, Some(14.0));
+        assert_eq!(aligned.sections[2].format.font_id.size, 10.0);
+
+        let mut folded = job("a\tb", egui::Color32::TRANSPARENT);
+        align(&mut folded, 4, 14.0);
+        assert_eq!(folded.sections.len(), 1);
+    }
+}
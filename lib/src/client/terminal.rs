@@ -0,0 +1,222 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::server::capture::Capture;
+use crate::server::stream::decode_available;
+use crate::shared::buffers::Buffer;
+use crate::shared::frames::FrameKind;
+use crate::shared::{Shared, SharedError};
+
+// Turns raw pty output into buffer text. The line parser only decodes and
+// drops carriage returns; an ANSI/VT parser can slot in behind this trait.
+pub trait OutputParser: Send {
+    fn feed(&mut self, bytes: &[u8], out: &mut String);
+    // Called once the output has ended, for whatever `feed` held back.
+    fn finish(&mut self, out: &mut String);
+}
+
+// A character split across two reads is held until its rest arrives.
+#[derive(Default)]
+pub struct LineParser {
+    pending: Vec<u8>,
+}
+
+impl OutputParser for LineParser {
+    fn feed(&mut self, bytes: &[u8], out: &mut String) {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        decode_available(&mut self.pending, &mut text);
+        out.extend(text.chars().filter(|c| *c != '\r'));
+    }
+
+    fn finish(&mut self, out: &mut String) {
+        out.extend(String::from_utf8_lossy(&self.pending).chars().filter(|c| *c != '\r'));
+        self.pending.clear();
+    }
+}
+
+pub struct Terminal {
+    pub buffer_index: usize,
+    pub closed: bool,
+    parser: Box<dyn OutputParser>,
+    // Set for terminals whose output is kept in full only on disk.
+    pub capture: Option<Capture>,
+    output: mpsc::Receiver<Vec<u8>>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    _master: Box<dyn MasterPty + Send>,
+}
+
+impl Terminal {
+    pub fn spawn(shared: &Shared, command: CommandBuilder) -> io::Result<Self> {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(io::Error::other)?;
+        let child = pair.slave.spawn_command(command).map_err(io::Error::other)?;
+        let mut reader = pair.master.try_clone_reader().map_err(io::Error::other)?;
+        let writer = pair.master.take_writer().map_err(io::Error::other)?;
+
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut chunk = [0u8; 4096];
+            loop {
+                match reader.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => {
+                        if sender.send(chunk[..read].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let buffer_index = shared.with_buffers_mut(|buffers| {
+            let mut buffer = Buffer::default();
+            buffer.is_terminal = true;
+            buffers.buffers.push(buffer);
+            buffers.buffers.len() - 1
+        })?;
+        Ok(Self {
+            buffer_index,
+            closed: false,
+            parser: Box::new(LineParser::default()),
+            capture: None,
+            output,
+            writer,
+            child,
+            _master: pair.master,
+        })
+    }
+
+    pub fn capture_to(mut self, capture: Capture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    pub fn spawn_shell(shared: &Shared) -> io::Result<Self> {
+        Self::spawn(shared, CommandBuilder::new_default_prog())
+    }
+
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\r")?;
+        self.writer.flush()
+    }
+
+    // Moves pending output into the pane's buffer and marks every frame
+    // showing it closed once the output has ended, which is after the
+    // process exited and everything it wrote has been read.
+    pub fn pump(&mut self, shared: &Shared) -> Result<(), SharedError> {
+        if self.closed {
+            return Ok(());
+        }
+        let exited = shared.with_buffers_mut(|buffers| {
+            let Some(buffer) = buffers.buffers.get_mut(self.buffer_index) else {
+                return false;
+            };
+            let mut output = String::new();
+            let disconnected = loop {
+                match self.output.try_recv() {
+                    Ok(bytes) => self.parser.feed(&bytes, &mut output),
+                    Err(mpsc::TryRecvError::Empty) => break false,
+                    Err(mpsc::TryRecvError::Disconnected) => break true,
+                }
+            };
+            if disconnected {
+                self.parser.finish(&mut output);
+                output.push_str("\n[process exited]\n");
+            }
+            match &mut self.capture {
+                Some(capture) if !output.is_empty() => {
+                    if let Err(err) = capture.append(buffer, &output) {
+                        log::error!("cant write capture {}: {err}", capture.path.display());
+                    }
+                }
+                _ => buffer.append(&output),
+            }
+            disconnected
+        })?;
+        if !exited {
+            return Ok(());
+        }
+
+        self.closed = true;
+        // Reaps the process; the reader only stops once it's gone.
+        if let Err(err) = self.child.try_wait() {
+            log::error!("cant wait for terminal process: {err}");
+        }
+        shared.with_frames_mut(|frames| {
+            frames
+                .frame_clusters
+                .iter_mut()
+                .flat_map(|cluster| cluster.frames.iter_mut())
+                .filter(|frame| frame.buffer_index == self.buffer_index)
+                .for_each(|frame| frame.kind = FrameKind::Terminal { closed: true });
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::{InMemoryStorage, Storage};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn line_parser_keeps_characters_split_across_reads() {
+        let mut parser = LineParser::default();
+        let mut out = String::new();
+        let bytes = "é€\r\n".as_bytes();
+        for byte in bytes {
+            parser.feed(std::slice::from_ref(byte), &mut out);
+        }
+        parser.finish(&mut out);
+        assert_eq!(out, "é€\n");
+    }
+
+    fn run(shared: &Shared, script: &str, capture: Option<Capture>) -> Terminal {
+        let mut command = CommandBuilder::new("sh");
+        command.args(["-c", script]);
+        let mut terminal = Terminal::spawn(shared, command).unwrap();
+        terminal.capture = capture;
+        let started = Instant::now();
+        while !terminal.closed && started.elapsed() < Duration::from_secs(10) {
+            terminal.pump(shared).unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(terminal.closed);
+        terminal
+    }
+
+    #[test]
+    fn output_written_right_before_exit_is_kept() {
+        let shared = Shared::default();
+        let terminal = run(&shared, "printf 'last words'", None);
+        let content = shared.with_buffers(|buffers| buffers.buffers[terminal.buffer_index].content().to_string()).unwrap();
+        assert!(content.contains("last words\n[process exited]"), "{content:?}");
+    }
+
+    #[test]
+    fn captured_output_goes_to_storage_and_the_buffer_keeps_the_tail() {
+        let shared = Shared::default();
+        let storage = Arc::new(InMemoryStorage::default());
+        let path = PathBuf::from("/captures/terminal.log");
+        let capture = Capture::new(path.clone(), 32, storage.clone());
+        let terminal = run(&shared, "seq 1 100", Some(capture));
+        let logged = String::from_utf8(storage.read(&path).unwrap()).unwrap();
+        assert!(logged.starts_with("1\n2\n3\n") && logged.contains("\n100\n"), "{logged:?}");
+        let content = shared.with_buffers(|buffers| buffers.buffers[terminal.buffer_index].content().to_string()).unwrap();
+        assert!(content.len() <= 32 && content.ends_with("[process exited]\n"), "{content:?}");
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+
+pub enum WelcomeAction {
+    NewFile,
+    OpenFile(PathBuf),
+}
+
+#[derive(Default)]
+pub struct WelcomeScreen {
+    pub open_path: String,
+    pub dismissed: bool,
+}
+
+impl WelcomeScreen {
+    pub fn show(&mut self, ui: &mut egui::Ui, recent_files: &[PathBuf]) -> Option<WelcomeAction> {
+        let mut action = None;
+
+        ui.heading("Riptide");
+        ui.add_space(8.0);
+
+        if ui.button("New file").clicked() {
+            action = Some(WelcomeAction::NewFile);
+        }
+        ui.horizontal(|ui| {
+            let response = ui.text_edit_singleline(&mut self.open_path);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if (ui.button("Open file").clicked() || submitted) && !self.open_path.is_empty() {
+                action = Some(WelcomeAction::OpenFile(PathBuf::from(&self.open_path)));
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.label("Recent files");
+        if recent_files.is_empty() {
+            ui.weak("No recent files");
+        }
+        for path in recent_files {
+            if ui.link(display_name(path)).on_hover_text(path.display().to_string()).clicked() {
+                action = Some(WelcomeAction::OpenFile(path.clone()));
+            }
+        }
+
+        action
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
@@ -1,8 +1,27 @@
+use super::annotations;
+use super::editor::{EditorView, EditorWidget};
+use super::guides;
+use super::movement;
+use crate::interfaces::enums::ClientEvents;
+use crate::shared::frames::{FrameCluster, FrameKind, WindowGeometry};
+use crate::shared::snapshot::{BufferMeta, FrameLayout, FrameView};
+use crate::shared::buffers::{Buffer, BufferStorage};
+use crate::shared::{Shared, SharedError};
+
+use eframe::egui;
+
+const SPLIT_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Backslash);
+// Plain Tab is for indenting and Ctrl+Tab switches windows.
+const NEXT_FRAME_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F6);
+
 #[derive(Clone)]
 pub struct Window {
     pub id: u32,
     pub title: &'static str,
     pub frame_cluster_index : usize,
+    // Size and position the viewport is opened with.
+    pub placement: Option<WindowGeometry>,
 }
 
 impl Window {
@@ -13,6 +32,352 @@ impl Window {
             id: 0,
             title,
             frame_cluster_index: 0,
+            placement: None,
+        }
+    }
+
+    // Named after the buffer in the window's first frame, so it follows
+    // whatever that buffer is saved as. `title` is only the fallback for a
+    // window without frames; the viewport is keyed by `id`, never by title.
+    pub fn title(&self, shared: &Shared) -> String {
+        let layout = shared.frame_layout();
+        let buffer_index = layout
+            .clusters
+            .get(self.frame_cluster_index)
+            .and_then(|cluster| cluster.frames.first())
+            .map(|frame| frame.buffer_index);
+        let title = buffer_index.and_then(|buffer_index| shared.buffer_meta().get(buffer_index).map(buffer_title));
+        title.unwrap_or_else(|| self.title.to_string())
+    }
+
+    // Whether any editor in the window shows a buffer with unsaved changes.
+    pub fn is_modified(&self, shared: &Shared) -> bool {
+        let buffer_indices: Vec<usize> = shared
+            .with_frames(|frames| {
+                frames
+                    .frame_clusters
+                    .get(self.frame_cluster_index)
+                    .map(|cluster| {
+                        cluster
+                            .frames
+                            .iter()
+                            .filter(|frame| frame.kind == FrameKind::Editor)
+                            .map(|frame| frame.buffer_index)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        shared
+            .with_buffers(|buffers| {
+                buffer_indices
+                    .iter()
+                    .filter_map(|&buffer_index| buffers.buffers.get(buffer_index))
+                    .any(Buffer::is_unsaved)
+            })
+            .unwrap_or(false)
+    }
+
+    // Never waits on a lock: while another thread holds the buffers the
+    // window shows a placeholder and asks to be drawn again.
+    pub fn show(&self, ui: &mut egui::Ui, shared: &Shared, view: &mut EditorView) -> Vec<ClientEvents> {
+        let mut events = Vec::new();
+        let shown = shared.try_with_buffers_mut(|buffers| self.show_frames(ui, shared, buffers, view, &mut events));
+        match shown {
+            Ok(Some(Ok(()))) => {}
+            Ok(None) => {
+                Self::show_empty_state(ui, "Busy");
+                ui.ctx().request_repaint();
+            }
+            Ok(Some(Err(err))) | Err(err) => log::error!("cant show window {}: {err}", self.id),
+        }
+        events
+    }
+
+    // Holds the buffers for the whole pass. The layout comes from the frame
+    // snapshot; per-frame state is only updated when the frames lock is free
+    // and otherwise waits for a later pass.
+    fn show_frames(
+        &self,
+        ui: &mut egui::Ui,
+        shared: &Shared,
+        buffers: &mut BufferStorage,
+        view: &mut EditorView,
+        events: &mut Vec<ClientEvents>,
+    ) -> Result<(), SharedError> {
+        let cluster_idx = self.frame_cluster_index;
+        let layout = shared.frame_layout();
+        let Some(cluster) = layout.clusters.get(cluster_idx).filter(|cluster| !cluster.frames.is_empty()) else {
+            let message = if cluster_idx < layout.clusters.len() { "No frames open" } else { "No frame cluster for this window" };
+            Self::show_empty_state(ui, message);
+            return Ok(());
+        };
+        let frame_count = cluster.frames.len();
+        let frame_height = ui.available_height() / frame_count.max(1) as f32;
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let focus_request = if frame_count > 1 && ui.input_mut(|i| i.consume_shortcut(&NEXT_FRAME_SHORTCUT)) {
+            shared.try_with_frames_mut(|frames| frames.frame_clusters.get_mut(cluster_idx).map(FrameCluster::activate_next))?.flatten()
+        } else {
+            None
+        };
+
+        for frame_idx in 0..frame_count {
+            let FrameView { kind, buffer_index, scroll_offset } = cluster.frames[frame_idx];
+            let (pending_cursor, pending_selection) = shared
+                .try_with_frames_mut(|frames| {
+                    let frame = frames.frame_clusters.get_mut(cluster_idx)?.frames.get_mut(frame_idx)?;
+                    Some((frame.pending_cursor.take(), frame.pending_selection.take()))
+                })?
+                .flatten()
+                .unwrap_or_default();
+            let Some(buffer) = buffers.buffers.get_mut(buffer_index) else {
+                continue;
+            };
+
+            let is_terminal = matches!(kind, FrameKind::Terminal { .. });
+            let input_height = if is_terminal { ui.spacing().interact_size.y } else { 0.0 };
+
+            let output = egui::ScrollArea::vertical()
+                .id_salt((self.id, cluster_idx, frame_idx))
+                .max_height(frame_height - input_height)
+                .vertical_scroll_offset(scroll_offset)
+                .show(ui, |ui| {
+                    let id = ui.make_persistent_id(("editor", self.id, cluster_idx, frame_idx));
+                    if let Some(char_idx) = pending_cursor {
+                        movement::set_cursor(ui.ctx(), id, char_idx);
+                    }
+                    if let Some(range) = pending_selection {
+                        movement::set_selection(ui.ctx(), id, range);
+                    }
+                    if focus_request == Some(frame_idx) {
+                        ui.memory_mut(|memory| memory.request_focus(id));
+                    }
+                    let output = EditorWidget::new(id, buffer, buffer_index).terminal(is_terminal).show(ui, view, events);
+                    if !is_terminal {
+                        guides::paint(ui, view.guides, &output.galley, output.galley_pos, output.response.rect);
+                        annotations::paint(ui, &buffer.annotations, &output.galley, output.galley_pos);
+                    }
+                    let focused = ui.memory(|memory| memory.has_focus(id));
+                    if focused {
+                        view.focused_frame = Some((cluster_idx, frame_idx));
+                        if !is_terminal && let Some(cursor_range) = output.cursor_range {
+                            view.cursor = Some((buffer_index, cursor_range.primary.index));
+                            view.selection = Some((buffer_index, cursor_range.as_sorted_char_range()));
+                        }
+                    }
+                    if focused && ui.input_mut(|i| i.consume_shortcut(&SPLIT_SHORTCUT)) {
+                        let cursor = egui::TextEdit::load_state(ui.ctx(), id)
+                            .and_then(|state| state.cursor.char_range())
+                            .map_or(0, |cursor_range| cursor_range.primary.index);
+                        events.push(ClientEvents::SplitFrame { cluster_idx, frame_idx, cursor });
+                    }
+                    focused
+                });
+
+            // Clicking into a frame makes it the active one.
+            if output.inner && cluster.active_frame != frame_idx && focus_request.is_none() {
+                shared.try_with_frames_mut(|frames| {
+                    if let Some(cluster) = frames.frame_clusters.get_mut(cluster_idx) {
+                        cluster.set_active(frame_idx);
+                    }
+                })?;
+            }
+            if frame_count > 1 && focus_request.unwrap_or(cluster.active_frame) == frame_idx {
+                let stroke = ui.visuals().selection.stroke;
+                ui.painter().rect_stroke(output.inner_rect, 0.0, stroke, egui::StrokeKind::Inside);
+            }
+
+            if let FrameKind::Terminal { closed } = kind
+                && let Some(line) = Self::show_terminal_input(ui, (self.id, cluster_idx, frame_idx), closed)
+            {
+                events.push(ClientEvents::TerminalInput(buffer_index, line));
+            }
+
+            let max_scroll_offset = (output.content_size.y - output.inner_rect.height()).max(0.0);
+            let delta = output.state.offset.y - scroll_offset;
+            shared.try_with_frames_mut(|frames| {
+                let Some(frame) = frames
+                    .frame_clusters
+                    .get_mut(cluster_idx)
+                    .and_then(|cluster| cluster.frames.get_mut(frame_idx))
+                else {
+                    return;
+                };
+                if frame.set_max_scroll_offset(max_scroll_offset) {
+                    ui.ctx().request_repaint();
+                }
+                if let Some(line) = frame.pending_jump.take() {
+                    frame.scroll_offset = (line as f32 * row_height).clamp(0.0, max_scroll_offset);
+                } else if delta != 0.0 {
+                    frames.scroll_by(cluster_idx, frame_idx, delta);
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn show_empty_state(ui: &mut egui::Ui, message: &str) {
+        ui.centered_and_justified(|ui| {
+            ui.weak(message);
+        });
+    }
+
+    fn show_terminal_input(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, closed: bool) -> Option<String> {
+        let id = ui.make_persistent_id(id_salt);
+        let mut line = ui.data_mut(|data| data.get_temp::<String>(id)).unwrap_or_default();
+
+        let response = ui.add_enabled(
+            !closed,
+            egui::TextEdit::singleline(&mut line)
+                .code_editor()
+                .desired_width(f32::INFINITY),
+        );
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if submitted {
+            response.request_focus();
+        }
+
+        let submitted = submitted.then(|| std::mem::take(&mut line));
+        ui.data_mut(|data| data.insert_temp(id, line));
+        submitted
+    }
+}
+
+pub fn buffer_title(buffer: &BufferMeta) -> String {
+    if buffer.is_scratch {
+        return "scratch".to_string();
+    }
+    buffer
+        .file_path
+        .file_name()
+        .map_or_else(|| "untitled".to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+// Brings `windows` in line with the frame clusters. Windows whose cluster
+// is still there are left exactly as they are, so nothing they hold is
+// lost; windows on clusters that are gone are dropped and visible clusters
+// without a window get one. Returns how many were added.
+pub fn reconcile(windows: &mut Vec<Window>, layout: &FrameLayout) -> usize {
+    windows.retain(|window| window.frame_cluster_index < layout.clusters.len());
+    let mut next_id = windows.iter().map(|window| window.id + 1).max().unwrap_or(0);
+    let mut added = 0;
+    for (cluster_idx, cluster) in layout.clusters.iter().enumerate() {
+        if !cluster.is_visible || windows.iter().any(|window| window.frame_cluster_index == cluster_idx) {
+            continue;
         }
+        windows.push(Window {
+            id: next_id,
+            frame_cluster_index: cluster_idx,
+            placement: cluster.geometry,
+            ..Window::default("Window")
+        });
+        next_id += 1;
+        added += 1;
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::frames::{Frame, FrameStorage};
+    use std::path::PathBuf;
+
+    fn shared(buffers: Vec<Buffer>, clusters: Vec<Vec<usize>>) -> Shared {
+        let frame_clusters = clusters
+            .into_iter()
+            .map(|buffer_indices| FrameCluster {
+                frames: buffer_indices.into_iter().map(|buffer_index| Frame { buffer_index, ..Frame::default() }).collect(),
+                ..FrameCluster::default()
+            })
+            .collect();
+        Shared::new(BufferStorage { buffers }, FrameStorage { frame_clusters })
+    }
+
+    #[test]
+    fn windows_are_titled_after_their_first_frames_buffer() {
+        let mut buffers = vec![Buffer::default(), Buffer::default(), Buffer::default()];
+        buffers[0].file_path = PathBuf::from("/project/src/main.rs");
+        buffers[1].is_scratch = true;
+        let shared = shared(buffers, vec![vec![0, 1], vec![1], vec![2], Vec::new()]);
+        let titles: Vec<String> = (0..4)
+            .map(|frame_cluster_index| Window { frame_cluster_index, ..Window::default("Riptide") }.title(&shared))
+            .collect();
+        assert_eq!(titles, ["main.rs", "scratch", "untitled", "Riptide"]);
+    }
+
+    fn show(window: &Window, shared: &Shared) -> Vec<ClientEvents> {
+        let mut search = super::super::search::SearchState::default();
+        let mut spell = super::super::spell::SpellState::default();
+        let mut view = EditorView {
+            hover: None,
+            guides: guides::Guides::default(),
+            syntax_theme: Default::default(),
+            auto_pair: false,
+            indent_width: 4,
+            modal: None,
+            active: None,
+            focused_frame: None,
+            cursor: None,
+            selection: None,
+            search: &mut search,
+            spell: &mut spell,
+        };
+        let mut events = Vec::new();
+        let _ = egui::Context::default().run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| events = window.show(ui, shared, &mut view));
+        });
+        events
+    }
+
+    #[test]
+    fn windows_without_frames_or_clusters_show_an_empty_state() {
+        let shared = shared(Vec::new(), vec![Vec::new()]);
+        assert!(show(&Window::default("Riptide"), &shared).is_empty());
+        let stray = Window { frame_cluster_index: 3, ..Window::default("Riptide") };
+        assert!(show(&stray, &shared).is_empty());
+
+        let empty = Shared::new(BufferStorage::default(), FrameStorage { frame_clusters: Vec::new() });
+        let mut windows = vec![Window::default("Riptide")];
+        assert_eq!(reconcile(&mut windows, &empty.frame_layout()), 0);
+        assert!(windows.is_empty());
+    }
+
+    #[test]
+    fn reconciling_keeps_live_windows_and_fills_in_missing_ones() {
+        let geometry = WindowGeometry { size: [640.0, 480.0], position: None };
+        let frame_clusters = vec![
+            FrameCluster { is_visible: true, ..FrameCluster::default() },
+            FrameCluster::default(),
+            FrameCluster { is_visible: true, geometry: Some(geometry), ..FrameCluster::default() },
+        ];
+        let shared = Shared::new(BufferStorage::default(), FrameStorage { frame_clusters });
+        let mut windows = vec![
+            Window { id: 4, frame_cluster_index: 0, ..Window::default("Kept") },
+            Window { id: 7, frame_cluster_index: 5, ..Window::default("Stale") },
+        ];
+        assert_eq!(reconcile(&mut windows, &shared.frame_layout()), 1);
+        let placed: Vec<_> = windows.iter().map(|window| (window.id, window.title, window.frame_cluster_index, window.placement)).collect();
+        assert_eq!(placed, [(4, "Kept", 0, None), (5, "Window", 2, Some(geometry))]);
+        assert_eq!(reconcile(&mut windows, &shared.frame_layout()), 0);
+    }
+
+    #[test]
+    fn drawing_never_waits_on_a_held_buffers_lock() {
+        let shared = shared(vec![Buffer::default()], vec![vec![0]]);
+        let (held, release) = (std::sync::Barrier::new(2), std::sync::Barrier::new(2));
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                shared
+                    .with_buffers_mut(|_| {
+                        held.wait();
+                        release.wait();
+                    })
+                    .unwrap();
+            });
+            held.wait();
+            assert!(show(&Window::default("Riptide"), &shared).is_empty());
+            release.wait();
+        });
     }
 }
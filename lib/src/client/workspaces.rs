@@ -0,0 +1,63 @@
+use crate::shared::snapshot::FrameLayout;
+
+use eframe::egui;
+
+pub enum WorkspaceAction {
+    Switch(usize),
+    New,
+    Close(usize),
+}
+
+// What a cluster is called on its tab.
+pub fn name(layout: &FrameLayout, cluster_idx: usize) -> String {
+    layout
+        .clusters
+        .get(cluster_idx)
+        .map(|cluster| cluster.name.trim())
+        .filter(|name| !name.is_empty())
+        .map_or_else(|| format!("Workspace {}", cluster_idx + 1), str::to_string)
+}
+
+// One tab per frame cluster along the top of the main window. Hidden
+// clusters keep their tab, dimmed, so they can be brought back.
+pub fn show(ctx: &egui::Context, layout: &FrameLayout, current: Option<usize>) -> Option<WorkspaceAction> {
+    let mut action = None;
+    egui::TopBottomPanel::top("workspace_bar").show(ctx, |ui| {
+        ui.horizontal_wrapped(|ui| {
+            for (cluster_idx, cluster) in layout.clusters.iter().enumerate() {
+                let mut label = egui::RichText::new(name(layout, cluster_idx));
+                if !cluster.is_visible {
+                    label = label.weak();
+                }
+                if ui.selectable_label(current == Some(cluster_idx), label).clicked() {
+                    action = Some(WorkspaceAction::Switch(cluster_idx));
+                }
+                if cluster.is_visible && ui.small_button("x").on_hover_text("Close workspace").clicked() {
+                    action = Some(WorkspaceAction::Close(cluster_idx));
+                }
+                ui.separator();
+            }
+            if ui.small_button("+").on_hover_text("New workspace").clicked() {
+                action = Some(WorkspaceAction::New);
+            }
+        });
+    });
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::Shared;
+    use crate::shared::buffers::BufferStorage;
+    use crate::shared::frames::{FrameCluster, FrameStorage};
+
+    #[test]
+    fn unnamed_workspaces_are_numbered() {
+        let cluster = |name: &str| FrameCluster { name: name.to_string(), ..FrameCluster::default() };
+        let frames = FrameStorage { frame_clusters: vec![cluster("notes"), cluster("  "), cluster(" docs ")] };
+        let layout = Shared::new(BufferStorage::default(), frames).frame_layout();
+        let names: Vec<String> = (0..4).map(|cluster_idx| name(&layout, cluster_idx)).collect();
+        assert_eq!(names, ["notes", "Workspace 2", "docs", "Workspace 4"]);
+    }
+}
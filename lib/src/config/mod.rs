@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::server::syntax::TokenKind;
+use crate::shared::buffers::{ExternalChange, FinalNewlinePolicy};
+
+const CONFIG_FILE_NAME: &str = "riptide.toml";
+
+// Syntax color palettes. The global one is `ThemeConfig::syntax`; a buffer
+// can pick another with `Buffer::theme_override`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeId {
+    #[default]
+    Default,
+    Soft,
+    Contrast,
+}
+
+impl ThemeId {
+    pub const ALL: [Self; 3] = [Self::Default, Self::Soft, Self::Contrast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::Soft => "Soft",
+            Self::Contrast => "High contrast",
+        }
+    }
+
+    pub fn color(self, token: TokenKind) -> egui::Color32 {
+        let [r, g, b] = match (self, token) {
+            (Self::Default, TokenKind::Keyword) => [198, 120, 221],
+            (Self::Default, TokenKind::String) => [152, 195, 121],
+            (Self::Default, TokenKind::Comment) => [110, 118, 129],
+            (Self::Default, TokenKind::Number) => [209, 154, 102],
+            (Self::Default, TokenKind::Type) => [229, 192, 123],
+            (Self::Default, TokenKind::Function) => [97, 175, 239],
+            (Self::Soft, TokenKind::Keyword) => [180, 160, 200],
+            (Self::Soft, TokenKind::String) => [160, 185, 150],
+            (Self::Soft, TokenKind::Comment) => [130, 130, 130],
+            (Self::Soft, TokenKind::Number) => [200, 175, 150],
+            (Self::Soft, TokenKind::Type) => [200, 190, 160],
+            (Self::Soft, TokenKind::Function) => [150, 175, 200],
+            (Self::Contrast, TokenKind::Keyword) => [255, 100, 255],
+            (Self::Contrast, TokenKind::String) => [100, 255, 100],
+            (Self::Contrast, TokenKind::Comment) => [160, 160, 160],
+            (Self::Contrast, TokenKind::Number) => [255, 170, 60],
+            (Self::Contrast, TokenKind::Type) => [255, 255, 80],
+            (Self::Contrast, TokenKind::Function) => [80, 200, 255],
+        };
+        egui::Color32::from_rgb(r, g, b)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub dark_mode: bool,
+    pub background: Option<[u8; 3]>,
+    pub accent: Option<[u8; 3]>,
+    pub syntax: ThemeId,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            dark_mode: true,
+            background: None,
+            accent: None,
+            syntax: ThemeId::Default,
+        }
+    }
+}
+
+impl ThemeConfig {
+    pub fn visuals(&self) -> egui::Visuals {
+        let mut visuals = if self.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        if let Some([r, g, b]) = self.background {
+            visuals.panel_fill = egui::Color32::from_rgb(r, g, b);
+            visuals.window_fill = visuals.panel_fill;
+        }
+        if let Some([r, g, b]) = self.accent {
+            visuals.selection.bg_fill = egui::Color32::from_rgb(r, g, b);
+        }
+        visuals
+    }
+}
+
+// Files over `threshold_bytes` open in big-file mode: read in chunks, with
+// no syntax highlighting, folding ranges or language server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BigFileConfig {
+    pub threshold_bytes: u64,
+}
+
+impl Default for BigFileConfig {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+// Native window chrome for the main and side windows. On by default;
+// tiling window managers usually want it off.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct WindowDecorations {
+    pub titlebar: bool,
+    pub close_button: bool,
+    pub resizable: bool,
+}
+
+impl Default for WindowDecorations {
+    fn default() -> Self {
+        Self {
+            titlebar: true,
+            close_button: true,
+            resizable: true,
+        }
+    }
+}
+
+impl WindowDecorations {
+    pub fn apply(self, builder: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        builder
+            .with_decorations(self.titlebar)
+            .with_close_button(self.close_button)
+            .with_resizable(self.resizable)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window_title: String,
+    pub theme: ThemeConfig,
+    pub keymap: HashMap<String, String>,
+    pub autosave_interval_secs: u64,
+    pub channel_capacity: usize,
+    pub recent_files_limit: usize,
+    // Files whose last cursor position is remembered for reopening.
+    pub file_positions_limit: usize,
+    pub language_servers: HashMap<String, String>,
+    pub tab_width: u32,
+    pub format_on_save: bool,
+    pub format_on_save_languages: HashMap<String, bool>,
+    pub memory_budget_bytes: usize,
+    pub big_file: BigFileConfig,
+    pub ruler_column: Option<usize>,
+    pub show_whitespace: bool,
+    // Shows git blame after each line.
+    pub inline_blame: bool,
+    pub recover_poisoned_locks: bool,
+    pub final_newline: FinalNewlinePolicy,
+    // What to do when an open file changes on disk.
+    pub external_change: ExternalChange,
+    pub modal_editing: bool,
+    pub auto_pair: bool,
+    // Files open in the focused frame instead of a new one.
+    pub open_in_current_frame: bool,
+    // Piped input and terminal output are written through to a file under
+    // `data_dir` and only their last `capture_window_bytes` stay in the
+    // buffer.
+    pub capture_streams: bool,
+    pub capture_window_bytes: usize,
+    pub session_path: PathBuf,
+    // How many autosaved session snapshots to keep, newest first.
+    pub session_snapshots: usize,
+    // New side windows open on the main window's monitor, cascaded from it.
+    pub windows_follow_main: bool,
+    // Shrinks the main window to its menus, workspace tabs and status bar,
+    // a launcher for the side windows the editors are in.
+    pub main_window_toolbar: bool,
+    pub window_decorations: WindowDecorations,
+    // Enables the frame and buffer inspector on Shift+F12.
+    pub debug: bool,
+    // Word list for spell checking, one word per line.
+    pub dictionary_path: PathBuf,
+    pub data_dir: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let data_dir = dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("riptide");
+        Self {
+            window_title: String::from("Riptide"),
+            theme: ThemeConfig::default(),
+            keymap: HashMap::new(),
+            autosave_interval_secs: 60,
+            channel_capacity: 64,
+            recent_files_limit: 10,
+            file_positions_limit: 500,
+            language_servers: HashMap::from([(String::from("rs"), String::from("rust-analyzer"))]),
+            tab_width: 4,
+            format_on_save: false,
+            format_on_save_languages: HashMap::new(),
+            memory_budget_bytes: 256 * 1024 * 1024,
+            big_file: BigFileConfig::default(),
+            ruler_column: None,
+            show_whitespace: false,
+            inline_blame: false,
+            recover_poisoned_locks: false,
+            final_newline: FinalNewlinePolicy::Ensure,
+            external_change: ExternalChange::PromptIfModified,
+            modal_editing: false,
+            auto_pair: true,
+            open_in_current_frame: false,
+            capture_streams: false,
+            capture_window_bytes: 1024 * 1024,
+            session_path: data_dir.join("session"),
+            session_snapshots: 5,
+            windows_follow_main: true,
+            main_window_toolbar: false,
+            window_decorations: WindowDecorations::default(),
+            debug: false,
+            dictionary_path: PathBuf::from("/usr/share/dict/words"),
+            data_dir,
+        }
+    }
+}
+
+impl Config {
+    // Per-language entries (keyed by LSP language id) override the global
+    // toggle.
+    pub fn format_on_save_for(&self, language_id: &str) -> bool {
+        self.format_on_save_languages
+            .get(language_id)
+            .copied()
+            .unwrap_or(self.format_on_save)
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("riptide").join(CONFIG_FILE_NAME))
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+
+    // A missing config file is the normal case; an unreadable or malformed
+    // one is reported and otherwise treated the same way.
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(source) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        Self::from_toml(&source).unwrap_or_else(|err| {
+            log::warn!("invalid config at {}: {err}", path.display());
+            Self::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_left_out_keep_their_defaults() {
+        let config = Config::from_toml(
+            r#"
+            tab_width = 2
+            format_on_save = true
+
+            [theme]
+            syntax = "soft"
+
+            [format_on_save_languages]
+            markdown = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.tab_width, 2);
+        assert_eq!(config.theme.syntax, ThemeId::Soft);
+        assert!(config.theme.dark_mode);
+        assert!(config.format_on_save_for("rust") && !config.format_on_save_for("markdown"));
+        assert_eq!(config.window_title, Config::default().window_title);
+        assert!(Config::from_toml("tab_width = \"wide\"").is_err());
+    }
+
+    #[test]
+    fn window_decorations_come_from_their_table() {
+        let config = Config::from_toml("[window_decorations]\ntitlebar = false\n").unwrap();
+        let builder = config.window_decorations.apply(egui::ViewportBuilder::default());
+        assert_eq!(builder.decorations, Some(false));
+        assert_eq!(builder.close_button, Some(true));
+        assert_eq!(builder.resizable, Some(true));
+    }
+}
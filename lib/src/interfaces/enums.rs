@@ -1,12 +1,28 @@
 
 
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::movement::Movement;
 use crate::shared::frames::Frame;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RiptideEvents {
     OpenWindow,
     CloseWindow,
     CloseFrame,
+    LoadProgress { path: PathBuf, pct: u8 },
+    FileSaved { path: PathBuf },
+    BufferEvents(usize, BufferEvent),
+}
+
+// Things that happened in one buffer, sent as `RiptideEvents::BufferEvents`
+// with the buffer's index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferEvent {
+    // Lines and columns count from 0; columns are in chars.
+    CursorMoved { line: usize, col: usize },
 }
 
 #[derive(Debug)]
@@ -18,5 +34,27 @@ pub enum ClientEvents {
     WindowCloseEvent(u32),
     WindowOpenEvent(u32),
     FrameCloseEvent(usize, usize),
-    FrameOpenEvent(Frame, usize)
+    FrameOpenEvent(Frame, usize),
+    TerminalInput(usize, String),
+    Hover(usize, usize),
+    GoToDefinition(usize, usize),
+    RenameSymbol(usize, usize),
+    Save(usize),
+    // Edits typed into a buffer and the cursor they left behind.
+    Edited { buffer_index: usize, actions: Vec<BufferActions>, cursor: usize },
+    Moved { buffer_index: usize, movement: Movement, cursor: usize },
+    ToggleMacroRecording(usize, usize),
+    ReplayMacro(usize, usize),
+    SplitFrame { cluster_idx: usize, frame_idx: usize, cursor: usize },
+    SetGlobalMark { buffer_index: usize, name: char, cursor: usize },
+    JumpToMark { buffer_index: usize, name: char, cursor: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BufferActions {
+    InsertText { char_idx: usize, text: String },
+    DeleteRange { start: usize, end: usize },
+    // `start..end` swapped for `text` in one step, never leaving the
+    // content with just the deletion done.
+    ReplaceRange { start: usize, end: usize, text: String },
 }
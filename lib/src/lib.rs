@@ -1,33 +1,47 @@
+pub mod cli;
 pub mod client;
+pub mod config;
+pub mod logging;
 pub mod server;
 pub mod shared;
+#[cfg(test)]
+mod test_support;
 pub mod interfaces {
     pub mod enums;
 }
 
 use eframe::egui;
 
+#[derive(Default)]
 pub struct Libs {
     pub client : client::Client,
+    pub config : config::Config,
 }
 
-impl Default for Libs {
-    fn default() -> Self {
+impl Libs {
+    pub fn new(config : config::Config) -> Self {
         Self {
-            client : client::Client::default(),
+            client : client::Client::new(&config),
+            config,
         }
     }
 }
 
 
 pub fn run_riptide(libs : Libs) -> eframe::Result {
+    let viewport = libs.config.window_decorations.apply(egui::ViewportBuilder::default().with_inner_size([320.0, 240.0]));
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([320.0, 240.0]),
+        viewport: client::geometry::apply(viewport, libs.client.main_geometry, None),
         ..Default::default()
     };
     eframe::run_native(
-        "Multiple viewports",
+        &libs.config.window_title,
         options,
-        Box::new(|_cc| Ok(Box::<client::Client>::default())),
+        Box::new(|cc| {
+            cc.egui_ctx.set_visuals(libs.config.theme.visuals());
+            let ctx = cc.egui_ctx.clone();
+            libs.client.server.waker.set(move || ctx.request_repaint());
+            Ok(Box::new(libs.client))
+        }),
     )
 }
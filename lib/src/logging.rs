@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Errors held for the status bar until it takes them.
+const KEPT_ERRORS: usize = 20;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+// Writes to stderr and keeps recent errors for the status bar. Levels come
+// from `RUST_LOG`: a bare level (`debug`), `target=level` entries, or both,
+// comma separated. A target covers its submodules; the longest match wins.
+pub struct Logger {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+    errors: Mutex<VecDeque<String>>,
+}
+
+impl Logger {
+    // Unparseable entries are skipped; without a bare level only warnings
+    // and errors show.
+    pub fn from_spec(spec: &str) -> Self {
+        let mut default = LevelFilter::Warn;
+        let mut targets = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            match entry.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        targets.push((target.trim().to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = entry.parse() {
+                        default = level;
+                    }
+                }
+            }
+        }
+        Self {
+            default,
+            targets,
+            errors: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix || target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.lock().map(|mut errors| errors.drain(..).collect()).unwrap_or_default()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        if record.level() == Level::Error
+            && let Ok(mut errors) = self.errors.lock()
+        {
+            if errors.len() == KEPT_ERRORS {
+                errors.pop_front();
+            }
+            errors.push_back(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Installs the logger once; later calls do nothing.
+pub fn init() {
+    let logger = LOGGER.get_or_init(|| Logger::from_spec(&env::var("RUST_LOG").unwrap_or_default()));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger.max_level());
+    }
+}
+
+// Errors logged since the last call, oldest first. Empty until `init`.
+pub fn take_errors() -> Vec<String> {
+    LOGGER.get().map(Logger::take_errors).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(logger: &Logger, level: Level, target: &str, message: &str) {
+        logger.log(&Record::builder().level(level).target(target).args(format_args!("{message}")).build());
+    }
+
+    #[test]
+    fn levels_come_from_the_longest_matching_target() {
+        let logger = Logger::from_spec("info, riptide_lib::server=debug ,riptide_lib::server::lsp=error,bogus=loud");
+        assert_eq!(logger.level_for("app"), LevelFilter::Info);
+        assert_eq!(logger.level_for("riptide_lib::server"), LevelFilter::Debug);
+        assert_eq!(logger.level_for("riptide_lib::server::bus"), LevelFilter::Debug);
+        assert_eq!(logger.level_for("riptide_lib::server::lsp::transport"), LevelFilter::Error);
+        // Only whole path segments match.
+        assert_eq!(logger.level_for("riptide_lib::serverless"), LevelFilter::Info);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+        assert_eq!(Logger::from_spec("").level_for("anything"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn enabled_errors_are_kept_for_the_status_bar() {
+        let logger = Logger::from_spec("quiet=off");
+        log(&logger, Level::Warn, "app", "just a warning");
+        log(&logger, Level::Error, "quiet", "filtered out");
+        for n in 0..KEPT_ERRORS + 2 {
+            log(&logger, Level::Error, "app", &format!("error {n}"));
+        }
+        let errors = logger.take_errors();
+        assert_eq!(errors.len(), KEPT_ERRORS);
+        assert_eq!(errors[0], "error 2");
+        assert!(logger.take_errors().is_empty());
+    }
+}
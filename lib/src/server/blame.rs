@@ -0,0 +1,100 @@
+use std::process::Command;
+
+use crate::shared::buffers::annotations::AnnotationProvider;
+use crate::shared::buffers::Buffer;
+
+// Lines that aren't committed yet are blamed on this hash.
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+// Author and summary of the commit that last touched each line, as of the
+// file on disk. Files outside a repository get nothing.
+#[derive(Default)]
+pub struct GitBlame;
+
+impl AnnotationProvider for GitBlame {
+    fn source(&self) -> &'static str {
+        "blame"
+    }
+
+    fn annotate(&mut self, buffer: &Buffer) -> Vec<(usize, String)> {
+        let (Some(dir), Some(name)) = (buffer.file_path.parent(), buffer.file_path.file_name()) else {
+            return Vec::new();
+        };
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["blame", "--line-porcelain", "--"])
+            .arg(name)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => parse_line_porcelain(&String::from_utf8_lossy(&output.stdout)),
+            Ok(_) => Vec::new(),
+            Err(err) => {
+                log::debug!("cant run git blame: {err}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+// Each line of `--line-porcelain` output is a header starting with the
+// commit hash and the final line number, the commit's details, and then
+// the line itself prefixed with a tab.
+pub fn parse_line_porcelain(output: &str) -> Vec<(usize, String)> {
+    let mut entries = Vec::new();
+    let mut line = None;
+    let mut author = "";
+    let mut summary = "";
+    for row in output.lines() {
+        if row.starts_with('\t') {
+            if let Some((line, hash)) = line.take()
+                && hash != UNCOMMITTED
+            {
+                entries.push((line, format!("{author}, {summary}")));
+            }
+        } else if let Some(value) = row.strip_prefix("author ") {
+            author = value;
+        } else if let Some(value) = row.strip_prefix("summary ") {
+            summary = value;
+        } else if line.is_none() {
+            let mut fields = row.split(' ');
+            if let (Some(hash), Some(_), Some(Ok(final_line))) =
+                (fields.next(), fields.next(), fields.next().map(str::parse::<usize>))
+            {
+                line = Some((final_line.saturating_sub(1), hash));
+            }
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn porcelain_blames_committed_lines_by_final_line() {
+        let output = "\
+1f2e3d4c5b6a79880000000000000000000000ab 1 1 1
+author Ada
+summary Add the parser
+filename main.rs
+\tfn main() {
+0000000000000000000000000000000000000000 2 2 1
+author Not Committed Yet
+summary Version of main.rs from main.rs
+filename main.rs
+\t    todo!();
+1f2e3d4c5b6a79880000000000000000000000ab 2 3
+author Ada
+summary Add the parser
+filename main.rs
+\t}
+";
+        assert_eq!(
+            parse_line_porcelain(output),
+            [(0, String::from("Ada, Add the parser")), (2, String::from("Ada, Add the parser"))]
+        );
+        assert!(parse_line_porcelain("").is_empty());
+    }
+}
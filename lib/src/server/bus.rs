@@ -0,0 +1,171 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use super::wake::Waker;
+use crate::interfaces::enums::RiptideEvents;
+
+type Filter = Box<dyn Fn(&RiptideEvents) -> bool + Send>;
+
+// Broadcasts each event to the subscribers whose filter it matches. Each
+// subscriber has its own bounded queue; when it falls `capacity` events
+// behind, newer events are dropped for it rather than blocking the sender.
+// A send that reached a subscriber wakes the UI so subscribers there see
+// the event on the next frame.
+pub struct Bus {
+    capacity: usize,
+    subscribers: Vec<(mpsc::SyncSender<RiptideEvents>, Filter)>,
+    waker: Waker,
+}
+
+impl Bus {
+    pub fn new(capacity: usize, waker: Waker) -> Self {
+        Self {
+            capacity,
+            subscribers: Vec::new(),
+            waker,
+        }
+    }
+
+    pub fn subscribe(&mut self) -> Subscription {
+        self.subscribe_to(|_| true)
+    }
+
+    // Only events matching `filter` are queued for this subscriber; the
+    // rest never take up room in its queue or wake anyone for it.
+    pub fn subscribe_to(&mut self, filter: impl Fn(&RiptideEvents) -> bool + Send + 'static) -> Subscription {
+        let (sender, events) = mpsc::sync_channel(self.capacity);
+        self.subscribers.push((sender, Box::new(filter)));
+        Subscription { events }
+    }
+
+    pub fn send(&mut self, event: RiptideEvents) {
+        let mut delivered = false;
+        self.subscribers.retain(|(subscriber, filter)| {
+            if !filter(&event) {
+                return true;
+            }
+            match subscriber.try_send(event.clone()) {
+                Ok(()) => {
+                    delivered = true;
+                    true
+                }
+                Err(mpsc::TrySendError::Full(_)) => true,
+                Err(mpsc::TrySendError::Disconnected(_)) => false,
+            }
+        });
+        if delivered {
+            self.waker.wake();
+        }
+    }
+}
+
+pub struct Subscription {
+    events: mpsc::Receiver<RiptideEvents>,
+}
+
+impl Subscription {
+    // Returns the next event without blocking.
+    pub fn try_recv(&self) -> Option<RiptideEvents> {
+        self.events.try_recv().ok()
+    }
+
+    pub fn recv(&self) -> Option<RiptideEvents> {
+        self.events.recv().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throttled {
+    Send,
+    // Too soon after the last one; offer it again after this long.
+    Hold(Duration),
+    Unchanged,
+}
+
+// Lets a value through at most once per `interval`, and only when it
+// differs from the last one let through. Held values aren't queued, so the
+// caller offers its latest one again once the wait is over.
+pub struct Throttle<T> {
+    interval: Duration,
+    sent: Option<(T, Instant)>,
+}
+
+impl<T: PartialEq> Throttle<T> {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, sent: None }
+    }
+
+    pub fn offer(&mut self, value: T, now: Instant) -> Throttled {
+        match &self.sent {
+            Some((sent, _)) if *sent == value => Throttled::Unchanged,
+            Some((_, sent_at)) if now.duration_since(*sent_at) < self.interval => {
+                Throttled::Hold(self.interval - now.duration_since(*sent_at))
+            }
+            _ => {
+                self.sent = Some((value, now));
+                Throttled::Send
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn saved(name: &str) -> RiptideEvents {
+        RiptideEvents::FileSaved { path: PathBuf::from(name) }
+    }
+
+    fn counting_waker() -> (Waker, Arc<AtomicUsize>) {
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = Waker::default();
+        let counted = wakes.clone();
+        waker.set(move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+        (waker, wakes)
+    }
+
+    #[test]
+    fn filtered_out_events_neither_fill_the_queue_nor_wake() {
+        let (waker, wakes) = counting_waker();
+        let mut bus = Bus::new(2, waker);
+        let saves = bus.subscribe_to(|event| matches!(event, RiptideEvents::FileSaved { .. }));
+        for _ in 0..5 {
+            bus.send(RiptideEvents::OpenWindow);
+        }
+        assert_eq!(wakes.load(Ordering::Relaxed), 0);
+        bus.send(saved("a"));
+        bus.send(saved("b"));
+        bus.send(saved("c"));
+        assert_eq!(wakes.load(Ordering::Relaxed), 2);
+        let received: Vec<_> = std::iter::from_fn(|| saves.try_recv()).collect();
+        assert!(matches!(&received[..], [RiptideEvents::FileSaved { path: a }, RiptideEvents::FileSaved { path: b }]
+            if a == &PathBuf::from("a") && b == &PathBuf::from("b")));
+    }
+
+    #[test]
+    fn dropped_subscribers_are_forgotten() {
+        let mut bus = Bus::new(4, Waker::default());
+        let everything = bus.subscribe();
+        drop(bus.subscribe());
+        bus.send(RiptideEvents::OpenWindow);
+        assert_eq!(bus.subscribers.len(), 1);
+        assert!(matches!(everything.try_recv(), Some(RiptideEvents::OpenWindow)));
+        assert!(everything.try_recv().is_none());
+    }
+
+    #[test]
+    fn throttle_holds_quick_changes_and_skips_repeats() {
+        let mut throttle = Throttle::new(Duration::from_millis(50));
+        let start = Instant::now();
+        assert_eq!(throttle.offer(1, start), Throttled::Send);
+        assert_eq!(throttle.offer(1, start + Duration::from_millis(100)), Throttled::Unchanged);
+        assert_eq!(throttle.offer(2, start + Duration::from_millis(20)), Throttled::Hold(Duration::from_millis(30)));
+        assert_eq!(throttle.offer(2, start + Duration::from_millis(50)), Throttled::Send);
+    }
+}
@@ -0,0 +1,75 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::interfaces::enums::BufferActions;
+use crate::server::persistence::Storage;
+use crate::shared::buffers::Buffer;
+
+// Output captured into a buffer but kept in full only on disk: every append
+// goes through to `path`, while the buffer holds the last `window` bytes.
+pub struct Capture {
+    pub path: PathBuf,
+    pub window: usize,
+    pub written: u64,
+    storage: Arc<dyn Storage>,
+}
+
+impl Capture {
+    pub fn new(path: PathBuf, window: usize, storage: Arc<dyn Storage>) -> Self {
+        Self {
+            path,
+            window,
+            written: 0,
+            storage,
+        }
+    }
+
+    // The head is cut at a line start where there's one to cut at, so the
+    // buffer doesn't begin mid-line.
+    pub fn append(&mut self, buffer: &mut Buffer, text: &str) -> io::Result<()> {
+        self.storage.append(&self.path, text.as_bytes())?;
+        self.written += text.len() as u64;
+        buffer.append(text);
+
+        let excess = buffer.content().len().saturating_sub(self.window);
+        if excess == 0 {
+            return Ok(());
+        }
+        let cut = match buffer.content()[excess..].find('\n') {
+            Some(newline) => excess + newline + 1,
+            None => (excess..=buffer.content().len())
+                .find(|&at| buffer.content().is_char_boundary(at))
+                .unwrap_or(buffer.content().len()),
+        };
+        let end = buffer.content()[..cut].chars().count();
+        buffer.apply(&BufferActions::DeleteRange { start: 0, end });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+
+    #[test]
+    fn captured_output_goes_to_disk_in_full_and_the_buffer_keeps_a_tail() {
+        let storage: Arc<dyn Storage> = Arc::new(InMemoryStorage::default());
+        let path = PathBuf::from("/captures/build.log");
+        let mut capture = Capture::new(path.clone(), 12, storage.clone());
+        let mut buffer = Buffer::default();
+
+        capture.append(&mut buffer, "line one\n").unwrap();
+        assert_eq!(buffer.content(), "line one\n");
+        capture.append(&mut buffer, "line two\nline three\n").unwrap();
+        // Cut back to a line start inside the window.
+        assert_eq!(buffer.content(), "line three\n");
+        capture.append(&mut buffer, "ünbroken output").unwrap();
+        assert!(buffer.content().len() <= 12 && "ünbroken output".ends_with(buffer.content()));
+
+        let full = "line one\nline two\nline three\nünbroken output";
+        assert_eq!(storage.read(&path).unwrap(), full.as_bytes());
+        assert_eq!(capture.written, full.len() as u64);
+    }
+}
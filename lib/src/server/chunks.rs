@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use memmap2::Mmap;
+
+use super::persistence::Storage;
+
+// How many mapped chunks are kept around.
+pub const CHUNK_CACHE_ENTRIES: usize = 8;
+// The window `read_file` maps at a time.
+pub const CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+// What a file looked like when a chunk of it was mapped. Any difference
+// means the chunk may no longer match the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    len: u64,
+    modified: SystemTime,
+}
+
+impl Stamp {
+    fn of(storage: &dyn Storage, path: &Path) -> io::Result<Self> {
+        let metadata = storage.metadata(path)?;
+        Ok(Self {
+            len: metadata.len,
+            modified: metadata.modified,
+        })
+    }
+}
+
+struct Chunk {
+    path: PathBuf,
+    offset: u64,
+    len: usize,
+    stamp: Stamp,
+    map: Arc<Mmap>,
+}
+
+// Maps windows of a file for paging through it, keeping the last few so
+// going back over the same part doesn't map it again. Newest first; a file
+// that changed since its chunks were mapped has them all dropped.
+#[derive(Default)]
+pub struct ChunkReader {
+    chunks: VecDeque<Chunk>,
+    hits: usize,
+}
+
+impl ChunkReader {
+    // `len` bytes from `offset`, cut short at the end of the file.
+    pub fn read(&mut self, storage: &dyn Storage, path: &Path, offset: u64, len: usize) -> io::Result<Arc<Mmap>> {
+        let stamp = Stamp::of(storage, path)?;
+        self.chunks.retain(|chunk| chunk.path != path || chunk.stamp == stamp);
+        let len = len.min(stamp.len.saturating_sub(offset) as usize);
+        let cached = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.path == path && chunk.offset == offset && chunk.len == len);
+        if let Some(chunk) = cached.and_then(|idx| self.chunks.remove(idx)) {
+            let map = chunk.map.clone();
+            self.chunks.push_front(chunk);
+            self.hits += 1;
+            return Ok(map);
+        }
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "chunk starts past the end of the file"));
+        }
+        let map = Arc::new(storage.map(path, offset, len)?);
+        self.chunks.push_front(Chunk {
+            path: path.to_path_buf(),
+            offset,
+            len,
+            stamp,
+            map: map.clone(),
+        });
+        self.chunks.truncate(CHUNK_CACHE_ENTRIES);
+        Ok(map)
+    }
+
+    // The whole file, copied out `CHUNK_BYTES` at a time.
+    pub fn read_file(&mut self, storage: &dyn Storage, path: &Path) -> io::Result<Vec<u8>> {
+        let len = storage.metadata(path)?.len;
+        let mut bytes = Vec::with_capacity(len as usize);
+        let mut offset = 0;
+        while offset < len {
+            let chunk = self.read(storage, path, offset, CHUNK_BYTES)?;
+            bytes.extend_from_slice(&chunk);
+            offset += chunk.len() as u64;
+        }
+        Ok(bytes)
+    }
+
+    // How many reads were served from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
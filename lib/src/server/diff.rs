@@ -0,0 +1,158 @@
+// Line indices are 0-based into the old (`a`) and new (`b`) texts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+// Myers keeps one vector per edit step, so the middle section is diffed in
+// windows of this many lines to bound memory on large files. Edits that
+// straddle a window boundary may come out less minimal than a full diff.
+const CHUNK_LINES: usize = 1024;
+
+pub fn line_diff(a: &str, b: &str) -> Vec<DiffOp> {
+    let a: Vec<&str> = a.lines().collect();
+    let b: Vec<&str> = b.lines().collect();
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Equal(i, i)).collect();
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let chunks = a_mid.len().max(b_mid.len()).div_ceil(CHUNK_LINES);
+    for chunk in 0..chunks {
+        let a_range = chunk_range(chunk, a_mid.len());
+        let b_range = chunk_range(chunk, b_mid.len());
+        myers(
+            &a_mid[a_range.clone()],
+            &b_mid[b_range.clone()],
+            prefix + a_range.start,
+            prefix + b_range.start,
+            &mut ops,
+        );
+    }
+
+    let a_suffix = a.len() - suffix;
+    let b_suffix = b.len() - suffix;
+    ops.extend((0..suffix).map(|i| DiffOp::Equal(a_suffix + i, b_suffix + i)));
+    ops
+}
+
+fn chunk_range(chunk: usize, len: usize) -> std::ops::Range<usize> {
+    let start = (chunk * CHUNK_LINES).min(len);
+    start..(start + CHUNK_LINES).min(len)
+}
+
+fn myers(a: &[&str], b: &[&str], a_offset: usize, b_offset: usize, ops: &mut Vec<DiffOp>) {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut reversed = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            reversed.push(DiffOp::Equal(a_offset + x as usize, b_offset + y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                reversed.push(DiffOp::Insert(b_offset + prev_y as usize));
+            } else {
+                reversed.push(DiffOp::Delete(a_offset + prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.extend(reversed.into_iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The new text rebuilt from the old one and the ops.
+    fn apply(a: &str, b: &str, ops: &[DiffOp]) -> Vec<String> {
+        let (a, b): (Vec<&str>, Vec<&str>) = (a.lines().collect(), b.lines().collect());
+        ops.iter()
+            .filter_map(|op| match *op {
+                DiffOp::Equal(i, j) => {
+                    assert_eq!(a[i], b[j]);
+                    Some(a[i].to_string())
+                }
+                DiffOp::Insert(j) => Some(b[j].to_string()),
+                DiffOp::Delete(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_lines_come_out_as_a_minimal_edit() {
+        let (a, b) = ("one\ntwo\nthree\nfour\n", "one\n2\nthree\nfour\nfive\n");
+        let ops = line_diff(a, b);
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Equal(0, 0),
+                DiffOp::Delete(1),
+                DiffOp::Insert(1),
+                DiffOp::Equal(2, 2),
+                DiffOp::Equal(3, 3),
+                DiffOp::Insert(4),
+            ]
+        );
+        assert_eq!(apply(a, b, &ops), b.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn diffs_spanning_several_windows_still_rebuild_the_new_text() {
+        let a: String = (0..3000).map(|i| format!("line {i}\n")).collect();
+        let b: String = (0..3000).filter(|i| i % 700 != 5).map(|i| format!("line {i}\n")).collect();
+        let ops = line_diff(&a, &b);
+        assert_eq!(apply(&a, &b, &ops), b.lines().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,74 @@
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+// What the encoding menu offers. Latin-1 is windows-1252, as browsers treat
+// the label.
+pub const CHOICES: [&Encoding; 4] = [UTF_8, UTF_16LE, UTF_16BE, WINDOWS_1252];
+
+pub fn display_name(encoding: &'static Encoding) -> &'static str {
+    if encoding == WINDOWS_1252 { "Latin-1" } else { encoding.name() }
+}
+
+// A byte order mark wins over the guess; otherwise files are UTF-8.
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    Encoding::for_bom(bytes).map_or(UTF_8, |(encoding, _)| encoding)
+}
+
+// Bytes that don't fit `encoding` become U+FFFD; a matching BOM is dropped.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_with_bom_removal(bytes).0.into_owned()
+}
+
+// Returns the encoded bytes and how many characters the encoding couldn't
+// represent; each of those is written as `?`. UTF-16 gets a BOM so `detect`
+// picks it up again.
+pub fn encode(content: &str, encoding: &'static Encoding) -> (Vec<u8>, usize) {
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let to_bytes = if encoding == UTF_16LE { u16::to_le_bytes } else { u16::to_be_bytes };
+        let bytes = std::iter::once(0xFEFF)
+            .chain(content.encode_utf16())
+            .flat_map(to_bytes)
+            .collect();
+        return (bytes, 0);
+    }
+
+    let mut encoder = encoding.new_encoder();
+    let mut bytes = Vec::with_capacity(content.len());
+    let mut unmappable = 0;
+    let mut rest = content;
+    loop {
+        let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut bytes, true);
+        rest = &rest[read..];
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::Unmappable(_) => {
+                bytes.push(b'?');
+                unmappable += 1;
+            }
+            EncoderResult::OutputFull => bytes.reserve(rest.len().max(16)),
+        }
+    }
+    (bytes, unmappable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_choice_round_trips_text_it_can_represent() {
+        let content = "naïve café\n";
+        for encoding in CHOICES {
+            let (bytes, unmappable) = encode(content, encoding);
+            assert_eq!(unmappable, 0);
+            assert_eq!(detect(&bytes), if encoding == WINDOWS_1252 { UTF_8 } else { encoding });
+            assert_eq!(decode(&bytes, encoding), content, "{}", encoding.name());
+        }
+        assert_eq!(encode("ï", WINDOWS_1252).0, vec![0xEF]);
+    }
+
+    #[test]
+    fn characters_latin1_lacks_become_question_marks() {
+        assert_eq!(encode("a→b😀", WINDOWS_1252), (b"a?b?".to_vec(), 2));
+        assert_eq!(decode(&[b'a', 0xFF, 0xFE], UTF_8), "a\u{FFFD}\u{FFFD}");
+    }
+}
@@ -0,0 +1,57 @@
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::persistence::Storage;
+use crate::shared::frames::WindowGeometry;
+
+pub const WINDOW_GEOMETRY_NAME: &str = "windows.json";
+
+// Window places kept between runs: the main window's, and one per frame
+// cluster by index.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedGeometry {
+    pub main: Option<WindowGeometry>,
+    pub clusters: Vec<Option<WindowGeometry>>,
+}
+
+impl SavedGeometry {
+    // A missing or unreadable file leaves every window at its default size.
+    pub fn load(storage: &dyn Storage, path: &Path) -> Self {
+        storage.read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn Storage, path: &Path) -> io::Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)?;
+        storage.write(path, &bytes, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+
+    #[test]
+    fn window_places_round_trip_and_bad_files_fall_back() {
+        let storage = InMemoryStorage::default();
+        let path = Path::new("/data").join(WINDOW_GEOMETRY_NAME);
+        assert!(SavedGeometry::load(&storage, &path).main.is_none());
+
+        let saved = SavedGeometry {
+            main: Some(WindowGeometry { size: [1280.0, 720.0], position: Some([10.0, 20.0]) }),
+            clusters: vec![None, Some(WindowGeometry { size: [400.0, 300.0], position: None })],
+        };
+        saved.save(&storage, &path).unwrap();
+        let loaded = SavedGeometry::load(&storage, &path);
+        assert_eq!((loaded.main, loaded.clusters), (saved.main, saved.clusters));
+
+        storage.write(&path, b"{ not json", false).unwrap();
+        let loaded = SavedGeometry::load(&storage, &path);
+        assert!(loaded.main.is_none() && loaded.clusters.is_empty());
+    }
+}
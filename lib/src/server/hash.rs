@@ -0,0 +1,21 @@
+use xxhash_rust::xxh64::xxh64;
+
+// XXH64, for hashes that get written to disk: unlike std's hashers its
+// output is fixed by the spec, so saved hashes in sessions keep matching
+// across runs and Rust releases.
+pub fn content_hash(content: &str) -> u64 {
+    xxh64(content.as_bytes(), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_reference_implementation() {
+        assert_eq!(content_hash(""), 0xEF46_DB37_51D8_E999);
+        assert_eq!(content_hash("a"), 0xD24E_C4F1_A98C_6E5B);
+        assert_eq!(content_hash("abc"), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(content_hash("Nobody inspects the spammish repetition"), 0xFBCE_A83C_8A37_8BF1);
+    }
+}
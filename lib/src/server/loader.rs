@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use memmap2::Mmap;
+
+use crate::interfaces::enums::RiptideEvents;
+
+// Files at least this big load in the background with a progress bar.
+pub const BACKGROUND_LOAD_BYTES: u64 = 16 * 1024 * 1024;
+const CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+// Copies the file out of a memory map one chunk at a time, reporting each
+// new whole percentage and checking `cancel` between chunks.
+pub fn read_with_progress(
+    path: &Path,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(u8),
+) -> io::Result<Vec<u8>> {
+    let file = File::open(path)?;
+    // SAFETY: the map is only read from while copying and dropped before
+    // returning.
+    let mmap = unsafe { Mmap::map(&file)? };
+    let mut bytes = Vec::with_capacity(mmap.len());
+    let mut reported = None;
+    for chunk in mmap.chunks(CHUNK_BYTES) {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "load cancelled"));
+        }
+        bytes.extend_from_slice(chunk);
+        let pct = (bytes.len() * 100 / mmap.len()) as u8;
+        if reported != Some(pct) {
+            on_progress(pct);
+            reported = Some(pct);
+        }
+    }
+    if reported.is_none() {
+        on_progress(100);
+    }
+    Ok(bytes)
+}
+
+// A read running on its own thread. Dropping it cancels the read.
+pub struct FileLoad {
+    pub path: PathBuf,
+    pub pct: u8,
+    cancel: Arc<AtomicBool>,
+    events: mpsc::Receiver<RiptideEvents>,
+    result: mpsc::Receiver<io::Result<Vec<u8>>>,
+}
+
+impl FileLoad {
+    pub fn spawn(path: PathBuf) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (event_sender, events) = mpsc::channel();
+        let (result_sender, result) = mpsc::channel();
+        let thread_cancel = Arc::clone(&cancel);
+        let thread_path = path.clone();
+        thread::spawn(move || {
+            let bytes = read_with_progress(&thread_path, &thread_cancel, |pct| {
+                let _ = event_sender.send(RiptideEvents::LoadProgress {
+                    path: thread_path.clone(),
+                    pct,
+                });
+            });
+            let _ = result_sender.send(bytes);
+        });
+        Self {
+            path,
+            pct: 0,
+            cancel,
+            events,
+            result,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    // Takes in progress updates and returns the file's bytes once the read
+    // has finished, failed or been cancelled.
+    pub fn poll(&mut self) -> Option<io::Result<Vec<u8>>> {
+        while let Ok(event) = self.events.try_recv() {
+            if let RiptideEvents::LoadProgress { pct, .. } = event {
+                self.pct = pct;
+            }
+        }
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err(io::Error::other("loader thread exited")))
+            }
+        }
+    }
+}
+
+impl Drop for FileLoad {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn reads_report_progress_and_stop_when_cancelled() {
+        let dir = TempDir::new("loader");
+        let path = dir.join("big.log");
+        let bytes: Vec<u8> = (0..CHUNK_BYTES * 2 + 10).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &bytes).unwrap();
+
+        let mut reported = Vec::new();
+        assert_eq!(read_with_progress(&path, &AtomicBool::new(false), |pct| reported.push(pct)).unwrap(), bytes);
+        assert_eq!(reported, vec![49, 99, 100]);
+        let cancelled = read_with_progress(&path, &AtomicBool::new(true), |_| {}).unwrap_err();
+        assert_eq!(cancelled.kind(), io::ErrorKind::Interrupted);
+
+        let mut load = FileLoad::spawn(path.clone());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let result = loop {
+            if let Some(result) = load.poll() {
+                break result;
+            }
+            assert!(Instant::now() < deadline, "load never finished");
+            thread::sleep(Duration::from_millis(5));
+        };
+        assert_eq!(result.unwrap(), bytes);
+        assert_eq!(load.pct, 100);
+    }
+}
@@ -0,0 +1,708 @@
+pub mod transport;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::interfaces::enums::BufferActions;
+use crate::server::symbols::{self, Symbol};
+use crate::server::wake::Waker;
+use crate::shared::buffers::Buffer;
+use crate::shared::Shared;
+use transport::LanguageServer;
+
+// LSP positions count UTF-16 code units within a 0-based line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+impl Position {
+    pub fn to_json(self) -> Value {
+        json!({ "line": self.line, "character": self.character })
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            line: value.get("line")?.as_u64()? as u32,
+            character: value.get("character")?.as_u64()? as u32,
+        })
+    }
+}
+
+pub fn position_at(content: &str, char_idx: usize) -> Position {
+    let mut position = Position { line: 0, character: 0 };
+    for c in content.chars().take(char_idx) {
+        if c == '\n' {
+            position.line += 1;
+            position.character = 0;
+        } else {
+            position.character += c.len_utf16() as u32;
+        }
+    }
+    position
+}
+
+// Positions past the end of a line clamp to that line's end, and lines
+// past the end of the content clamp to the end of the content.
+pub fn char_index_at(content: &str, position: Position) -> usize {
+    let mut line = 0;
+    let mut character = 0;
+    for (char_idx, c) in content.chars().enumerate() {
+        if line == position.line && (c == '\n' || character >= position.character) {
+            return char_idx;
+        }
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16() as u32;
+        }
+    }
+    content.chars().count()
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+pub fn language_id(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or_default() {
+        "rs" => "rust",
+        "py" => "python",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "hpp" => "cpp",
+        "js" => "javascript",
+        "ts" => "typescript",
+        "json" => "json",
+        "toml" => "toml",
+        "md" => "markdown",
+        _ => "plaintext",
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|ext| ext.to_str()).map(String::from)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub position: Position,
+}
+
+impl Location {
+    // Accepts both `Location` and `LocationLink` shapes.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let uri = value.get("uri").or_else(|| value.get("targetUri"))?.as_str()?;
+        let range = value
+            .get("range")
+            .or_else(|| value.get("targetSelectionRange"))?;
+        Some(Self {
+            path: uri_to_path(uri)?,
+            position: Position::from_json(range.get("start")?)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: Position,
+    pub end: Position,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let range = value.get("range")?;
+        Some(Self {
+            start: Position::from_json(range.get("start")?)?,
+            end: Position::from_json(range.get("end")?)?,
+            new_text: value.get("newText")?.as_str()?.to_string(),
+        })
+    }
+}
+
+// Converts edits expressed against `content` into actions that can be
+// applied in sequence: they run back to front so earlier ranges are not
+// shifted by later ones.
+pub fn edits_to_actions(content: &str, edits: &[TextEdit]) -> Vec<BufferActions> {
+    let mut ranges: Vec<(usize, usize, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let start = char_index_at(content, edit.start);
+            let end = char_index_at(content, edit.end).max(start);
+            (start, end, edit.new_text.as_str())
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    ranges
+        .into_iter()
+        .filter_map(|(start, end, new_text)| match (end > start, new_text.is_empty()) {
+            (true, false) => Some(BufferActions::ReplaceRange { start, end, text: new_text.to_string() }),
+            (true, true) => Some(BufferActions::DeleteRange { start, end }),
+            (false, false) => Some(BufferActions::InsertText { char_idx: start, text: new_text.to_string() }),
+            (false, true) => None,
+        })
+        .collect()
+}
+
+// Edits per file, from either `changes` or `documentChanges`.
+pub type WorkspaceEdit = Vec<(PathBuf, Vec<TextEdit>)>;
+
+fn workspace_edit_from_json(value: &Value) -> WorkspaceEdit {
+    let parse_edits = |edits: &Value| -> Vec<TextEdit> {
+        edits
+            .as_array()
+            .map(|edits| edits.iter().filter_map(TextEdit::from_json).collect())
+            .unwrap_or_default()
+    };
+
+    let mut workspace_edit = Vec::new();
+    if let Some(changes) = value.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            if let Some(path) = uri_to_path(uri) {
+                workspace_edit.push((path, parse_edits(edits)));
+            }
+        }
+    }
+    if let Some(document_changes) = value.get("documentChanges").and_then(Value::as_array) {
+        for change in document_changes {
+            let path = change["textDocument"]["uri"].as_str().and_then(uri_to_path);
+            if let Some(path) = path {
+                workspace_edit.push((path, parse_edits(&change["edits"])));
+            }
+        }
+    }
+    workspace_edit
+}
+
+// Handles both the hierarchical `DocumentSymbol` shape and the flat
+// `SymbolInformation` one.
+fn symbol_from_json(value: &Value) -> Option<Symbol> {
+    let range = value
+        .get("selectionRange")
+        .or_else(|| value.get("range"))
+        .or_else(|| value.get("location")?.get("range"))?;
+    let children = value
+        .get("children")
+        .and_then(Value::as_array)
+        .map(|children| children.iter().filter_map(symbol_from_json).collect())
+        .unwrap_or_default();
+    Some(Symbol {
+        name: value.get("name")?.as_str()?.to_string(),
+        kind: symbols::kind_name(value.get("kind").and_then(Value::as_u64).unwrap_or_default()),
+        line: Position::from_json(range.get("start")?)?.line as usize,
+        children,
+    })
+}
+
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspRequest {
+    Hover { buffer_index: usize, char_idx: usize },
+    Definition { buffer_index: usize },
+    Rename { buffer_index: usize },
+    Formatting { buffer_index: usize },
+    DocumentSymbol { buffer_index: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspReply {
+    Hover { buffer_index: usize, char_idx: usize, contents: String },
+    Definition(Vec<Location>),
+    Rename(WorkspaceEdit),
+    Formatting { buffer_index: usize, edits: Vec<TextEdit> },
+    DocumentSymbol { buffer_index: usize, symbols: Vec<Symbol> },
+}
+
+struct Document {
+    version: i32,
+    content_hash: u64,
+}
+
+// `false` when the buffer doesn't exist, can't be reached or is in big-file
+// mode.
+fn with_buffer(shared: &Shared, buffer_index: usize, f: impl FnOnce(&Buffer) -> bool) -> bool {
+    shared
+        .with_buffers(|buffers| {
+            buffers
+                .buffers
+                .get(buffer_index)
+                .filter(|buffer| !buffer.big_file)
+                .is_some_and(f)
+        })
+        .unwrap_or(false)
+}
+
+// Owns one language server per file extension, started on first use, and
+// keeps each server's view of open documents in sync before every request.
+pub struct LspManager {
+    pub commands: HashMap<String, String>,
+    root_uri: String,
+    servers: HashMap<String, LanguageServer>,
+    failed: HashSet<String>,
+    documents: HashMap<PathBuf, Document>,
+    pending: HashMap<(String, u64), LspRequest>,
+    waker: Waker,
+}
+
+impl LspManager {
+    pub fn new(commands: HashMap<String, String>, root: &Path, waker: Waker) -> Self {
+        Self {
+            commands,
+            root_uri: path_to_uri(root),
+            servers: HashMap::new(),
+            failed: HashSet::new(),
+            documents: HashMap::new(),
+            pending: HashMap::new(),
+            waker,
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn server_for(&mut self, extension: &str) -> Option<&mut LanguageServer> {
+        if !self.servers.contains_key(extension) && !self.failed.contains(extension) {
+            let command = self.commands.get(extension)?;
+            match LanguageServer::spawn(command, &self.root_uri, self.waker.clone()) {
+                Ok(server) => {
+                    self.servers.insert(extension.to_string(), server);
+                }
+                Err(err) => {
+                    log::error!("cant start language server `{command}`: {err}");
+                    self.failed.insert(extension.to_string());
+                }
+            }
+        }
+        self.servers.get_mut(extension)
+    }
+
+    // Sends didOpen the first time a document is seen and a full-text
+    // didChange whenever its content differs from what the server has.
+    fn sync_document(&mut self, buffer: &Buffer) -> Option<String> {
+        let extension = extension(&buffer.file_path)?;
+        let hash = buffer.content_hash();
+        let uri = path_to_uri(&buffer.file_path);
+
+        let previous = self
+            .documents
+            .get(&buffer.file_path)
+            .map(|document| (document.version, document.content_hash));
+        let server = self.server_for(&extension)?;
+        let version = match previous {
+            None => {
+                server.notify("textDocument/didOpen", json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": language_id(&buffer.file_path),
+                        "version": 1,
+                        "text": buffer.content(),
+                    },
+                }));
+                1
+            }
+            Some((version, previous_hash)) if previous_hash != hash => {
+                server.notify("textDocument/didChange", json!({
+                    "textDocument": { "uri": uri, "version": version + 1 },
+                    "contentChanges": [{ "text": buffer.content() }],
+                }));
+                version + 1
+            }
+            Some((version, _)) => version,
+        };
+        self.documents.insert(buffer.file_path.clone(), Document {
+            version,
+            content_hash: hash,
+        });
+        Some(extension)
+    }
+
+    // Tells the server a document it has open was written to disk.
+    pub fn did_save(&mut self, buffer: &Buffer) {
+        if !self.documents.contains_key(&buffer.file_path) {
+            return;
+        }
+        let Some(extension) = self.sync_document(buffer) else {
+            return;
+        };
+        if let Some(server) = self.servers.get_mut(&extension) {
+            server.notify("textDocument/didSave", json!({
+                "textDocument": { "uri": path_to_uri(&buffer.file_path) },
+            }));
+        }
+    }
+
+    // Sends didClose for every open document no buffer holds any more, e.g.
+    // after a session switch or a save-as, so a later reopen is a didOpen.
+    pub fn close_unloaded(&mut self, shared: &Shared) {
+        let Ok(loaded) = shared.with_buffers(|buffers| {
+            buffers.buffers.iter().map(|buffer| buffer.file_path.clone()).collect::<HashSet<_>>()
+        }) else {
+            return;
+        };
+        let unloaded: Vec<PathBuf> = self.documents.keys().filter(|path| !loaded.contains(*path)).cloned().collect();
+        for path in unloaded {
+            self.documents.remove(&path);
+            let Some(server) = extension(&path).and_then(|extension| self.servers.get_mut(&extension)) else {
+                continue;
+            };
+            server.notify("textDocument/didClose", json!({
+                "textDocument": { "uri": path_to_uri(&path) },
+            }));
+        }
+    }
+
+    fn request(&mut self, buffer: &Buffer, method: &str, params: Value, request: LspRequest) -> bool {
+        let Some(extension) = self.sync_document(buffer) else {
+            return false;
+        };
+        let Some(server) = self.servers.get_mut(&extension) else {
+            return false;
+        };
+        let id = server.request(method, params);
+        self.pending.insert((extension, id), request);
+        true
+    }
+
+    pub fn hover(&mut self, shared: &Shared, buffer_index: usize, char_idx: usize) -> bool {
+        with_buffer(shared, buffer_index, |buffer| {
+            let params = json!({
+                "textDocument": { "uri": path_to_uri(&buffer.file_path) },
+                "position": position_at(buffer.content(), char_idx).to_json(),
+            });
+            self.request(buffer, "textDocument/hover", params, LspRequest::Hover { buffer_index, char_idx })
+        })
+    }
+
+    pub fn definition(&mut self, shared: &Shared, buffer_index: usize, char_idx: usize) -> bool {
+        with_buffer(shared, buffer_index, |buffer| {
+            let params = json!({
+                "textDocument": { "uri": path_to_uri(&buffer.file_path) },
+                "position": position_at(buffer.content(), char_idx).to_json(),
+            });
+            self.request(buffer, "textDocument/definition", params, LspRequest::Definition { buffer_index })
+        })
+    }
+
+    pub fn rename(&mut self, shared: &Shared, buffer_index: usize, char_idx: usize, new_name: &str) -> bool {
+        with_buffer(shared, buffer_index, |buffer| {
+            let params = json!({
+                "textDocument": { "uri": path_to_uri(&buffer.file_path) },
+                "position": position_at(buffer.content(), char_idx).to_json(),
+                "newName": new_name,
+            });
+            self.request(buffer, "textDocument/rename", params, LspRequest::Rename { buffer_index })
+        })
+    }
+
+    // Returns false when no server will answer, e.g. the language has no
+    // server configured or its server doesn't advertise formatting.
+    pub fn formatting(&mut self, shared: &Shared, buffer_index: usize, tab_size: u32) -> bool {
+        with_buffer(shared, buffer_index, |buffer| {
+            let Some(extension) = extension(&buffer.file_path) else {
+                return false;
+            };
+            let supported = self
+                .server_for(&extension)
+                .and_then(|server| server.supports("documentFormattingProvider"));
+            if supported == Some(false) {
+                return false;
+            }
+            let params = json!({
+                "textDocument": { "uri": path_to_uri(&buffer.file_path) },
+                "options": { "tabSize": tab_size, "insertSpaces": true },
+            });
+            self.request(buffer, "textDocument/formatting", params, LspRequest::Formatting { buffer_index })
+        })
+    }
+
+    pub fn document_symbols(&mut self, shared: &Shared, buffer_index: usize) -> bool {
+        with_buffer(shared, buffer_index, |buffer| {
+            let params = json!({ "textDocument": { "uri": path_to_uri(&buffer.file_path) } });
+            self.request(buffer, "textDocument/documentSymbol", params, LspRequest::DocumentSymbol { buffer_index })
+        })
+    }
+
+    pub fn poll(&mut self) -> Vec<LspReply> {
+        let mut replies = Vec::new();
+        for (extension, server) in &mut self.servers {
+            while let Some((id, result)) = server.try_recv() {
+                let Some(request) = self.pending.remove(&(extension.clone(), id)) else {
+                    continue;
+                };
+                // Failed requests still produce an (empty) reply so callers
+                // waiting on one, like format-on-save, can carry on.
+                let result = result.unwrap_or_else(|err| {
+                    log::error!("language server error: {err}");
+                    Value::Null
+                });
+                replies.extend(reply_for(request, result));
+            }
+        }
+        replies
+    }
+}
+
+fn reply_for(request: LspRequest, result: Value) -> Option<LspReply> {
+    match request {
+        LspRequest::Hover { buffer_index, char_idx } => Some(LspReply::Hover {
+            buffer_index,
+            char_idx,
+            contents: hover_contents(result.get("contents")?),
+        }),
+        LspRequest::Definition { .. } => {
+            let locations = match &result {
+                Value::Array(items) => items.iter().filter_map(Location::from_json).collect(),
+                Value::Null => Vec::new(),
+                single => Location::from_json(single).into_iter().collect(),
+            };
+            Some(LspReply::Definition(locations))
+        }
+        LspRequest::Rename { .. } => Some(LspReply::Rename(workspace_edit_from_json(&result))),
+        LspRequest::Formatting { buffer_index } => Some(LspReply::Formatting {
+            buffer_index,
+            edits: result
+                .as_array()
+                .map(|edits| edits.iter().filter_map(TextEdit::from_json).collect())
+                .unwrap_or_default(),
+        }),
+        LspRequest::DocumentSymbol { buffer_index } => Some(LspReply::DocumentSymbol {
+            buffer_index,
+            symbols: result
+                .as_array()
+                .map(|symbols| symbols.iter().filter_map(symbol_from_json).collect())
+                .unwrap_or_default(),
+        }),
+    }
+}
+
+// Hover contents may be a plain string, a `{ language, value }` or
+// `{ kind, value }` object, or an array of either.
+fn hover_contents(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(hover_contents)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(object) => {
+            let value = object.get("value").and_then(Value::as_str).unwrap_or_default();
+            match object.get("language").and_then(Value::as_str) {
+                Some(language) => format!("```{language}\n{value}\n```"),
+                None => value.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::buffers::BufferStorage;
+    use crate::shared::frames::FrameStorage;
+    use crate::test_support::TempDir;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn hover_contents_of_every_shape_flatten_to_markdown() {
+        let contents = serde_json::json!([
+            "plain",
+            { "language": "rust", "value": "fn main()" },
+            { "kind": "markdown", "value": "**docs**" },
+            { "kind": "markdown", "value": "" }
+        ]);
+        assert_eq!(hover_contents(&contents), "plain\n\n```rust\nfn main()\n```\n\n**docs**");
+        assert_eq!(hover_contents(&Value::Null), "");
+    }
+
+    #[test]
+    fn definitions_accept_locations_links_and_null() {
+        let definition = || LspRequest::Definition { buffer_index: 0 };
+        let location = |line| Location { path: PathBuf::from("/src/lib.rs"), position: Position { line, character: 4 } };
+        let range = |line| serde_json::json!({ "start": { "line": line, "character": 4 }, "end": { "line": line, "character": 8 } });
+        let single = serde_json::json!({ "uri": "file:///src/lib.rs", "range": range(3) });
+        let links = serde_json::json!([
+            { "targetUri": "file:///src/lib.rs", "targetRange": range(1), "targetSelectionRange": range(2) },
+            { "uri": "not a file uri", "range": range(5) }
+        ]);
+        assert_eq!(reply_for(definition(), single), Some(LspReply::Definition(vec![location(3)])));
+        assert_eq!(reply_for(definition(), links), Some(LspReply::Definition(vec![location(2)])));
+        assert_eq!(reply_for(definition(), Value::Null), Some(LspReply::Definition(Vec::new())));
+    }
+
+    #[test]
+    fn edits_apply_back_to_front() {
+        let content = "let x = 1;\nlet y = x;\n";
+        let edit = |line, start, end, text: &str| TextEdit {
+            start: Position { line, character: start },
+            end: Position { line, character: end },
+            new_text: text.to_string(),
+        };
+        let actions = edits_to_actions(content, &[edit(0, 4, 5, "value"), edit(1, 8, 9, "value"), edit(1, 10, 10, " // renamed")]);
+        let mut buffer = Buffer::with_content(content);
+        buffer.edit(&actions);
+        assert_eq!(buffer.content(), "let value = 1;\nlet y = value; // renamed\n");
+        assert_eq!(buffer.history.undo_len(), 1);
+    }
+
+    #[test]
+    fn workspace_edits_read_changes_and_document_changes() {
+        let edit = serde_json::json!({
+            "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } },
+            "newText": "value"
+        });
+        let value = serde_json::json!({
+            "changes": { "file:///src/a.rs": [edit.clone()] },
+            "documentChanges": [{ "textDocument": { "uri": "file:///src/b.rs", "version": 3 }, "edits": [edit] }]
+        });
+        let parsed = workspace_edit_from_json(&value);
+        let paths: Vec<&Path> = parsed.iter().map(|(path, _)| path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/src/a.rs"), Path::new("/src/b.rs")]);
+        assert!(parsed.iter().all(|(_, edits)| edits.len() == 1 && edits[0].new_text == "value"));
+        assert!(is_valid_identifier("_renamed2") && !is_valid_identifier("2x") && !is_valid_identifier("a-b"));
+    }
+
+    #[test]
+    fn document_symbols_read_both_shapes() {
+        let range = |line| serde_json::json!({ "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 1 } });
+        let nested = serde_json::json!({
+            "name": "Point", "kind": 23, "range": range(0), "selectionRange": range(1),
+            "children": [{ "name": "x", "kind": 8, "range": range(2), "selectionRange": range(2) }]
+        });
+        let flat = serde_json::json!({ "name": "main", "kind": 12, "location": { "uri": "file:///a.rs", "range": range(7) } });
+        let reply = reply_for(LspRequest::DocumentSymbol { buffer_index: 2 }, Value::Array(vec![nested, flat]));
+        let x = Symbol { name: String::from("x"), kind: "field", line: 2, children: Vec::new() };
+        let symbols = vec![
+            Symbol { name: String::from("Point"), kind: "struct", line: 1, children: vec![x] },
+            Symbol { name: String::from("main"), kind: "function", line: 7, children: Vec::new() },
+        ];
+        assert_eq!(reply, Some(LspReply::DocumentSymbol { buffer_index: 2, symbols }));
+    }
+
+    #[test]
+    fn positions_count_utf16_units_and_round_trip() {
+        let content = "fn a() {}\nlet 😀 = é;\n";
+        let position = position_at(content, 17);
+        assert_eq!(position, Position { line: 1, character: 8 });
+        assert_eq!(char_index_at(content, position), 17);
+        assert_eq!(Position::from_json(&position.to_json()), Some(position));
+        // Past the line's end clamps to it; past the last line to the end.
+        assert_eq!(char_index_at(content, Position { line: 0, character: 99 }), 9);
+        assert_eq!(char_index_at(content, Position { line: 9, character: 0 }), content.chars().count());
+    }
+
+    #[test]
+    fn uris_escape_and_round_trip_paths() {
+        let path = Path::new("/home/me/my notes/ü#1.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/me/my%20notes/%C3%BC%231.rs");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(uri_to_path("https://example.com/a.rs"), None);
+    }
+
+    // A language server that answers `initialize` and then writes whatever
+    // it's sent to a log.
+    fn fake_server(dir: &Path) -> (String, PathBuf) {
+        let reply = r#"{"jsonrpc":"2.0","id":0,"result":{"capabilities":{}}}"#;
+        let script = dir.join("server.sh");
+        let log = dir.join("server.log");
+        fs::write(
+            &script,
+            format!("printf 'Content-Length: {}\\r\\n\\r\\n%s' '{reply}'\nexec cat > \"$1\"\n", reply.len()),
+        )
+        .unwrap();
+        (format!("sh {} {}", script.display(), log.display()), log)
+    }
+
+    fn wait_for(log: &Path, done: impl Fn(&str) -> bool) -> String {
+        let started = Instant::now();
+        loop {
+            let logged = fs::read_to_string(log).unwrap_or_default();
+            if done(&logged) || started.elapsed() > Duration::from_secs(10) {
+                return logged;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn unloaded_documents_are_closed_and_reopened() {
+        let dir = TempDir::new("lsp");
+        let (command, log) = fake_server(dir.path());
+        let mut lsp = LspManager::new(HashMap::from([(String::from("txt"), command)]), dir.path(), Waker::default());
+        let buffer = || {
+            let mut buffer = Buffer::with_content("one");
+            buffer.file_path = dir.join("notes.txt");
+            buffer
+        };
+        let shared = Shared::new(BufferStorage { buffers: vec![buffer()] }, FrameStorage::default());
+
+        assert!(lsp.document_symbols(&shared, 0));
+        shared.with_buffers(|buffers| lsp.did_save(&buffers.buffers[0])).unwrap();
+        shared.with_buffers_mut(|buffers| *buffers = BufferStorage::default()).unwrap();
+        lsp.close_unloaded(&shared);
+        shared.with_buffers_mut(|buffers| buffers.buffers = vec![buffer()]).unwrap();
+        lsp.close_unloaded(&shared);
+        assert!(lsp.document_symbols(&shared, 0));
+
+        let logged = wait_for(&log, |logged| logged.matches("textDocument/documentSymbol").count() == 2);
+        let order: Vec<&str> = ["didOpen", "didSave", "didClose", "didOpen"]
+            .into_iter()
+            .scan(0, |at, method| {
+                let found = logged[*at..].find(&format!("textDocument/{method}"))?;
+                *at += found + 1;
+                Some(method)
+            })
+            .collect();
+        assert_eq!(order, ["didOpen", "didSave", "didClose", "didOpen"], "{logged}");
+        assert_eq!(logged.matches("textDocument/didClose").count(), 1);
+    }
+}
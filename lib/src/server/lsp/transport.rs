@@ -0,0 +1,206 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::server::wake::Waker;
+
+const INITIALIZE_ID: u64 = 0;
+
+pub type Response = (u64, Result<Value, String>);
+
+// One language server process speaking JSON-RPC over stdio. Requests go
+// out through an mpsc channel to a writer thread and responses come back
+// through another, so nothing here ever blocks the caller.
+pub struct LanguageServer {
+    outgoing: mpsc::Sender<Value>,
+    responses: mpsc::Receiver<Response>,
+    next_id: u64,
+    capabilities: Option<Value>,
+    child: Child,
+}
+
+impl LanguageServer {
+    pub fn spawn(command: &str, root_uri: &str, waker: Waker) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty language server command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("language server stdio unavailable"));
+        };
+
+        let (outgoing, outgoing_rx) = mpsc::channel();
+        let (response_tx, responses) = mpsc::channel();
+        let (initialized_tx, initialized_rx) = mpsc::channel();
+
+        let replies = outgoing.clone();
+        thread::spawn(move || read_messages(stdout, response_tx, initialized_tx, replies, waker));
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": INITIALIZE_ID,
+            "method": "initialize",
+            "params": {
+                "processId": std::process::id(),
+                "rootUri": root_uri,
+                "capabilities": {},
+            },
+        });
+        thread::spawn(move || write_messages(stdin, initialize, outgoing_rx, initialized_rx));
+
+        Ok(Self {
+            outgoing,
+            responses,
+            next_id: INITIALIZE_ID + 1,
+            capabilities: None,
+            child,
+        })
+    }
+
+    pub fn request(&mut self, method: &str, params: Value) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }));
+        id
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    // `None` until the server has answered `initialize`.
+    pub fn supports(&self, capability: &str) -> Option<bool> {
+        let capabilities = self.capabilities.as_ref()?;
+        Some(match capabilities.get(capability) {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+            Some(_) => true,
+        })
+    }
+
+    pub fn try_recv(&mut self) -> Option<Response> {
+        loop {
+            let (id, result) = self.responses.try_recv().ok()?;
+            if id != INITIALIZE_ID {
+                return Some((id, result));
+            }
+            self.capabilities = result.ok().and_then(|result| result.get("capabilities").cloned());
+        }
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+fn write_message(stdin: &mut ChildStdin, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(stdin, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    stdin.flush()
+}
+
+// Everything but `initialize` is held back until the server has answered
+// it, as the protocol requires.
+fn write_messages(
+    mut stdin: ChildStdin,
+    initialize: Value,
+    outgoing: mpsc::Receiver<Value>,
+    initialized: mpsc::Receiver<()>,
+) {
+    if write_message(&mut stdin, &initialize).is_err() || initialized.recv().is_err() {
+        return;
+    }
+    let notification = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+    if write_message(&mut stdin, &notification).is_err() {
+        return;
+    }
+    for message in outgoing {
+        if write_message(&mut stdin, &message).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Value> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+    let length = content_length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map_err(io::Error::other)
+}
+
+fn read_messages(
+    stdout: ChildStdout,
+    responses: mpsc::Sender<Response>,
+    initialized: mpsc::Sender<()>,
+    replies: mpsc::Sender<Value>,
+    waker: Waker,
+) {
+    let mut reader = BufReader::new(stdout);
+    while let Ok(message) = read_message(&mut reader) {
+        let id = message.get("id").cloned();
+        match (id, message.get("method")) {
+            // Requests from the server; answer with an empty result so it
+            // doesn't wait on features we don't implement.
+            (Some(id), Some(method)) => {
+                let result = match method.as_str() {
+                    Some("workspace/configuration") => {
+                        let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let _ = replies.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+            }
+            (Some(id), None) => {
+                let Some(id) = id.as_u64() else {
+                    continue;
+                };
+                if id == INITIALIZE_ID {
+                    let _ = initialized.send(());
+                }
+                let result = match message.get("error") {
+                    Some(error) => Err(error["message"].as_str().unwrap_or("unknown error").to_string()),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                if responses.send((id, result)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+            (None, _) => {}
+        }
+    }
+}
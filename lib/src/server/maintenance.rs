@@ -0,0 +1,115 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::wake::Waker;
+
+// Runs every `interval`. Returns whether it found something for the UI to
+// do, which wakes it.
+pub struct Task {
+    pub interval: Duration,
+    pub run: Box<dyn FnMut() -> bool + Send>,
+}
+
+impl Task {
+    pub fn new(interval: Duration, run: impl FnMut() -> bool + Send + 'static) -> Self {
+        Self { interval, run: Box::new(run) }
+    }
+}
+
+enum Event {
+    RunNow,
+    Shutdown,
+}
+
+// Periodic upkeep on a thread of its own. Between ticks it waits on its
+// channel rather than polling, and the UI is only woken when a task asks
+// for it, so an idle editor doesn't redraw just to check on things.
+pub struct Maintenance {
+    events: mpsc::Sender<Event>,
+}
+
+impl Maintenance {
+    pub fn spawn(tasks: Vec<Task>, waker: Waker) -> Self {
+        let (events, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let now = Instant::now();
+            let mut tasks: Vec<(Task, Instant)> = tasks
+                .into_iter()
+                .map(|task| {
+                    let due = now + task.interval;
+                    (task, due)
+                })
+                .collect();
+            loop {
+                let next = tasks.iter().map(|(_, due)| *due).min();
+                let event = match next {
+                    Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                let now = Instant::now();
+                match event {
+                    Ok(Event::RunNow) => tasks.iter_mut().for_each(|(_, due)| *due = now),
+                    Ok(Event::Shutdown) | Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+                let mut wake = false;
+                for (task, due) in tasks.iter_mut().filter(|(_, due)| *due <= now) {
+                    wake |= (task.run)();
+                    *due = now + task.interval;
+                }
+                if wake {
+                    waker.wake();
+                }
+            }
+        });
+        Self { events }
+    }
+
+    // Runs every task now instead of at its next tick.
+    pub fn run_now(&self) {
+        let _ = self.events.send(Event::RunNow);
+    }
+}
+
+impl Drop for Maintenance {
+    fn drop(&mut self) {
+        let _ = self.events.send(Event::Shutdown);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn tasks_run_on_their_tick_or_when_asked_and_only_wake_when_they_say() {
+        let (ran, runs) = mpsc::channel();
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker = Waker::default();
+        let counted = wakes.clone();
+        waker.set(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+        });
+        let hourly = ran.clone();
+        let maintenance = Maintenance::spawn(
+            vec![
+                Task::new(Duration::from_secs(3600), move || hourly.send("hourly").is_ok()),
+                Task::new(Duration::from_millis(10), move || ran.send("quiet").is_err()),
+            ],
+            waker,
+        );
+        let timeout = Duration::from_secs(5);
+        assert_eq!(runs.recv_timeout(timeout), Ok("quiet"));
+        assert_eq!(runs.recv_timeout(timeout), Ok("quiet"));
+        assert_eq!(wakes.load(Ordering::SeqCst), 0);
+
+        maintenance.run_now();
+        while runs.recv_timeout(timeout) != Ok("hourly") {}
+        drop(maintenance);
+        while runs.recv_timeout(timeout).is_ok() {}
+        assert_eq!(wakes.load(Ordering::SeqCst), 1);
+    }
+}
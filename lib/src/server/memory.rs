@@ -0,0 +1,110 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::persistence::Storage;
+use crate::shared::buffers::BufferStorage;
+use crate::shared::Shared;
+
+// Undo groups that always stay in memory so recent undos don't hit the disk.
+const KEEP_UNDO_GROUPS: usize = 8;
+
+// Caps the memory held by buffer contents and undo history. Contents can't
+// be evicted, so going over the budget spills the oldest undo groups of the
+// buffers with the most history to `spill_dir`.
+pub struct MemoryBudget {
+    pub bytes: usize,
+    pub spill_dir: PathBuf,
+    spill_count: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(bytes: usize, spill_dir: PathBuf) -> Self {
+        Self {
+            bytes,
+            spill_dir,
+            spill_count: 0,
+        }
+    }
+
+    pub fn used_bytes(buffers: &BufferStorage) -> usize {
+        buffers
+            .buffers
+            .iter()
+            .map(|buffer| buffer.content().len() + buffer.history.memory_bytes())
+            .sum()
+    }
+
+    // Returns the number of bytes spilled.
+    pub fn enforce(&mut self, shared: &Shared, storage: &Arc<dyn Storage>) -> io::Result<usize> {
+        shared.with_buffers_mut(|buffers| self.spill(buffers, storage))?
+    }
+
+    fn spill(&mut self, buffers: &mut BufferStorage, storage: &Arc<dyn Storage>) -> io::Result<usize> {
+        let mut used = Self::used_bytes(buffers);
+        let mut spilled = 0;
+        while used > self.bytes {
+            let Some(buffer) = buffers
+                .buffers
+                .iter_mut()
+                .max_by_key(|buffer| buffer.history.memory_bytes())
+            else {
+                break;
+            };
+            storage.create_dir(&self.spill_dir)?;
+            let path = self
+                .spill_dir
+                .join(format!("{}-{}.json", std::process::id(), self.spill_count));
+            let freed = buffer.history.spill(storage, &path, KEEP_UNDO_GROUPS)?;
+            if freed == 0 {
+                break;
+            }
+            self.spill_count += 1;
+            used -= freed;
+            spilled += freed;
+        }
+        Ok(spilled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::enums::BufferActions;
+    use crate::server::persistence::InMemoryStorage;
+    use crate::shared::buffers::Buffer;
+    use crate::shared::frames::FrameStorage;
+
+    #[test]
+    fn going_over_budget_spills_undo_history_that_undo_reads_back() {
+        let lines: Vec<String> = (0..20).map(|line| format!("\nline {line:02} {}", "x".repeat(100))).collect();
+        let original = format!("start{}", lines.concat());
+        let mut buffer = Buffer::with_content(original.clone());
+        // Deleting a line keeps its text in the undo history.
+        for line in lines.iter().rev() {
+            let end = buffer.content().len();
+            buffer.edit(&[BufferActions::DeleteRange { start: end - line.len(), end }]);
+        }
+        let shared = Shared::new(BufferStorage { buffers: vec![buffer] }, FrameStorage::default());
+        let storage: Arc<dyn Storage> = Arc::new(InMemoryStorage::default());
+        let spill_dir = PathBuf::from("/data/spill");
+        let mut budget = MemoryBudget::new(2000, spill_dir.clone());
+
+        let before = shared.with_buffers(MemoryBudget::used_bytes).unwrap();
+        let spilled = budget.enforce(&shared, &storage).unwrap();
+        assert!(spilled > 0);
+        assert_eq!(shared.with_buffers(MemoryBudget::used_bytes).unwrap(), before - spilled);
+        let spill_file = spill_dir.join(format!("{}-0.json", std::process::id()));
+        assert!(storage.exists(&spill_file));
+        assert_eq!(budget.enforce(&shared, &storage).unwrap(), 0);
+
+        shared
+            .with_buffers_mut(|buffers| {
+                let buffer = &mut buffers.buffers[0];
+                while buffer.undo() {}
+                assert_eq!(buffer.content(), original);
+            })
+            .unwrap();
+        assert!(!storage.exists(&spill_file));
+    }
+}
@@ -0,0 +1,694 @@
+pub mod blame;
+pub mod bus;
+pub mod capture;
+pub mod chunks;
+pub mod diff;
+pub mod encoding;
+pub mod geometry;
+pub mod hash;
+pub mod loader;
+pub mod maintenance;
+pub mod lsp;
+pub mod memory;
+pub mod parse;
+pub mod persistence;
+pub mod positions;
+pub mod recent;
+pub mod search;
+pub mod session;
+pub mod spell;
+pub mod stream;
+pub mod symbols;
+pub mod syntax;
+pub mod wake;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use encoding_rs::Encoding;
+
+use crate::config::Config;
+use crate::interfaces::enums::{BufferEvent, RiptideEvents};
+use crate::shared::buffers::annotations::AnnotationProvider;
+use crate::shared::buffers::{byte_index, Buffer, ExternalAction, ExternalChange, FinalNewlinePolicy};
+use crate::shared::Shared;
+use blame::GitBlame;
+use bus::{Bus, Throttle, Throttled};
+use chunks::ChunkReader;
+use geometry::{SavedGeometry, WINDOW_GEOMETRY_NAME};
+use lsp::LspManager;
+use memory::MemoryBudget;
+use persistence::{MmapStorage, Storage};
+use positions::{FilePositions, FILE_POSITIONS_NAME};
+use recent::{RecentFiles, RECENT_FILES_NAME};
+use session::{Session, SESSION_EXTENSION, SNAPSHOT_DIR_NAME};
+use wake::Waker;
+
+// Cursor events go out at most this often while the cursor keeps moving.
+pub const CURSOR_EVENT_INTERVAL: Duration = Duration::from_millis(50);
+
+// What `Server::save_all` did.
+#[derive(Debug)]
+pub struct SaveAll {
+    // Each buffer it tried, with the count `save_buffer` gives.
+    pub saved: Vec<(usize, io::Result<usize>)>,
+    // Modified buffers with no file yet, to be saved as one.
+    pub untitled: Vec<usize>,
+}
+
+pub struct Server {
+    pub storage: Arc<dyn Storage>,
+    pub data_dir: PathBuf,
+    pub session_dir: PathBuf,
+    pub snapshot_limit: usize,
+    pub recent: RecentFiles,
+    pub positions: FilePositions,
+    pub lsp: LspManager,
+    pub memory_budget: MemoryBudget,
+    pub bus: Bus,
+    pub cursor_throttle: Throttle<(usize, usize)>,
+    pub final_newline: FinalNewlinePolicy,
+    pub big_file_threshold: u64,
+    pub chunks: ChunkReader,
+    pub annotators: Vec<Box<dyn AnnotationProvider>>,
+    pub waker: Waker,
+}
+
+impl Server {
+    pub fn new(config: &Config) -> Self {
+        Self::with_storage(config, Arc::new(MmapStorage))
+    }
+
+    // Everything is read from and written to `storage`, including the
+    // recent files and positions loaded here.
+    pub fn with_storage(config: &Config, storage: Arc<dyn Storage>) -> Self {
+        let recent = RecentFiles::load(
+            storage.as_ref(),
+            &config.data_dir.join(RECENT_FILES_NAME),
+            config.recent_files_limit,
+        );
+        let positions = FilePositions::load(
+            storage.as_ref(),
+            &config.data_dir.join(FILE_POSITIONS_NAME),
+            config.file_positions_limit,
+        );
+        let waker = Waker::default();
+        let mut annotators: Vec<Box<dyn AnnotationProvider>> = Vec::new();
+        if config.inline_blame {
+            annotators.push(Box::new(GitBlame));
+        }
+        Self {
+            storage,
+            data_dir: config.data_dir.clone(),
+            session_dir: config.session_path.clone(),
+            snapshot_limit: config.session_snapshots,
+            recent,
+            positions,
+            lsp: LspManager::new(
+                config.language_servers.clone(),
+                &std::env::current_dir().unwrap_or_default(),
+                waker.clone(),
+            ),
+            memory_budget: MemoryBudget::new(config.memory_budget_bytes, config.data_dir.join("spill")),
+            bus: Bus::new(config.channel_capacity, waker.clone()),
+            cursor_throttle: Throttle::new(CURSOR_EVENT_INTERVAL),
+            final_newline: config.final_newline,
+            big_file_threshold: config.big_file.threshold_bytes,
+            chunks: ChunkReader::default(),
+            annotators,
+            waker,
+        }
+    }
+
+    // Files big enough for big-file mode are read through the chunk cache,
+    // so opening one again while it's unchanged doesn't map it again.
+    pub fn open_file(&mut self, shared: &Shared, path: &Path) -> io::Result<usize> {
+        let storage = self.storage.as_ref();
+        let buffer = if storage.metadata(path).is_ok_and(|metadata| metadata.len > self.big_file_threshold) {
+            Buffer::from_bytes(path, &self.chunks.read_file(storage, path)?, storage)
+        } else {
+            Buffer::from_file(path, storage)?
+        };
+        self.insert_buffer(shared, buffer)
+    }
+
+    // Turns bytes read elsewhere, e.g. by a background load, into a buffer.
+    pub fn add_buffer(&mut self, shared: &Shared, path: &Path, bytes: &[u8]) -> io::Result<usize> {
+        self.insert_buffer(shared, Buffer::from_bytes(path, bytes, self.storage.as_ref()))
+    }
+
+    fn insert_buffer(&mut self, shared: &Shared, mut buffer: Buffer) -> io::Result<usize> {
+        let file_path = buffer.file_path.clone();
+        buffer.big_file = buffer.content().len() as u64 > self.big_file_threshold;
+        if buffer.big_file {
+            log::warn!("opening {} in big-file mode", file_path.display());
+        }
+        let buffer_index = shared.with_buffers_mut(|buffers| {
+            buffers.buffers.push(buffer);
+            buffers.buffers.len() - 1
+        })?;
+
+        self.refresh_annotations(shared, buffer_index);
+        self.recent.push(file_path);
+        if let Err(err) = self.save_recent_files() {
+            log::error!("cant save recent files: {err}");
+        }
+        Ok(buffer_index)
+    }
+
+    // Applies the final newline policy to the buffer first, but skips the
+    // write when the content is what was last saved and the file is still
+    // there. Returns how many characters the buffer's encoding couldn't
+    // represent (written as `?`), or `None` when nothing was written.
+    pub fn save_buffer(&mut self, shared: &Shared, buffer_index: usize) -> io::Result<Option<usize>> {
+        self.write_buffer(shared, buffer_index, false)
+    }
+
+    // Points the buffer at `path` and writes it there, even if unchanged.
+    // The scratch pad saved this way becomes an ordinary file buffer.
+    pub fn save_buffer_as(&mut self, shared: &Shared, buffer_index: usize, path: &Path) -> io::Result<usize> {
+        shared.with_buffers_mut(|buffers| {
+            let buffer = buffers
+                .buffers
+                .get_mut(buffer_index)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such buffer"))?;
+            buffer.file_path = path.to_path_buf();
+            buffer.language = lsp::language_id(path);
+            buffer.is_scratch = false;
+            Ok::<_, io::Error>(())
+        })??;
+        self.lsp.close_unloaded(shared);
+        let unmappable = self.write_buffer(shared, buffer_index, true)?.unwrap_or_default();
+        self.recent.push(path.to_path_buf());
+        if let Err(err) = self.save_recent_files() {
+            log::error!("cant save recent files: {err}");
+        }
+        Ok(unmappable)
+    }
+
+    // Writes every modified buffer that has a file, carrying on past
+    // failures. Stream buffers are left alone.
+    pub fn save_all(&mut self, shared: &Shared) -> io::Result<SaveAll> {
+        let (titled, untitled): (Vec<_>, Vec<_>) = shared.with_buffers(|buffers| {
+            buffers
+                .buffers
+                .iter()
+                .enumerate()
+                .filter(|(_, buffer)| !buffer.is_stream && buffer.is_unsaved())
+                .map(|(buffer_index, buffer)| (buffer_index, !buffer.file_path.as_os_str().is_empty()))
+                .partition(|(_, has_path)| *has_path)
+        })?;
+        let saved = titled
+            .into_iter()
+            .map(|(buffer_index, _)| {
+                let saved = self.save_buffer(shared, buffer_index).map(Option::unwrap_or_default);
+                (buffer_index, saved)
+            })
+            .collect();
+        let untitled = untitled.into_iter().map(|(buffer_index, _)| buffer_index).collect();
+        Ok(SaveAll { saved, untitled })
+    }
+
+    fn write_buffer(&mut self, shared: &Shared, buffer_index: usize, force: bool) -> io::Result<Option<usize>> {
+        let saved = shared.with_buffers_mut(|buffers| {
+            let buffer = buffers
+                .buffers
+                .get_mut(buffer_index)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such buffer"))?;
+            if buffer.is_scratch {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "the scratch buffer isn't saved to a file"));
+            }
+            if buffer.file_path.as_os_str().is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "buffer has no file path"));
+            }
+            if !force && !buffer.is_modified() && self.storage.exists(&buffer.file_path) {
+                return Ok(None);
+            }
+            buffer.edit(&self.final_newline.edits(buffer.content()));
+            let (bytes, unmappable) = buffer.encoded();
+            self.storage.write(&buffer.file_path, &bytes, false)?;
+            buffer.mark_saved(self.storage.as_ref());
+            self.lsp.did_save(buffer);
+            Ok(Some((buffer.file_path.clone(), unmappable)))
+        })??;
+        let Some((path, unmappable)) = saved else {
+            return Ok(None);
+        };
+        self.bus.send(RiptideEvents::FileSaved { path });
+        self.refresh_annotations(shared, buffer_index);
+        Ok(Some(unmappable))
+    }
+
+    // Tells the bus where the cursor is, at most once per
+    // `CURSOR_EVENT_INTERVAL`. Returns how long to wait before offering a
+    // position that was held back again.
+    pub fn cursor_moved(&mut self, shared: &Shared, buffer_index: usize, char_idx: usize) -> Option<Duration> {
+        match self.cursor_throttle.offer((buffer_index, char_idx), Instant::now()) {
+            Throttled::Send => {}
+            Throttled::Hold(wait) => return Some(wait),
+            Throttled::Unchanged => return None,
+        }
+        let position = shared.with_buffers(|buffers| {
+            let content = buffers.buffers.get(buffer_index)?.content();
+            let before = &content[..byte_index(content, char_idx)];
+            let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+            Some((before.bytes().filter(|byte| *byte == b'\n').count(), before[line_start..].chars().count()))
+        });
+        match position {
+            Ok(Some((line, col))) => {
+                self.bus.send(RiptideEvents::BufferEvents(buffer_index, BufferEvent::CursorMoved { line, col }));
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("cant read cursor position: {err}"),
+        }
+        None
+    }
+
+    // Asks every provider again; called when the file is opened or saved.
+    // Big files and the scratch pad aren't annotated.
+    pub fn refresh_annotations(&mut self, shared: &Shared, buffer_index: usize) {
+        if self.annotators.is_empty() {
+            return;
+        }
+        let annotated = shared.with_buffers(|buffers| {
+            let buffer = buffers.buffers.get(buffer_index).filter(|buffer| !buffer.big_file && !buffer.is_scratch)?;
+            Some(
+                self.annotators
+                    .iter_mut()
+                    .map(|annotator| (annotator.source(), annotator.annotate(buffer)))
+                    .collect::<Vec<_>>(),
+            )
+        });
+        let Ok(Some(annotated)) = annotated else {
+            return;
+        };
+        let stored = shared.with_buffers_mut(|buffers| {
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                for (source, entries) in annotated {
+                    buffer.set_annotations(source, entries);
+                }
+            }
+        });
+        if let Err(err) = stored {
+            log::error!("cant annotate buffer {buffer_index}: {err}");
+        }
+    }
+
+    // Re-saves the buffer in `target`, which later saves keep using.
+    pub fn convert_encoding(
+        &mut self,
+        shared: &Shared,
+        buffer_index: usize,
+        target: &'static Encoding,
+    ) -> io::Result<usize> {
+        let previous = shared.with_buffers_mut(|buffers| {
+            let buffer = buffers.buffers.get_mut(buffer_index)?;
+            Some(std::mem::replace(&mut buffer.encoding, target))
+        })?;
+        let Some(previous) = previous else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such buffer"));
+        };
+        // The content may be unchanged, but the bytes on disk aren't.
+        let saved = self.write_buffer(shared, buffer_index, true).map(Option::unwrap_or_default);
+        if saved.is_err() {
+            shared.with_buffers_mut(|buffers| {
+                if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                    buffer.encoding = previous;
+                }
+            })?;
+        }
+        saved
+    }
+
+    // Decodes the file on disk again as `source`, e.g. to fix a file that
+    // was opened as UTF-8 but isn't. The reload is one undo step.
+    pub fn reinterpret_buffer(
+        &self,
+        shared: &Shared,
+        buffer_index: usize,
+        source: &'static Encoding,
+    ) -> io::Result<()> {
+        let path = shared.with_buffers(|buffers| Some(buffers.buffers.get(buffer_index)?.file_path.clone()))?;
+        let Some(path) = path else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no such buffer"));
+        };
+        let bytes = self.storage.read(&path)?;
+        shared.with_buffers_mut(|buffers| {
+            if let Some(buffer) = buffers.buffers.get_mut(buffer_index) {
+                buffer.replace_decoded(&bytes, source);
+            }
+        })?;
+        Ok(())
+    }
+
+    // Replaces the buffer with the file on disk, unsaved changes or not. The
+    // reload is one undo step, so discarded changes can still be undone.
+    pub fn reload_buffer(&self, shared: &Shared, buffer_index: usize) -> io::Result<()> {
+        shared.with_buffers_mut(|buffers| {
+            let buffer = buffers
+                .buffers
+                .get_mut(buffer_index)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such buffer"))?;
+            buffer.reload(self.storage.as_ref())
+        })?
+    }
+
+    // Buffers whose files changed on disk since the last call, with what
+    // their policy (or `default`) says to do about it.
+    pub fn external_changes(&self, shared: &Shared, default: ExternalChange) -> io::Result<Vec<(usize, ExternalAction)>> {
+        Ok(shared.with_buffers_mut(|buffers| {
+            buffers
+                .buffers
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(buffer_index, buffer)| {
+                    let policy = buffer.external_change.unwrap_or(default);
+                    buffer
+                        .take_disk_change(self.storage.as_ref())
+                        .then(|| (buffer_index, policy.action(buffer.is_modified())))
+                })
+                .collect()
+        })?)
+    }
+
+    // The buffer's file as it is on disk, and whether it changed since the
+    // buffer last read or wrote it.
+    pub fn disk_state(&self, shared: &Shared, buffer_index: usize) -> io::Result<(String, bool)> {
+        shared.with_buffers(|buffers| {
+            let buffer = buffers
+                .buffers
+                .get(buffer_index)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such buffer"))?;
+            let disk = buffer.disk_content(self.storage.as_ref())?;
+            let changed = hash::content_hash(&disk) != buffer.saved_hash;
+            Ok((disk, changed))
+        })?
+    }
+
+    pub fn enforce_memory_budget(&mut self, shared: &Shared) {
+        if let Err(err) = self.memory_budget.enforce(shared, &self.storage) {
+            log::error!("cant spill undo history: {err}");
+        }
+    }
+
+    pub fn recent_files(&self) -> Vec<PathBuf> {
+        self.recent.existing()
+    }
+
+    fn session_path(&self, name: &str) -> io::Result<PathBuf> {
+        session::validate_name(name)?;
+        Ok(self.session_dir.join(name).with_extension(SESSION_EXTENSION))
+    }
+
+    pub fn save_session(&self, shared: &Shared, name: &str) -> io::Result<()> {
+        let path = self.session_path(name)?;
+        self.write_encoded(&path, || Ok(serde_json::to_vec(&Session::capture(shared)?)?))
+    }
+
+    // Encodes fully in memory first and then replaces `path` in one rename,
+    // so the old file stays as it was unless the new one made it out whole.
+    pub fn write_encoded(&self, path: &Path, encode: impl FnOnce() -> io::Result<Vec<u8>>) -> io::Result<()> {
+        let bytes = session::encode_guarded(encode)?;
+        if let Some(dir) = path.parent() {
+            self.storage.create_dir(dir)?;
+        }
+        self.storage.write_atomic(path, &bytes)
+    }
+
+    // The current buffers and frames are only replaced once the session has
+    // been read successfully.
+    pub fn load_session(&self, shared: &Shared, name: &str) -> io::Result<()> {
+        let bytes = self.storage.read(&self.session_path(name)?)?;
+        let session: Session = serde_json::from_slice(&bytes)?;
+        session.restore(shared)?;
+        Ok(())
+    }
+
+    fn snapshot_path(&self, index: usize) -> PathBuf {
+        self.session_dir
+            .join(SNAPSHOT_DIR_NAME)
+            .join(format!("session.{index}.{SESSION_EXTENSION}"))
+    }
+
+    // Keeps the last `snapshot_limit` sessions: each older snapshot moves up
+    // one index, dropping the oldest, and the new one becomes `0`. Nothing
+    // shifts unless the session could be captured and differs from the
+    // newest snapshot.
+    pub fn save_session_rotating(&self, shared: &Shared) -> io::Result<()> {
+        if self.snapshot_limit == 0 {
+            return Ok(());
+        }
+        let bytes = session::encode_guarded(|| Ok(serde_json::to_vec(&Session::capture(shared)?)?))?;
+        // An idle editor would otherwise rotate the good snapshots away.
+        if self.storage.read(&self.snapshot_path(0)).is_ok_and(|newest| newest == bytes) {
+            return Ok(());
+        }
+        self.storage.create_dir(&self.session_dir.join(SNAPSHOT_DIR_NAME))?;
+        for index in (0..self.snapshot_limit - 1).rev() {
+            match self.storage.rename(&self.snapshot_path(index), &self.snapshot_path(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        self.storage.write_atomic(&self.snapshot_path(0), &bytes)
+    }
+
+    // Indices of the snapshots on disk, newest first.
+    pub fn list_snapshots(&self) -> Vec<usize> {
+        (0..self.snapshot_limit)
+            .filter(|&index| self.storage.exists(&self.snapshot_path(index)))
+            .collect()
+    }
+
+    pub fn restore_snapshot(&self, shared: &Shared, index: usize) -> io::Result<()> {
+        let bytes = self.storage.read(&self.snapshot_path(index))?;
+        let session: Session = serde_json::from_slice(&bytes)?;
+        session.restore(shared)?;
+        Ok(())
+    }
+
+    pub fn list_sessions(&self) -> Vec<String> {
+        let Ok(entries) = self.storage.list_dir(&self.session_dir) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|extension| extension == SESSION_EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn load_geometry(&self) -> SavedGeometry {
+        SavedGeometry::load(self.storage.as_ref(), &self.data_dir.join(WINDOW_GEOMETRY_NAME))
+    }
+
+    pub fn save_geometry(&self, geometry: &SavedGeometry) -> io::Result<()> {
+        self.storage.create_dir(&self.data_dir)?;
+        geometry.save(self.storage.as_ref(), &self.data_dir.join(WINDOW_GEOMETRY_NAME))
+    }
+
+    pub fn save_positions(&self) -> io::Result<()> {
+        self.storage.create_dir(&self.data_dir)?;
+        self.positions
+            .save(self.storage.as_ref(), &self.data_dir.join(FILE_POSITIONS_NAME))
+    }
+
+    fn save_recent_files(&self) -> io::Result<()> {
+        self.storage.create_dir(&self.data_dir)?;
+        self.recent
+            .save(self.storage.as_ref(), &self.data_dir.join(RECENT_FILES_NAME))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::enums::BufferActions;
+    use crate::server::persistence::InMemoryStorage;
+    use crate::shared::buffers::BufferStorage;
+    use crate::shared::frames::{Frame, FrameCluster, FrameKind, FrameStorage};
+
+    fn edited(setup: impl FnOnce(&mut Buffer)) -> Buffer {
+        let mut buffer = Buffer::with_content("edited\n");
+        setup(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn save_all_writes_files_and_leaves_untitled_buffers_for_save_as() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut server = Server::with_storage(&Config::default(), storage.clone());
+        let path = PathBuf::from("/project/notes.txt");
+        let buffers = BufferStorage {
+            buffers: vec![
+                edited(|buffer| buffer.file_path = path.clone()),
+                edited(|_| {}),
+                edited(|buffer| buffer.is_terminal = true),
+                edited(|buffer| buffer.is_stream = true),
+                edited(|buffer| buffer.is_scratch = true),
+            ],
+        };
+        let shared = Shared::new(buffers, FrameStorage::default());
+        let SaveAll { saved, untitled } = server.save_all(&shared).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert!(matches!(saved[0], (0, Ok(0))));
+        assert_eq!(untitled, vec![1]);
+        assert_eq!(storage.read(&path).unwrap(), b"edited\n");
+        assert!(!shared.with_buffers(|buffers| buffers.buffers[0].is_modified()).unwrap());
+    }
+
+    #[test]
+    fn files_round_trip_through_the_servers_storage() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let path = PathBuf::from("/project/main.rs");
+        storage.write(&path, b"fn main() {}\r\n", false).unwrap();
+        let mut server = Server::with_storage(&Config::default(), storage.clone());
+        let shared = Shared::default();
+        let buffer_index = server.open_file(&shared, &path).unwrap();
+        shared
+            .with_buffers_mut(|buffers| {
+                let buffer = &mut buffers.buffers[buffer_index];
+                assert_eq!(buffer.content(), "fn main() {}\n");
+                buffer.edit(&[BufferActions::InsertText { char_idx: 11, text: String::from(" run(); ") }]);
+            })
+            .unwrap();
+        assert_eq!(server.save_buffer(&shared, buffer_index).unwrap(), Some(0));
+        assert_eq!(storage.read(&path).unwrap(), b"fn main() { run(); }\r\n");
+        assert_eq!(server.save_buffer(&shared, buffer_index).unwrap(), None);
+    }
+
+    #[test]
+    fn named_sessions_round_trip_buffers_and_editor_frames() {
+        let server = Server::with_storage(&Config::default(), Arc::new(InMemoryStorage::default()));
+        let notes = edited(|notes| {
+            notes.file_path = PathBuf::from("/project/notes.md");
+            notes.marks.insert('a', 3);
+            notes.folds.push(0..1);
+        });
+        let buffers = BufferStorage { buffers: vec![notes, edited(|buffer| buffer.is_scratch = true)] };
+        let editor = Frame { buffer_index: 1, scroll_offset: 40.0, scroll_group: Some(2), ..Frame::default() };
+        let terminal = Frame { kind: FrameKind::Terminal { closed: false }, ..Frame::default() };
+        let frames = FrameStorage {
+            frame_clusters: vec![FrameCluster { name: String::from("docs"), frames: vec![editor, terminal], ..FrameCluster::default() }],
+        };
+        server.save_session(&Shared::new(buffers, frames), "work").unwrap();
+
+        let shared = Shared::default();
+        server.load_session(&shared, "work").unwrap();
+        shared
+            .with_buffers(|buffers| {
+                let [notes, scratch] = &buffers.buffers[..] else { panic!("expected two buffers") };
+                assert_eq!((notes.content(), notes.marks.get(&'a')), ("edited\n", Some(&3)));
+                assert_eq!(notes.folds, vec![0..1]);
+                assert_eq!(notes.language, "markdown");
+                assert!(scratch.is_scratch);
+            })
+            .unwrap();
+        shared
+            .with_frames(|frames| {
+                let cluster = &frames.frame_clusters[0];
+                assert_eq!(cluster.name, "docs");
+                let restored: Vec<_> = cluster.frames.iter().map(|frame| (frame.buffer_index, frame.scroll_offset, frame.scroll_group)).collect();
+                assert_eq!(restored, vec![(1, 40.0, Some(2))]);
+            })
+            .unwrap();
+        assert!(server.load_session(&shared, "missing").is_err());
+        assert!(server.save_session(&shared, "../escape").is_err());
+    }
+
+    #[test]
+    fn files_over_the_threshold_open_in_big_file_mode() {
+        let mut server = Server::with_storage(&Config::default(), Arc::new(InMemoryStorage::default()));
+        server.big_file_threshold = 10;
+        let shared = Shared::default();
+        let small = server.add_buffer(&shared, Path::new("/project/small.rs"), b"fn a() {}\n").unwrap();
+        let big = server.add_buffer(&shared, Path::new("/project/big.rs"), b"fn a() {}\nfn b() {}\n").unwrap();
+        let modes = shared.with_buffers(|buffers| (buffers.buffers[small].big_file, buffers.buffers[big].big_file));
+        assert_eq!(modes.unwrap(), (false, true));
+        assert_eq!(server.recent.entries.first(), Some(&PathBuf::from("/project/big.rs")));
+    }
+
+    #[test]
+    fn cursor_positions_count_chars_after_multibyte_text() {
+        let mut server = Server::with_storage(&Config::default(), Arc::new(InMemoryStorage::default()));
+        let buffers = BufferStorage { buffers: vec![Buffer::with_content("héllo\nwörld ünï\n")] };
+        let shared = Shared::new(buffers, FrameStorage::default());
+        let events = server.bus.subscribe();
+        // Char 13 is the `n` of `ünï`, after two-byte chars on both lines.
+        assert_eq!(server.cursor_moved(&shared, 0, 13), None);
+        match events.try_recv() {
+            Some(RiptideEvents::BufferEvents(0, BufferEvent::CursorMoved { line, col })) => assert_eq!((line, col), (1, 7)),
+            other => panic!("expected a cursor event, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn big_files_reopen_from_the_chunk_cache_until_they_change() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let mut server = Server::with_storage(&Config::default(), storage.clone());
+        server.big_file_threshold = 10;
+        let shared = Shared::default();
+        let path = Path::new("/project/big.log");
+        storage.write(path, b"first line\nsecond line\n", false).unwrap();
+
+        let first = server.open_file(&shared, path).unwrap();
+        assert_eq!(server.chunks.hits(), 0);
+        let again = server.open_file(&shared, path).unwrap();
+        assert_eq!(server.chunks.hits(), 1);
+
+        storage.write(path, b"rewritten on disk\n", false).unwrap();
+        let changed = server.open_file(&shared, path).unwrap();
+        assert_eq!(server.chunks.hits(), 1);
+        let contents = shared.with_buffers(|buffers| {
+            [first, again, changed].map(|index| (buffers.buffers[index].content().to_string(), buffers.buffers[index].big_file))
+        });
+        assert_eq!(
+            contents.unwrap(),
+            [
+                (String::from("first line\nsecond line\n"), true),
+                (String::from("first line\nsecond line\n"), true),
+                (String::from("rewritten on disk\n"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshots_rotate_skip_unchanged_sessions_and_restore() {
+        let config = Config { session_snapshots: 2, ..Config::default() };
+        let server = Server::with_storage(&config, Arc::new(InMemoryStorage::default()));
+        let shared = Shared::default();
+        let set_content = |content: &str| {
+            shared.with_buffers_mut(|buffers| buffers.buffers[0].set_content(content)).unwrap();
+        };
+        assert!(server.list_snapshots().is_empty());
+        set_content("first");
+        server.save_session_rotating(&shared).unwrap();
+        server.save_session_rotating(&shared).unwrap();
+        assert_eq!(server.list_snapshots(), vec![0]);
+        for content in ["second", "third"] {
+            set_content(content);
+            server.save_session_rotating(&shared).unwrap();
+        }
+        assert_eq!(server.list_snapshots(), vec![0, 1]);
+
+        let restored = Shared::default();
+        server.restore_snapshot(&restored, 1).unwrap();
+        assert_eq!(restored.with_buffers(|buffers| buffers.buffers[0].content().to_string()).unwrap(), "second");
+        assert!(server.restore_snapshot(&restored, 2).is_err());
+    }
+
+    #[test]
+    fn a_failed_encoding_leaves_the_old_file() {
+        let storage = Arc::new(InMemoryStorage::default());
+        let server = Server::with_storage(&Config::default(), storage.clone());
+        let path = PathBuf::from("/sessions/work.json");
+        server.write_encoded(&path, || Ok(b"old".to_vec())).unwrap();
+        assert!(server.write_encoded(&path, || panic!("bad frame")).is_err());
+        assert!(server.write_encoded(&path, || Err(io::Error::other("full"))).is_err());
+        assert_eq!(storage.read(&path).unwrap(), b"old");
+    }
+}
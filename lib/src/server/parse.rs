@@ -0,0 +1,222 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tree_sitter::{Language, ParseOptions, Parser, Tree};
+
+use super::bus::Throttled;
+use super::hash;
+use super::syntax::{self, TokenKind};
+use super::wake::Waker;
+
+// Typing holds off a parse until it pauses this long, unless this many
+// bytes have changed since the last one.
+pub const PARSE_IDLE: Duration = Duration::from_millis(150);
+pub const PARSE_BATCH_BYTES: usize = 4096;
+
+pub type Spans = Arc<Vec<(Range<usize>, TokenKind)>>;
+pub type Errors = Arc<Vec<Range<usize>>>;
+
+// The outcome of one parse and the text it was made from.
+#[derive(Clone)]
+pub struct Parsed {
+    pub content: Arc<str>,
+    pub spans: Spans,
+    pub errors: Errors,
+}
+
+// Decides when edited text is worth parsing again: the first text right
+// away, after that once edits pause or pile up.
+#[derive(Default)]
+pub struct ParseThrottle {
+    submitted: Option<u64>,
+    seen: Option<(u64, usize)>,
+    edited_at: Option<Instant>,
+    unparsed_bytes: usize,
+}
+
+impl ParseThrottle {
+    pub fn offer(&mut self, content: &str, now: Instant) -> Throttled {
+        let hash = hash::content_hash(content);
+        if self.submitted == Some(hash) {
+            return Throttled::Unchanged;
+        }
+        match self.seen {
+            Some((seen, _)) if seen == hash => {}
+            Some((_, len)) => {
+                self.seen = Some((hash, content.len()));
+                self.edited_at = Some(now);
+                self.unparsed_bytes += content.len().abs_diff(len).max(1);
+            }
+            None => self.seen = Some((hash, content.len())),
+        }
+        let waited = self.edited_at.map_or(PARSE_IDLE, |edited_at| now.duration_since(edited_at));
+        if self.submitted.is_some() && waited < PARSE_IDLE && self.unparsed_bytes < PARSE_BATCH_BYTES {
+            return Throttled::Hold(PARSE_IDLE - waited);
+        }
+        self.submitted = Some(hash);
+        self.unparsed_bytes = 0;
+        Throttled::Send
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    pending: Option<Arc<str>>,
+    closed: bool,
+}
+
+struct Inner {
+    slot: Mutex<Slot>,
+    ready: Condvar,
+    // Set when newer text comes in; the parse underway gives up on it.
+    superseded: AtomicBool,
+    parsed: Mutex<Option<Parsed>>,
+    parses: AtomicUsize,
+}
+
+// Parses one document on its own thread. Only the newest text submitted is
+// parsed: older pending text is dropped and a parse underway is cancelled.
+// Each parse reuses the last tree, edited to the new text.
+pub struct BackgroundParser {
+    inner: Arc<Inner>,
+}
+
+impl BackgroundParser {
+    pub fn spawn(language: Language, waker: Waker) -> Self {
+        let inner = Arc::new(Inner {
+            slot: Mutex::new(Slot::default()),
+            ready: Condvar::new(),
+            superseded: AtomicBool::new(false),
+            parsed: Mutex::new(None),
+            parses: AtomicUsize::new(0),
+        });
+        let worker = inner.clone();
+        thread::spawn(move || {
+            let mut parser = Parser::new();
+            if let Err(err) = parser.set_language(&language) {
+                log::error!("cant set parser language: {err}");
+                return;
+            }
+            let mut last: Option<(Arc<str>, Tree)> = None;
+            while let Some(content) = worker.next() {
+                let old_tree = last.as_ref().map(|(old, tree)| {
+                    let mut tree = tree.clone();
+                    tree.edit(&syntax::input_edit(old, &content));
+                    tree
+                });
+                let bytes = content.as_bytes();
+                let mut cancelled = |_: &_| worker.superseded.load(Ordering::Relaxed);
+                let options = ParseOptions::new().progress_callback(&mut cancelled);
+                let Some(tree) = parser.parse_with_options(
+                    &mut |idx, _| bytes.get(idx..).unwrap_or_default(),
+                    old_tree.as_ref(),
+                    Some(options),
+                ) else {
+                    // Cancelled; the last tree still matches its own text.
+                    parser.reset();
+                    continue;
+                };
+                let spans = Arc::new(syntax::tree_highlights(&tree));
+                let errors = Arc::new(syntax::syntax_errors(&tree, &content));
+                if let Ok(mut parsed) = worker.parsed.lock() {
+                    *parsed = Some(Parsed { content: content.clone(), spans, errors });
+                }
+                worker.parses.fetch_add(1, Ordering::Relaxed);
+                last = Some((content, tree));
+                waker.wake();
+            }
+        });
+        Self { inner }
+    }
+
+    pub fn submit(&self, content: Arc<str>) {
+        let Ok(mut slot) = self.inner.slot.lock() else {
+            return;
+        };
+        slot.pending = Some(content);
+        self.inner.superseded.store(true, Ordering::Relaxed);
+        self.inner.ready.notify_one();
+    }
+
+    // The newest parse not taken yet.
+    pub fn take(&self) -> Option<Parsed> {
+        self.inner.parsed.lock().ok()?.take()
+    }
+
+    // How many parses have finished.
+    pub fn parses(&self) -> usize {
+        self.inner.parses.load(Ordering::Relaxed)
+    }
+}
+
+impl Inner {
+    // Blocks until there's text to parse; `None` once the parser is dropped.
+    fn next(&self) -> Option<Arc<str>> {
+        let mut slot = self.slot.lock().ok()?;
+        loop {
+            if slot.closed {
+                return None;
+            }
+            if let Some(content) = slot.pending.take() {
+                self.superseded.store(false, Ordering::Relaxed);
+                return Some(content);
+            }
+            slot = self.ready.wait(slot).ok()?;
+        }
+    }
+}
+
+impl Drop for BackgroundParser {
+    fn drop(&mut self) {
+        if let Ok(mut slot) = self.inner.slot.lock() {
+            slot.closed = true;
+            self.inner.superseded.store(true, Ordering::Relaxed);
+            self.inner.ready.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_are_parsed_once_typing_pauses_or_piles_up() {
+        let mut throttle = ParseThrottle::default();
+        let start = Instant::now();
+        assert_eq!(throttle.offer("fn a", start), Throttled::Send);
+        assert_eq!(throttle.offer("fn a", start), Throttled::Unchanged);
+        assert_eq!(throttle.offer("fn ab", start), Throttled::Hold(PARSE_IDLE));
+        let later = start + Duration::from_millis(50);
+        assert_eq!(throttle.offer("fn ab", later), Throttled::Hold(PARSE_IDLE - Duration::from_millis(50)));
+        assert_eq!(throttle.offer("fn ab", start + PARSE_IDLE), Throttled::Send);
+
+        let pasted = format!("fn ab{}", "x".repeat(PARSE_BATCH_BYTES));
+        assert_eq!(throttle.offer(&pasted, start + PARSE_IDLE), Throttled::Send);
+    }
+
+    #[test]
+    fn background_parses_report_spans_and_errors() {
+        let parser = BackgroundParser::spawn(tree_sitter_rust::LANGUAGE.into(), Waker::default());
+        parser.submit(Arc::from("fn main() {}\n"));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut parsed = None;
+        while parsed.is_none() && Instant::now() < deadline {
+            parsed = parser.take();
+            thread::yield_now();
+        }
+        let parsed = parsed.expect("parse finished");
+        assert_eq!(&*parsed.content, "fn main() {}\n");
+        assert!(parsed.spans.iter().any(|(range, _)| *range == (0..2)));
+        assert!(parsed.errors.is_empty());
+
+        parser.submit(Arc::from("fn main( {}\n"));
+        while parser.parses() < 2 && Instant::now() < deadline {
+            thread::yield_now();
+        }
+        assert!(!parser.take().expect("second parse").errors.is_empty());
+    }
+}
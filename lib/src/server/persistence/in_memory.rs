@@ -0,0 +1,148 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use memmap2::{Mmap, MmapMut};
+
+use super::{FileMetadata, Storage};
+
+// A map standing in for the filesystem, so save and load logic can be
+// exercised without touching disk. Directories always "exist", and are
+// listed as long as some file is inside them.
+#[derive(Debug, Default)]
+pub struct InMemoryStorage {
+    files: Mutex<HashMap<PathBuf, File>>,
+}
+
+#[derive(Debug)]
+struct File {
+    content: Vec<u8>,
+    modified: SystemTime,
+}
+
+impl File {
+    fn new(content: Vec<u8>) -> Self {
+        Self { content, modified: SystemTime::now() }
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+impl InMemoryStorage {
+    fn files(&self) -> io::Result<MutexGuard<'_, HashMap<PathBuf, File>>> {
+        self.files.lock().map_err(|_| io::Error::other("in-memory files poisoned"))
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files()?;
+        files.get(path).map(|file| file.content.clone()).ok_or_else(|| not_found(path))
+    }
+
+    fn write(&self, path: &Path, content: &[u8], _durable: bool) -> io::Result<()> {
+        self.files()?.insert(path.to_path_buf(), File::new(content.to_vec()));
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        let mut files = self.files()?;
+        let file = files.entry(path.to_path_buf()).or_insert_with(|| File::new(Vec::new()));
+        file.content.extend_from_slice(content);
+        file.modified = SystemTime::now();
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files()?;
+        let file = files.remove(from).ok_or_else(|| not_found(from))?;
+        files.insert(to.to_path_buf(), file);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.files()?.remove(path).map(drop).ok_or_else(|| not_found(path))
+    }
+
+    fn create_dir(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files().is_ok_and(|files| files.contains_key(path))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let files = self.files()?;
+        if let Some(file) = files.get(path) {
+            return Ok(FileMetadata { len: file.content.len() as u64, modified: file.modified, is_dir: false });
+        }
+        files
+            .iter()
+            .filter(|(file_path, _)| file_path.starts_with(path))
+            .map(|(_, file)| file.modified)
+            .max()
+            .map(|modified| FileMetadata { len: 0, modified, is_dir: true })
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn list_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let entries: BTreeSet<PathBuf> = self
+            .files()?
+            .keys()
+            .filter_map(|path| Some(dir.join(path.strip_prefix(dir).ok()?.components().next()?)))
+            .collect();
+        Ok(entries.into_iter().collect())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    // A private anonymous map holding a copy, as nothing backs a file here.
+    fn map(&self, path: &Path, offset: u64, len: usize) -> io::Result<Mmap> {
+        let files = self.files()?;
+        let content = &files.get(path).ok_or_else(|| not_found(path))?.content;
+        let start = (offset as usize).min(content.len());
+        let bytes = &content[start..(start + len).min(content.len())];
+        let mut map = MmapMut::map_anon(bytes.len())?;
+        map.copy_from_slice(bytes);
+        map.make_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_round_trip() {
+        let storage = InMemoryStorage::default();
+        let path = Path::new("/notes/today.md");
+        assert_eq!(storage.read(path).unwrap_err().kind(), io::ErrorKind::NotFound);
+        storage.write(path, b"one\n", false).unwrap();
+        storage.append(path, b"two\n").unwrap();
+        assert_eq!(storage.read(path).unwrap(), b"one\ntwo\n");
+        let moved = Path::new("/notes/yesterday.md");
+        storage.rename(path, moved).unwrap();
+        assert!(storage.read(path).is_err());
+        assert_eq!(storage.read(moved).unwrap(), b"one\ntwo\n");
+        storage.remove(moved).unwrap();
+        assert!(storage.remove(moved).is_err());
+    }
+
+    #[test]
+    fn atomic_writes_replace_the_file_and_leave_no_temp_behind() {
+        let storage = InMemoryStorage::default();
+        let path = Path::new("/session.json");
+        storage.write(path, b"old", false).unwrap();
+        storage.write_atomic(path, b"new").unwrap();
+        storage.write_atomic(path, b"newer").unwrap();
+        assert_eq!(storage.read(path).unwrap(), b"newer");
+        assert_eq!(storage.files().unwrap().len(), 1);
+    }
+}
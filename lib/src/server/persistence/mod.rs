@@ -0,0 +1,267 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+pub mod in_memory;
+
+pub use in_memory::InMemoryStorage;
+
+// What can be learned about a path without reading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub len: u64,
+    pub modified: SystemTime,
+    pub is_dir: bool,
+}
+
+// Where files are read from and written to. Everything the server
+// persists goes through one of these, so a backend that isn't the local
+// disk only has to implement this.
+pub trait Storage: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    // `durable` waits until the data has actually been stored, where that
+    // is a separate step.
+    fn write(&self, path: &Path, content: &[u8], durable: bool) -> io::Result<()>;
+    fn append(&self, path: &Path, content: &[u8]) -> io::Result<()>;
+    // Replaces `to` if it exists.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+    // Everything directly inside `dir`, in no particular order.
+    fn list_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+    // `path` made absolute with links resolved, so two paths to one file
+    // compare equal.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    // `len` bytes of `path` from `offset`, mapped read-only. The file may
+    // still change under the map, so callers keeping one compare
+    // `metadata` before trusting it again.
+    fn map(&self, path: &Path, offset: u64, len: usize) -> io::Result<Mmap>;
+
+    // Writes next to `path` and renames over it, so a crash mid-write leaves
+    // the old file intact instead of a truncated one. Each write has its own
+    // temp file, so two racing writes of one path can't mix.
+    fn write_atomic(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        let temp = temp_path(path);
+        if let Err(err) = self.write(&temp, content, true) {
+            let _ = self.remove(&temp);
+            return Err(err);
+        }
+        self.rename(&temp, path)
+    }
+}
+
+// `<path>.<pid>.<n>.tmp`, unique within and across running editors.
+fn temp_path(path: &Path) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.{}.tmp", process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+    PathBuf::from(temp)
+}
+
+// The local filesystem, reading and writing through memory maps.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MmapStorage;
+
+impl Storage for MmapStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        handle_mem_read(path)
+    }
+
+    fn write(&self, path: &Path, content: &[u8], durable: bool) -> io::Result<()> {
+        handle_mem_write(path, content, durable)
+    }
+
+    fn append(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        handle_append(path, content)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        handle_rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        handle_create_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = fs::metadata(path)?;
+        Ok(FileMetadata {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+            is_dir: metadata.is_dir(),
+        })
+    }
+
+    fn list_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(dir)?.map(|entry| Ok(entry?.path())).collect()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn map(&self, path: &Path, offset: u64, len: usize) -> io::Result<Mmap> {
+        let file = File::open(path)?;
+        // SAFETY: see the trait; the file can change under the map, which
+        // callers check for through `metadata`.
+        unsafe { MmapOptions::new().offset(offset).len(len).map(&file) }
+    }
+}
+
+// Empty files are returned as is; zero bytes can't be mapped.
+pub fn handle_mem_read(path: &Path) -> io::Result<Vec<u8>> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(Vec::new());
+    }
+    // SAFETY: the map is copied out immediately and dropped before returning.
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    Ok(mmap.to_vec())
+}
+
+// Flushing the map only hands the pages to the OS; `durable` additionally
+// waits for the data and the new file length to reach the disk.
+pub fn handle_mem_write(path: &Path, content: &[u8], durable: bool) -> io::Result<()> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+
+    if !content.is_empty() {
+        file.set_len(content.len() as u64)?;
+        // SAFETY: the file was just truncated and resized by us and the map
+        // does not outlive this function.
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        mmap.copy_from_slice(content);
+        mmap.flush()?;
+    }
+
+    if durable {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+// Plain buffered appends; the map used by `handle_mem_write` would have to be
+// remapped on every growth.
+pub fn handle_append(path: &Path, content: &[u8]) -> io::Result<()> {
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(content)
+}
+
+// Replaces `to` if it exists.
+pub fn handle_rename(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)
+}
+
+pub fn handle_create_dir(path: &Path) -> io::Result<()> {
+    fs::create_dir_all(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::config::Config;
+    use crate::server::Server;
+    use crate::shared::Shared;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn durable_and_plain_writes_replace_the_whole_file() {
+        let dir = TempDir::new("durable");
+        let path = dir.join("notes.txt");
+        MmapStorage.write(&path, b"a longer first version", true).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"a longer first version");
+        MmapStorage.write(&path, b"short", false).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"short");
+    }
+
+    #[test]
+    fn empty_files_read_as_empty() {
+        let dir = TempDir::new("empty");
+        let path = dir.join("empty.txt");
+        MmapStorage.write(&path, b"", true).unwrap();
+        assert_eq!(MmapStorage.read(&path).unwrap(), b"");
+        MmapStorage.write(&path, b"grown", false).unwrap();
+        MmapStorage.append(&path, b" more").unwrap();
+        assert_eq!(MmapStorage.read(&path).unwrap(), b"grown more");
+    }
+
+    #[test]
+    fn racing_atomic_writes_each_land_whole() {
+        let dir = TempDir::new("atomic");
+        let path = dir.join("session.json");
+        let contents = [vec![b'a'; 64 * 1024], vec![b'b'; 32 * 1024]];
+        thread::scope(|scope| {
+            for content in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        MmapStorage.write_atomic(path, content).unwrap();
+                    }
+                });
+            }
+        });
+        let written = MmapStorage.read(&path).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    // What callers rely on, whichever backend they're given.
+    fn exercise(storage: Arc<dyn Storage>, dir: &Path) {
+        let (from, to) = (dir.join("a.txt"), dir.join("b.txt"));
+        storage.create_dir(dir).unwrap();
+        assert_eq!(storage.read(&from).unwrap_err().kind(), io::ErrorKind::NotFound);
+        storage.write(&from, b"one", false).unwrap();
+        storage.append(&from, b" two").unwrap();
+        storage.write(&to, b"old", true).unwrap();
+        storage.rename(&from, &to).unwrap();
+        assert!(!storage.exists(&from));
+        assert_eq!(storage.read(&to).unwrap(), b"one two");
+        storage.remove(&to).unwrap();
+        assert!(!storage.exists(&to));
+
+        storage.write(&from, b"four", false).unwrap();
+        let metadata = storage.metadata(&from).unwrap();
+        assert_eq!((metadata.len, metadata.is_dir), (4, false));
+        assert_eq!(&storage.map(&from, 1, 2).unwrap()[..], b"ou");
+        assert!(storage.metadata(dir).unwrap().is_dir);
+        assert_eq!(storage.metadata(&to).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(storage.list_dir(dir).unwrap(), vec![from.clone()]);
+        storage.remove(&from).unwrap();
+
+        let sessions = dir.join("sessions");
+        storage.create_dir(&sessions).unwrap();
+        let server = Server::with_storage(&Config { session_path: sessions.clone(), ..Config::default() }, storage.clone());
+        server.save_session(&Shared::default(), "work").unwrap();
+        server.save_session(&Shared::default(), "notes").unwrap();
+        storage.write(&sessions.join("stray.txt"), b"", false).unwrap();
+        assert_eq!(server.list_sessions(), ["notes", "work"]);
+    }
+
+    #[test]
+    fn both_backends_behave_alike() {
+        let dir = TempDir::new("backends");
+        exercise(Arc::new(MmapStorage), dir.path());
+        exercise(Arc::new(InMemoryStorage::default()), dir.path());
+    }
+}
@@ -0,0 +1,89 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::persistence::Storage;
+
+pub const FILE_POSITIONS_NAME: &str = "file_positions.json";
+
+// Where the cursor was last left in each file, as a char index, most
+// recently used first.
+pub struct FilePositions {
+    pub capacity: usize,
+    pub entries: Vec<(PathBuf, usize)>,
+}
+
+impl FilePositions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+        }
+    }
+
+    // Files that no longer exist are dropped on the way in.
+    pub fn load(storage: &dyn Storage, path: &Path, capacity: usize) -> Self {
+        let mut positions = Self::new(capacity);
+        if let Ok(bytes) = storage.read(path) {
+            positions.entries = serde_json::from_slice(&bytes).unwrap_or_default();
+            positions.entries.retain(|(file, _)| storage.exists(file));
+            positions.entries.truncate(capacity);
+        }
+        positions
+    }
+
+    pub fn save(&self, storage: &dyn Storage, path: &Path) -> io::Result<()> {
+        let bytes = serde_json::to_vec_pretty(&self.entries)?;
+        storage.write(path, &bytes, false)
+    }
+
+    pub fn remember(&mut self, file: &Path, char_idx: usize) {
+        if file.as_os_str().is_empty() {
+            return;
+        }
+        if self.entries.first().is_some_and(|(first, _)| first == file) {
+            self.entries[0].1 = char_idx;
+            return;
+        }
+        self.entries.retain(|(entry, _)| entry != file);
+        self.entries.insert(0, (file.to_path_buf(), char_idx));
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn get(&self, file: &Path) -> Option<usize> {
+        self.entries.iter().find(|(entry, _)| entry == file).map(|(_, char_idx)| *char_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+
+    #[test]
+    fn positions_are_most_recent_first_and_capped() {
+        let mut positions = FilePositions::new(2);
+        positions.remember(Path::new("/a.rs"), 1);
+        positions.remember(Path::new("/b.rs"), 2);
+        positions.remember(Path::new("/a.rs"), 3);
+        positions.remember(Path::new(""), 4);
+        positions.remember(Path::new("/c.rs"), 5);
+        assert_eq!(positions.entries, [(PathBuf::from("/c.rs"), 5), (PathBuf::from("/a.rs"), 3)]);
+        assert_eq!(positions.get(Path::new("/b.rs")), None);
+    }
+
+    #[test]
+    fn loading_drops_files_that_are_gone() {
+        let storage = InMemoryStorage::default();
+        let kept = PathBuf::from("/project/kept.rs");
+        storage.write(&kept, b"", false).unwrap();
+        let mut positions = FilePositions::new(10);
+        positions.remember(&kept, 7);
+        positions.remember(Path::new("/no/such/file.rs"), 9);
+        let path = Path::new("/data").join(FILE_POSITIONS_NAME);
+        positions.save(&storage, &path).unwrap();
+
+        let loaded = FilePositions::load(&storage, &path, 10);
+        assert_eq!(loaded.entries, [(kept.clone(), 7)]);
+        assert!(FilePositions::load(&storage, Path::new("/data/missing.json"), 10).entries.is_empty());
+    }
+}
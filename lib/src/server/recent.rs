@@ -0,0 +1,70 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::persistence::Storage;
+
+pub const RECENT_FILES_NAME: &str = "recent_files.json";
+
+pub struct RecentFiles {
+    pub capacity: usize,
+    pub entries: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+        }
+    }
+
+    // A missing or unreadable list just starts out empty.
+    pub fn load(storage: &dyn Storage, path: &Path, capacity: usize) -> Self {
+        let mut recent = Self::new(capacity);
+        if let Ok(bytes) = storage.read(path) {
+            recent.entries = serde_json::from_slice(&bytes).unwrap_or_default();
+            recent.entries.truncate(capacity);
+        }
+        recent
+    }
+
+    pub fn save(&self, storage: &dyn Storage, path: &Path) -> io::Result<()> {
+        let bytes = serde_json::to_vec_pretty(&self.entries)?;
+        storage.write(path, &bytes, false)
+    }
+
+    pub fn push(&mut self, file: PathBuf) {
+        self.entries.retain(|entry| *entry != file);
+        self.entries.insert(0, file);
+        self.entries.truncate(self.capacity);
+    }
+
+    pub fn existing(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.exists())
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+
+    #[test]
+    fn reopened_files_move_to_the_front_and_the_list_round_trips() {
+        let mut recent = RecentFiles::new(2);
+        for file in ["a.rs", "b.rs", "a.rs", "c.rs"] {
+            recent.push(PathBuf::from(file));
+        }
+        assert_eq!(recent.entries, vec![PathBuf::from("c.rs"), PathBuf::from("a.rs")]);
+
+        let storage = InMemoryStorage::default();
+        let path = Path::new("/data/recent_files.json");
+        recent.save(&storage, path).unwrap();
+        assert_eq!(RecentFiles::load(&storage, path, 1).entries, vec![PathBuf::from("c.rs")]);
+        assert!(RecentFiles::load(&storage, Path::new("/data/missing.json"), 2).entries.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::ops::Range;
+use std::sync::mpsc;
+
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+use crate::shared::snapshot::BufferCopy;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub buffer_id: usize,
+    pub line: usize,
+    pub range: Range<usize>,
+}
+
+// The regex engine never backtracks, so matching is linear in the input;
+// the limits below bound what a pathological pattern can make it compile.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const REGEX_DFA_SIZE_LIMIT: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    #[default]
+    Plain,
+    Regex,
+}
+
+fn compile(query: &str, mode: SearchMode) -> Result<Regex, regex::Error> {
+    let pattern = match mode {
+        SearchMode::Plain => regex::escape(query),
+        SearchMode::Regex => query.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+        .build()
+}
+
+// Lines are 0-based and `range` is a char range within the line.
+fn matches_in(buffer_id: usize, content: &str, pattern: &Regex) -> Vec<Match> {
+    content
+        .lines()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            pattern.find_iter(text).map(move |found| {
+                let start = text[..found.start()].chars().count();
+                let end = start + found.as_str().chars().count();
+                Match {
+                    buffer_id,
+                    line,
+                    range: start..end,
+                }
+            })
+        })
+        .collect()
+}
+
+// Every non-empty match in `content`, found line by line like `grep` so a
+// pattern never spans lines, with its line's text and the line's offset.
+fn line_matches<'a>(pattern: &'a Regex, content: &'a str) -> impl Iterator<Item = (usize, &'a str, Range<usize>)> + 'a {
+    content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line.strip_suffix('\n').unwrap_or(line)))
+    })
+    .flat_map(move |(offset, text)| {
+        pattern
+            .find_iter(text)
+            .filter(|found| !found.is_empty())
+            .map(move |found| (offset, text, found.range()))
+    })
+}
+
+// Byte ranges of every match in `content`. Empty matches are left out.
+pub fn match_ranges(query: &str, mode: SearchMode, content: &str) -> Result<Vec<Range<usize>>, regex::Error> {
+    let pattern = compile(query, mode)?;
+    Ok(line_matches(&pattern, content)
+        .map(|(offset, _, range)| offset + range.start..offset + range.end)
+        .collect())
+}
+
+pub fn grep(query: &str, mode: SearchMode, buffers: &[BufferCopy]) -> Result<Vec<Match>, regex::Error> {
+    let pattern = compile(query, mode)?;
+    Ok(buffers
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(buffer_id, buffer)| matches_in(buffer_id, &buffer.content, &pattern))
+        .collect())
+}
+
+// Sends matches as each buffer finishes instead of waiting for the whole
+// set; the receiver sees them in no particular buffer order.
+pub fn grep_streaming(
+    query: &str,
+    mode: SearchMode,
+    buffers: &[BufferCopy],
+    sender: mpsc::Sender<Match>,
+) -> Result<(), regex::Error> {
+    let pattern = compile(query, mode)?;
+    buffers
+        .par_iter()
+        .enumerate()
+        .for_each_with(sender, |sender, (buffer_id, buffer)| {
+            for found in matches_in(buffer_id, &buffer.content, &pattern) {
+                if sender.send(found).is_err() {
+                    return;
+                }
+            }
+        });
+    Ok(())
+}
+
+// The byte ranges `match_ranges` finds, each with the text to put there.
+// In regex mode the replacement may reference capture groups (`$1`,
+// `${name}`); in plain mode it is inserted literally.
+pub fn replacements(
+    query: &str,
+    mode: SearchMode,
+    replacement: &str,
+    content: &str,
+) -> Result<Vec<(Range<usize>, String)>, regex::Error> {
+    let pattern = compile(query, mode)?;
+    Ok(line_matches(&pattern, content)
+        .map(|(offset, text, range)| {
+            let replaced = match mode {
+                SearchMode::Plain => replacement.to_string(),
+                SearchMode::Regex => {
+                    let mut replaced = String::new();
+                    if let Some(captures) = pattern.captures_at(text, range.start) {
+                        captures.expand(replacement, &mut replaced);
+                    }
+                    replaced
+                }
+            };
+            (offset + range.start..offset + range.end, replaced)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::buffers::Buffer;
+
+    fn copies(contents: &[&str]) -> Vec<BufferCopy> {
+        contents
+            .iter()
+            .map(|content| BufferCopy::from(&Buffer::with_content(*content)))
+            .collect()
+    }
+
+    #[test]
+    fn grep_finds_matches_in_every_buffer_by_line_and_char() {
+        let buffers = copies(&["fn main() {}\n", "// ünï main\nmain();\n", "nothing here"]);
+        let found = grep("main", SearchMode::Plain, &buffers).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                Match { buffer_id: 0, line: 0, range: 3..7 },
+                Match { buffer_id: 1, line: 0, range: 7..11 },
+                Match { buffer_id: 1, line: 1, range: 0..4 },
+            ]
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        grep_streaming(r"ma\w+", SearchMode::Regex, &buffers, sender).unwrap();
+        let mut streamed: Vec<Match> = receiver.iter().collect();
+        streamed.sort_by_key(|found| (found.buffer_id, found.line));
+        assert_eq!(streamed, found);
+        assert!(grep("(", SearchMode::Regex, &buffers).is_err());
+    }
+
+    #[test]
+    fn regex_mode_matches_patterns_and_plain_mode_matches_them_literally() {
+        let content = "é1 x22\nb333";
+        assert_eq!(match_ranges(r"\d+", SearchMode::Regex, content).unwrap(), vec![2..3, 5..7, 9..12]);
+        assert_eq!(match_ranges(r"\d+", SearchMode::Plain, r"a\d+b").unwrap(), vec![1..4]);
+        assert_eq!(match_ranges("x*", SearchMode::Regex, content).unwrap(), vec![4..5]);
+    }
+}
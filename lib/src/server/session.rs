@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+use encoding_rs::{Encoding, UTF_8};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ThemeId;
+use crate::server::lsp;
+use crate::shared::buffers::{Buffer, BufferStorage, ExternalChange, LineEnding};
+use crate::shared::frames::{Frame, FrameCluster, FrameKind, FrameStorage, WindowGeometry};
+use crate::shared::{Shared, SharedError};
+
+pub const SESSION_EXTENSION: &str = "json";
+pub const SNAPSHOT_DIR_NAME: &str = "snapshots";
+
+#[derive(Serialize, Deserialize)]
+struct SessionBuffer {
+    file_path: PathBuf,
+    content: String,
+    folds: Vec<Range<usize>>,
+    #[serde(default)]
+    encoding: String,
+    #[serde(default)]
+    line_ending: LineEnding,
+    #[serde(default)]
+    marks: HashMap<char, usize>,
+    #[serde(default)]
+    theme_override: Option<ThemeId>,
+    #[serde(default)]
+    saved_hash: u64,
+    #[serde(default)]
+    is_scratch: bool,
+    #[serde(default)]
+    is_terminal: bool,
+    #[serde(default)]
+    is_stream: bool,
+    #[serde(default)]
+    big_file: bool,
+    #[serde(default)]
+    spell_check: bool,
+    #[serde(default)]
+    external_change: Option<ExternalChange>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionFrame {
+    buffer_index: usize,
+    scroll_offset: f32,
+    scroll_group: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionCluster {
+    #[serde(default)]
+    name: String,
+    is_visible: bool,
+    frames: Vec<SessionFrame>,
+    #[serde(default)]
+    geometry: Option<WindowGeometry>,
+}
+
+// Buffers keep their content so unsaved edits survive a switch; terminal
+// frames are left out since their processes can't be brought back.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    buffers: Vec<SessionBuffer>,
+    clusters: Vec<SessionCluster>,
+}
+
+impl Session {
+    pub fn capture(shared: &Shared) -> Result<Self, SharedError> {
+        let buffers = shared.with_buffers(|buffers| {
+            buffers
+                .buffers
+                .iter()
+                .map(|buffer| SessionBuffer {
+                    file_path: buffer.file_path.clone(),
+                    content: buffer.content().to_string(),
+                    folds: buffer.folds.clone(),
+                    encoding: buffer.encoding.name().to_string(),
+                    line_ending: buffer.line_ending,
+                    marks: buffer.marks.clone(),
+                    theme_override: buffer.theme_override,
+                    saved_hash: buffer.saved_hash,
+                    is_scratch: buffer.is_scratch,
+                    is_terminal: buffer.is_terminal,
+                    is_stream: buffer.is_stream,
+                    big_file: buffer.big_file,
+                    spell_check: buffer.spell_check,
+                    external_change: buffer.external_change,
+                })
+                .collect()
+        })?;
+        let clusters = shared.with_frames(|frames| {
+            frames
+                .frame_clusters
+                .iter()
+                .map(|cluster| SessionCluster {
+                    name: cluster.name.clone(),
+                    is_visible: cluster.is_visible,
+                    frames: cluster
+                        .frames
+                        .iter()
+                        .filter(|frame| frame.kind == FrameKind::Editor)
+                        .map(|frame| SessionFrame {
+                            buffer_index: frame.buffer_index,
+                            scroll_offset: frame.scroll_offset,
+                            scroll_group: frame.scroll_group,
+                        })
+                        .collect(),
+                    geometry: cluster.geometry,
+                })
+                .collect()
+        })?;
+        Ok(Self { buffers, clusters })
+    }
+
+    // Replaces everything in `shared`; empty parts fall back to defaults.
+    pub fn restore(self, shared: &Shared) -> Result<(), SharedError> {
+        let buffers = if self.buffers.is_empty() {
+            BufferStorage::default()
+        } else {
+            BufferStorage {
+                buffers: self
+                    .buffers
+                    .into_iter()
+                    .map(|buffer| {
+                        let mut restored = Buffer::with_content(buffer.content);
+                        restored.folds = buffer.folds;
+                        restored.encoding = Encoding::for_label(buffer.encoding.as_bytes()).unwrap_or(UTF_8);
+                        restored.line_ending = buffer.line_ending;
+                        restored.marks = buffer.marks;
+                        restored.theme_override = buffer.theme_override;
+                        restored.saved_hash = buffer.saved_hash;
+                        restored.is_scratch = buffer.is_scratch;
+                        restored.is_terminal = buffer.is_terminal;
+                        restored.is_stream = buffer.is_stream;
+                        restored.big_file = buffer.big_file;
+                        restored.spell_check = buffer.spell_check;
+                        restored.external_change = buffer.external_change;
+                        restored.language = lsp::language_id(&buffer.file_path);
+                        restored.file_path = buffer.file_path;
+                        restored
+                    })
+                    .collect(),
+            }
+        };
+        let frames = if self.clusters.is_empty() {
+            FrameStorage::default()
+        } else {
+            FrameStorage {
+                frame_clusters: self
+                    .clusters
+                    .into_iter()
+                    .map(|cluster| FrameCluster {
+                        name: cluster.name,
+                        is_visible: cluster.is_visible,
+                        frames: cluster
+                            .frames
+                            .into_iter()
+                            .map(|frame| Frame {
+                                buffer_index: frame.buffer_index,
+                                scroll_offset: frame.scroll_offset,
+                                scroll_group: frame.scroll_group,
+                                ..Frame::default()
+                            })
+                            .collect(),
+                        geometry: cluster.geometry,
+                        ..FrameCluster::default()
+                    })
+                    .collect(),
+            }
+        };
+        shared.with_buffers_mut(|current| {
+            *current = buffers;
+            shared.with_frames_mut(|current| *current = frames)
+        })?
+    }
+}
+
+// Runs `encode` to completion before anything is written, turning a panic
+// partway through into an error, so a failed encode never touches the file
+// it was meant for.
+pub fn encode_guarded(encode: impl FnOnce() -> io::Result<Vec<u8>>) -> io::Result<Vec<u8>> {
+    panic::catch_unwind(AssertUnwindSafe(encode))
+        .unwrap_or_else(|_| Err(io::Error::other("session encoding panicked")))
+}
+
+pub fn validate_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\']);
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid session name `{name}`")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_panics_become_errors() {
+        assert_eq!(encode_guarded(|| Ok(b"session".to_vec())).unwrap(), b"session");
+        assert_eq!(encode_guarded(|| Err(io::Error::other("full"))).unwrap_err().to_string(), "full");
+        assert!(encode_guarded(|| panic!("bad frame")).is_err());
+    }
+
+    #[test]
+    fn names_stay_inside_the_session_directory() {
+        assert!(validate_name("work").is_ok());
+        for name in ["", ".", "..", "a/b", "a\\b"] {
+            assert_eq!(validate_name(name).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+
+use super::syntax::TokenKind;
+
+// Words known to be spelled right: one per line as in `/usr/share/dict/words`,
+// or a hunspell `.dic` whose `/FLAGS` suffixes are ignored.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn parse(text: &str) -> Self {
+        let words = text
+            .lines()
+            .filter_map(|line| line.split('/').next())
+            .map(str::trim)
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { words }
+    }
+
+    // A capitalised word is also known by its lowercase form, since it may
+    // start a sentence; a possessive `'s` is left off.
+    pub fn knows(&self, word: &str) -> bool {
+        let known = |word: &str| self.words.contains(word) || self.words.contains(&word.to_lowercase());
+        known(word) || word.strip_suffix("'s").is_some_and(known)
+    }
+
+    // Known words one edit away from `word` (a letter dropped, added,
+    // swapped with the next or changed), capitalised like it. At most
+    // `limit`, alphabetical.
+    pub fn suggestions(&self, word: &str, limit: usize) -> Vec<String> {
+        let lower: Vec<char> = word.to_lowercase().chars().collect();
+        let mut letters: BTreeSet<char> = ('a'..='z').collect();
+        letters.extend(lower.iter().copied());
+        let mut candidates = BTreeSet::new();
+        for idx in 0..=lower.len() {
+            if idx < lower.len() {
+                let mut dropped = lower.clone();
+                dropped.remove(idx);
+                candidates.insert(dropped);
+            }
+            if idx + 1 < lower.len() {
+                let mut swapped = lower.clone();
+                swapped.swap(idx, idx + 1);
+                candidates.insert(swapped);
+            }
+            for &letter in &letters {
+                let mut added = lower.clone();
+                added.insert(idx, letter);
+                candidates.insert(added);
+                if idx < lower.len() {
+                    let mut changed = lower.clone();
+                    changed[idx] = letter;
+                    candidates.insert(changed);
+                }
+            }
+        }
+        let capitalised = word.chars().next().is_some_and(char::is_uppercase);
+        candidates
+            .into_iter()
+            .map(|chars| chars.into_iter().collect::<String>())
+            .filter(|candidate| *candidate != word.to_lowercase() && self.knows(candidate))
+            .map(|candidate| if capitalised { capitalise(&candidate) } else { candidate })
+            .take(limit)
+            .collect()
+    }
+}
+
+fn capitalise(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+// Byte ranges of the words in `text` worth checking. Anything that looks
+// like part of an identifier (next to a digit or `_`, or with capitals past
+// its first letter) or an acronym is skipped, as are single letters.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let is_word_char = |c: char| c.is_alphabetic() || c == '\'';
+    let is_identifier_char = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit() || c == '_');
+    let mut words = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !is_word_char(c) {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some((idx, c)) = chars.next_if(|(_, c)| is_word_char(*c)) {
+            end = idx + c.len_utf8();
+        }
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        let word = text[start..end].trim_matches('\'');
+        let start = start + text[start..end].find(word).unwrap_or(0);
+        let mut letters = word.chars();
+        let tail_capitals = letters.next().is_some() && letters.any(char::is_uppercase);
+        if is_identifier_char(before) || is_identifier_char(after) || tail_capitals || word.chars().count() < 2 {
+            continue;
+        }
+        words.push(start..start + word.len());
+    }
+    words
+}
+
+// The parts of a prose file to check: everything but fenced code blocks
+// and inline code, as written in markdown.
+pub fn prose_regions(content: &str) -> Vec<Range<usize>> {
+    let mut regions = Vec::new();
+    let mut fenced = false;
+    let mut at = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        } else if !fenced {
+            let mut start = at;
+            for (idx, part) in line.split('`').enumerate() {
+                if idx % 2 == 0 {
+                    regions.push(start..start + part.len());
+                }
+                start += part.len() + 1;
+            }
+        }
+        at += line.len();
+    }
+    regions
+}
+
+// The parts of a code file to check: its comments and strings.
+pub fn code_regions(spans: &[(Range<usize>, TokenKind)]) -> Vec<Range<usize>> {
+    spans
+        .iter()
+        .filter(|(_, kind)| matches!(kind, TokenKind::Comment | TokenKind::String))
+        .map(|(range, _)| range.clone())
+        .collect()
+}
+
+// Byte ranges of the words in `regions` of `content` that `dictionary`
+// doesn't know, in order.
+pub fn misspelled(dictionary: &Dictionary, content: &str, regions: &[Range<usize>]) -> Vec<Range<usize>> {
+    regions
+        .iter()
+        .filter_map(|region| content.get(region.clone()).map(|text| (region.start, text)))
+        .flat_map(|(offset, text)| {
+            words(text)
+                .into_iter()
+                .filter(|word| !dictionary.knows(&text[word.clone()]))
+                .map(move |word| offset + word.start..offset + word.end)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary() -> Dictionary {
+        Dictionary::parse("the\nword/MS\n  cat\nParis\nspell\nspelt\n")
+    }
+
+    fn found<'a>(content: &'a str, regions: &[Range<usize>]) -> Vec<&'a str> {
+        misspelled(&dictionary(), content, regions).into_iter().map(|range| &content[range]).collect()
+    }
+
+    #[test]
+    fn known_words_allow_capitals_and_possessives() {
+        let dictionary = dictionary();
+        assert!(dictionary.knows("word"));
+        assert!(dictionary.knows("The"));
+        assert!(dictionary.knows("cat's"));
+        assert!(dictionary.knows("Paris"));
+        assert!(!dictionary.knows("paris"));
+        assert!(!dictionary.knows("word/MS"));
+        assert_eq!(dictionary.suggestions("Spel", 8), ["Spell", "Spelt"]);
+        assert_eq!(dictionary.suggestions("teh", 8), ["the"]);
+        assert_eq!(dictionary.suggestions("spel", 1), ["spell"]);
+    }
+
+    #[test]
+    fn identifiers_acronyms_and_markdown_code_are_skipped() {
+        let content = "teh cat x wrod_2 parseJson HTTP 'wrod' and `wrod`\n```\nwrod\n```\nthe wrod";
+        let all = 0..content.len();
+        assert_eq!(found(content, std::slice::from_ref(&all)), ["teh", "wrod", "and", "wrod", "wrod", "wrod"]);
+        assert_eq!(found(content, &prose_regions(content)), ["teh", "wrod", "and", "wrod"]);
+    }
+
+    #[test]
+    fn code_files_only_check_comments_and_strings() {
+        let spans = [(0..2, TokenKind::Keyword), (3..10, TokenKind::Comment), (11..16, TokenKind::String)];
+        assert_eq!(code_regions(&spans), [3..10, 11..16]);
+        assert_eq!(found("fn // wrod \"teh\"", &code_regions(&spans)), ["wrod", "teh"]);
+    }
+}
@@ -0,0 +1,171 @@
+use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::server::capture::Capture;
+use crate::shared::buffers::Buffer;
+use crate::shared::{Shared, SharedError};
+
+// Fills an unnamed buffer from a byte stream (e.g. piped stdin) on a
+// background thread, so a pipe that stays open never blocks the UI.
+pub struct StreamReader {
+    pub buffer_index: usize,
+    pub finished: bool,
+    chunks: mpsc::Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+    // Set for streams too big to keep whole in the buffer.
+    pub capture: Option<Capture>,
+}
+
+impl StreamReader {
+    pub fn spawn(shared: &Shared, mut reader: impl Read + Send + 'static) -> Result<Self, SharedError> {
+        let (sender, chunks) = mpsc::channel();
+        thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            loop {
+                match reader.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => {
+                        if sender.send(chunk[..read].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let buffer_index = shared.with_buffers_mut(|buffers| {
+            let mut buffer = Buffer::default();
+            buffer.is_stream = true;
+            buffers.buffers.push(buffer);
+            buffers.buffers.len() - 1
+        })?;
+        Ok(Self {
+            buffer_index,
+            finished: false,
+            chunks,
+            pending: Vec::new(),
+            capture: None,
+        })
+    }
+
+    pub fn capture_to(mut self, capture: Capture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    pub fn pump(&mut self, shared: &Shared) -> Result<(), SharedError> {
+        if self.finished {
+            return Ok(());
+        }
+        shared.with_buffers_mut(|buffers| match buffers.buffers.get_mut(self.buffer_index) {
+            Some(buffer) => self.receive(buffer),
+            None => self.finished = true,
+        })
+    }
+
+    fn receive(&mut self, buffer: &mut Buffer) {
+        let mut content = String::new();
+        loop {
+            match self.chunks.try_recv() {
+                Ok(bytes) => {
+                    self.pending.extend_from_slice(&bytes);
+                    decode_available(&mut self.pending, &mut content);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    content.push_str(&String::from_utf8_lossy(&self.pending));
+                    self.pending.clear();
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        if content.is_empty() {
+            return;
+        }
+        match &mut self.capture {
+            Some(capture) => {
+                if let Err(err) = capture.append(buffer, &content) {
+                    log::error!("cant write capture {}: {err}", capture.path.display());
+                }
+            }
+            None => buffer.append(&content),
+        }
+    }
+}
+
+// Decodes everything except a trailing partial UTF-8 sequence, which stays
+// in `pending` until the rest of it arrives. Invalid bytes become U+FFFD.
+pub fn decode_available(pending: &mut Vec<u8>, out: &mut String) {
+    let mut start = 0;
+    loop {
+        match std::str::from_utf8(&pending[start..]) {
+            Ok(text) => {
+                out.push_str(text);
+                start = pending.len();
+                break;
+            }
+            Err(err) => {
+                let valid_end = start + err.valid_up_to();
+                out.push_str(&String::from_utf8_lossy(&pending[start..valid_end]));
+                match err.error_len() {
+                    Some(len) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        start = valid_end + len;
+                    }
+                    None => {
+                        start = valid_end;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    pending.drain(..start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    use crate::shared::buffers::BufferStorage;
+    use crate::shared::frames::FrameStorage;
+
+    // Hands out one byte per read, splitting every multi-byte char.
+    struct Trickle(io::Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(&mut buf[..1])
+        }
+    }
+
+    #[test]
+    fn piped_bytes_fill_a_stream_buffer_across_split_chars() {
+        let shared = Shared::new(BufferStorage { buffers: Vec::new() }, FrameStorage::default());
+        let mut bytes = "héllo ünï\n".as_bytes().to_vec();
+        bytes.push(0xFF);
+        let mut stream = StreamReader::spawn(&shared, Trickle(io::Cursor::new(bytes))).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !stream.finished && Instant::now() < deadline {
+            stream.pump(&shared).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        let buffer = shared.with_buffers(|buffers| (buffers.buffers[0].content().to_string(), buffers.buffers[0].is_stream));
+        assert_eq!(buffer.unwrap(), (String::from("héllo ünï\n\u{FFFD}"), true));
+    }
+
+    #[test]
+    fn partial_chars_wait_for_the_rest() {
+        let (mut pending, mut out) = (vec![b'a', 0xC3], String::new());
+        decode_available(&mut pending, &mut out);
+        assert_eq!((out.as_str(), pending.as_slice()), ("a", &[0xC3][..]));
+        pending.extend_from_slice(&[0xA9, 0xFF, b'b']);
+        decode_available(&mut pending, &mut out);
+        assert_eq!(out, "aé\u{FFFD}b");
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use super::syntax;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: &'static str,
+    // 0-based line the symbol's name is on.
+    pub line: usize,
+    pub children: Vec<Symbol>,
+}
+
+// Names for LSP `SymbolKind` values.
+pub fn kind_name(kind: u64) -> &'static str {
+    match kind {
+        1 => "file",
+        2 => "module",
+        3 => "namespace",
+        4 => "package",
+        5 => "class",
+        6 => "method",
+        7 => "property",
+        8 => "field",
+        9 => "constructor",
+        10 => "enum",
+        11 => "interface",
+        12 => "function",
+        13 => "variable",
+        14 => "constant",
+        22 => "variant",
+        23 => "struct",
+        26 => "type parameter",
+        _ => "symbol",
+    }
+}
+
+// Outline derived from the syntax tree, for buffers no language server
+// covers. `None` when there's no grammar for the file type.
+pub fn from_syntax(path: &Path, content: &str) -> Option<Vec<Symbol>> {
+    let tree = syntax::parse(path, content)?;
+    Some(collect(tree.root_node(), content))
+}
+
+fn collect(node: Node, content: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match symbol_for(child, content) {
+            Some(mut symbol) => {
+                symbol.children = collect(child, content);
+                symbols.push(symbol);
+            }
+            None => symbols.extend(collect(child, content)),
+        }
+    }
+    symbols
+}
+
+fn symbol_for(node: Node, content: &str) -> Option<Symbol> {
+    let kind = match node.kind() {
+        "function_item" | "function_signature_item" => "function",
+        "struct_item" => "struct",
+        "enum_item" => "enum",
+        "enum_variant" => "variant",
+        "trait_item" => "interface",
+        "impl_item" => "impl",
+        "mod_item" => "module",
+        "const_item" | "static_item" => "constant",
+        "type_item" => "type",
+        "macro_definition" => "macro",
+        "field_declaration" => "field",
+        _ => return None,
+    };
+    let text = |field: &str| node.child_by_field_name(field)?.utf8_text(content.as_bytes()).ok();
+    let name = if node.kind() == "impl_item" {
+        match text("trait") {
+            Some(trait_name) => format!("impl {trait_name} for {}", text("type")?),
+            None => format!("impl {}", text("type")?),
+        }
+    } else {
+        text("name")?.to_string()
+    };
+    Some(Symbol {
+        name,
+        kind,
+        line: node.start_position().row,
+        children: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, kind: &'static str, line: usize, children: Vec<Symbol>) -> Symbol {
+        Symbol { name: name.to_string(), kind, line, children }
+    }
+
+    #[test]
+    fn rust_items_nest_under_their_parents() {
+        let content = "struct Point {\n    x: i32,\n}\n\nimpl Display for Point {\n    fn fmt() {}\n}\n\nmod inner {\n    const MAX: u8 = 1;\n}\n";
+        assert_eq!(
+            from_syntax(Path::new("lib.rs"), content).unwrap(),
+            vec![
+                symbol("Point", "struct", 0, vec![symbol("x", "field", 1, Vec::new())]),
+                symbol("impl Display for Point", "impl", 4, vec![symbol("fmt", "function", 5, Vec::new())]),
+                symbol("inner", "module", 8, vec![symbol("MAX", "constant", 9, Vec::new())]),
+            ]
+        );
+        assert_eq!(from_syntax(Path::new("notes.txt"), content), None);
+    }
+}
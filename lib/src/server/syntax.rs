@@ -0,0 +1,198 @@
+use std::ops::Range;
+use std::path::Path;
+
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Tree};
+
+pub fn language(path: &Path) -> Option<Language> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(tree_sitter_rust::LANGUAGE.into()),
+        _ => None,
+    }
+}
+
+// `None` when there's no grammar for the file type.
+pub fn parse(path: &Path, content: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&language(path)?).ok()?;
+    parser.parse(content, None)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Type,
+    Function,
+}
+
+fn token_kind(node: Node) -> Option<TokenKind> {
+    let kind = node.kind();
+    if !node.is_named() {
+        let is_word = kind.chars().all(|c| c.is_ascii_alphabetic() || c == '_');
+        return is_word.then_some(TokenKind::Keyword);
+    }
+    match kind {
+        "line_comment" | "block_comment" => Some(TokenKind::Comment),
+        "string_literal" | "raw_string_literal" | "char_literal" => Some(TokenKind::String),
+        "integer_literal" | "float_literal" | "boolean_literal" => Some(TokenKind::Number),
+        "primitive_type" | "type_identifier" => Some(TokenKind::Type),
+        "identifier" | "field_identifier" => {
+            let parent = node.parent()?;
+            let is_function = match parent.kind() {
+                "function_item" | "function_signature_item" => parent.child_by_field_name("name") == Some(node),
+                "call_expression" => parent.child_by_field_name("function") == Some(node),
+                "field_expression" => parent.parent().is_some_and(|call| {
+                    call.kind() == "call_expression" && call.child_by_field_name("function") == Some(parent)
+                }),
+                _ => false,
+            };
+            is_function.then_some(TokenKind::Function)
+        }
+        _ => None,
+    }
+}
+
+// Byte ranges to color, in order and not overlapping. Empty when there's
+// no grammar for the file type.
+pub fn highlights(path: &Path, content: &str) -> Vec<(Range<usize>, TokenKind)> {
+    parse(path, content).map_or_else(Vec::new, |tree| tree_highlights(&tree))
+}
+
+pub fn tree_highlights(tree: &Tree) -> Vec<(Range<usize>, TokenKind)> {
+    let mut spans = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if let Some(kind) = token_kind(node) {
+            spans.push((node.byte_range(), kind));
+        } else if cursor.goto_first_child() {
+            continue;
+        }
+        // Colored nodes are taken whole, their children aren't visited.
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return spans;
+            }
+        }
+    }
+}
+
+// Byte ranges the parser couldn't make sense of (`ERROR` nodes) or had to
+// assume something was left out (`MISSING` nodes), in order and not
+// overlapping. A missing node takes no text, so it gets the char it sits
+// before, or the one before that at the end of a line.
+pub fn syntax_errors(tree: &Tree, content: &str) -> Vec<Range<usize>> {
+    let mut errors: Vec<Range<usize>> = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            let mut range = node.byte_range();
+            if range.is_empty() {
+                range = match content[range.start..].chars().next().filter(|c| *c != '\n') {
+                    Some(c) => range.start..range.start + c.len_utf8(),
+                    None => content[..range.start].char_indices().next_back().map_or(range.clone(), |(idx, _)| idx..range.start),
+                };
+            }
+            match errors.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => errors.push(range),
+            }
+        } else if node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return errors;
+            }
+        }
+    }
+}
+
+// Bytes `old` and `new` share at the start and, after that, at the end.
+// Both fall on char boundaries in either text.
+pub fn changed_span(old: &str, new: &str) -> (usize, usize) {
+    let (old_bytes, new_bytes) = (old.as_bytes(), new.as_bytes());
+    let mut prefix = old_bytes.iter().zip(new_bytes).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old_bytes
+        .iter()
+        .rev()
+        .zip(new_bytes.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    (prefix, suffix)
+}
+
+fn point_at(content: &str, byte_idx: usize) -> Point {
+    let before = &content[..byte_idx];
+    let row = before.matches('\n').count();
+    let column = before.rfind('\n').map_or(byte_idx, |newline| byte_idx - newline - 1);
+    Point { row, column }
+}
+
+// Everything that changed from `old` to `new` as one edit, for bringing a
+// tree of `old` in line before parsing `new` against it.
+pub fn input_edit(old: &str, new: &str) -> InputEdit {
+    let (prefix, suffix) = changed_span(old, new);
+    let (old_end_byte, new_end_byte) = (old.len() - suffix, new.len() - suffix);
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte,
+        new_end_byte,
+        start_position: point_at(old, prefix),
+        old_end_position: point_at(old, old_end_byte),
+        new_end_position: point_at(new, new_end_byte),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_tokens_are_classified_without_overlap() {
+        let content = "fn main() { let s: u8 = parse(\"hi\", 2); } // done";
+        let spans: Vec<(&str, TokenKind)> = highlights(Path::new("main.rs"), content)
+            .into_iter()
+            .map(|(range, kind)| (&content[range], kind))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("fn", TokenKind::Keyword),
+                ("main", TokenKind::Function),
+                ("let", TokenKind::Keyword),
+                ("u8", TokenKind::Type),
+                ("parse", TokenKind::Function),
+                ("\"hi\"", TokenKind::String),
+                ("2", TokenKind::Number),
+                ("// done", TokenKind::Comment),
+            ]
+        );
+        assert!(highlights(Path::new("notes.txt"), content).is_empty());
+    }
+
+    #[test]
+    fn syntax_errors_cover_bad_and_missing_text() {
+        let errors = |content: &str| -> Vec<String> {
+            let tree = parse(Path::new("main.rs"), content).unwrap();
+            syntax_errors(&tree, content).into_iter().map(|range| content[range].to_string()).collect()
+        };
+        assert!(errors("fn main() { let x = 1; }\n").is_empty());
+        assert_eq!(errors("fn é() { $$ }"), ["$$"]);
+        // A missing `;` takes the char it sits before, or at the end of a
+        // line the one before it.
+        assert_eq!(errors("fn main() { let x = 1 }\n"), [" "]);
+        assert_eq!(errors("fn main() { let x = 1\n}\n"), ["1"]);
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::{Arc, OnceLock};
+
+type Wake = Box<dyn Fn() + Send + Sync>;
+
+// Lets the server and its threads ask the UI to draw again, so results show
+// up without waiting for input. Clones share one handle, set once the UI is
+// up; until then waking does nothing.
+#[derive(Clone, Default)]
+pub struct Waker {
+    wake: Arc<OnceLock<Wake>>,
+}
+
+impl Waker {
+    pub fn set(&self, wake: impl Fn() + Send + Sync + 'static) {
+        let _ = self.wake.set(Box::new(wake));
+    }
+
+    pub fn wake(&self) {
+        if let Some(wake) = self.wake.get() {
+            wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn clones_share_the_first_wake_set() {
+        let waker = Waker::default();
+        let clone = waker.clone();
+        waker.wake();
+
+        let (first, second) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let counted = first.clone();
+        clone.set(move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+        let counted = second.clone();
+        waker.set(move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+        waker.wake();
+        clone.wake();
+        assert_eq!((first.load(Ordering::Relaxed), second.load(Ordering::Relaxed)), (2, 0));
+    }
+}
@@ -0,0 +1,24 @@
+use std::collections::HashMap;
+
+use super::Buffer;
+
+// Text shown after the end of a line, such as blame or an inlay hint. It is
+// never part of the content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    // The provider it came from; see `AnnotationProvider::source`.
+    pub source: &'static str,
+    pub text: String,
+}
+
+// Keyed by line.
+pub type Annotations = HashMap<usize, Vec<Annotation>>;
+
+// Something that can annotate lines, e.g. git blame or LSP inlay hints.
+// Each provider only replaces the annotations tagged with its own source.
+pub trait AnnotationProvider: Send {
+    fn source(&self) -> &'static str;
+
+    // `(line, text)` pairs for the buffer as it is now.
+    fn annotate(&mut self, buffer: &Buffer) -> Vec<(usize, String)>;
+}
@@ -0,0 +1,81 @@
+use crate::interfaces::enums::BufferActions;
+
+// Reduces an edit to the single span between the common prefix and suffix.
+// A typed or deleted run lands exactly on that span; edits touching several
+// regions (e.g. a paste replacing scattered text) collapse into one coarse
+// replacement covering all of them.
+pub fn diff(old: &str, new: &str) -> Vec<BufferActions> {
+    if old == new {
+        return Vec::new();
+    }
+
+    let old_chars: Vec<char> = old.chars().collect();
+    let new_chars: Vec<char> = new.chars().collect();
+
+    let prefix = old_chars
+        .iter()
+        .zip(&new_chars)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let max_suffix = old_chars.len().min(new_chars.len()) - prefix;
+    let suffix = old_chars
+        .iter()
+        .rev()
+        .zip(new_chars.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let deleted_end = old_chars.len() - suffix;
+    let inserted_end = new_chars.len() - suffix;
+
+    let text: String = new_chars[prefix..inserted_end].iter().collect();
+    let action = match (deleted_end > prefix, text.is_empty()) {
+        (true, false) => BufferActions::ReplaceRange { start: prefix, end: deleted_end, text },
+        (true, true) => BufferActions::DeleteRange { start: prefix, end: deleted_end },
+        _ => BufferActions::InsertText { char_idx: prefix, text },
+    };
+    vec![action]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::buffers::Buffer;
+
+    #[test]
+    fn typing_one_char_inserts_just_that_char() {
+        assert_eq!(diff("fn man()", "fn main()"), vec![BufferActions::InsertText { char_idx: 5, text: String::from("i") }]);
+    }
+
+    #[test]
+    fn deleting_a_run_deletes_just_that_run() {
+        assert_eq!(diff("héllo, wörld", "héllo"), vec![BufferActions::DeleteRange { start: 5, end: 12 }]);
+    }
+
+    #[test]
+    fn a_paste_over_scattered_text_is_one_coarse_replacement() {
+        let (old, new) = ("a1b2c3d", "aXbYcZd");
+        let actions = diff(old, new);
+        assert_eq!(actions, vec![BufferActions::ReplaceRange { start: 1, end: 6, text: String::from("XbYcZ") }]);
+        let mut buffer = Buffer::with_content(old);
+        buffer.edit(&actions);
+        assert_eq!(buffer.content, new);
+    }
+
+    #[test]
+    fn edits_undo_and_redo_as_one_step() {
+        let mut buffer = Buffer::with_content("one two");
+        buffer.edit(&diff("one two", "one 2"));
+        buffer.edit(&diff("one 2", "one 2 three"));
+        assert!(buffer.undo());
+        assert_eq!(buffer.content, "one 2");
+        assert!(buffer.undo());
+        assert_eq!(buffer.content, "one two");
+        assert!(!buffer.undo());
+        assert!(buffer.redo() && buffer.redo());
+        assert_eq!(buffer.content, "one 2 three");
+        assert!(diff("same", "same").is_empty());
+    }
+}
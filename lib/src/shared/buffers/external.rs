@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+// What happens when a buffer's file changes on disk. Set globally with
+// `external_change` and per buffer with `Buffer::external_change`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExternalChange {
+    AlwaysReload,
+    #[default]
+    PromptIfModified,
+    AlwaysPrompt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalAction {
+    Reload,
+    Prompt,
+}
+
+impl ExternalChange {
+    pub const ALL: [Self; 3] = [Self::AlwaysReload, Self::PromptIfModified, Self::AlwaysPrompt];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::AlwaysReload => "Always reload",
+            Self::PromptIfModified => "Ask if modified",
+            Self::AlwaysPrompt => "Always ask",
+        }
+    }
+
+    // `AlwaysReload` goes ahead even over unsaved changes; the reload is
+    // one undo step, so they can be brought back.
+    pub fn action(self, modified: bool) -> ExternalAction {
+        match self {
+            Self::AlwaysReload => ExternalAction::Reload,
+            Self::PromptIfModified if !modified => ExternalAction::Reload,
+            Self::PromptIfModified | Self::AlwaysPrompt => ExternalAction::Prompt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_reload_or_ask_depending_on_unsaved_changes() {
+        let actions: Vec<_> = ExternalChange::ALL.iter().map(|policy| (policy.action(false), policy.action(true))).collect();
+        assert_eq!(
+            actions,
+            [
+                (ExternalAction::Reload, ExternalAction::Reload),
+                (ExternalAction::Reload, ExternalAction::Prompt),
+                (ExternalAction::Prompt, ExternalAction::Prompt),
+            ]
+        );
+    }
+}
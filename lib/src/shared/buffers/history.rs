@@ -0,0 +1,287 @@
+use std::io;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::interfaces::enums::BufferActions;
+use crate::server::persistence::Storage;
+
+// Typing with pauses no longer than this between edits is undone as one
+// step.
+pub const COALESCE_WINDOW: Duration = Duration::from_millis(300);
+
+// Old groups moved to disk by `History::spill`; read back the first time
+// undo reaches them.
+struct Spill {
+    path: PathBuf,
+    storage: Arc<dyn Storage>,
+}
+
+impl Spill {
+    fn load(&self) -> io::Result<Vec<Vec<BufferActions>>> {
+        let groups = serde_json::from_slice(self.storage.read(&self.path)?.as_slice())?;
+        let _ = self.storage.remove(&self.path);
+        Ok(groups)
+    }
+}
+
+enum UndoEntry {
+    Loaded(Vec<BufferActions>),
+    Spilled(Spill),
+}
+
+fn group_bytes(group: &[BufferActions]) -> usize {
+    group
+        .iter()
+        .map(|action| match action {
+            BufferActions::InsertText { text, .. } | BufferActions::ReplaceRange { text, .. } => {
+                size_of::<BufferActions>() + text.len()
+            }
+            BufferActions::DeleteRange { .. } => size_of::<BufferActions>(),
+        })
+        .sum()
+}
+
+// Whether the edit happens at `cursor`: an insert there, or a deletion on
+// either side of it.
+fn touches(action: &BufferActions, cursor: usize) -> bool {
+    match action {
+        BufferActions::InsertText { char_idx, .. } => *char_idx == cursor,
+        BufferActions::DeleteRange { start, end } | BufferActions::ReplaceRange { start, end, .. } => {
+            (*start..=(*end).max(*start)).contains(&cursor)
+        }
+    }
+}
+
+// Each entry is the inverse of one edit group, stored in the order it has
+// to be applied to roll that group back.
+pub struct History {
+    undo_stack: Vec<UndoEntry>,
+    redo_stack: Vec<Vec<BufferActions>>,
+    loaded_bytes: usize,
+    pub coalesce_window: Duration,
+    // When the newest group was last typed into and where that left the
+    // cursor; `None` once it's closed.
+    last_typed: Option<(Instant, usize)>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            loaded_bytes: 0,
+            coalesce_window: COALESCE_WINDOW,
+            last_typed: None,
+        }
+    }
+}
+
+impl History {
+    // A group of its own, e.g. for a command.
+    pub fn record(&mut self, inverse: Vec<BufferActions>) {
+        if inverse.is_empty() {
+            return;
+        }
+        self.push_undo(inverse);
+        self.clear_redo();
+    }
+
+    // Typing at `at`: `actions` with their `inverse`, leaving the cursor at
+    // `cursor`. Joins the newest group while edits keep coming within
+    // `coalesce_window` of each other and start where the last one left
+    // the cursor, so a burst is undone at once but a jump elsewhere isn't.
+    pub fn record_typed(&mut self, actions: &[BufferActions], inverse: Vec<BufferActions>, at: Instant, cursor: usize) {
+        if inverse.is_empty() {
+            return;
+        }
+        let coalesce = self.last_typed.is_some_and(|(last, last_cursor)| {
+            at.saturating_duration_since(last) <= self.coalesce_window
+                && actions.first().is_some_and(|action| touches(action, last_cursor))
+        });
+        match self.undo_stack.last_mut() {
+            Some(UndoEntry::Loaded(group)) if coalesce => {
+                self.loaded_bytes += group_bytes(&inverse);
+                // Newer edits are rolled back first.
+                group.splice(0..0, inverse);
+            }
+            _ => self.push_undo(inverse),
+        }
+        self.last_typed = Some((at, cursor));
+        self.clear_redo();
+    }
+
+    // Closes the newest group, so the next edit starts one of its own.
+    pub fn seal(&mut self) {
+        self.last_typed = None;
+    }
+
+    fn clear_redo(&mut self) {
+        for group in self.redo_stack.drain(..) {
+            self.loaded_bytes -= group_bytes(&group);
+        }
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    // Approximate size of the groups held in memory.
+    pub fn memory_bytes(&self) -> usize {
+        self.loaded_bytes
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Vec<BufferActions>> {
+        self.last_typed = None;
+        loop {
+            match self.undo_stack.pop()? {
+                UndoEntry::Loaded(group) => {
+                    self.loaded_bytes -= group_bytes(&group);
+                    return Some(group);
+                }
+                UndoEntry::Spilled(spill) => match spill.load() {
+                    Ok(groups) => groups.into_iter().for_each(|group| self.push_undo(group)),
+                    Err(err) => {
+                        log::error!("cant reload undo history from {}: {err}", spill.path.display());
+                        return None;
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Vec<BufferActions>> {
+        let group = self.redo_stack.pop()?;
+        self.loaded_bytes -= group_bytes(&group);
+        Some(group)
+    }
+
+    // Always starts a group; typing after a redo doesn't join the redone one.
+    pub fn push_undo(&mut self, inverse: Vec<BufferActions>) {
+        self.last_typed = None;
+        self.loaded_bytes += group_bytes(&inverse);
+        self.undo_stack.push(UndoEntry::Loaded(inverse));
+    }
+
+    pub fn push_redo(&mut self, inverse: Vec<BufferActions>) {
+        self.loaded_bytes += group_bytes(&inverse);
+        self.redo_stack.push(inverse);
+    }
+
+    // Writes the oldest run of in-memory undo groups to `path`, leaving the
+    // newest `keep` groups alone. Returns the number of bytes freed.
+    pub fn spill(&mut self, storage: &Arc<dyn Storage>, path: &Path, keep: usize) -> io::Result<usize> {
+        let spillable = self.undo_stack.len().saturating_sub(keep);
+        let Some(start) = self.undo_stack[..spillable]
+            .iter()
+            .position(|entry| matches!(entry, UndoEntry::Loaded(_)))
+        else {
+            return Ok(0);
+        };
+        let end = self.undo_stack[start..spillable]
+            .iter()
+            .position(|entry| matches!(entry, UndoEntry::Spilled(_)))
+            .map_or(spillable, |offset| start + offset);
+
+        let groups: Vec<&Vec<BufferActions>> = self.undo_stack[start..end]
+            .iter()
+            .filter_map(|entry| match entry {
+                UndoEntry::Loaded(group) => Some(group),
+                UndoEntry::Spilled(_) => None,
+            })
+            .collect();
+        let freed: usize = groups.iter().map(|group| group_bytes(group)).sum();
+        storage.write(path, &serde_json::to_vec(&groups)?, false)?;
+
+        let spill = UndoEntry::Spilled(Spill {
+            path: path.to_path_buf(),
+            storage: storage.clone(),
+        });
+        self.undo_stack.splice(start..end, [spill]);
+        self.loaded_bytes -= freed;
+        Ok(freed)
+    }
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        for entry in &self.undo_stack {
+            if let UndoEntry::Spilled(spill) = entry {
+                let _ = spill.storage.remove(&spill.path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+    use crate::shared::buffers::Buffer;
+
+    fn typed(char_idx: usize, text: &str) -> (Vec<BufferActions>, Vec<BufferActions>) {
+        let end = char_idx + text.chars().count();
+        (vec![BufferActions::InsertText { char_idx, text: text.to_string() }], vec![BufferActions::DeleteRange { start: char_idx, end }])
+    }
+
+    #[test]
+    fn bursts_of_typing_at_the_cursor_are_one_undo_step() {
+        let mut history = History::default();
+        let start = Instant::now();
+        for (n, text) in ["a", "b", "c"].into_iter().enumerate() {
+            let (actions, inverse) = typed(n, text);
+            history.record_typed(&actions, inverse, start + Duration::from_millis(100 * n as u64), n + 1);
+        }
+        assert_eq!(history.undo_len(), 1);
+
+        // A pause, a jump elsewhere or a seal each start a new step.
+        let (actions, inverse) = typed(3, "d");
+        history.record_typed(&actions, inverse, start + Duration::from_secs(5), 4);
+        let (actions, inverse) = typed(0, "e");
+        history.record_typed(&actions, inverse, start + Duration::from_secs(5), 1);
+        history.seal();
+        let (actions, inverse) = typed(1, "f");
+        history.record_typed(&actions, inverse, start + Duration::from_secs(5), 2);
+        assert_eq!(history.undo_len(), 4);
+
+        history.pop_undo();
+        history.pop_undo();
+        history.pop_undo();
+        // Newest first, so rolling back runs in reverse typing order.
+        let burst = history.pop_undo().unwrap();
+        let starts: Vec<usize> = burst
+            .iter()
+            .map(|action| match action {
+                BufferActions::DeleteRange { start, .. } => *start,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(starts, [2, 1, 0]);
+        assert_eq!(history.memory_bytes(), 0);
+    }
+
+    #[test]
+    fn backspacing_joins_the_step_but_undo_and_saves_end_it() {
+        let mut buffer = Buffer::with_content("abc");
+        let now = Instant::now();
+        let backspace = |buffer: &mut Buffer, cursor: usize| {
+            let action = BufferActions::DeleteRange { start: cursor - 1, end: cursor };
+            let inverse = vec![buffer.apply(&action)];
+            buffer.history.record_typed(&[action], inverse, now, cursor - 1);
+        };
+        backspace(&mut buffer, 3);
+        backspace(&mut buffer, 2);
+        assert_eq!(buffer.history.undo_len(), 1);
+        buffer.mark_saved(&InMemoryStorage::default());
+        backspace(&mut buffer, 1);
+        assert_eq!((buffer.content.as_str(), buffer.history.undo_len()), ("", 2));
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.content, "a");
+        let (actions, inverse) = typed(1, "b");
+        buffer.apply(&actions[0]);
+        buffer.history.record_typed(&actions, inverse, now, 2);
+        assert_eq!(buffer.history.undo_len(), 2);
+    }
+}
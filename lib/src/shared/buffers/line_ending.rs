@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::interfaces::enums::BufferActions;
+
+// Buffers always hold `\n`; the file's own line ending is put back on save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    // Goes by the first line break; a file without one counts as `\n`.
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(at) if text[..at].ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+
+    pub fn normalize(text: String) -> String {
+        if text.contains("\r\n") { text.replace("\r\n", "\n") } else { text }
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Lf => Cow::Borrowed(text),
+            Self::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+// What saving does with the newline at the end of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalNewlinePolicy {
+    Keep,
+    #[default]
+    Ensure,
+    Remove,
+}
+
+impl FinalNewlinePolicy {
+    // The edits that bring `content` in line with the policy. Empty content
+    // is left alone; `Remove` strips every trailing newline.
+    pub fn edits(&self, content: &str) -> Vec<BufferActions> {
+        let len = content.chars().count();
+        match self {
+            Self::Ensure if !content.is_empty() && !content.ends_with('\n') => vec![BufferActions::InsertText {
+                char_idx: len,
+                text: String::from("\n"),
+            }],
+            Self::Remove if content.ends_with('\n') => {
+                let trailing = content.len() - content.trim_end_matches('\n').len();
+                vec![BufferActions::DeleteRange {
+                    start: len - trailing,
+                    end: len,
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_endings_are_detected_normalized_and_put_back() {
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\nb\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no break"), LineEnding::Lf);
+        assert_eq!(LineEnding::normalize("a\r\nb\r\n".to_string()), "a\nb\n");
+        assert_eq!(LineEnding::CrLf.apply("a\nb\n"), "a\r\nb\r\n");
+        assert_eq!(LineEnding::Lf.apply("a\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn final_newline_policies_only_touch_the_end() {
+        assert_eq!(
+            FinalNewlinePolicy::Ensure.edits("a\nb"),
+            vec![BufferActions::InsertText { char_idx: 3, text: "\n".to_string() }]
+        );
+        assert!(FinalNewlinePolicy::Ensure.edits("a\nb\n").is_empty());
+        assert!(FinalNewlinePolicy::Ensure.edits("").is_empty());
+        assert_eq!(
+            FinalNewlinePolicy::Remove.edits("é\n\n\n"),
+            vec![BufferActions::DeleteRange { start: 1, end: 4 }]
+        );
+        assert!(FinalNewlinePolicy::Remove.edits("a").is_empty());
+        assert!(FinalNewlinePolicy::Keep.edits("a").is_empty());
+    }
+}
@@ -1,12 +1,545 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Instant, SystemTime};
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::config::ThemeId;
+use crate::interfaces::enums::BufferActions;
+use crate::server::encoding;
+use crate::server::hash;
+use crate::server::lsp;
+use crate::server::persistence::Storage;
+
+pub mod annotations;
+mod diff;
+pub mod external;
+pub mod history;
+pub mod line_ending;
+pub use diff::diff;
+pub use external::{ExternalAction, ExternalChange};
+use annotations::{Annotation, Annotations};
+use history::History;
+pub use line_ending::{FinalNewlinePolicy, LineEnding};
+
+// Edit locations kept per buffer for `Buffer::recent_edits`.
+const RECENT_EDITS_LIMIT: usize = 16;
+
 pub struct Buffer {
-    pub content : String,
+    // Private so every change goes through a method that resets `hash`.
+    content : String,
+    // `content`'s hash, worked out when first asked for after a change.
+    hash : OnceLock<u64>,
+    pub file_path : PathBuf,
+    pub history : History,
+    // Collapsed line ranges; the first line of each stays visible.
+    pub folds : Vec<Range<usize>>,
+    // How `content` is written back to `file_path`.
+    pub encoding : &'static Encoding,
+    pub line_ending : LineEnding,
+    // LSP language id, from the file extension.
+    pub language : &'static str,
+    // Named positions, moved along with edits.
+    pub marks : HashMap<char, usize>,
+    // Syntax palette for this buffer instead of the global one.
+    pub theme_override : Option<ThemeId>,
+    // Hash of the content as last read from or written to disk.
+    pub saved_hash : u64,
+    // The file's modification time at that point, to notice outside edits.
+    pub disk_modified : Option<SystemTime>,
+    // Overrides the global `external_change` policy.
+    pub external_change : Option<ExternalChange>,
+    // A notes pad that's never written to a file.
+    pub is_scratch : bool,
+    // The output of a terminal pane, live or exited; never written to a file.
+    pub is_terminal : bool,
+    // Filled from piped input; it has no file until saved as one.
+    pub is_stream : bool,
+    // Too large for highlighting and the language server; see `BigFileConfig`.
+    pub big_file : bool,
+    // Underlines unknown words: comments and strings in code, all of a
+    // prose file.
+    pub spell_check : bool,
+    // Shown after line ends; filled by `AnnotationProvider`s.
+    pub annotations : Annotations,
+    // Where the latest edits ended and when, newest last. Edits on the same
+    // line as the newest one replace it, so typing leaves a single entry.
+    pub recent_edits : VecDeque<(usize, Instant)>,
 }
 
-impl Buffer{
-    pub fn default() -> Self {
+impl Default for Buffer {
+    fn default() -> Self {
         Self {
-            content: String::from("")
+            content: String::new(),
+            hash: OnceLock::new(),
+            file_path: PathBuf::new(),
+            history: History::default(),
+            folds: Vec::new(),
+            encoding: UTF_8,
+            line_ending: LineEnding::Lf,
+            language: lsp::language_id(Path::new("")),
+            marks: HashMap::new(),
+            theme_override: None,
+            saved_hash: hash::content_hash(""),
+            disk_modified: None,
+            external_change: None,
+            is_scratch: false,
+            is_terminal: false,
+            is_stream: false,
+            big_file: false,
+            spell_check: false,
+            annotations: HashMap::new(),
+            recent_edits: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferStats {
+    pub chars: usize,
+    // Runs of non-whitespace, by Unicode's idea of whitespace.
+    pub words: usize,
+    // As the editor shows them: an empty buffer has one, and so does the
+    // end after a final newline.
+    pub lines: usize,
+    pub bytes: usize,
+}
+
+// An explicit checkpoint of a buffer, for previews that may be thrown away.
+// Clones share the content.
+#[derive(Debug, Clone)]
+pub struct BufferSnapshot {
+    content: Arc<str>,
+    folds: Vec<Range<usize>>,
+    marks: HashMap<char, usize>,
+    pub cursor: usize,
+}
+
+fn modified_time(storage: &dyn Storage, path: &Path) -> Option<SystemTime> {
+    storage.metadata(path).ok().map(|metadata| metadata.modified)
+}
+
+// Text inserted at or before a position pushes it along; a position inside
+// a deleted range moves to where the range started. A replacement counts as
+// the deletion followed by the insertion.
+fn remap_position(position: &mut usize, action: &BufferActions) {
+    match action {
+        BufferActions::InsertText { char_idx, text } => {
+            if *char_idx <= *position {
+                *position += text.chars().count();
+            }
+        }
+        BufferActions::DeleteRange { start, end } => {
+            let end = (*end).max(*start);
+            if *position >= end {
+                *position -= end - start;
+            } else if *position > *start {
+                *position = *start;
+            }
+        }
+        BufferActions::ReplaceRange { start, end, text } => {
+            let end = (*end).max(*start);
+            if *position >= end {
+                *position = *position - (end - start) + text.chars().count();
+            } else if *position >= *start {
+                *position = start + text.chars().count();
+            }
+        }
+    }
+}
+
+pub fn byte_index(content: &str, char_idx: usize) -> usize {
+    content
+        .char_indices()
+        .nth(char_idx)
+        .map_or(content.len(), |(byte_idx, _)| byte_idx)
+}
+
+// The char a byte offset falls in; offsets past the end give the end.
+pub fn char_index_at_byte(content: &str, byte_idx: usize) -> usize {
+    let mut byte_idx = byte_idx.min(content.len());
+    while !content.is_char_boundary(byte_idx) {
+        byte_idx -= 1;
+    }
+    content[..byte_idx].chars().count()
+}
+
+impl Buffer {
+    // An unnamed buffer holding `content`, as if typed.
+    pub fn with_content(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn from_file(path: &Path, storage: &dyn Storage) -> io::Result<Self> {
+        let bytes = storage.read(path)?;
+        Ok(Self::from_bytes(path, &bytes, storage))
+    }
+
+    // For bytes read elsewhere, e.g. by a background load.
+    pub fn from_bytes(path: &Path, bytes: &[u8], storage: &dyn Storage) -> Self {
+        let file_path = storage.canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let encoding = encoding::detect(bytes);
+        let content = encoding::decode(bytes, encoding);
+        let line_ending = LineEnding::detect(&content);
+        let content = LineEnding::normalize(content);
+        Self {
+            line_ending,
+            saved_hash: hash::content_hash(&content),
+            disk_modified: modified_time(storage, &file_path),
+            content,
+            language: lsp::language_id(&file_path),
+            file_path,
+            encoding,
+            ..Self::default()
+        }
+    }
+
+    // Picks up changes made on disk as one undoable edit. Only the span
+    // that differs is replaced, so cursors, scroll positions and folds
+    // before it stay where they were.
+    pub fn reload(&mut self, storage: &dyn Storage) -> io::Result<()> {
+        let bytes = storage.read(&self.file_path)?;
+        self.replace_decoded(&bytes, self.encoding);
+        self.mark_saved(storage);
+        Ok(())
+    }
+
+    // The file as it is on disk now, decoded the way this buffer would be.
+    pub fn disk_content(&self, storage: &dyn Storage) -> io::Result<String> {
+        let bytes = storage.read(&self.file_path)?;
+        Ok(LineEnding::normalize(encoding::decode(&bytes, self.encoding)))
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        BufferStats {
+            chars: self.content.chars().count(),
+            words: self.content.split_whitespace().count(),
+            lines: self.content.split('\n').count(),
+            bytes: self.content.len(),
+        }
+    }
+
+    // Whether the content differs from what's on disk, as far as this
+    // buffer last saw it.
+    pub fn is_modified(&self) -> bool {
+        self.content_hash() != self.saved_hash
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    // Swaps in new content wholesale, outside the undo history.
+    pub fn set_content(&mut self, content: impl Into<String>) {
+        self.content = content.into();
+        self.hash = OnceLock::new();
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        *self.hash.get_or_init(|| hash::content_hash(&self.content))
+    }
+
+    // Adds output to the end, for buffers filled from a process or pipe.
+    pub fn append(&mut self, text: &str) {
+        self.content.push_str(text);
+        self.hash = OnceLock::new();
+    }
+
+    // Modified and meant to be saved: scratch and terminal buffers never are.
+    pub fn is_unsaved(&self) -> bool {
+        !self.is_scratch && !self.is_terminal && self.is_modified()
+    }
+
+    // Also ends the undo step being typed, so undo stops at the save.
+    pub fn mark_saved(&mut self, storage: &dyn Storage) {
+        self.history.seal();
+        self.saved_hash = self.content_hash();
+        self.disk_modified = modified_time(storage, &self.file_path);
+    }
+
+    // Whether the file was touched since the buffer last read or wrote it.
+    // Notes the new time, so each change is reported once; a buffer that
+    // never saw its file just starts tracking it.
+    pub fn take_disk_change(&mut self, storage: &dyn Storage) -> bool {
+        if self.is_scratch || self.file_path.as_os_str().is_empty() {
+            return false;
+        }
+        let current = modified_time(storage, &self.file_path);
+        let previous = std::mem::replace(&mut self.disk_modified, current);
+        previous.is_some() && current.is_some() && previous != current
+    }
+
+    // The baseline `take_disk_change` compares against, if it has one.
+    pub fn disk_baseline(&self) -> Option<(&Path, SystemTime)> {
+        if self.is_scratch || self.file_path.as_os_str().is_empty() {
+            return None;
+        }
+        Some((&self.file_path, self.disk_modified?))
+    }
+
+    // Decodes `bytes` as `encoding` and swaps them in for the content.
+    pub fn replace_decoded(&mut self, bytes: &[u8], encoding: &'static Encoding) {
+        let content = encoding::decode(bytes, encoding);
+        self.line_ending = LineEnding::detect(&content);
+        self.encoding = encoding;
+        let content = LineEnding::normalize(content);
+        self.edit(&diff(&self.content, &content));
+    }
+
+    // The content as it goes to disk, in the file's encoding and line
+    // ending, and how many characters the encoding couldn't represent.
+    pub fn encoded(&self) -> (Vec<u8>, usize) {
+        encoding::encode(&self.line_ending.apply(&self.content), self.encoding)
+    }
+
+    // Applies one action without touching history and returns its inverse.
+    // Indices past the end clamp to the end of the content.
+    pub fn apply(&mut self, action: &BufferActions) -> BufferActions {
+        self.remap_folds(action);
+        self.remap_marks(action);
+        self.remap_annotations(action);
+        for (position, _) in &mut self.recent_edits {
+            remap_position(position, action);
+        }
+        self.hash = OnceLock::new();
+        let inverse = match action {
+            BufferActions::InsertText { char_idx, text } => {
+                let at = byte_index(&self.content, *char_idx);
+                let char_idx = self.content[..at].chars().count();
+                self.content.insert_str(at, text);
+                BufferActions::DeleteRange {
+                    start: char_idx,
+                    end: char_idx + text.chars().count(),
+                }
+            }
+            BufferActions::DeleteRange { start, end } => {
+                let start_byte = byte_index(&self.content, *start);
+                let end_byte = byte_index(&self.content, (*end).max(*start));
+                let removed: String = self.content.drain(start_byte..end_byte).collect();
+                BufferActions::InsertText {
+                    char_idx: self.content[..start_byte].chars().count(),
+                    text: removed,
+                }
+            }
+            BufferActions::ReplaceRange { start, end, text } => {
+                let start_byte = byte_index(&self.content, *start);
+                let end_byte = byte_index(&self.content, (*end).max(*start));
+                let removed = self.content[start_byte..end_byte].to_string();
+                self.content.replace_range(start_byte..end_byte, text);
+                let start = self.content[..start_byte].chars().count();
+                BufferActions::ReplaceRange {
+                    start,
+                    end: start + text.chars().count(),
+                    text: removed,
+                }
+            }
+        };
+        let edited_at = match &inverse {
+            BufferActions::DeleteRange { end, .. } | BufferActions::ReplaceRange { end, .. } => *end,
+            BufferActions::InsertText { char_idx, .. } => *char_idx,
+        };
+        self.note_edit(edited_at);
+        inverse
+    }
+
+    fn note_edit(&mut self, char_idx: usize) {
+        let same_line = self.recent_edits.back().is_some_and(|(newest, _)| {
+            let (from, to) = ((*newest).min(char_idx), (*newest).max(char_idx));
+            !self.content.chars().skip(from).take(to - from).any(|c| c == '\n')
+        });
+        if same_line {
+            self.recent_edits.pop_back();
+        } else if self.recent_edits.len() == RECENT_EDITS_LIMIT {
+            self.recent_edits.pop_front();
+        }
+        self.recent_edits.push_back((char_idx, Instant::now()));
+    }
+
+    // Keeps folds attached to the same lines when an edit adds or removes
+    // lines; a fold whose first line is merged away is dropped.
+    fn remap_folds(&mut self, action: &BufferActions) {
+        if self.folds.is_empty() {
+            return;
         }
+        let line_of = |char_idx: usize| self.content.chars().take(char_idx).filter(|c| *c == '\n').count();
+        match action {
+            BufferActions::InsertText { char_idx, text } => {
+                let line = line_of(*char_idx);
+                let added = text.matches('\n').count();
+                let at_line_start = *char_idx == 0 || self.content.chars().nth(char_idx - 1) == Some('\n');
+                for fold in &mut self.folds {
+                    if line < fold.start || (line == fold.start && at_line_start) {
+                        fold.start += added;
+                        fold.end += added;
+                    } else if line < fold.end {
+                        fold.end += added;
+                    }
+                }
+            }
+            BufferActions::DeleteRange { start, end } => {
+                let first = line_of(*start);
+                let removed = line_of((*end).max(*start)) - first;
+                // Deleting from the start of a line removes whole lines from
+                // `first` on; otherwise the lines after `first` merge into it.
+                let at_line_start = *start == 0 || self.content.chars().nth(start - 1) == Some('\n');
+                let offset = if at_line_start { first } else { first + 1 };
+                let removed_before = |line: usize| line.saturating_sub(offset).min(removed);
+                self.folds.retain_mut(|fold| {
+                    if fold.start >= offset && fold.start < offset + removed {
+                        return false;
+                    }
+                    fold.start -= removed_before(fold.start);
+                    fold.end -= removed_before(fold.end);
+                    fold.end > fold.start + 1
+                });
+            }
+            // Both halves only look at the text before `start`, which the
+            // deletion leaves alone.
+            BufferActions::ReplaceRange { start, end, text } => {
+                self.remap_folds(&BufferActions::DeleteRange { start: *start, end: *end });
+                self.remap_folds(&BufferActions::InsertText { char_idx: *start, text: text.clone() });
+            }
+        }
+    }
+
+    pub fn set_mark(&mut self, name: char, char_idx: usize) {
+        self.marks.insert(name, char_idx.min(self.content.chars().count()));
+    }
+
+    pub fn mark(&self, name: char) -> Option<usize> {
+        self.marks.get(&name).copied()
+    }
+
+    fn remap_marks(&mut self, action: &BufferActions) {
+        for mark in self.marks.values_mut() {
+            remap_position(mark, action);
+        }
+    }
+
+    // Replaces everything from `source` with `entries`.
+    pub fn set_annotations(&mut self, source: &'static str, entries: Vec<(usize, String)>) {
+        self.annotations.retain(|_, annotations| {
+            annotations.retain(|annotation| annotation.source != source);
+            !annotations.is_empty()
+        });
+        for (line, text) in entries {
+            self.annotations.entry(line).or_default().push(Annotation { source, text });
+        }
+    }
+
+    // Same rules as `remap_folds`: annotations stay with their line and are
+    // dropped when it's merged away.
+    fn remap_annotations(&mut self, action: &BufferActions) {
+        if self.annotations.is_empty() {
+            return;
+        }
+        let line_of = |char_idx: usize| self.content.chars().take(char_idx).filter(|c| *c == '\n').count();
+        let at_line_start = |char_idx: usize| char_idx == 0 || self.content.chars().nth(char_idx - 1) == Some('\n');
+        let remapped: Annotations = match action {
+            BufferActions::InsertText { char_idx, text } => {
+                let added = text.matches('\n').count();
+                if added == 0 {
+                    return;
+                }
+                let line = line_of(*char_idx);
+                let from = if at_line_start(*char_idx) { line } else { line + 1 };
+                self.annotations
+                    .drain()
+                    .map(|(at, annotations)| (if at >= from { at + added } else { at }, annotations))
+                    .collect()
+            }
+            BufferActions::DeleteRange { start, end } => {
+                let first = line_of(*start);
+                let removed = line_of((*end).max(*start)) - first;
+                if removed == 0 {
+                    return;
+                }
+                let offset = if at_line_start(*start) { first } else { first + 1 };
+                self.annotations
+                    .drain()
+                    .filter(|(at, _)| *at < offset || *at >= offset + removed)
+                    .map(|(at, annotations)| (if at >= offset { at - removed } else { at }, annotations))
+                    .collect()
+            }
+            BufferActions::ReplaceRange { start, end, text } => {
+                self.remap_annotations(&BufferActions::DeleteRange { start: *start, end: *end });
+                self.remap_annotations(&BufferActions::InsertText { char_idx: *start, text: text.clone() });
+                return;
+            }
+        };
+        self.annotations = remapped;
+    }
+
+    pub fn snapshot(&self, cursor: usize) -> BufferSnapshot {
+        BufferSnapshot {
+            content: Arc::from(self.content.as_str()),
+            folds: self.folds.clone(),
+            marks: self.marks.clone(),
+            cursor,
+        }
+    }
+
+    // Puts the content, folds and marks back as they were and returns the
+    // cursor. The rollback is one undo step of its own, so the history
+    // stays consistent with the content.
+    pub fn restore(&mut self, snapshot: &BufferSnapshot) -> usize {
+        self.edit(&diff(&self.content, &snapshot.content));
+        self.folds = snapshot.folds.clone();
+        self.marks = snapshot.marks.clone();
+        snapshot.cursor
+    }
+
+    // Swaps the bytes in `range` for `text` as one undo step and returns the
+    // inverse. Unlike `apply`, which clamps, a range past the end or inside
+    // a char is refused.
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) -> io::Result<BufferActions> {
+        if range.start > range.end || range.end > self.content.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{range:?} is outside the buffer")));
+        }
+        if !self.content.is_char_boundary(range.start) || !self.content.is_char_boundary(range.end) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{range:?} splits a char")));
+        }
+        let start = self.content[..range.start].chars().count();
+        let end = start + self.content[range].chars().count();
+        let inverse = self.apply(&BufferActions::ReplaceRange { start, end, text: text.to_string() });
+        self.history.record(vec![inverse.clone()]);
+        Ok(inverse)
+    }
+
+    fn apply_all(&mut self, actions: &[BufferActions]) -> Vec<BufferActions> {
+        let mut inverse: Vec<BufferActions> = actions.iter().map(|action| self.apply(action)).collect();
+        inverse.reverse();
+        inverse
+    }
+
+    // Applies `actions` in order as a single undo step.
+    pub fn edit(&mut self, actions: &[BufferActions]) {
+        let inverse = self.apply_all(actions);
+        self.history.record(inverse);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(inverse) = self.history.pop_undo() else {
+            return false;
+        };
+        let redo = self.apply_all(&inverse);
+        self.history.push_redo(redo);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(actions) = self.history.pop_redo() else {
+            return false;
+        };
+        let undo = self.apply_all(&actions);
+        self.history.push_undo(undo);
+        true
     }
 }
 
@@ -14,10 +547,225 @@ pub struct BufferStorage {
     pub buffers : Vec<Buffer>,
 }
 
-impl BufferStorage {
-    pub fn default() -> Self {
+impl Default for BufferStorage {
+    fn default() -> Self {
         Self {
             buffers: vec![Buffer::default()]
         }
     }
 }
+
+impl BufferStorage {
+    // Only the untouched default buffer exists: nothing opened or typed yet.
+    pub fn is_pristine(&self) -> bool {
+        self.buffers
+            .iter()
+            .all(|buffer| buffer.content.is_empty() && buffer.file_path.as_os_str().is_empty())
+    }
+
+    // Adds the scratch buffer unless there already is one. Returns its index.
+    pub fn ensure_scratch(&mut self) -> usize {
+        if let Some(buffer_index) = self.buffers.iter().position(|buffer| buffer.is_scratch) {
+            return buffer_index;
+        }
+        self.buffers.push(Buffer {
+            is_scratch: true,
+            ..Buffer::default()
+        });
+        self.buffers.len() - 1
+    }
+
+    // Buffers with changes that would be lost on exit.
+    pub fn unsaved(&self) -> Vec<usize> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.is_unsaved())
+            .map(|(buffer_index, _)| buffer_index)
+            .collect()
+    }
+
+    pub fn find_by_path(&self, path: &Path) -> Option<usize> {
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        self.buffers.iter().position(|buffer| buffer.file_path == path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::persistence::InMemoryStorage;
+
+    #[test]
+    fn unsaved_skips_scratch_and_terminal_buffers() {
+        let edited = |buffer: Buffer| Buffer { content: String::from("typed"), ..buffer };
+        let storage = BufferStorage {
+            buffers: vec![
+                edited(Buffer::default()),
+                edited(Buffer { is_scratch: true, ..Buffer::default() }),
+                edited(Buffer { is_terminal: true, ..Buffer::default() }),
+                Buffer::default(),
+            ],
+        };
+        assert_eq!(storage.unsaved(), vec![0]);
+    }
+
+    #[test]
+    fn modified_follows_edits_appends_and_saves() {
+        let mut buffer = Buffer::default();
+        assert!(!buffer.is_modified());
+        buffer.edit(&[BufferActions::InsertText { char_idx: 0, text: String::from("hi") }]);
+        assert!(buffer.is_modified());
+        buffer.mark_saved(&InMemoryStorage::default());
+        assert!(!buffer.is_modified());
+        buffer.append("!");
+        assert!(buffer.is_modified());
+        assert!(buffer.undo() && buffer.content == "!");
+        buffer.apply(&BufferActions::DeleteRange { start: 0, end: 1 });
+        buffer.append("hi");
+        assert!(!buffer.is_modified());
+        buffer.set_content("hi!");
+        assert!(buffer.is_modified());
+        buffer.set_content("hi");
+        assert!(!buffer.is_modified());
+        assert_eq!(buffer.saved_hash, 0xEA88_42E9_EA26_38FA, "saved hashes must stay stable");
+    }
+
+    #[test]
+    fn files_load_decoded_and_reload_as_one_undoable_edit() {
+        let storage = InMemoryStorage::default();
+        let path = Path::new("/project/notes.md");
+        storage.write(path, b"one\r\ntwo\r\n", false).unwrap();
+        let mut buffer = Buffer::from_file(path, &storage).unwrap();
+        assert_eq!((buffer.content.as_str(), buffer.line_ending, buffer.language), ("one\ntwo\n", LineEnding::CrLf, "markdown"));
+        assert!(!buffer.is_modified());
+
+        storage.write(path, b"one\r\n2\r\n", false).unwrap();
+        buffer.reload(&storage).unwrap();
+        assert_eq!(buffer.content, "one\n2\n");
+        assert!(!buffer.is_modified());
+        assert!(buffer.undo());
+        assert_eq!(buffer.content, "one\ntwo\n");
+        assert!(Buffer::from_file(Path::new("/project/missing.md"), &storage).is_err());
+    }
+
+    #[test]
+    fn marks_follow_edits_and_clamp_to_the_content() {
+        let mut buffer = Buffer::with_content("one two");
+        buffer.set_mark('a', 4);
+        buffer.set_mark('b', 99);
+        assert_eq!(buffer.mark('b'), Some(7));
+        buffer.edit(&[BufferActions::InsertText { char_idx: 0, text: String::from(">> ") }]);
+        assert_eq!((buffer.mark('a'), buffer.mark('b')), (Some(7), Some(10)));
+        buffer.edit(&[BufferActions::DeleteRange { start: 0, end: 7 }]);
+        assert_eq!(buffer.mark('a'), Some(0));
+        assert_eq!(buffer.mark('c'), None);
+    }
+
+    #[test]
+    fn annotations_stay_on_their_line_per_source() {
+        let mut buffer = Buffer::with_content("a\nb\nc\n");
+        buffer.set_annotations("blame", vec![(1, String::from("on b")), (2, String::from("on c"))]);
+        buffer.set_annotations("hints", vec![(2, String::from("hint"))]);
+        buffer.set_annotations("blame", vec![(1, String::from("on b again"))]);
+        let texts = |buffer: &Buffer, line: usize| -> Vec<String> {
+            buffer.annotations.get(&line).map(|annotations| annotations.iter().map(|a| a.text.clone()).collect()).unwrap_or_default()
+        };
+        assert_eq!((texts(&buffer, 1), texts(&buffer, 2)), (vec![String::from("on b again")], vec![String::from("hint")]));
+
+        buffer.edit(&[BufferActions::InsertText { char_idx: 0, text: String::from("new\n") }]);
+        assert_eq!((texts(&buffer, 2), texts(&buffer, 3)), (vec![String::from("on b again")], vec![String::from("hint")]));
+        // Joining b into the line above merges its line away.
+        buffer.edit(&[BufferActions::DeleteRange { start: 5, end: 6 }]);
+        assert_eq!((texts(&buffer, 2), texts(&buffer, 3)), (vec![String::from("hint")], Vec::new()));
+        assert_eq!(buffer.annotations.len(), 1);
+    }
+
+    #[test]
+    fn outside_changes_are_reported_once() {
+        let storage = InMemoryStorage::default();
+        let path = PathBuf::from("/project/notes.md");
+        storage.write(&path, b"on disk", false).unwrap();
+        let mut buffer = Buffer { file_path: path.clone(), ..Buffer::default() };
+        // The first look only starts tracking the file.
+        assert!(!buffer.take_disk_change(&storage));
+        storage.write(&path, b"changed", false).unwrap();
+        buffer.disk_modified = Some(std::time::SystemTime::UNIX_EPOCH);
+        assert!(buffer.take_disk_change(&storage));
+        assert!(!buffer.take_disk_change(&storage));
+        buffer.is_scratch = true;
+        buffer.disk_modified = Some(std::time::SystemTime::UNIX_EPOCH);
+        assert!(!buffer.take_disk_change(&storage));
+    }
+
+    #[test]
+    fn replacing_a_range_splices_once_and_inverts() {
+        let mut buffer = Buffer::with_content("héllo wörld");
+        let replace = BufferActions::ReplaceRange { start: 2, end: 8, text: String::from("ip, w") };
+        let inverse = buffer.apply(&replace);
+        assert_eq!(buffer.content, "héip, wrld");
+        assert_eq!(inverse, BufferActions::ReplaceRange { start: 2, end: 7, text: String::from("llo wö") });
+        assert_eq!(buffer.apply(&inverse), replace);
+        assert_eq!(buffer.content, "héllo wörld");
+    }
+
+    #[test]
+    fn replace_range_takes_byte_offsets_and_undoes_in_one_step() {
+        let mut buffer = Buffer::with_content("héllo wörld");
+        assert_eq!(buffer.replace_range(2..3, "e").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(buffer.replace_range(7..20, "").is_err());
+        assert_eq!(buffer.history.undo_len(), 0);
+
+        // "é" and "ö" are two bytes each.
+        let inverse = buffer.replace_range(1..10, "ey, w").unwrap();
+        assert_eq!(buffer.content(), "hey, wrld");
+        assert_eq!(inverse, BufferActions::ReplaceRange { start: 1, end: 6, text: String::from("éllo wö") });
+        assert_eq!(buffer.history.undo_len(), 1);
+        assert!(buffer.undo());
+        assert_eq!(buffer.content(), "héllo wörld");
+        assert!(!buffer.undo());
+    }
+
+    #[test]
+    fn restoring_a_snapshot_brings_back_content_folds_marks_and_cursor() {
+        let mut buffer = Buffer::with_content("one\ntwo\nthree");
+        buffer.folds.push(4..8);
+        buffer.marks.insert('a', 9);
+        let snapshot = buffer.snapshot(5);
+
+        buffer.edit(&[BufferActions::ReplaceRange { start: 0, end: 3, text: String::from("uno") }]);
+        buffer.edit(&[BufferActions::DeleteRange { start: 7, end: 13 }]);
+        buffer.folds.clear();
+        buffer.marks.insert('a', 0);
+
+        assert_eq!(buffer.restore(&snapshot), 5);
+        assert_eq!(buffer.content, "one\ntwo\nthree");
+        assert_eq!(buffer.folds, vec![4..8]);
+        assert_eq!(buffer.marks.get(&'a'), Some(&9));
+        assert!(buffer.undo());
+        assert_eq!(buffer.content, "uno\ntwo");
+    }
+
+    #[test]
+    fn recent_edits_keep_one_entry_per_line_and_follow_edits() {
+        let mut buffer = Buffer::with_content("one\ntwo\n");
+        buffer.edit(&[BufferActions::InsertText { char_idx: 7, text: String::from("!") }]);
+        buffer.edit(&[BufferActions::InsertText { char_idx: 8, text: String::from("!") }]);
+        buffer.edit(&[BufferActions::InsertText { char_idx: 0, text: String::from(">") }]);
+        let positions = |buffer: &Buffer| -> Vec<usize> { buffer.recent_edits.iter().map(|(at, _)| *at).collect() };
+        // The typing on `two` left one entry, pushed along by the `>`.
+        assert_eq!(positions(&buffer), vec![10, 1]);
+        buffer.edit(&[BufferActions::DeleteRange { start: 0, end: 1 }]);
+        assert_eq!(positions(&buffer), vec![9, 0]);
+    }
+
+    #[test]
+    fn stats_count_what_the_editor_shows() {
+        let stats = |content: &str| Buffer::with_content(content).stats();
+        assert_eq!(stats(""), BufferStats { chars: 0, words: 0, lines: 1, bytes: 0 });
+        assert_eq!(stats("héllo  wörld\n"), BufferStats { chars: 13, words: 2, lines: 2, bytes: 15 });
+        assert_eq!(stats("a\u{3000}b\n\nc").words, 3);
+    }
+}
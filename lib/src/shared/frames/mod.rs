@@ -1,8 +1,19 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug)]
 pub enum FramePositionType {
     Fixed,
     Absolute
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameKind {
+    #[default]
+    Editor,
+    Terminal { closed: bool },
+}
+
 #[derive(Debug)]
 pub struct Coordinates {
     pub x: i32,
@@ -11,45 +22,383 @@ pub struct Coordinates {
 
 #[derive(Debug)]
 pub struct Frame {
+    pub kind: FrameKind,
     pub position_type: FramePositionType,
     pub position: Coordinates,
-    pub buffer_index: usize
+    pub buffer_index: usize,
+    pub scroll_offset: f32,
+    pub max_scroll_offset: f32,
+    pub scroll_group: Option<u32>,
+    pub pending_jump: Option<usize>,
+    pub pending_cursor: Option<usize>,
+    pub pending_selection: Option<Range<usize>>,
+    // Set on frames showing output as it's appended, which stay at the end
+    // while scrolled there.
+    pub follow: bool,
 }
 
-impl Frame {
-    pub fn default() -> Self{
+impl Default for Frame {
+    fn default() -> Self {
         Self {
+            kind: FrameKind::Editor,
             position_type : FramePositionType::Fixed,
             position: Coordinates { x : 0, y : 0 },
             buffer_index: 0,
+            scroll_offset: 0.0,
+            max_scroll_offset: 0.0,
+            scroll_group: None,
+            pending_jump: None,
+            pending_cursor: None,
+            pending_selection: None,
+            follow: false,
         }
     }
 }
 
+impl Frame {
+    // A new frame on the same buffer, placed beside this one and starting at
+    // the same scroll offset. It scrolls on its own unless it shares a
+    // scroll group.
+    pub fn split(&self) -> Self {
+        Self {
+            kind: self.kind,
+            position: Coordinates {
+                x: self.position.x + 1,
+                y: self.position.y,
+            },
+            buffer_index: self.buffer_index,
+            scroll_offset: self.scroll_offset,
+            max_scroll_offset: self.max_scroll_offset,
+            follow: self.follow,
+            ..Self::default()
+        }
+    }
+
+    pub fn scroll_by(&mut self, delta: f32) {
+        self.scroll_offset = (self.scroll_offset + delta).clamp(0.0, self.max_scroll_offset);
+    }
+
+    // Within half a point, so rounding in the scroll area doesn't count as
+    // having scrolled up.
+    pub fn is_at_bottom(&self) -> bool {
+        self.scroll_offset >= self.max_scroll_offset - 0.5
+    }
+
+    // Takes the scroll range the content now needs. A following frame that
+    // was at the bottom moves to the new bottom, like `tail -f`; scrolled
+    // up to read back, it stays put. Returns whether it moved.
+    pub fn set_max_scroll_offset(&mut self, max_scroll_offset: f32) -> bool {
+        let tail = self.follow && self.is_at_bottom();
+        self.max_scroll_offset = max_scroll_offset;
+        if !tail || self.scroll_offset == max_scroll_offset {
+            return false;
+        }
+        self.scroll_offset = max_scroll_offset;
+        true
+    }
+}
+
+
+// Where the window showing a cluster was last placed, in points.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub size: [f32; 2],
+    pub position: Option<[f32; 2]>,
+}
 
 pub struct FrameCluster {
+    // Shown on its tab in the main window; empty means a numbered default.
+    pub name: String,
     pub is_visible: bool,
-    pub frames : Vec<Frame>
+    pub frames : Vec<Frame>,
+    pub geometry: Option<WindowGeometry>,
+    // The frame commands act on when none has keyboard focus.
+    pub active_frame: usize,
 }
 
-impl FrameCluster {
-    pub fn default() -> Self {
+impl Default for FrameCluster {
+    fn default() -> Self {
         Self {
+            name: String::new(),
             is_visible: false,
-            frames : vec![Frame::default()]
+            frames : vec![Frame::default()],
+            geometry: None,
+            active_frame: 0,
         }
     }
 }
 
+impl FrameCluster {
+    // Moves on to the next frame down, wrapping around. Returns the new one.
+    pub fn activate_next(&mut self) -> usize {
+        self.active_frame = (self.active_frame + 1) % self.frames.len().max(1);
+        self.active_frame
+    }
+
+    pub fn set_active(&mut self, frame_idx: usize) {
+        self.active_frame = frame_idx.min(self.frames.len().saturating_sub(1));
+    }
+
+    // Takes a frame out. Frames after the active one shift up, so the
+    // active index follows; removing the active frame itself activates the
+    // one that took its place, or the last one.
+    pub fn remove_frame(&mut self, frame_idx: usize) -> Option<Frame> {
+        if frame_idx >= self.frames.len() {
+            return None;
+        }
+        let frame = self.frames.remove(frame_idx);
+        if frame_idx < self.active_frame {
+            self.active_frame -= 1;
+        }
+        self.set_active(self.active_frame);
+        Some(frame)
+    }
+}
+
 
 pub struct FrameStorage {
     pub frame_clusters : Vec<FrameCluster>
 }
 
-impl FrameStorage {
-    pub fn default() -> Self {
+impl Default for FrameStorage {
+    fn default() -> Self {
         Self {
             frame_clusters: vec![ FrameCluster::default() ]
         }
     }
 }
+
+impl FrameStorage {
+    // Adds a cluster with one frame unless there already is one, so
+    // windows always have a cluster 0 to show. Returns its index.
+    pub fn ensure_cluster(&mut self) -> usize {
+        if self.frame_clusters.is_empty() {
+            self.frame_clusters.push(FrameCluster::default());
+        }
+        0
+    }
+
+    pub fn shows_buffer(&self, buffer_index: usize) -> bool {
+        self.frame_clusters
+            .iter()
+            .flat_map(|cluster| &cluster.frames)
+            .any(|frame| frame.buffer_index == buffer_index)
+    }
+
+    // Queues a jump to `line` on every frame showing the buffer; the
+    // renderer resolves it once it knows the row height.
+    pub fn jump_to_line(&mut self, buffer_index: usize, line: usize) {
+        self.frame_clusters
+            .iter_mut()
+            .flat_map(|cluster| cluster.frames.iter_mut())
+            .filter(|frame| frame.buffer_index == buffer_index)
+            .for_each(|frame| frame.pending_jump = Some(line));
+    }
+
+    // Queues a cursor move (as a char index) on every frame showing the buffer.
+    pub fn set_cursor(&mut self, buffer_index: usize, char_idx: usize) {
+        self.frame_clusters
+            .iter_mut()
+            .flat_map(|cluster| cluster.frames.iter_mut())
+            .filter(|frame| frame.buffer_index == buffer_index)
+            .for_each(|frame| frame.pending_cursor = Some(char_idx));
+    }
+
+    // Queues a selection (in chars) on every frame showing the buffer.
+    pub fn set_selection(&mut self, buffer_index: usize, range: Range<usize>) {
+        self.frame_clusters
+            .iter_mut()
+            .flat_map(|cluster| cluster.frames.iter_mut())
+            .filter(|frame| frame.buffer_index == buffer_index)
+            .for_each(|frame| frame.pending_selection = Some(range.clone()));
+    }
+
+    pub fn add_frame(&mut self, cluster_idx: usize, frame: Frame) -> Option<usize> {
+        let cluster = self.frame_clusters.get_mut(cluster_idx)?;
+        cluster.frames.push(frame);
+        Some(cluster.frames.len() - 1)
+    }
+
+    // Points an editor frame at another buffer, starting it from the top.
+    // `buffer_count` bounds the index. Returns the buffer it showed before,
+    // or `None` when nothing changed.
+    pub fn set_frame_buffer(
+        &mut self,
+        cluster_idx: usize,
+        frame_idx: usize,
+        buffer_index: usize,
+        buffer_count: usize,
+    ) -> Option<usize> {
+        let frame = self.frame_clusters.get_mut(cluster_idx)?.frames.get_mut(frame_idx)?;
+        if buffer_index >= buffer_count || frame.kind != FrameKind::Editor {
+            return None;
+        }
+        let previous = std::mem::replace(&mut frame.buffer_index, buffer_index);
+        frame.scroll_offset = 0.0;
+        frame.max_scroll_offset = 0.0;
+        frame.pending_jump = None;
+        frame.pending_cursor = Some(0);
+        frame.follow = false;
+        Some(previous)
+    }
+
+    pub fn remove_frame(&mut self, cluster_idx: usize, frame_idx: usize) -> Option<Frame> {
+        self.frame_clusters.get_mut(cluster_idx)?.remove_frame(frame_idx)
+    }
+
+    pub fn split_frame(&mut self, cluster_idx: usize, frame_idx: usize) -> Option<usize> {
+        let frame = self.frame_clusters.get(cluster_idx)?.frames.get(frame_idx)?.split();
+        self.add_frame(cluster_idx, frame)
+    }
+
+    // Detaches a frame and appends it to `to_cluster`, keeping its buffer,
+    // scroll position and group. Within one cluster this moves the frame to
+    // the end. Clusters are never removed, even when emptied, so windows'
+    // cluster indices stay valid. Returns the frame's new index.
+    pub fn move_frame(&mut self, from_cluster: usize, frame_idx: usize, to_cluster: usize) -> Option<usize> {
+        if to_cluster >= self.frame_clusters.len() || frame_idx >= self.frame_clusters.get(from_cluster)?.frames.len() {
+            return None;
+        }
+        let frame = self.frame_clusters[from_cluster].remove_frame(frame_idx)?;
+        self.add_frame(to_cluster, frame)
+    }
+
+    // Scrolls a frame and, when it belongs to a scroll group, every other
+    // frame in that group by the same delta. Each frame clamps to its own
+    // length, so shorter frames stop at their end.
+    pub fn scroll_by(&mut self, cluster_idx: usize, frame_idx: usize, delta: f32) {
+        let Some(frame) = self
+            .frame_clusters
+            .get_mut(cluster_idx)
+            .and_then(|cluster| cluster.frames.get_mut(frame_idx))
+        else {
+            return;
+        };
+
+        let Some(group) = frame.scroll_group else {
+            frame.scroll_by(delta);
+            return;
+        };
+
+        self.frame_clusters
+            .iter_mut()
+            .flat_map(|cluster| cluster.frames.iter_mut())
+            .filter(|frame| frame.scroll_group == Some(group))
+            .for_each(|frame| frame.scroll_by(delta));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(scroll_group: Option<u32>, max_scroll_offset: f32) -> Frame {
+        Frame { scroll_group, max_scroll_offset, ..Frame::default() }
+    }
+
+    #[test]
+    fn scroll_groups_scroll_together_and_clamp_per_frame() {
+        let mut frames = FrameStorage {
+            frame_clusters: vec![
+                FrameCluster { frames: vec![frame(Some(1), 100.0), frame(None, 100.0)], ..FrameCluster::default() },
+                FrameCluster { frames: vec![frame(Some(1), 30.0)], ..FrameCluster::default() },
+            ],
+        };
+        frames.scroll_by(0, 0, 50.0);
+        let offsets = |frames: &FrameStorage| -> Vec<f32> {
+            frames.frame_clusters.iter().flat_map(|cluster| &cluster.frames).map(|frame| frame.scroll_offset).collect()
+        };
+        assert_eq!(offsets(&frames), vec![50.0, 0.0, 30.0]);
+        frames.scroll_by(0, 1, 20.0);
+        frames.scroll_by(1, 0, -80.0);
+        assert_eq!(offsets(&frames), vec![0.0, 20.0, 0.0]);
+    }
+
+    #[test]
+    fn splitting_opens_the_same_buffer_at_the_same_scroll() {
+        let mut frames = FrameStorage::default();
+        let original = &mut frames.frame_clusters[0].frames[0];
+        *original = Frame { buffer_index: 3, scroll_offset: 120.0, max_scroll_offset: 500.0, scroll_group: Some(1), ..Frame::default() };
+        let split_idx = frames.split_frame(0, 0).unwrap();
+        let split = &frames.frame_clusters[0].frames[split_idx];
+        assert_eq!((split_idx, split.buffer_index, split.scroll_offset, split.scroll_group), (1, 3, 120.0, None));
+        assert_eq!(split.position.x, 1);
+        assert_eq!(frames.split_frame(0, 5), None);
+    }
+
+    #[test]
+    fn retargeted_frames_start_the_new_buffer_from_the_top() {
+        let mut frames = FrameStorage::default();
+        frames.frame_clusters[0].frames[0] = Frame { buffer_index: 1, scroll_offset: 80.0, follow: true, ..frame(None, 200.0) };
+        frames.frame_clusters[0].frames.push(Frame { kind: FrameKind::Terminal { closed: false }, ..Frame::default() });
+        assert_eq!(frames.set_frame_buffer(0, 0, 2, 3), Some(1));
+        let frame = &frames.frame_clusters[0].frames[0];
+        assert_eq!((frame.buffer_index, frame.scroll_offset, frame.pending_cursor, frame.follow), (2, 0.0, Some(0), false));
+        assert_eq!(frames.set_frame_buffer(0, 0, 3, 3), None);
+        assert_eq!(frames.set_frame_buffer(0, 1, 0, 3), None);
+        assert_eq!(frames.set_frame_buffer(0, 4, 0, 3), None);
+    }
+
+    #[test]
+    fn moved_frames_keep_their_state_and_leave_empty_clusters_behind() {
+        let mut frames = FrameStorage {
+            frame_clusters: vec![
+                FrameCluster { frames: vec![Frame { buffer_index: 4, scroll_offset: 40.0, ..frame(Some(2), 100.0) }], ..FrameCluster::default() },
+                FrameCluster { frames: vec![frame(None, 0.0)], ..FrameCluster::default() },
+            ],
+        };
+        assert_eq!(frames.move_frame(0, 0, 1), Some(1));
+        assert!(frames.frame_clusters[0].frames.is_empty());
+        let moved = &frames.frame_clusters[1].frames[1];
+        assert_eq!((moved.buffer_index, moved.scroll_offset, moved.scroll_group), (4, 40.0, Some(2)));
+
+        assert_eq!(frames.move_frame(1, 0, 1), Some(1));
+        assert_eq!(frames.frame_clusters[1].frames[0].buffer_index, 4);
+        assert_eq!(frames.move_frame(1, 0, 7), None);
+        assert_eq!(frames.move_frame(0, 0, 1), None);
+        assert_eq!(frames.frame_clusters.len(), 2);
+    }
+
+    #[test]
+    fn the_active_frame_cycles_and_follows_removals() {
+        let mut cluster = FrameCluster { frames: (0..3).map(|_| Frame::default()).collect(), ..FrameCluster::default() };
+        assert_eq!((cluster.activate_next(), cluster.activate_next(), cluster.activate_next()), (1, 2, 0));
+        cluster.set_active(9);
+        assert_eq!(cluster.active_frame, 2);
+        cluster.remove_frame(0);
+        assert_eq!(cluster.active_frame, 1);
+        cluster.remove_frame(1);
+        assert_eq!(cluster.active_frame, 0);
+        assert!(cluster.remove_frame(1).is_none());
+        cluster.remove_frame(0);
+        assert_eq!(cluster.activate_next(), 0);
+    }
+
+    #[test]
+    fn following_frames_stay_at_the_bottom_unless_scrolled_up() {
+        let mut output = Frame { follow: true, ..frame(None, 100.0) };
+        output.scroll_offset = 99.8;
+        assert!(output.set_max_scroll_offset(150.0));
+        assert_eq!(output.scroll_offset, 150.0);
+        assert!(!output.set_max_scroll_offset(150.0));
+        output.scroll_offset = 40.0;
+        assert!(!output.set_max_scroll_offset(200.0));
+        assert_eq!(output.scroll_offset, 40.0);
+
+        let mut file = Frame { scroll_offset: 100.0, ..frame(None, 100.0) };
+        assert!(!file.set_max_scroll_offset(150.0));
+        assert_eq!((file.scroll_offset, file.max_scroll_offset), (100.0, 150.0));
+    }
+
+    #[test]
+    fn a_cluster_is_added_only_when_there_is_none() {
+        let mut frames = FrameStorage { frame_clusters: Vec::new() };
+        assert_eq!(frames.ensure_cluster(), 0);
+        assert_eq!(frames.frame_clusters.len(), 1);
+        assert_eq!(frames.frame_clusters[0].frames.len(), 1);
+        frames.frame_clusters[0].name = String::from("kept");
+        assert_eq!(frames.ensure_cluster(), 0);
+        assert_eq!(frames.frame_clusters.len(), 1);
+        assert_eq!(frames.frame_clusters[0].name, "kept");
+    }
+}
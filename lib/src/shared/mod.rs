@@ -1,16 +1,355 @@
 pub mod frames;
 pub mod buffers;
+pub mod snapshot;
 
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use arc_swap::ArcSwap;
+
+use buffers::BufferStorage;
+use frames::FrameStorage;
+use snapshot::{BufferCopy, BufferMeta, FrameLayout, SharedSnapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedError {
+    Poisoned(&'static str),
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned(name) => write!(f, "{name} lock poisoned"),
+        }
+    }
+}
+
+impl std::error::Error for SharedError {}
+
+impl From<SharedError> for std::io::Error {
+    fn from(err: SharedError) -> Self {
+        std::io::Error::other(err)
+    }
+}
+
+// How often a lock was taken, and how often that meant waiting for (or, for
+// the `try_*` helpers, giving up on) another holder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockStats {
+    pub acquired: u64,
+    pub contended: u64,
+}
+
+struct TrackedLock<T> {
+    lock: RwLock<T>,
+    name: &'static str,
+    acquired: AtomicU64,
+    contended: AtomicU64,
+}
+
+impl<T> TrackedLock<T> {
+    fn new(value: T, name: &'static str) -> Self {
+        Self {
+            lock: RwLock::new(value),
+            name,
+            acquired: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+        }
+    }
+
+    fn stats(&self) -> LockStats {
+        LockStats {
+            acquired: self.acquired.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Frames and buffers sit behind their own locks and are only reached through
+// the `with_*` helpers. When both are needed, take frames inside buffers,
+// never the other way round.
+//
+// Every mutable access republishes a snapshot of the frame layout and the
+// buffer metadata afterwards, for readers that mustn't block.
 pub struct Shared {
-    pub frames : frames::FrameStorage,
-    pub buffers : buffers::BufferStorage
+    frames : TrackedLock<FrameStorage>,
+    buffers : TrackedLock<BufferStorage>,
+    frame_layout : ArcSwap<FrameLayout>,
+    buffer_meta : ArcSwap<Vec<BufferMeta>>,
+    recover_poison : bool,
 }
 
-impl Default for Shared{
+impl Default for Shared {
     fn default() -> Self {
-        Self{
-            frames: frames::FrameStorage::default(),
-            buffers: buffers::BufferStorage::default()
+        Self::new(BufferStorage::default(), FrameStorage::default())
+    }
+}
+
+impl Shared {
+    pub fn new(buffers: BufferStorage, frames: FrameStorage) -> Self {
+        Self {
+            frame_layout: ArcSwap::from_pointee(FrameLayout::capture(&frames)),
+            buffer_meta: ArcSwap::from_pointee(snapshot::buffer_meta(&buffers)),
+            frames: TrackedLock::new(frames, "frames"),
+            buffers: TrackedLock::new(buffers, "buffers"),
+            recover_poison: false,
+        }
+    }
+
+    // Lock-free: the layout as of the last frames write.
+    pub fn frame_layout(&self) -> Arc<FrameLayout> {
+        self.frame_layout.load_full()
+    }
+
+    // Lock-free: buffer paths and kinds as of the last buffers write.
+    pub fn buffer_meta(&self) -> Arc<Vec<BufferMeta>> {
+        self.buffer_meta.load_full()
+    }
+
+    // Holds the buffers just long enough to copy them.
+    pub fn snapshot(&self) -> Result<SharedSnapshot, SharedError> {
+        let buffers = self.with_buffers(|buffers| buffers.buffers.iter().map(BufferCopy::from).collect())?;
+        Ok(SharedSnapshot {
+            buffers,
+            layout: self.frame_layout(),
+        })
+    }
+
+    // With recovery on, a lock poisoned by a panic is logged, cleared and
+    // used as is instead of failing every later access. The data may be
+    // half-updated, which is why it's opt-in.
+    pub fn set_poison_recovery(&mut self, enabled: bool) {
+        self.recover_poison = enabled;
+    }
+
+    // (frames, buffers)
+    pub fn lock_stats(&self) -> (LockStats, LockStats) {
+        (self.frames.stats(), self.buffers.stats())
+    }
+
+    fn read<'a, T>(&self, tracked: &'a TrackedLock<T>) -> Result<RwLockReadGuard<'a, T>, SharedError> {
+        let result = match tracked.lock.try_read() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::WouldBlock) => {
+                tracked.contended.fetch_add(1, Ordering::Relaxed);
+                tracked.lock.read()
+            }
+            Err(TryLockError::Poisoned(poisoned)) => Err(poisoned),
+        };
+        tracked.acquired.fetch_add(1, Ordering::Relaxed);
+        match result {
+            Ok(guard) => Ok(guard),
+            Err(poisoned) if self.recover_poison => {
+                log::warn!("recovering poisoned {} lock", tracked.name);
+                tracked.lock.clear_poison();
+                Ok(poisoned.into_inner())
+            }
+            Err(_) => Err(SharedError::Poisoned(tracked.name)),
+        }
+    }
+
+    fn write<'a, T>(&self, tracked: &'a TrackedLock<T>) -> Result<RwLockWriteGuard<'a, T>, SharedError> {
+        let result = match tracked.lock.try_write() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::WouldBlock) => {
+                tracked.contended.fetch_add(1, Ordering::Relaxed);
+                tracked.lock.write()
+            }
+            Err(TryLockError::Poisoned(poisoned)) => Err(poisoned),
+        };
+        tracked.acquired.fetch_add(1, Ordering::Relaxed);
+        match result {
+            Ok(guard) => Ok(guard),
+            Err(poisoned) if self.recover_poison => {
+                log::warn!("recovering poisoned {} lock", tracked.name);
+                tracked.lock.clear_poison();
+                Ok(poisoned.into_inner())
+            }
+            Err(_) => Err(SharedError::Poisoned(tracked.name)),
+        }
+    }
+
+    fn try_write<'a, T>(&self, tracked: &'a TrackedLock<T>) -> Result<Option<RwLockWriteGuard<'a, T>>, SharedError> {
+        match tracked.lock.try_write() {
+            Ok(guard) => {
+                tracked.acquired.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(guard))
+            }
+            Err(TryLockError::WouldBlock) => {
+                tracked.contended.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(TryLockError::Poisoned(poisoned)) if self.recover_poison => {
+                log::warn!("recovering poisoned {} lock", tracked.name);
+                tracked.lock.clear_poison();
+                tracked.acquired.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(poisoned.into_inner()))
+            }
+            Err(TryLockError::Poisoned(_)) => Err(SharedError::Poisoned(tracked.name)),
         }
     }
+
+    pub fn with_buffers<R>(&self, f: impl FnOnce(&BufferStorage) -> R) -> Result<R, SharedError> {
+        let buffers = self.read(&self.buffers)?;
+        Ok(f(&buffers))
+    }
+
+    pub fn with_buffers_mut<R>(&self, f: impl FnOnce(&mut BufferStorage) -> R) -> Result<R, SharedError> {
+        let mut buffers = self.write(&self.buffers)?;
+        let result = f(&mut buffers);
+        self.buffer_meta.store(Arc::new(snapshot::buffer_meta(&buffers)));
+        Ok(result)
+    }
+
+    // Like `with_buffers_mut`, but gives up with `None` instead of waiting
+    // when another thread holds the buffers.
+    pub fn try_with_buffers_mut<R>(&self, f: impl FnOnce(&mut BufferStorage) -> R) -> Result<Option<R>, SharedError> {
+        let Some(mut buffers) = self.try_write(&self.buffers)? else {
+            return Ok(None);
+        };
+        let result = f(&mut buffers);
+        self.buffer_meta.store(Arc::new(snapshot::buffer_meta(&buffers)));
+        Ok(Some(result))
+    }
+
+    pub fn with_frames<R>(&self, f: impl FnOnce(&FrameStorage) -> R) -> Result<R, SharedError> {
+        let frames = self.read(&self.frames)?;
+        Ok(f(&frames))
+    }
+
+    pub fn with_frames_mut<R>(&self, f: impl FnOnce(&mut FrameStorage) -> R) -> Result<R, SharedError> {
+        let mut frames = self.write(&self.frames)?;
+        let result = f(&mut frames);
+        self.frame_layout.store(Arc::new(FrameLayout::capture(&frames)));
+        Ok(result)
+    }
+
+    pub fn try_with_frames_mut<R>(&self, f: impl FnOnce(&mut FrameStorage) -> R) -> Result<Option<R>, SharedError> {
+        let Some(mut frames) = self.try_write(&self.frames)? else {
+            return Ok(None);
+        };
+        let result = f(&mut frames);
+        self.frame_layout.store(Arc::new(FrameLayout::capture(&frames)));
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+
+    use buffers::Buffer;
+    use frames::FrameCluster;
+
+    #[test]
+    fn writers_on_many_threads_all_land_and_try_gives_up_when_held() {
+        let shared = Shared::default();
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        shared.with_buffers_mut(|buffers| buffers.buffers[0].append("x")).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.with_buffers(|buffers| buffers.buffers[0].content().len()).unwrap(), 800);
+
+        let (held, release) = (Barrier::new(2), Barrier::new(2));
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                shared
+                    .with_buffers_mut(|_| {
+                        held.wait();
+                        release.wait();
+                    })
+                    .unwrap();
+            });
+            held.wait();
+            assert_eq!(shared.try_with_buffers_mut(|buffers| buffers.buffers.push(Buffer::default())), Ok(None));
+            release.wait();
+        });
+        let (_, buffer_stats) = shared.lock_stats();
+        assert!(buffer_stats.contended >= 1 && buffer_stats.acquired >= 802);
+    }
+
+    #[test]
+    fn snapshots_read_without_blocking_while_writers_hold_the_locks() {
+        let shared = Shared::default();
+        let (held, release) = (Barrier::new(2), Barrier::new(2));
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                shared
+                    .with_frames_mut(|frames| {
+                        shared
+                            .with_buffers_mut(|buffers| {
+                                buffers.buffers.push(Buffer::default());
+                                frames.frame_clusters.push(FrameCluster::default());
+                                held.wait();
+                                release.wait();
+                            })
+                            .unwrap();
+                    })
+                    .unwrap();
+            });
+            held.wait();
+            // Both locks are held mid-write: the readers see the last
+            // published state and return straight away.
+            assert_eq!(shared.frame_layout().clusters.len(), 1);
+            assert_eq!(shared.buffer_meta().len(), 1);
+            assert_eq!(shared.try_with_buffers_mut(|_| ()), Ok(None));
+            release.wait();
+        });
+        assert_eq!(shared.frame_layout().clusters.len(), 2);
+        assert_eq!(shared.buffer_meta().len(), 2);
+    }
+
+    #[test]
+    fn poisoned_locks_fail_unless_recovery_is_on() {
+        let mut shared = Shared::default();
+        let poison = |shared: &Shared| {
+            thread::scope(|scope| {
+                let panicked = scope.spawn(|| shared.with_frames_mut(|_| panic!("poisoning the frames lock")));
+                assert!(panicked.join().is_err());
+            });
+        };
+        poison(&shared);
+        assert_eq!(shared.with_frames(|_| ()), Err(SharedError::Poisoned("frames")));
+        assert_eq!(shared.try_with_frames_mut(|_| ()), Err(SharedError::Poisoned("frames")));
+        assert!(shared.with_buffers(|_| ()).is_ok());
+
+        shared.set_poison_recovery(true);
+        assert_eq!(shared.with_frames(|frames| frames.frame_clusters.len()), Ok(1));
+        poison(&shared);
+        assert_eq!(shared.try_with_frames_mut(|frames| frames.frame_clusters.len()), Ok(Some(1)));
+        shared.set_poison_recovery(false);
+        assert!(shared.with_frames_mut(|_| ()).is_ok());
+    }
+
+    #[test]
+    fn snapshots_keep_what_was_there_when_taken() {
+        let shared = Shared::default();
+        shared
+            .with_buffers_mut(|buffers| {
+                let buffer = &mut buffers.buffers[0];
+                buffer.set_content("saved\n");
+                buffer.mark_saved(&crate::server::persistence::InMemoryStorage::default());
+                buffer.line_ending = buffers::LineEnding::CrLf;
+            })
+            .unwrap();
+        let before = shared.snapshot().unwrap();
+        shared.with_buffers_mut(|buffers| buffers.buffers[0].append("more\n")).unwrap();
+        let after = shared.snapshot().unwrap();
+
+        assert_eq!(&*before.buffers[0].content, "saved\n");
+        assert!(!before.buffers[0].is_modified());
+        assert!(after.buffers[0].is_modified());
+        let buffer_encoded = shared.with_buffers(|buffers| buffers.buffers[0].encoded()).unwrap();
+        assert_eq!(after.buffers[0].encoded(), buffer_encoded);
+        assert_eq!(before.buffers[0].encoded().0, b"saved\r\n");
+        assert_eq!(before.layout.clusters.len(), 1);
+    }
 }
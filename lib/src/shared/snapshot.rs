@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use encoding_rs::Encoding;
+
+use super::buffers::{Buffer, BufferStorage, LineEnding};
+use crate::server::encoding;
+use crate::server::hash;
+use super::frames::{Frame, FrameKind, FrameStorage, WindowGeometry};
+
+// Immutable copies of what the UI reads every frame, republished by each
+// writer so readers never wait on a lock.
+#[derive(Debug, Clone, Default)]
+pub struct FrameLayout {
+    pub clusters: Vec<ClusterLayout>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterLayout {
+    pub name: String,
+    pub is_visible: bool,
+    pub frames: Vec<FrameView>,
+    pub geometry: Option<WindowGeometry>,
+    pub active_frame: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameView {
+    pub kind: FrameKind,
+    pub buffer_index: usize,
+    pub scroll_offset: f32,
+}
+
+impl FrameLayout {
+    pub fn capture(frames: &FrameStorage) -> Self {
+        Self {
+            clusters: frames
+                .frame_clusters
+                .iter()
+                .map(|cluster| ClusterLayout {
+                    name: cluster.name.clone(),
+                    is_visible: cluster.is_visible,
+                    frames: cluster.frames.iter().map(FrameView::from).collect(),
+                    geometry: cluster.geometry,
+                    active_frame: cluster.active_frame,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&Frame> for FrameView {
+    fn from(frame: &Frame) -> Self {
+        Self {
+            kind: frame.kind,
+            buffer_index: frame.buffer_index,
+            scroll_offset: frame.scroll_offset,
+        }
+    }
+}
+
+// Only what's cheap to copy on every edit; content stays behind the lock.
+#[derive(Debug, Clone)]
+pub struct BufferMeta {
+    pub file_path: PathBuf,
+    pub is_scratch: bool,
+    pub language: &'static str,
+    pub big_file: bool,
+}
+
+impl From<&Buffer> for BufferMeta {
+    fn from(buffer: &Buffer) -> Self {
+        Self {
+            file_path: buffer.file_path.clone(),
+            is_scratch: buffer.is_scratch,
+            language: buffer.language,
+            big_file: buffer.big_file,
+        }
+    }
+}
+
+pub fn buffer_meta(buffers: &BufferStorage) -> Vec<BufferMeta> {
+    buffers.buffers.iter().map(BufferMeta::from).collect()
+}
+
+// One buffer's text and what's needed to write it out, owned.
+#[derive(Debug, Clone)]
+pub struct BufferCopy {
+    pub meta: BufferMeta,
+    pub content: Arc<str>,
+    pub encoding: &'static Encoding,
+    pub line_ending: LineEnding,
+    pub saved_hash: u64,
+}
+
+impl From<&Buffer> for BufferCopy {
+    fn from(buffer: &Buffer) -> Self {
+        Self {
+            meta: BufferMeta::from(buffer),
+            content: Arc::from(buffer.content()),
+            encoding: buffer.encoding,
+            line_ending: buffer.line_ending,
+            saved_hash: buffer.saved_hash,
+        }
+    }
+}
+
+impl BufferCopy {
+    pub fn is_modified(&self) -> bool {
+        hash::content_hash(&self.content) != self.saved_hash
+    }
+
+    // Same as `Buffer::encoded`.
+    pub fn encoded(&self) -> (Vec<u8>, usize) {
+        encoding::encode(&self.line_ending.apply(&self.content), self.encoding)
+    }
+}
+
+// The buffers and frame layout as of one moment, for long work off the UI
+// thread (saving, searching, exporting) that shouldn't hold either lock.
+// Later edits don't reach it.
+#[derive(Debug, Clone)]
+pub struct SharedSnapshot {
+    pub buffers: Vec<BufferCopy>,
+    pub layout: Arc<FrameLayout>,
+}